@@ -4,12 +4,24 @@ use anyhow::Result;
 use chrono::prelude::*;
 use crypto::{digest::Digest, md5::Md5};
 use serde::{Deserialize, Serialize};
+use std::future::Future;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{error, info};
 
+use crate::common::github_auth::{self, GitHubAuth, GitHubAuthProvider};
+use crate::deadline::Deadline;
 use crate::git::Repo;
-use crate::model::{Db, Dependencies};
-use crate::rust::RustAnalysis;
+use crate::model::{Config, Db, Dependencies, History, RepoConfig};
+use crate::rust::{
+    backfill::{Backfill, Snapshot},
+    cargoaudit,
+    estimate::CostEstimate,
+    prefetch::PrefetchSummary,
+    RustAnalysis,
+};
+use crate::timing::{self, TimingReport};
 
 //
 // Data that is stored in MongoDB
@@ -29,6 +41,9 @@ pub struct Analysis {
     previous_analysis: Option<PreviousAnalysis>,
     /// The result of the rust dependencies analysis
     rust_dependencies: RustAnalysis,
+    /// The time spent in the slow parts of the analysis (if enabled, see [`crate::timing`])
+    #[serde(default)]
+    timings: Option<TimingReport>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,44 +52,215 @@ pub struct PreviousAnalysis {
     timestamp: DateTime<Utc>,
 }
 
+impl Analysis {
+    pub fn repository(&self) -> &str {
+        &self.repository
+    }
+
+    pub fn commit(&self) -> &str {
+        &self.commit
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn rust_dependencies(&self) -> &RustAnalysis {
+        &self.rust_dependencies
+    }
+
+    pub fn timings(&self) -> Option<&TimingReport> {
+        self.timings.as_ref()
+    }
+}
+
 //
 // App
 //
 
 pub struct MetricsApp {
     db: Db,
+    /// the credentials for GitHub, if not the ones of the process (see [`github_auth`])
+    auth: Option<Arc<GitHubAuth>>,
 }
 
 impl MetricsApp {
     pub async fn new() -> Result<Self> {
         let db = Db::new(None, None, None, None).await?;
-        Ok(Self { db })
+        Ok(Self::with_db(db))
     }
 
-    /// The analyze function does the following:
-    /// 1. It initializes a given repository (if not already done previously).
-    /// 2. It pulls the latest changes.
-    /// 3. It records the commit pointed by the HEAD of the repository.
-    /// 4. It runs language-dependent analysis to "extract" information about our dependencies (this step only works for Rust dependencies stuff at the moment).
-    /// 5. It stores the results in the database.
-    pub async fn refresh(&self, repo_url: &str, repo_dir: &Path) -> Result<()> {
-        // 1. initialize repo if not done
+    /// Uses an existing database connection.
+    pub fn with_db(db: Db) -> Self {
+        Self { db, auth: None }
+    }
+
+    /// Authenticates the requests to GitHub with the tokens of the given provider
+    /// (e.g. the installation of a GitHub App on the repositories this app analyzes).
+    pub fn with_auth_provider(mut self, provider: Arc<dyn GitHubAuthProvider>) -> Self {
+        self.auth = Some(Arc::new(GitHubAuth::with_provider(provider)));
+        self
+    }
+
+    /// The credentials for GitHub (see [`Self::with_auth_provider`]).
+    fn auth(&self) -> Arc<GitHubAuth> {
+        self.auth.clone().unwrap_or_else(github_auth::current)
+    }
+
+    /// Runs a future with the credentials for GitHub (see [`github_auth::scope`]).
+    async fn authenticated<F: Future>(&self, future: F) -> F::Output {
+        github_auth::scope(self.auth(), future).await
+    }
+
+    /// Opens the local clone of a repository, or clones it if not done previously.
+    async fn get_repo(repo_url: &str, repo_dir: &Path) -> Result<Repo> {
         let mut md5 = Md5::new();
         md5.input_str(repo_url);
         let repo_path = repo_dir.join(&md5.result_str());
         info!("getting {} repo", repo_url);
-        let repo = match Repo::new(&repo_path) {
-            Ok(repo) => repo,
+        // (opening the repository is blocking, cloning it runs git as a child process)
+        let open_path = repo_path.clone();
+        match tokio::task::spawn_blocking(move || Repo::new(&open_path)).await? {
+            Ok(repo) => Ok(repo),
             Err(_) => {
                 info!("cloning {} into {}", repo_url, repo_path.to_string_lossy());
-                Repo::clone(repo_url, &repo_path).await?
+                Repo::clone(repo_url, &repo_path).await
             }
-        };
+        }
+    }
 
-        // 2. pull latest changes on the repo
+    /// Obtains the configuration of a repository (or a default one).
+    async fn get_repo_config(&self, repo_url: &str) -> RepoConfig {
+        let config = Config::new(self.db.clone());
+        match config.get_repo(repo_url).await {
+            Ok(Some(repo_config)) => repo_config,
+            Ok(None) => RepoConfig {
+                repo: repo_url.to_string(),
+                ..Default::default()
+            },
+            Err(e) => {
+                error!(
+                    "couldn't get repository configuration, using default: {}",
+                    e
+                );
+                RepoConfig {
+                    repo: repo_url.to_string(),
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    /// Estimates the cost of refreshing the analysis of a repository, without running it (dry-run).
+    /// Note that this still clones the repository if not done previously,
+    /// as the dependency graph is needed for the estimation.
+    pub async fn estimate(&self, repo_url: &str, repo_dir: &Path) -> Result<CostEstimate> {
+        let repo = Self::get_repo(repo_url, repo_dir).await?;
+
+        let db = Dependencies::new(self.db.clone());
+        let previous_analysis = db.get_last_analysis(repo_url).await.ok().flatten();
+        let previous_rust_analysis = previous_analysis.as_ref().map(|x| &x.rust_dependencies);
+
+        let repo_config = self.get_repo_config(repo_url).await;
+        let is_diem = repo_url == "https://github.com/diem/diem.git";
+        self.authenticated(RustAnalysis::estimate(
+            &repo.repo_folder,
+            previous_rust_analysis,
+            is_diem,
+            &repo_config,
+        ))
+        .await
+    }
+
+    /// Downloads everything an analysis of the repository needs into the cache (see [`crate::cache`]),
+    /// so that the analysis can then run offline.
+    pub async fn prefetch(&self, repo_url: &str, repo_dir: &Path) -> Result<PrefetchSummary> {
+        let repo = Self::get_repo(repo_url, repo_dir).await?;
         info!("pulling latest changes");
         repo.update().await?;
 
+        let repo_config = self.get_repo_config(repo_url).await;
+        let is_diem = repo_url == "https://github.com/diem/diem.git";
+        self.authenticated(RustAnalysis::prefetch(
+            &repo.repo_folder,
+            is_diem,
+            &repo_config,
+        ))
+        .await
+    }
+
+    /// Refreshes the analyses of several repositories, one after the other,
+    /// so that they share the cache and the rate limits of the data sources (see [`crate::rust::batch`]).
+    /// A failed analysis doesn't stop the batch, the repositories whose analysis failed are returned.
+    pub async fn batch(&self, repo_urls: &[String], repo_dir: &Path) -> Vec<String> {
+        let mut failed = Vec::new();
+        for repo_url in repo_urls {
+            info!("batch: analyzing {}", repo_url);
+            if let Err(e) = self.refresh(repo_url, repo_dir).await {
+                error!("batch: the analysis of {} failed: {}", repo_url, e);
+                failed.push(repo_url.clone());
+            }
+        }
+        failed
+    }
+
+    /// Reviews every change of the `Cargo.lock` of a repository in its git history
+    /// (see [`crate::rust::backfill`]), and stores the result in the history store.
+    pub async fn backfill(&self, repo_url: &str, repo_dir: &Path) -> Result<Backfill> {
+        let repo = Self::get_repo(repo_url, repo_dir).await?;
+        if crate::sources::offline() {
+            info!("offline mode, using the local history as is");
+        } else {
+            info!("fetching the history");
+            repo.update().await?;
+            repo.unshallow().await?;
+        }
+
+        let advisory_db = cargoaudit::advisory_db().await?;
+        let commits = repo.file_history("Cargo.lock").await?;
+        info!("{} commits changed Cargo.lock", commits.len());
+        let mut snapshots = Vec::new();
+        for (commit, date) in commits {
+            // (the commits deleting the lockfile, or with an unparsable one, are skipped)
+            let lockfile = match repo.show_file(&commit, "Cargo.lock").await? {
+                Some(lockfile) => lockfile,
+                None => continue,
+            };
+            match Snapshot::new(&commit, date, &lockfile, &advisory_db) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(e) => error!("skipping {}: {}", commit, e),
+            }
+        }
+
+        let backfill = Backfill::new(repo_url, &snapshots, Utc::now());
+        History::new(self.db.clone())
+            .save_backfill(&backfill)
+            .await?;
+        Ok(backfill)
+    }
+
+    /// The analyze function does the following:
+    /// 1. It initializes a given repository (if not already done previously).
+    /// 2. It pulls the latest changes.
+    /// 3. It records the commit pointed by the HEAD of the repository.
+    /// 4. It runs language-dependent analysis to "extract" information about our dependencies (this step only works for Rust dependencies stuff at the moment).
+    /// 5. It stores the results in the database.
+    pub async fn refresh(&self, repo_url: &str, repo_dir: &Path) -> Result<()> {
+        timing::reset();
+        let start = Instant::now();
+
+        // 1. initialize repo if not done
+        let repo = Self::get_repo(repo_url, repo_dir).await?;
+
+        // 2. pull latest changes on the repo
+        // (offline, the local clone is analyzed as is)
+        if crate::sources::offline() {
+            info!("offline mode, not pulling latest changes");
+        } else {
+            info!("pulling latest changes");
+            repo.update().await?;
+        }
+
         // 3. get metadata
         let commit = repo.head().await.expect("couldn't get HEAD hash");
         info!("current commit: {}", commit);
@@ -92,18 +278,37 @@ impl MetricsApp {
             }
         };
 
-        // 5. run analysis for different languages
+        // 5. get the configuration of the repository
+        let repo_config = self.get_repo_config(repo_url).await;
+
+        // (finding who introduced each dependency needs the full history)
+        if repo_config.full_history && !crate::sources::offline() {
+            info!("fetching the history");
+            repo.unshallow().await?;
+        }
+
+        // 6. run analysis for different languages
         // (at the moment we only have Rust)
         let previous_rust_analysis = previous_analysis.as_ref().map(|x| &x.rust_dependencies);
         let is_diem = repo_url == "https://github.com/diem/diem.git";
-        let rust_analysis =
-            RustAnalysis::get_dependencies(&repo.repo_folder, previous_rust_analysis, is_diem)
-                .await?;
+        let deadline = match repo_config.max_duration {
+            Some(max_duration) => Deadline::after(Duration::from_secs(max_duration)),
+            None => Deadline::none(),
+        };
+        let rust_analysis = self
+            .authenticated(RustAnalysis::get_dependencies(
+                &repo.repo_folder,
+                previous_rust_analysis,
+                is_diem,
+                &repo_config,
+                &deadline,
+            ))
+            .await?;
 
-        // 6. store analysis in db
+        // 7. store analysis in db
         info!("analysis done, storing in db...");
 
-        // 4. get previous analysis
+        // link to the previous analysis
         let previous_analysis = if let Some(previous_analysis) = &previous_analysis {
             Some(PreviousAnalysis {
                 commit: previous_analysis.commit.clone(),
@@ -112,14 +317,45 @@ impl MetricsApp {
         } else {
             None
         };
+        if let Some(remaining) = self.auth().remaining_calls() {
+            info!(
+                "GitHub API calls left before the rate limits reset: {}",
+                remaining
+            );
+        }
+        let timings = if timing::enabled() {
+            let timings = TimingReport::new(start.elapsed());
+            info!("{}", timings.to_text());
+            Some(timings)
+        } else {
+            None
+        };
         let analysis = Analysis {
             commit,
             repository: repo_url.to_string(),
             timestamp: Utc::now(),
             previous_analysis,
             rust_dependencies: rust_analysis,
+            timings,
         };
-        db.write_analysis(analysis).await
+        db.write_analysis(&analysis).await?;
+
+        // 8. send a digest of the changes
+        #[cfg(feature = "email")]
+        if !repo_config.digest_recipients.is_empty() {
+            let digest =
+                crate::digest::Digest::new(&analysis, &repo_config.locale, repo_config.verbosity);
+            if !digest.is_empty() {
+                info!("sending digest to {:?}", repo_config.digest_recipients);
+                if let Err(e) =
+                    crate::digest::email::send(&digest, &repo_config.digest_recipients).await
+                {
+                    error!("couldn't send digest: {}", e);
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 #[cfg(test)]
@@ -128,6 +364,7 @@ mod tests {
     use tempfile::tempdir;
 
     #[tokio::test]
+    #[ignore] // (clones diem and queries crates.io and GitHub, run with `cargo test -- --ignored`)
     async fn test_analysis() {
         let temp_dir = tempdir().unwrap();
         MetricsApp::new()
@@ -0,0 +1,191 @@
+//! This module records every external call of a run in an audit log: the HTTP requests, the git clones,
+//! and the commands run (git, cargo, dependabot, secret commands, etc.).
+//! It lets security teams check that the service doesn't send anything where it shouldn't,
+//! and reproduce a run by replaying its commands.
+//!
+//! Setting the `AUDIT_LOG` environment variable to a file enables it: each call is appended to the file
+//! as one JSON object per line (e.g. `{"time":"2021-03-01T12:00:00Z","kind":"request","method":"GET","url":"https://crates.io/api/v1/crates/tokio"}`).
+//! Only what is sent is recorded, never the headers (tokens) nor the responses.
+
+use anyhow::Result;
+use chrono::Utc;
+use futures::future::{BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::http::{Response, Transport};
+
+/// The environment variable pointing to the audit log.
+pub const AUDIT_LOG_VAR: &str = "AUDIT_LOG";
+
+/// An external call.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Event {
+    /// an HTTP request
+    Request { method: String, url: String },
+    /// a git repository cloned (or fetched) from an URL
+    Clone { url: String, path: String },
+    /// a command run, in the given directory (the current one if none)
+    Command {
+        program: String,
+        args: Vec<String>,
+        dir: Option<String>,
+    },
+}
+
+/// A line of the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// when the call was made (RFC 3339)
+    pub time: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Returns the path of the audit log, if enabled.
+pub fn log_path() -> Option<PathBuf> {
+    std::env::var_os(AUDIT_LOG_VAR).map(PathBuf::from)
+}
+
+/// Records an external call in the audit log (if enabled).
+/// Failing to write the log is logged, as it shouldn't stop the analysis.
+pub fn record(event: Event) {
+    let path = match log_path() {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(e) = append(&path, event) {
+        warn!("couldn't write the audit log {}: {}", path.display(), e);
+    }
+}
+
+fn append(path: &Path, event: Event) -> Result<()> {
+    let entry = Entry {
+        time: Utc::now().to_rfc3339(),
+        event,
+    };
+    let mut line = serde_json::to_vec(&entry)?;
+    line.push(b'\n');
+    // (a line is appended with a single write, so that concurrent calls don't interleave)
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&line)?;
+    Ok(())
+}
+
+/// Records a command about to run (e.g. `command(Some(dir), "git", &["pull"])`).
+pub fn command(dir: Option<&Path>, program: &str, args: &[&str]) {
+    record(Event::Command {
+        program: program.to_string(),
+        args: args.iter().map(ToString::to_string).collect(),
+        dir: dir.map(|dir| dir.display().to_string()),
+    });
+}
+
+/// Records a git repository about to be cloned.
+pub fn clone(url: &str, path: &Path) {
+    record(Event::Clone {
+        url: url.to_string(),
+        path: path.display().to_string(),
+    });
+}
+
+/// Records the requests sent by another transport (see [`crate::http::default_transport`]).
+pub struct AuditedTransport {
+    inner: Arc<dyn Transport>,
+}
+
+impl AuditedTransport {
+    pub fn new(inner: Arc<dyn Transport>) -> Self {
+        Self { inner }
+    }
+}
+
+fn request(method: &str, url: &str) {
+    record(Event::Request {
+        method: method.to_string(),
+        url: url.to_string(),
+    });
+}
+
+impl Transport for AuditedTransport {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+    ) -> BoxFuture<'a, Result<Response>> {
+        async move {
+            request("GET", url);
+            self.inner.get(url, headers).await
+        }
+        .boxed()
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<Response>> {
+        async move {
+            request("POST", url);
+            self.inner.post(url, headers, body).await
+        }
+        .boxed()
+    }
+
+    fn is_network(&self) -> bool {
+        self.inner.is_network()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_append() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        append(
+            &path,
+            Event::Request {
+                method: "GET".to_string(),
+                url: "https://crates.io/api/v1/crates/tokio".to_string(),
+            },
+        )
+        .unwrap();
+        append(
+            &path,
+            Event::Command {
+                program: "git".to_string(),
+                args: vec!["pull".to_string()],
+                dir: None,
+            },
+        )
+        .unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let entries: Vec<Entry> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[1].event,
+            Event::Command {
+                program: "git".to_string(),
+                args: vec!["pull".to_string()],
+                dir: None,
+            }
+        );
+        assert!(log.lines().next().unwrap().contains(
+            r#""kind":"request","method":"GET","url":"https://crates.io/api/v1/crates/tokio""#
+        ));
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use metrics::{analysis::Analysis, dashboard, locale::Locale, redact, verbosity::Verbosity};
+use std::{env, fs, path::Path};
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = env::args().collect();
+    let redacted = args.iter().any(|arg| arg == "--redact");
+    args.retain(|arg| arg != "--redact");
+    let mut locale = Locale::default();
+    if let Some(idx) = args.iter().position(|arg| arg == "--locale") {
+        if idx + 1 < args.len() {
+            locale = Locale::new(&args.remove(idx + 1));
+        }
+        args.remove(idx);
+    }
+    let mut verbosity = Verbosity::default();
+    if let Some(idx) = args.iter().position(|arg| arg == "--verbosity") {
+        if idx + 1 < args.len() {
+            verbosity = args.remove(idx + 1).parse()?;
+        }
+        args.remove(idx);
+    }
+
+    if args.len() < 3 {
+        println!(
+            "usage: cargo run --bin dashboard [--redact] [--locale <TAG>] [--verbosity <minimal|standard|full>] <OUT_DIR> <ANALYSIS_JSON>..."
+        );
+        println!("(analyses can be obtained from the /dependencies route of the backend)");
+        println!(
+            "(with --redact, internal names, paths and URLs are removed, see metrics::redact)"
+        );
+        println!("(with --locale, dates and numbers are formatted for a language, e.g. en-US)");
+        println!(
+            "(with --verbosity minimal the history is left out, with --verbosity full every dependency is listed)"
+        );
+        return Ok(());
+    }
+
+    let mut analyses = Vec::new();
+    for path in &args[2..] {
+        let analysis =
+            fs::read_to_string(path).with_context(|| format!("couldn't read {}", path))?;
+        let analysis: Analysis = serde_json::from_str(&analysis)
+            .with_context(|| format!("couldn't deserialize analysis {}", path))?;
+        if redacted {
+            analyses.push(redact::redact(&analysis)?);
+        } else {
+            analyses.push(analysis);
+        }
+    }
+
+    let out_dir = Path::new(&args[1]);
+    dashboard::generate(&analyses, out_dir, &locale, verbosity)?;
+    println!(
+        "dashboard written in {}",
+        out_dir.join("index.html").display()
+    );
+
+    Ok(())
+}
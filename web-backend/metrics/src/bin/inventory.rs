@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use metrics::rust::{cargoaudit, inventory::Inventory};
+use metrics::sources::Source;
+use std::{env, fs, path::Path};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!("usage: cargo run --bin inventory <OUT_DIR> [<NAME>=]<CARGO_LOCK>...");
+        println!(
+            "(lists the third-party crates used by the lockfiles, and the ones lagging on versions with advisories)"
+        );
+        println!("(the lockfiles are named by their path, unless a name is given, e.g. service-a=../a/Cargo.lock)");
+        return Ok(());
+    }
+
+    let mut lockfiles = Vec::new();
+    for arg in &args[2..] {
+        let (name, path) = match arg.find('=') {
+            Some(idx) => (&arg[..idx], &arg[idx + 1..]),
+            None => (arg.as_str(), arg.as_str()),
+        };
+        let lockfile =
+            fs::read_to_string(path).with_context(|| format!("couldn't read {}", path))?;
+        lockfiles.push((name.to_string(), lockfile));
+    }
+
+    // (without the advisory database, only the crates are listed)
+    let advisory_db = if Source::Advisories.is_enabled() {
+        Some(cargoaudit::advisory_db().await?)
+    } else {
+        None
+    };
+    let inventory = Inventory::new(&lockfiles, advisory_db.as_deref())?;
+
+    let out_dir = Path::new(&args[1]);
+    fs::create_dir_all(out_dir)?;
+    fs::write(
+        out_dir.join("inventory.json"),
+        serde_json::to_string_pretty(&inventory)?,
+    )?;
+    fs::write(out_dir.join("inventory.html"), inventory.to_html())?;
+    println!(
+        "inventory written in {} ({} crates, {} lagging)",
+        out_dir.display(),
+        inventory.crates.len(),
+        inventory.lagging.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use metrics::rust::monitor::{self, CheckReport, MonitorState};
+use std::{env, path::Path, process, time::Duration};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args: Vec<String> = env::args().collect();
+    let every = match args.iter().position(|arg| arg == "--every") {
+        Some(idx) if idx + 1 < args.len() => {
+            let seconds = args.remove(idx + 1).parse::<u64>()?;
+            args.remove(idx);
+            Some(Duration::from_secs(seconds))
+        }
+        _ => None,
+    };
+
+    if args.len() != 3 {
+        println!("usage: cargo run --bin monitor [--every <SECONDS>] <CARGO_LOCK> <STATE_JSON>");
+        println!(
+            "(reports the advisories that newly affect the lockfile, or were revised, since the previous check)"
+        );
+        println!(
+            "(without --every, checks once and exits with 1 if there are new or revised advisories, for cron)"
+        );
+        return Ok(());
+    }
+    let lockfile_path = Path::new(&args[1]);
+    let state_path = Path::new(&args[2]);
+
+    loop {
+        let report = match check(lockfile_path, state_path).await {
+            Ok(report) => report,
+            // (a failed check, e.g. the advisory database being unreachable, is retried at the next one)
+            Err(e) if every.is_some() => {
+                eprintln!("check failed: {:#}", e);
+                CheckReport::default()
+            }
+            Err(e) => return Err(e),
+        };
+        if !report.is_empty() {
+            println!("{}", monitor::to_markdown(lockfile_path, &report));
+        }
+
+        match every {
+            Some(every) => tokio::time::sleep(every).await,
+            None if report.is_empty() => return Ok(()),
+            None => process::exit(1),
+        }
+    }
+}
+
+async fn check(lockfile_path: &Path, state_path: &Path) -> Result<CheckReport> {
+    let mut state = MonitorState::load(state_path)?;
+    let report = monitor::check(lockfile_path, &mut state).await?;
+    state.save(state_path)?;
+    Ok(report)
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use metrics::rust::{prepublish, theme::MarkdownTheme, verdict::Verdict};
+use std::{env, fs, path::Path, process};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args: Vec<String> = env::args().collect();
+    let json = match args.iter().position(|arg| arg == "--json") {
+        Some(idx) if idx + 1 < args.len() => {
+            let path = args.remove(idx + 1);
+            args.remove(idx);
+            Some(path)
+        }
+        _ => None,
+    };
+
+    if args.len() != 2 {
+        println!("usage: cargo run --bin prepublish [--json <OUT_JSON>] <REPO_DIR>");
+        println!(
+            "(audits the packages of the workspace before they are published: files not in git, secrets, manifest metadata)"
+        );
+        println!("(exits with 1 if a package would be rejected or published from outside git)");
+        return Ok(());
+    }
+
+    let audit = prepublish::audit(Path::new(&args[1])).await?;
+    println!("{}", audit.to_markdown(&MarkdownTheme::default()));
+    if let Some(path) = json {
+        fs::write(path, serde_json::to_string_pretty(&audit)?)?;
+    }
+
+    if audit.verdict() == Verdict::Fail {
+        process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use anyhow::Result;
+use metrics::rpc;
+use std::env;
+use tokio::io::{self, BufReader};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    if env::args().len() != 1 {
+        println!("usage: cargo run --bin rpc");
+        println!(
+            "(reads JSON-RPC requests on stdin, one per line, and writes a response line for each on stdout)"
+        );
+        println!("(methods: analyze {{repo_dir}}, report {{name, version}}, compare {{names}})");
+        return Ok(());
+    }
+    rpc::serve(BufReader::new(io::stdin()), io::stdout()).await
+}
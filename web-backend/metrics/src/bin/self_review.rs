@@ -0,0 +1,44 @@
+use anyhow::Result;
+use metrics::rust::{
+    risky_paths::{RiskyPathOptions, RiskyPaths},
+    self_review,
+    theme::MarkdownTheme,
+    verdict::Verdict,
+};
+use metrics::verbosity::Verbosity;
+use std::{env, path::Path, process};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args: Vec<String> = env::args().collect();
+    let mut verbosity = Verbosity::default();
+    if let Some(idx) = args.iter().position(|arg| arg == "--verbosity") {
+        if idx + 1 < args.len() {
+            verbosity = args.remove(idx + 1).parse()?;
+        }
+        args.remove(idx);
+    }
+
+    if args.len() != 2 {
+        println!(
+            "usage: cargo run --bin self_review [--verbosity <minimal|standard|full>] <REPO_DIR>"
+        );
+        println!(
+            "(reviews the crates of the workspace about to be published against their latest versions on crates.io)"
+        );
+        println!("(exits with 1 if the review fails, e.g. in the CI job of a release)");
+        return Ok(());
+    }
+
+    let risky_paths = RiskyPaths::new(&RiskyPathOptions::default())?;
+    let review = self_review::review(Path::new(&args[1]), &risky_paths).await?;
+    println!(
+        "{}",
+        review.to_markdown(&MarkdownTheme::default(), verbosity)
+    );
+
+    if review.verdict() == Verdict::Fail {
+        process::exit(1);
+    }
+    Ok(())
+}
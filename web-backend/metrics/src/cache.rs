@@ -0,0 +1,88 @@
+//! This module caches the data obtained from the network (API responses, crate sources)
+//! so that an analysis can be split into two phases:
+//! a prefetch phase filling the cache (see [`crate::rust::RustAnalysis::prefetch`]),
+//! and an analysis phase that runs offline (see [`crate::sources::offline`]) out of the cache.
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::http::{self, Transport};
+use crate::sources;
+
+/// The directory in which network data is cached.
+pub fn cache_dir() -> PathBuf {
+    let mut cache_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    cache_dir.push("cache");
+    cache_dir
+}
+
+/// The directory in which the sources of prefetched crates are extracted
+/// (one directory per `<name>==<version>`).
+pub fn crates_dir() -> PathBuf {
+    cache_dir().join("crates")
+}
+
+/// The file in which the response of an URL is cached.
+fn response_path(url: &str) -> PathBuf {
+    cache_dir().join("responses").join(http::url_key(url))
+}
+
+/// Gets the body of an URL.
+/// Online, the response is fetched and cached, offline it is read from the cache.
+/// If the cached response has an ETag, the request is conditional
+/// (and the cached response is used if it's still up to date).
+/// Responses that don't come from the network (see [`Transport::is_network`]) are not cached.
+pub async fn get(transport: &dyn Transport, url: &str) -> Result<String> {
+    if !transport.is_network() {
+        return transport.get(url, &[]).await?.error_for_status()?.text();
+    }
+    let path = response_path(url);
+    if sources::offline() {
+        return fs::read_to_string(&path)
+            .map_err(|_| anyhow!("{} is not in the cache (offline mode)", url));
+    }
+
+    // (the ETag of a response is stored next to it)
+    let mut etag_path = path.clone().into_os_string();
+    etag_path.push(".etag");
+    let etag_path = PathBuf::from(etag_path);
+    let mut headers = Vec::new();
+    if let (Ok(etag), true) = (fs::read_to_string(&etag_path), path.exists()) {
+        headers.push(("if-none-match", etag));
+    }
+    let response = transport.get(url, &headers).await?;
+    if response.status == 304 {
+        return Ok(fs::read_to_string(&path)?);
+    }
+    let response = response.error_for_status()?;
+    let etag = response.header("etag").map(ToString::to_string);
+    let body = response.text()?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, &body)?;
+    match etag {
+        Some(etag) => fs::write(&etag_path, etag)?,
+        None => {
+            let _ = fs::remove_file(&etag_path);
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_path() {
+        assert_eq!(
+            response_path("https://crates.io/api/v1/crates/serde/owners"),
+            cache_dir()
+                .join("responses")
+                .join("crates.io_2Fapi_2Fv1_2Fcrates_2Fserde_2Fowners")
+        );
+    }
+}
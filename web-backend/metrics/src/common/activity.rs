@@ -0,0 +1,220 @@
+//! This module looks at who made the recent commits of a repository:
+//! the domains of the emails of the authors tell whether a project is backed by organizations
+//! (corporate emails) or by individuals (personal and noreply emails),
+//! and the commits made in GitHub's web editor tell how changes land (without local builds or tests).
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// the number of days of activity looked at
+pub const ACTIVITY_DAYS: i64 = 90;
+
+/// the providers of personal email addresses
+const PERSONAL_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "live.com",
+    "yahoo.com",
+    "icloud.com",
+    "me.com",
+    "protonmail.com",
+    "proton.me",
+    "pm.me",
+    "fastmail.com",
+    "gmx.de",
+    "gmx.net",
+    "web.de",
+    "mailbox.org",
+    "posteo.de",
+    "qq.com",
+    "163.com",
+    "126.com",
+    "yandex.ru",
+];
+
+/// the committer of the commits made on github.com (web editor, merges of pull requests)
+const WEB_FLOW_EMAIL: &str = "noreply@github.com";
+
+/// A commit, as returned by the GitHub API.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Commit {
+    pub commit: CommitDetails,
+    #[serde(default)]
+    pub parents: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CommitDetails {
+    pub author: Option<Signature>,
+    pub committer: Option<Signature>,
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Signature {
+    #[serde(default)]
+    pub email: String,
+}
+
+/// The kinds of email addresses.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmailKind {
+    Corporate,
+    Personal,
+    Noreply,
+}
+
+impl EmailKind {
+    pub fn of(email: &str) -> Self {
+        let email = email.to_lowercase();
+        let (local, domain) = match email.rfind('@') {
+            Some(at) => (&email[..at], &email[at + 1..]),
+            None => return EmailKind::Noreply,
+        };
+        if domain.ends_with("noreply.github.com") || local.contains("noreply") {
+            EmailKind::Noreply
+        } else if PERSONAL_DOMAINS.contains(&domain) {
+            EmailKind::Personal
+        } else {
+            EmailKind::Corporate
+        }
+    }
+}
+
+/// Who made the recent commits of a repository.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ActivityMetrics {
+    /// the number of commits of the last [`ACTIVITY_DAYS`] days
+    pub commits: usize,
+    /// the number of commits authored with corporate emails
+    pub corporate: usize,
+    /// the number of commits authored with personal emails (gmail, outlook, ...)
+    pub personal: usize,
+    /// the number of commits authored with noreply emails (hidden by GitHub)
+    pub noreply: usize,
+    /// the number of commits per corporate domain
+    pub corporate_domains: BTreeMap<String, usize>,
+    /// the number of commits made in GitHub's web editor
+    pub web_edits: usize,
+}
+
+impl ActivityMetrics {
+    pub fn from_commits(commits: &[Commit]) -> Self {
+        // (the merges of pull requests are also committed by GitHub, with a reference to the PR)
+        let pull_request = Regex::new(r"\(#[0-9]+\)|^Merge pull request #").expect("valid regex");
+        let mut metrics = Self {
+            commits: commits.len(),
+            ..Default::default()
+        };
+        for commit in commits {
+            let author = commit
+                .commit
+                .author
+                .as_ref()
+                .map(|author| author.email.as_str())
+                .unwrap_or_default();
+            match EmailKind::of(author) {
+                EmailKind::Corporate => {
+                    metrics.corporate += 1;
+                    let domain = author.rsplit('@').next().unwrap_or_default();
+                    *metrics
+                        .corporate_domains
+                        .entry(domain.to_lowercase())
+                        .or_default() += 1;
+                }
+                EmailKind::Personal => metrics.personal += 1,
+                EmailKind::Noreply => metrics.noreply += 1,
+            }
+
+            let committed_on_github = commit
+                .commit
+                .committer
+                .as_ref()
+                .map(|committer| committer.email == WEB_FLOW_EMAIL)
+                .unwrap_or(false);
+            let first_line = commit.commit.message.lines().next().unwrap_or_default();
+            if committed_on_github
+                && commit.parents.len() <= 1
+                && !pull_request.is_match(first_line)
+            {
+                metrics.web_edits += 1;
+            }
+        }
+        metrics
+    }
+
+    /// Describes the activity (e.g. `40 commits: 50% corporate (mozilla.com), 25% personal, 25% noreply, 2 in the web editor`).
+    pub fn describe(&self) -> String {
+        if self.commits == 0 {
+            return format!("no commits in {} days", ACTIVITY_DAYS);
+        }
+        let percent = |count: usize| count * 100 / self.commits;
+        let mut domains: Vec<(&String, &usize)> = self.corporate_domains.iter().collect();
+        domains.sort_by(|a, b| b.1.cmp(a.1));
+        let domains: Vec<&str> = domains
+            .iter()
+            .take(3)
+            .map(|(domain, _)| domain.as_str())
+            .collect();
+        let corporate = if domains.is_empty() {
+            format!("{}% corporate", percent(self.corporate))
+        } else {
+            format!(
+                "{}% corporate ({})",
+                percent(self.corporate),
+                domains.join(", ")
+            )
+        };
+        format!(
+            "{} commits: {}, {}% personal, {}% noreply, {} in the web editor",
+            self.commits,
+            corporate,
+            percent(self.personal),
+            percent(self.noreply),
+            self.web_edits
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_kind() {
+        assert_eq!(EmailKind::of("someone@mozilla.com"), EmailKind::Corporate);
+        assert_eq!(EmailKind::of("Someone@GMail.com"), EmailKind::Personal);
+        assert_eq!(
+            EmailKind::of("1234+someone@users.noreply.github.com"),
+            EmailKind::Noreply
+        );
+        assert_eq!(EmailKind::of("not an email"), EmailKind::Noreply);
+    }
+
+    #[test]
+    fn test_activity_metrics() {
+        let commits: Vec<Commit> = serde_json::from_str(
+            r#"[
+                {"commit": {"author": {"email": "a@mozilla.com"}, "committer": {"email": "a@mozilla.com"}, "message": "Fix"}, "parents": [{}]},
+                {"commit": {"author": {"email": "b@gmail.com"}, "committer": {"email": "noreply@github.com"}, "message": "Update README.md"}, "parents": [{}]},
+                {"commit": {"author": {"email": "c@users.noreply.github.com"}, "committer": {"email": "noreply@github.com"}, "message": "Add a feature (#12)\n\nSquashed"}, "parents": [{}]},
+                {"commit": {"author": {"email": "a@mozilla.com"}, "committer": {"email": "noreply@github.com"}, "message": "Merge pull request #13 from b/c"}, "parents": [{}, {}]}
+            ]"#,
+        )
+        .unwrap();
+        let metrics = ActivityMetrics::from_commits(&commits);
+        assert_eq!(metrics.corporate, 2);
+        assert_eq!(metrics.personal, 1);
+        assert_eq!(metrics.noreply, 1);
+        assert_eq!(metrics.web_edits, 1);
+        assert_eq!(
+            metrics.describe(),
+            "4 commits: 50% corporate (mozilla.com), 25% personal, 25% noreply, 1 in the web editor"
+        );
+    }
+}
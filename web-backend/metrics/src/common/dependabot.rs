@@ -6,6 +6,9 @@ use std::path::PathBuf;
 use tokio::process::Command;
 use tracing::error;
 
+use super::github_auth;
+use crate::audit;
+
 #[derive(Deserialize, Default, Serialize, Debug, PartialEq, Clone)]
 pub struct UpdateMetadata {
     changelog_url: Option<String>,
@@ -16,6 +19,17 @@ pub struct UpdateMetadata {
     commits: Vec<Commit>,
 }
 
+impl UpdateMetadata {
+    pub fn changelog_url(&self) -> Option<&str> {
+        self.changelog_url.as_deref()
+    }
+
+    /// the part of the changelog between the two versions
+    pub fn changelog_text(&self) -> Option<&str> {
+        self.changelog_text.as_deref()
+    }
+}
+
 #[derive(Deserialize, Default, Serialize, Debug, PartialEq, Clone)]
 pub struct Commit {
     message: String,
@@ -31,7 +45,13 @@ pub async fn get_update_metadata(
     let mut dependabot_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     dependabot_dir.push("dependabot");
 
-    let output = Command::new("ruby")
+    audit::command(Some(&dependabot_dir), "ruby", &["changelog.rb"]);
+    let mut command = Command::new("ruby");
+    // (dependabot reads its token from the environment, the current one of the pool is given)
+    if let Some(token) = github_auth::token().await {
+        command.env("GITHUB_TOKEN", token);
+    }
+    let output = command
         .current_dir(dependabot_dir)
         .env("DEPENDABOT_PACKAGE_MANAGER", package_manager)
         .env("DEPENDABOT_PACKAGE", package)
@@ -1,22 +1,356 @@
-use anyhow::Result;
-use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use anyhow::{bail, Result};
+use chrono::{Duration, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::Path;
-use std::{
-    fs,
-    time::{Duration, SystemTime},
-};
 use tracing::debug;
 
+use super::activity::{ActivityMetrics, Commit, ACTIVITY_DAYS};
+use super::github_auth;
+use crate::http::{self, Transport};
+
+/// Returns the path of a GitHub URL (what comes after `github.com/`).
+fn github_path(url: &str) -> Option<&str> {
+    url.trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .strip_prefix("github.com/")
+}
+
+/// Extracts the owner (user or organization) of a GitHub repository URL.
+/// For example, `https://github.com/tokio-rs/tokio` returns `tokio-rs`.
+pub fn repository_owner(repository_url: &str) -> Option<&str> {
+    let path = github_path(repository_url)?;
+    path.split('/').next().filter(|owner| !owner.is_empty())
+}
+
+/// Extracts the owner and the name of a GitHub repository URL.
+/// For example, `https://github.com/tokio-rs/tokio.git` returns `("tokio-rs", "tokio")`.
+pub fn repository_path(repository_url: &str) -> Option<(&str, &str)> {
+    let mut parts = github_path(repository_url)?.split('/');
+    let owner = parts.next().filter(|owner| !owner.is_empty())?;
+    let name = parts
+        .next()
+        .map(|name| name.trim_end_matches(".git"))
+        .filter(|name| !name.is_empty())?;
+    Some((owner, name))
+}
+
+/// Statistics about a GitHub repository.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct RepositoryStats {
+    pub full_name: String,
+    pub stargazers_count: u64,
+    pub forks_count: u64,
+    pub open_issues_count: u64,
+    pub archived: bool,
+    /// last time something was pushed on the repository
+    pub pushed_at: Option<String>,
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// the open issues with each of the labels of [`super::github_graphql::ISSUE_LABELS`]
+    /// (only counted by the GraphQL API, see [`super::github_graphql`])
+    #[serde(default)]
+    pub open_issues_by_label: BTreeMap<String, u64>,
+    /// how vulnerabilities are handled (not returned by the API, see [`get_security_policy`])
+    #[serde(default)]
+    pub security_policy: Option<SecurityPolicy>,
+    /// the fuzz targets found in the repository (not returned by the API, see [`get_file`])
+    #[serde(default)]
+    pub fuzz_targets: Option<Vec<String>>,
+    /// who made the recent commits (not returned by the API, see [`get_activity`])
+    #[serde(default)]
+    pub activity: Option<ActivityMetrics>,
+}
+
+/// Where GitHub looks for the security policy of a repository,
+/// which can also be inherited from the `.github` repository of its owner.
+const SECURITY_POLICY_LOCATIONS: &[&str] =
+    &["SECURITY.md", ".github/SECURITY.md", "docs/SECURITY.md"];
+
+/// What a security policy mentions when the project rewards reports.
+const BUG_BOUNTY_MARKERS: &[&str] = &[
+    "bug bounty",
+    "bounty program",
+    "hackerone.com",
+    "bugcrowd.com",
+    "huntr.dev",
+    "intigriti.com",
+];
+
+/// How the vulnerabilities of a repository are handled.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct SecurityPolicy {
+    /// the repository (or the `.github` repository of its owner) has a `SECURITY.md`
+    pub security_md: bool,
+    /// where to disclose vulnerabilities (an email address or an URL), as published by the security policy
+    pub disclosure_contact: Option<String>,
+    /// the security policy mentions a bug bounty program
+    pub bug_bounty: bool,
+    /// the repository is fuzzed by [OSS-Fuzz](https://github.com/google/oss-fuzz)
+    pub oss_fuzz: bool,
+}
+
+impl SecurityPolicy {
+    /// Reads a `SECURITY.md`.
+    pub fn parse(policy: &str) -> Self {
+        let email =
+            Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").expect("valid regex");
+        let url = Regex::new(r"https?://[^\s)>\]]+").expect("valid regex");
+        let disclosure_contact = email
+            .find(policy)
+            .or_else(|| url.find(policy))
+            .map(|contact| contact.as_str().trim_end_matches('.').to_string());
+        let lowercase = policy.to_lowercase();
+        Self {
+            security_md: true,
+            disclosure_contact,
+            bug_bounty: BUG_BOUNTY_MARKERS
+                .iter()
+                .any(|marker| lowercase.contains(marker)),
+            oss_fuzz: false,
+        }
+    }
+
+    /// Describes the policy (e.g. `SECURITY.md (contact: security@acme.org), bug bounty, OSS-Fuzz`).
+    pub fn describe(&self) -> String {
+        let policy = match (&self.disclosure_contact, self.security_md) {
+            (Some(contact), true) => format!("SECURITY.md (contact: {})", contact),
+            (None, true) => "SECURITY.md (no contact)".to_string(),
+            _ => "no SECURITY.md".to_string(),
+        };
+        let mut parts = vec![policy];
+        if self.bug_bounty {
+            parts.push("bug bounty".to_string());
+        }
+        if self.oss_fuzz {
+            parts.push("OSS-Fuzz".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Retrieves the security policy of a GitHub repository, and whether it is enrolled in OSS-Fuzz.
+/// The files are read from `raw.githubusercontent.com`, which doesn't count against the API rate limit.
+pub async fn get_security_policy(owner: &str, name: &str) -> Result<SecurityPolicy> {
+    let transport = http::default_transport("whackadep")?;
+    get_security_policy_with(&*transport, owner, name).await
+}
+
+/// Same as [`get_security_policy`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn get_security_policy_with(
+    transport: &dyn Transport,
+    owner: &str,
+    name: &str,
+) -> Result<SecurityPolicy> {
+    // the policy of the repository, or the default one of its owner
+    let mut urls: Vec<String> = SECURITY_POLICY_LOCATIONS
+        .iter()
+        .map(|location| raw_url(owner, name, location))
+        .collect();
+    urls.push(raw_url(owner, ".github", "SECURITY.md"));
+    let mut policy = SecurityPolicy::default();
+    for url in urls {
+        if let Some(content) = get_raw(transport, &url).await? {
+            policy = SecurityPolicy::parse(&content);
+            break;
+        }
+    }
+
+    // OSS-Fuzz projects are usually named after the repository, and declare it as their main repository
+    let project = format!(
+        "https://raw.githubusercontent.com/google/oss-fuzz/master/projects/{}/project.yaml",
+        name.to_lowercase()
+    );
+    let repository = format!("github.com/{}/{}", owner, name).to_lowercase();
+    policy.oss_fuzz = get_raw(transport, &project)
+        .await?
+        .map(|project| project.to_lowercase().contains(&repository))
+        .unwrap_or(false);
+    Ok(policy)
+}
+
+fn raw_url(owner: &str, name: &str, path: &str) -> String {
+    format!(
+        "https://raw.githubusercontent.com/{}/{}/HEAD/{}",
+        owner, name, path
+    )
+}
+
+/// Retrieves a file of the default branch of a GitHub repository, none if it doesn't exist.
+/// The file is read from `raw.githubusercontent.com`, which doesn't count against the API rate limit.
+pub async fn get_file(owner: &str, name: &str, path: &str) -> Result<Option<String>> {
+    let transport = http::default_transport("whackadep")?;
+    get_file_with(&*transport, owner, name, path).await
+}
+
+/// Same as [`get_file`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn get_file_with(
+    transport: &dyn Transport,
+    owner: &str,
+    name: &str,
+    path: &str,
+) -> Result<Option<String>> {
+    get_raw(transport, &raw_url(owner, name, path)).await
+}
+
+/// Returns the content of a file, none if it doesn't exist.
+async fn get_raw(transport: &dyn Transport, url: &str) -> Result<Option<String>> {
+    let response = transport.get(url, &[]).await?;
+    match response.status {
+        404 => Ok(None),
+        200..=299 => response.text().map(Some),
+        status => bail!("HTTP status {} for {}", status, url),
+    }
+}
+
+/// the number of redirects followed to find a repository
+const MAX_REDIRECTS: usize = 5;
+
+/// Retrieves statistics about a GitHub repository via the REST API.
+/// The credentials of the environment are used if present, to obtain a higher rate limit
+/// (see [`github_auth`]).
+pub async fn get_repository_stats(owner: &str, name: &str) -> Result<RepositoryStats> {
+    let transport = http::default_transport("whackadep")?;
+    get_repository_stats_with(&*transport, owner, name).await
+}
+
+/// Same as [`get_repository_stats`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn get_repository_stats_with(
+    transport: &dyn Transport,
+    owner: &str,
+    name: &str,
+) -> Result<RepositoryStats> {
+    let mut url = format!("https://api.github.com/repos/{}/{}", owner, name);
+    let headers = vec![("Accept", "application/vnd.github.v3+json".to_string())];
+    // renamed and transferred repositories redirect to the new one
+    // (its full name is returned, see [`crate::rust::provenance::repository_redirect`])
+    let mut redirects = 0;
+    let response = loop {
+        let response = github_auth::current()
+            .get(transport, &url, &headers)
+            .await?;
+        match response.header("location") {
+            Some(location)
+                if (300..400).contains(&response.status) && redirects < MAX_REDIRECTS =>
+            {
+                redirects += 1;
+                url = location.to_string();
+            }
+            _ => break response.error_for_status()?,
+        }
+    };
+    serde_json::from_slice(&response.body).map_err(anyhow::Error::msg)
+}
+
+/// Retrieves the commits of the last [`ACTIVITY_DAYS`] days of the default branch of a GitHub repository
+/// (at most 100), and looks at who made them (see [`crate::common::activity`]).
+pub async fn get_activity(owner: &str, name: &str) -> Result<ActivityMetrics> {
+    let transport = http::default_transport("whackadep")?;
+    get_activity_with(&*transport, owner, name).await
+}
+
+/// Same as [`get_activity`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn get_activity_with(
+    transport: &dyn Transport,
+    owner: &str,
+    name: &str,
+) -> Result<ActivityMetrics> {
+    let since = Utc::now() - Duration::days(ACTIVITY_DAYS);
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/commits?since={}&per_page=100",
+        owner,
+        name,
+        since.format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    let headers = vec![("Accept", "application/vnd.github.v3+json".to_string())];
+    let response = github_auth::current()
+        .get(transport, &url, &headers)
+        .await?
+        .error_for_status()?;
+    let commits: Vec<Commit> =
+        serde_json::from_slice(&response.body).map_err(anyhow::Error::msg)?;
+    Ok(ActivityMetrics::from_commits(&commits))
+}
+
+/// A GitHub organization.
+#[derive(Deserialize, Debug)]
+pub struct Organization {
+    pub login: String,
+    /// the organization proved that it owns its domains
+    #[serde(default)]
+    pub is_verified: bool,
+}
+
+/// Retrieves a GitHub organization, none if there is no such organization
+/// (e.g. the login of a user).
+pub async fn get_organization(org: &str) -> Result<Option<Organization>> {
+    let transport = http::default_transport("whackadep")?;
+    get_organization_with(&*transport, org).await
+}
+
+/// Same as [`get_organization`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn get_organization_with(
+    transport: &dyn Transport,
+    org: &str,
+) -> Result<Option<Organization>> {
+    let url = format!("https://api.github.com/orgs/{}", org);
+    let headers = vec![("Accept", "application/vnd.github.v3+json".to_string())];
+    let response = github_auth::current()
+        .get(transport, &url, &headers)
+        .await?;
+    if response.status == 404 {
+        return Ok(None);
+    }
+    let response = response.error_for_status()?;
+    serde_json::from_slice(&response.body)
+        .map(Some)
+        .map_err(anyhow::Error::msg)
+}
+
+/// Is a user a public member of a GitHub organization?
+/// (The private members can only be seen by the other members.)
+pub async fn is_public_member(org: &str, user: &str) -> Result<bool> {
+    let transport = http::default_transport("whackadep")?;
+    is_public_member_with(&*transport, org, user).await
+}
+
+/// Same as [`is_public_member`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn is_public_member_with(
+    transport: &dyn Transport,
+    org: &str,
+    user: &str,
+) -> Result<bool> {
+    let url = format!(
+        "https://api.github.com/orgs/{}/public_members/{}",
+        org, user
+    );
+    let headers = vec![("Accept", "application/vnd.github.v3+json".to_string())];
+    let response = github_auth::current()
+        .get(transport, &url, &headers)
+        .await?;
+    match response.status {
+        204 => Ok(true),
+        404 => Ok(false),
+        status => bail!("HTTP status {} for {}", status, url),
+    }
+}
+
 /// The function will retrieve repository metadata (like stargazers_count).
 /// It needs a Github personal access token (PAT) to function.
 pub async fn get_repository_info(
     access_token: Option<String>,
 ) -> Result<octocrab::models::Repository> {
     // get access token from ENV
-    let access_token = access_token.unwrap_or_else(|| {
-        std::env::var("GITHUB_TOKEN").expect("a GITHUB_TOKEN environment variable is missing")
-    });
+    let access_token = match access_token {
+        Some(access_token) => access_token,
+        None => match github_auth::token().await {
+            Some(access_token) => access_token,
+            None => bail!("no GitHub credentials in the environment"),
+        },
+    };
 
     // create client
     let octocrab = octocrab::OctocrabBuilder::new()
@@ -33,43 +367,122 @@ pub async fn get_repository_info(
 }
 
 pub async fn get_access_token(key_path: &Path) -> Result<String> {
-    #[derive(Debug, Serialize, Deserialize)]
-    struct Claims {
-        iss: String, // issuer
-        exp: usize,  // expiration time (limited to 10 min)
-        iat: usize,  // issued at
-    }
-
     let key = fs::read(key_path)?;
-
-    let now = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("getting time must work");
-    let expiration_time = now
-        .checked_add(Duration::from_secs(60 * 10))
-        .expect("overflowed adding 10min to now");
-
-    let my_claims = Claims {
-        iss: "97730".to_string(),
-        iat: now.as_secs() as usize,
-        exp: expiration_time.as_secs() as usize,
-    };
-
-    let token = encode(
-        &Header::new(Algorithm::RS256),
-        &my_claims,
-        &EncodingKey::from_rsa_pem(&key).unwrap(),
-    )?;
-
-    Ok(token)
+    github_auth::app_jwt("97730", &key)
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::http::{MockTransport, Response};
     use std::path::PathBuf;
 
+    #[test]
+    fn test_repository_owner() {
+        assert_eq!(
+            repository_owner("https://github.com/tokio-rs/tokio"),
+            Some("tokio-rs")
+        );
+        assert_eq!(
+            repository_owner("https://www.github.com/serde-rs/serde.git"),
+            Some("serde-rs")
+        );
+        assert_eq!(repository_owner("https://gitlab.com/a/b"), None);
+    }
+
+    #[test]
+    fn test_repository_path() {
+        assert_eq!(
+            repository_path("https://github.com/tokio-rs/tokio.git"),
+            Some(("tokio-rs", "tokio"))
+        );
+        assert_eq!(
+            repository_path("https://github.com/serde-rs/serde/tree/master/serde"),
+            Some(("serde-rs", "serde"))
+        );
+        assert_eq!(repository_path("https://github.com/serde-rs"), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_repository_stats_redirect() {
+        let transport = MockTransport::new()
+            .with_response(
+                "https://api.github.com/repos/a/old",
+                Response::empty(301)
+                    .with_header("Location", "https://api.github.com/repositories/42"),
+            )
+            .with_response(
+                "https://api.github.com/repositories/42",
+                Response::ok(
+                    r#"{"full_name": "b/new", "stargazers_count": 1, "forks_count": 0, "open_issues_count": 0, "archived": false}"#,
+                ),
+            );
+        let stats = get_repository_stats_with(&transport, "a", "old")
+            .await
+            .unwrap();
+        assert_eq!(stats.full_name, "b/new");
+    }
+
+    #[tokio::test]
+    async fn test_get_security_policy() {
+        let transport = MockTransport::new()
+            .with_response(
+                "https://raw.githubusercontent.com/acme/lib/HEAD/SECURITY.md",
+                Response::empty(404),
+            )
+            .with_response(
+                "https://raw.githubusercontent.com/acme/lib/HEAD/.github/SECURITY.md",
+                Response::ok(
+                    "# Security\n\nPlease report vulnerabilities to security@acme.org.\n\
+                     We run a bug bounty on https://hackerone.com/acme.",
+                ),
+            )
+            .with_response(
+                "https://raw.githubusercontent.com/google/oss-fuzz/master/projects/lib/project.yaml",
+                Response::ok("main_repo: 'https://github.com/acme/lib'"),
+            );
+        let policy = get_security_policy_with(&transport, "acme", "lib")
+            .await
+            .unwrap();
+        assert_eq!(
+            policy,
+            SecurityPolicy {
+                security_md: true,
+                disclosure_contact: Some("security@acme.org".to_string()),
+                bug_bounty: true,
+                oss_fuzz: true,
+            }
+        );
+
+        // no policy, and an OSS-Fuzz project of another repository with the same name
+        let transport = MockTransport::new()
+            .with_response(
+                "https://raw.githubusercontent.com/other/lib/HEAD/SECURITY.md",
+                Response::empty(404),
+            )
+            .with_response(
+                "https://raw.githubusercontent.com/other/lib/HEAD/.github/SECURITY.md",
+                Response::empty(404),
+            )
+            .with_response(
+                "https://raw.githubusercontent.com/other/lib/HEAD/docs/SECURITY.md",
+                Response::empty(404),
+            )
+            .with_response(
+                "https://raw.githubusercontent.com/other/.github/HEAD/SECURITY.md",
+                Response::empty(404),
+            )
+            .with_response(
+                "https://raw.githubusercontent.com/google/oss-fuzz/master/projects/lib/project.yaml",
+                Response::ok("main_repo: 'https://github.com/acme/lib'"),
+            );
+        let policy = get_security_policy_with(&transport, "other", "lib")
+            .await
+            .unwrap();
+        assert_eq!(policy, SecurityPolicy::default());
+    }
+
     #[tokio::test]
     async fn test_get_app_info() {
         let mut key_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -0,0 +1,802 @@
+//! This module authenticates the requests to GitHub, for organizations that need higher rate limits
+//! than a single personal access token (PAT) allows. The credentials are read from the secret provider
+//! (the environment by default, see [`crate::credentials`] for files, commands and other providers):
+//!
+//! - `GITHUB_TOKENS`: several PATs (comma-separated), used in turn: when the rate limit of one is exhausted,
+//!   the requests switch to the next one (skipping the ones known to be exhausted too),
+//! - `GITHUB_TOKEN`: a single PAT (added to the pool of `GITHUB_TOKENS`),
+//! - `GITHUB_APP_ID`, `GITHUB_APP_PRIVATE_KEY` (the PEM key of the app, or the path of its PEM file)
+//!   and `GITHUB_APP_INSTALLATION_ID`: a GitHub App installation. Installation tokens are obtained with a JWT
+//!   signed by the app, and are refreshed a few minutes before they expire (they are valid for an hour).
+//!   The installation comes first in the pool.
+//!
+//! Without credentials, requests are anonymous.
+//! Tools embedding this crate can also pass their credentials with [`install`] instead of the environment,
+//! or bring their own source of tokens (a [`GitHubAuthProvider`], see [`GitHubAuth::with_provider`]),
+//! e.g. a bot using the installation of its GitHub App on the repository it analyzes:
+//! the requests sent within a [`scope`] use its credentials rather than the ones of the process.
+//!
+//! The rate limits reported by GitHub (`X-RateLimit-Remaining` and `X-RateLimit-Reset`) are recorded,
+//! so that the calls left can be reported (see [`GitHubAuth::remaining_calls`]).
+//! When every credential is exhausted, requests wait for the earliest reset (and at least as long as `Retry-After` says,
+//! for the secondary rate limits), unless that is longer than `GITHUB_RATE_LIMIT_MAX_WAIT` seconds (15 minutes by default).
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as SyncMutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::credentials;
+use crate::http::{self, Response, Transport};
+
+/// installation tokens are refreshed when they expire in less than this many minutes
+const REFRESH_MARGIN_MINUTES: i64 = 5;
+/// the lifetime of the JWTs signed by the app (GitHub accepts at most 10 minutes)
+const JWT_LIFETIME: Duration = Duration::from_secs(60 * 9);
+
+/// The environment variable setting how long (in seconds) a request can wait for a rate limit to reset.
+pub const MAX_WAIT_VAR: &str = "GITHUB_RATE_LIMIT_MAX_WAIT";
+
+/// How long a request waits for a rate limit to reset by default.
+const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(15 * 60);
+
+/// how long a request can wait for a rate limit to reset (`$GITHUB_RATE_LIMIT_MAX_WAIT`, or 15 minutes)
+fn max_wait() -> Duration {
+    std::env::var(MAX_WAIT_VAR)
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_MAX_WAIT)
+}
+
+/// Creates a JWT authenticating as a GitHub App
+/// (see [GitHub's documentation](https://docs.github.com/en/developers/apps/authenticating-with-github-apps)).
+pub fn app_jwt(app_id: &str, private_key: &[u8]) -> Result<String> {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Claims {
+        iss: String, // issuer
+        exp: usize,  // expiration time (limited to 10 min)
+        iat: usize,  // issued at
+    }
+
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+    let claims = Claims {
+        iss: app_id.to_string(),
+        // (a minute in the past, in case the clocks drift)
+        iat: now.as_secs().saturating_sub(60) as usize,
+        exp: (now + JWT_LIFETIME).as_secs() as usize,
+    };
+    let token = encode(
+        &Header::new(Algorithm::RS256),
+        &claims,
+        &EncodingKey::from_rsa_pem(private_key)?,
+    )?;
+    Ok(token)
+}
+
+/// A GitHub App installation.
+struct AppInstallation {
+    app_id: String,
+    private_key: Vec<u8>,
+    installation_id: String,
+    /// the current installation token, and when it expires
+    token: Mutex<Option<(String, DateTime<Utc>)>>,
+}
+
+#[derive(Deserialize)]
+struct InstallationToken {
+    token: String,
+    /// (an RFC 3339 timestamp)
+    expires_at: String,
+}
+
+impl AppInstallation {
+    fn new(app_id: String, private_key: Vec<u8>, installation_id: String) -> Self {
+        Self {
+            app_id,
+            private_key,
+            installation_id,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Returns a valid installation token, refreshing it if needed.
+    async fn token(&self, transport: &dyn Transport) -> Result<String> {
+        // (the lock is held during the refresh, so that concurrent requests don't refresh it again)
+        let mut token = self.token.lock().await;
+        if let Some((token, expires_at)) = &*token {
+            if *expires_at - Utc::now() > ChronoDuration::minutes(REFRESH_MARGIN_MINUTES) {
+                return Ok(token.clone());
+            }
+        }
+
+        info!(
+            "refreshing the token of the GitHub App installation {}",
+            self.installation_id
+        );
+        let jwt = app_jwt(&self.app_id, &self.private_key)?;
+        let url = format!(
+            "https://api.github.com/app/installations/{}/access_tokens",
+            self.installation_id
+        );
+        let headers = vec![
+            ("Accept", "application/vnd.github.v3+json".to_string()),
+            ("Authorization", format!("Bearer {}", jwt)),
+        ];
+        let response = transport
+            .post(&url, &headers, &[])
+            .await?
+            .error_for_status()?;
+        let installation_token: InstallationToken =
+            serde_json::from_slice(&response.body).map_err(anyhow::Error::msg)?;
+        let expires_at = DateTime::parse_from_rfc3339(&installation_token.expires_at)?;
+        *token = Some((
+            installation_token.token.clone(),
+            expires_at.with_timezone(&Utc),
+        ));
+        Ok(installation_token.token)
+    }
+}
+
+/// A source of GitHub tokens, for tools embedding this crate that obtain them their own way
+/// (see [`GitHubAuth::with_provider`]). [`GitHubAuth`] is the default one, see [`GitHubAuth::from_env`].
+pub trait GitHubAuthProvider: Send + Sync {
+    /// Returns the token to use (none if requests are anonymous).
+    fn token(&self) -> BoxFuture<'_, Result<Option<String>>>;
+}
+
+/// A credential of the pool.
+enum Credential {
+    Token(String),
+    App(AppInstallation),
+    Provider(Arc<dyn GitHubAuthProvider>),
+}
+
+/// The calls left to a credential until its rate limit resets, as reported by GitHub.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitBudget {
+    pub remaining: u64,
+    pub reset: DateTime<Utc>,
+}
+
+impl RateLimitBudget {
+    /// Reads the rate limit headers of a response (`X-RateLimit-Remaining` and `X-RateLimit-Reset`).
+    pub fn from_response(response: &Response) -> Option<Self> {
+        let remaining = response.header("x-ratelimit-remaining")?.parse().ok()?;
+        let reset: i64 = response.header("x-ratelimit-reset")?.parse().ok()?;
+        Some(Self {
+            remaining,
+            reset: Utc.timestamp_opt(reset, 0).single()?,
+        })
+    }
+}
+
+/// The credentials used to authenticate to GitHub (see the module documentation).
+pub struct GitHubAuth {
+    credentials: Vec<Credential>,
+    /// the index of the credential in use
+    current: AtomicUsize,
+    /// the last rate limit reported for each credential (by index, anonymous requests being 0)
+    budgets: SyncMutex<HashMap<usize, RateLimitBudget>>,
+    /// how long a request can wait for a rate limit to reset
+    max_wait: Duration,
+}
+
+impl GitHubAuth {
+    /// Reads the credentials from the secret provider (the environment by default).
+    pub fn from_env() -> Self {
+        let var = credentials::secret;
+        let mut credentials = Vec::new();
+
+        if let (Some(app_id), Some(private_key), Some(installation_id)) = (
+            var("GITHUB_APP_ID"),
+            var("GITHUB_APP_PRIVATE_KEY"),
+            var("GITHUB_APP_INSTALLATION_ID"),
+        ) {
+            // (the key itself, when it comes from a secret manager)
+            let (private_key, key_path) = if private_key.starts_with("-----BEGIN") {
+                (Ok(private_key.into_bytes()), "secret".to_string())
+            } else {
+                (fs::read(&private_key), private_key)
+            };
+            match private_key {
+                Ok(private_key) => credentials.push(Credential::App(AppInstallation::new(
+                    app_id,
+                    private_key,
+                    installation_id,
+                ))),
+                Err(e) => warn!(
+                    "couldn't read the private key of the GitHub App ({}): {}",
+                    key_path, e
+                ),
+            }
+        }
+
+        let tokens = var("GITHUB_TOKENS")
+            .into_iter()
+            .chain(var("GITHUB_TOKEN"))
+            .flat_map(|tokens| {
+                tokens
+                    .split(',')
+                    .map(|token| token.trim().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|token| !token.is_empty());
+        for token in tokens {
+            if !credentials
+                .iter()
+                .any(|credential| matches!(credential, Credential::Token(t) if *t == token))
+            {
+                credentials.push(Credential::Token(token));
+            }
+        }
+
+        Self::new(credentials)
+    }
+
+    /// Authenticates with the given personal access tokens (used in turn).
+    pub fn with_tokens(tokens: &[&str]) -> Self {
+        Self::new(
+            tokens
+                .iter()
+                .map(|token| Credential::Token(token.to_string()))
+                .collect(),
+        )
+    }
+
+    /// Authenticates as an installation of a GitHub App, given the PEM key of the app
+    /// (its installation tokens are refreshed before they expire).
+    pub fn with_app(app_id: &str, private_key: Vec<u8>, installation_id: &str) -> Self {
+        Self::new(vec![Credential::App(AppInstallation::new(
+            app_id.to_string(),
+            private_key,
+            installation_id.to_string(),
+        ))])
+    }
+
+    /// Authenticates with the tokens of the given provider
+    /// (the rate limits are still recorded and waited for, see the module documentation).
+    pub fn with_provider(provider: Arc<dyn GitHubAuthProvider>) -> Self {
+        Self::new(vec![Credential::Provider(provider)])
+    }
+
+    /// Sends anonymous requests, whatever the environment contains.
+    pub fn anonymous() -> Self {
+        Self::new(Vec::new())
+    }
+
+    fn new(credentials: Vec<Credential>) -> Self {
+        Self {
+            credentials,
+            current: AtomicUsize::new(0),
+            budgets: SyncMutex::new(HashMap::new()),
+            max_wait: max_wait(),
+        }
+    }
+
+    /// Is there any credential? (otherwise requests are anonymous)
+    pub fn is_configured(&self) -> bool {
+        !self.credentials.is_empty()
+    }
+
+    /// Returns the token to use (none if requests are anonymous).
+    /// Obtaining an installation token needs requests, sent with the given transport.
+    pub async fn token(&self, transport: &dyn Transport) -> Result<Option<String>> {
+        if self.credentials.is_empty() {
+            return Ok(None);
+        }
+        let index = self.current.load(Ordering::SeqCst) % self.credentials.len();
+        match &self.credentials[index] {
+            Credential::Token(token) => Ok(Some(token.clone())),
+            Credential::App(app) => app.token(transport).await.map(Some),
+            Credential::Provider(provider) => provider.token().await,
+        }
+    }
+
+    /// Switches to the next credential whose rate limit isn't known to be exhausted, or to the one after
+    /// the given one if they all are (unless a concurrent request noticing the same exhaustion already did).
+    fn rotate(&self, from: usize) {
+        let len = self.credentials.len().max(1);
+        let now = Utc::now();
+        let next = (1..len)
+            .map(|offset| (from + offset) % len)
+            .find(|&index| !self.is_exhausted(index, now))
+            .unwrap_or((from + 1) % len);
+        let _ = self
+            .current
+            .compare_exchange(from, next, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Did GitHub report that the rate limit of a credential is exhausted until a reset still to come?
+    fn is_exhausted(&self, index: usize, now: DateTime<Utc>) -> bool {
+        self.budgets
+            .lock()
+            .ok()
+            .and_then(|budgets| budgets.get(&index).copied())
+            .map(|budget| budget.remaining == 0 && budget.reset > now)
+            .unwrap_or(false)
+    }
+
+    /// Sends a GET request to GitHub, authenticated with the current credential.
+    /// When its rate limit is exhausted, the request is sent again with the next credentials
+    /// (and the last response is returned if every credential is exhausted).
+    pub async fn get(
+        &self,
+        transport: &dyn Transport,
+        url: &str,
+        headers: &[(&str, String)],
+    ) -> Result<Response> {
+        self.send(transport, url, headers, None).await
+    }
+
+    /// Sends a POST request to GitHub (e.g. a GraphQL query), like [`Self::get`].
+    pub async fn post(
+        &self,
+        transport: &dyn Transport,
+        url: &str,
+        headers: &[(&str, String)],
+        body: &[u8],
+    ) -> Result<Response> {
+        self.send(transport, url, headers, Some(body)).await
+    }
+
+    /// Sends a GET request, or a POST request if there is a body.
+    async fn send(
+        &self,
+        transport: &dyn Transport,
+        url: &str,
+        headers: &[(&str, String)],
+        body: Option<&[u8]>,
+    ) -> Result<Response> {
+        let attempts = self.credentials.len().max(1);
+        let mut waited = false;
+        loop {
+            let mut last_response = None;
+            for _ in 0..attempts {
+                let index = self.current.load(Ordering::SeqCst) % attempts;
+                let mut all_headers = headers.to_vec();
+                if let Some(token) = self.token(transport).await? {
+                    all_headers.push(("Authorization", format!("token {}", token)));
+                }
+                let response = match body {
+                    Some(body) => transport.post(url, &all_headers, body).await?,
+                    None => transport.get(url, &all_headers).await?,
+                };
+                self.record(index, &response);
+                if !is_rate_limited(&response) {
+                    // (that was the last call of the credential: the next requests use another one)
+                    if self.credentials.len() > 1 && self.is_exhausted(index, Utc::now()) {
+                        self.rotate(index);
+                    }
+                    return Ok(response);
+                }
+                last_response = Some(response);
+                if self.credentials.len() < 2 {
+                    break;
+                }
+                warn!("GitHub credential {} is rate limited, rotating", index);
+                self.rotate(index);
+            }
+            let response = last_response.ok_or_else(|| anyhow!("no request sent to {}", url))?;
+
+            // every credential is exhausted: wait for the first to reset (once)
+            match self.wait(&response, Utc::now()) {
+                Some(wait) if !waited && wait <= self.max_wait => {
+                    warn!(
+                        "GitHub rate limit exhausted, waiting {}s for it to reset",
+                        wait.as_secs()
+                    );
+                    tokio::time::sleep(wait).await;
+                    waited = true;
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+
+    /// Records the rate limit reported by a response to a request sent with a credential.
+    fn record(&self, index: usize, response: &Response) {
+        if let Some(budget) = RateLimitBudget::from_response(response) {
+            if let Ok(mut budgets) = self.budgets.lock() {
+                budgets.insert(index, budget);
+            }
+        }
+    }
+
+    /// How long to wait before sending a rate limited request again, once every credential is exhausted:
+    /// until the earliest reset of the exhausted credentials, or as long as the response says,
+    /// but never less than its `Retry-After` (secondary rate limits don't show in the budgets).
+    fn wait(&self, response: &Response, now: DateTime<Utc>) -> Option<Duration> {
+        let wait = retry_after(response, now)?;
+        let earliest_reset = self
+            .budgets
+            .lock()
+            .ok()?
+            .values()
+            .filter(|budget| budget.remaining == 0 && budget.reset > now)
+            .map(|budget| budget.reset)
+            .min();
+        let wait = match earliest_reset {
+            Some(reset) => wait.min(until(reset, now)),
+            None => wait,
+        };
+        Some(wait.max(retry_after_header(response).unwrap_or_default()))
+    }
+
+    /// The calls left before the rate limits reset, summed over the credentials, as last reported by GitHub
+    /// (none before the first response). The credentials whose rate limit reset since are not counted.
+    pub fn remaining_calls(&self) -> Option<u64> {
+        let budgets = self.budgets.lock().ok()?;
+        if budgets.is_empty() {
+            return None;
+        }
+        let now = Utc::now();
+        Some(
+            budgets
+                .values()
+                .filter(|budget| budget.reset > now)
+                .map(|budget| budget.remaining)
+                .sum(),
+        )
+    }
+}
+
+impl GitHubAuthProvider for GitHubAuth {
+    /// (installation tokens are obtained with the default transport, see [`http::default_transport`])
+    fn token(&self) -> BoxFuture<'_, Result<Option<String>>> {
+        async move {
+            let transport = http::default_transport("whackadep")?;
+            GitHubAuth::token(self, &*transport).await
+        }
+        .boxed()
+    }
+}
+
+/// The time left until a rate limit resets (a second more, as the reset time is rounded down).
+fn until(reset: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (reset - now).to_std().unwrap_or_default() + Duration::from_secs(1)
+}
+
+/// How long to wait before sending a rate limited request again:
+/// as long as `Retry-After` says (secondary rate limits), or until the rate limit resets.
+pub fn retry_after(response: &Response, now: DateTime<Utc>) -> Option<Duration> {
+    if let Some(wait) = retry_after_header(response) {
+        return Some(wait);
+    }
+    let budget = RateLimitBudget::from_response(response).filter(|budget| budget.remaining == 0)?;
+    Some(until(budget.reset, now))
+}
+
+/// The `Retry-After` header of a response (in seconds).
+fn retry_after_header(response: &Response) -> Option<Duration> {
+    response
+        .header("retry-after")
+        .and_then(|seconds| seconds.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// Is the response a rejection because of an exhausted rate limit?
+pub fn is_rate_limited(response: &Response) -> bool {
+    (response.status == 403 || response.status == 429)
+        && (response.header("x-ratelimit-remaining") == Some("0")
+            || response.header("retry-after").is_some())
+}
+
+/// The credentials of the process.
+static SHARED: OnceCell<Arc<GitHubAuth>> = OnceCell::new();
+
+tokio::task_local! {
+    /// the credentials of the current [`scope`]
+    static SCOPED: Arc<GitHubAuth>;
+}
+
+/// Sets the credentials of the process, instead of reading them from the environment
+/// (e.g. for a bot that authenticates as a GitHub App, see [`GitHubAuth::with_app`]).
+/// This fails if a request to GitHub was already sent.
+pub fn install(auth: GitHubAuth) -> Result<()> {
+    SHARED
+        .set(Arc::new(auth))
+        .map_err(|_| anyhow!("the GitHub credentials of the process are already set"))
+}
+
+/// Returns the credentials of the process (read from the environment once, unless [`install`]ed).
+pub fn shared() -> &'static GitHubAuth {
+    shared_arc()
+}
+
+fn shared_arc() -> &'static Arc<GitHubAuth> {
+    SHARED.get_or_init(|| Arc::new(GitHubAuth::from_env()))
+}
+
+/// Runs a future with the given credentials instead of the ones of the process
+/// (e.g. an analysis, see [`crate::analysis::MetricsApp::with_auth_provider`]).
+pub async fn scope<F: Future>(auth: Arc<GitHubAuth>, future: F) -> F::Output {
+    SCOPED.scope(auth, future).await
+}
+
+/// Returns the credentials to use: the ones of the current [`scope`], or else the ones of the process.
+pub fn current() -> Arc<GitHubAuth> {
+    SCOPED
+        .try_with(Arc::clone)
+        .unwrap_or_else(|_| Arc::clone(shared_arc()))
+}
+
+/// Returns the token to use for GitHub (see [`GitHubAuth::token`]), none if requests are anonymous.
+/// Errors (e.g. an installation token that couldn't be refreshed) are logged.
+pub async fn token() -> Option<String> {
+    match GitHubAuthProvider::token(&*current()).await {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("couldn't authenticate to GitHub: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers with a rate limit error to the requests authenticated with `exhausted`,
+    /// and with the last call of the rate limit to the ones authenticated with `last_call`.
+    #[derive(Default)]
+    struct RateLimitedTransport {
+        exhausted: String,
+        last_call: String,
+        authorizations: SyncMutex<Vec<String>>,
+    }
+
+    impl Transport for RateLimitedTransport {
+        fn get<'a>(
+            &'a self,
+            _url: &'a str,
+            headers: &'a [(&'a str, String)],
+        ) -> BoxFuture<'a, Result<Response>> {
+            let authorization = headers
+                .iter()
+                .find(|(name, _)| *name == "Authorization")
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default();
+            let reset = (Utc::now() + ChronoDuration::hours(1))
+                .timestamp()
+                .to_string();
+            let response = if authorization == format!("token {}", self.exhausted) {
+                Response::empty(403)
+                    .with_header("X-RateLimit-Remaining", "0")
+                    .with_header("X-RateLimit-Reset", &reset)
+            } else if authorization == format!("token {}", self.last_call) {
+                Response::ok("{}")
+                    .with_header("X-RateLimit-Remaining", "0")
+                    .with_header("X-RateLimit-Reset", &reset)
+            } else {
+                Response::ok("{}")
+            };
+            self.authorizations.lock().unwrap().push(authorization);
+            async move { Ok(response) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_rotation() {
+        let transport = RateLimitedTransport {
+            exhausted: "a".to_string(),
+            ..Default::default()
+        };
+        let auth = GitHubAuth::with_tokens(&["a", "b"]);
+        let response = auth
+            .get(&transport, "https://api.github.com/repos/a/b", &[])
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        // the exhausted token is not used anymore
+        auth.get(&transport, "https://api.github.com/repos/a/b", &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            *transport.authorizations.lock().unwrap(),
+            vec!["token a", "token b", "token b"]
+        );
+
+        // with a single token, the rate limit error is returned
+        let auth = GitHubAuth::with_tokens(&["a"]);
+        let response = auth
+            .get(&transport, "https://api.github.com/repos/a/b", &[])
+            .await
+            .unwrap();
+        assert!(is_rate_limited(&response));
+
+        // anonymous requests
+        let auth = GitHubAuth::with_tokens(&[]);
+        assert!(!auth.is_configured());
+        assert_eq!(auth.token(&transport).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_tokens_skipped() {
+        let transport = RateLimitedTransport {
+            exhausted: "b".to_string(),
+            last_call: "a".to_string(),
+            ..Default::default()
+        };
+        let auth = GitHubAuth::with_tokens(&["a", "b", "c"]);
+        let url = "https://api.github.com/repos/a/b";
+        // the last call of a token switches to the next one right away
+        auth.get(&transport, url, &[]).await.unwrap();
+        auth.get(&transport, url, &[]).await.unwrap();
+        assert_eq!(
+            *transport.authorizations.lock().unwrap(),
+            vec!["token a", "token b", "token c"]
+        );
+
+        // the tokens known to be exhausted are skipped
+        auth.current.store(0, Ordering::SeqCst);
+        auth.rotate(0);
+        assert_eq!(auth.token(&transport).await.unwrap().as_deref(), Some("c"));
+    }
+
+    /// Answers with a secondary rate limit error to the first request.
+    #[derive(Default)]
+    struct SecondaryLimitTransport {
+        calls: AtomicUsize,
+    }
+
+    impl Transport for SecondaryLimitTransport {
+        fn get<'a>(
+            &'a self,
+            _url: &'a str,
+            _headers: &'a [(&'a str, String)],
+        ) -> BoxFuture<'a, Result<Response>> {
+            let response = match self.calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Response::empty(429).with_header("Retry-After", "1"),
+                _ => Response::ok("{}")
+                    .with_header("X-RateLimit-Remaining", "4999")
+                    .with_header(
+                        "X-RateLimit-Reset",
+                        &(Utc::now() + ChronoDuration::hours(1))
+                            .timestamp()
+                            .to_string(),
+                    ),
+            };
+            async move { Ok(response) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_wait() {
+        let now = Utc.timestamp(1_600_000_000, 0);
+        let exhausted = Response::empty(403)
+            .with_header("X-RateLimit-Remaining", "0")
+            .with_header("X-RateLimit-Reset", "1600000030");
+        assert!(is_rate_limited(&exhausted));
+        assert_eq!(retry_after(&exhausted, now), Some(Duration::from_secs(31)));
+        assert_eq!(
+            retry_after(&Response::empty(403).with_header("Retry-After", "60"), now),
+            Some(Duration::from_secs(60))
+        );
+        // (a forbidden request isn't rate limited)
+        assert!(!is_rate_limited(&Response::empty(403)));
+        // (nor is a response with a reset out of range)
+        assert_eq!(
+            RateLimitBudget::from_response(
+                &Response::empty(403)
+                    .with_header("X-RateLimit-Remaining", "0")
+                    .with_header("X-RateLimit-Reset", "99999999999999999")
+            ),
+            None
+        );
+
+        // only the credentials still exhausted are waited for, and never less than Retry-After
+        let auth = GitHubAuth::with_tokens(&["a", "b", "c"]);
+        {
+            let mut budgets = auth.budgets.lock().unwrap();
+            let budget = |remaining, seconds| RateLimitBudget {
+                remaining,
+                reset: now + ChronoDuration::seconds(seconds),
+            };
+            budgets.insert(1, budget(0, 20));
+            // (calls left, or reset already)
+            budgets.insert(2, budget(10, 5));
+            budgets.insert(3, budget(0, -5));
+        }
+        let exhausted = Response::empty(403)
+            .with_header("X-RateLimit-Remaining", "0")
+            .with_header("X-RateLimit-Reset", "1600000100");
+        assert_eq!(auth.wait(&exhausted, now), Some(Duration::from_secs(21)));
+        assert_eq!(
+            auth.wait(&exhausted.with_header("Retry-After", "60"), now),
+            Some(Duration::from_secs(60))
+        );
+
+        let transport = SecondaryLimitTransport::default();
+        let auth = GitHubAuth::with_tokens(&["a"]);
+        assert_eq!(auth.remaining_calls(), None);
+        let response = auth
+            .get(&transport, "https://api.github.com/repos/a/b", &[])
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(transport.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(auth.remaining_calls(), Some(4999));
+
+        // a reset too far away is not waited for
+        let mut auth = GitHubAuth::with_tokens(&["a"]);
+        auth.max_wait = Duration::from_millis(10);
+        let transport = SecondaryLimitTransport::default();
+        let response = auth
+            .get(&transport, "https://api.github.com/repos/a/b", &[])
+            .await
+            .unwrap();
+        assert_eq!(response.status, 429);
+    }
+
+    /// Hands out the token of the repository being analyzed, as a bot with an installation per repository would.
+    struct RepositoryTokens(&'static str);
+
+    impl GitHubAuthProvider for RepositoryTokens {
+        fn token(&self) -> BoxFuture<'_, Result<Option<String>>> {
+            async move { Ok(Some(format!("ghs_{}", self.0))) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider() {
+        let transport = RateLimitedTransport {
+            last_call: "ghs_a".to_string(),
+            ..Default::default()
+        };
+        let url = "https://api.github.com/repos/a/b";
+        let auth = GitHubAuth::with_provider(Arc::new(RepositoryTokens("a")));
+        assert!(auth.is_configured());
+        auth.get(&transport, url, &[]).await.unwrap();
+        // (the rate limits reported for the provider's tokens are still recorded)
+        assert_eq!(auth.remaining_calls(), Some(0));
+
+        // the requests sent within a scope use its credentials
+        let b = Arc::new(GitHubAuth::with_provider(Arc::new(RepositoryTokens("b"))));
+        scope(b, async { current().get(&transport, url, &[]).await })
+            .await
+            .unwrap();
+        assert_eq!(
+            *transport.authorizations.lock().unwrap(),
+            vec!["token ghs_a", "token ghs_b"]
+        );
+        assert!(Arc::ptr_eq(&current(), shared_arc()));
+    }
+
+    #[tokio::test]
+    async fn test_installation_token() {
+        let mut key_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        key_path.push("resources/keys/whackadep.2021-01-25.private-key.pem");
+        // (the key of the app is not committed)
+        let private_key = match fs::read(key_path) {
+            Ok(private_key) => private_key,
+            Err(_) => return,
+        };
+        let expires_at = (Utc::now() + ChronoDuration::hours(1)).to_rfc3339();
+        let transport = http::MockTransport::new().with_response(
+            "https://api.github.com/app/installations/42/access_tokens",
+            Response::ok(format!(
+                r#"{{"token": "ghs_installation", "expires_at": "{}"}}"#,
+                expires_at
+            )),
+        );
+        let auth = GitHubAuth::with_app("97730", private_key, "42");
+        assert_eq!(
+            auth.token(&transport).await.unwrap().as_deref(),
+            Some("ghs_installation")
+        );
+        // the token is reused until it nearly expires
+        auth.token(&transport).await.unwrap();
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
@@ -0,0 +1,377 @@
+//! This module fetches the metrics of many GitHub repositories with the GraphQL API,
+//! a batch of repositories per query, instead of several REST calls per repository
+//! (statistics, commits of the default branch, issues): the statistics, the default branch,
+//! the recent commits (see [`crate::common::activity`]) and the open issues with some labels.
+//! It is used instead of the REST API when `GITHUB_GRAPHQL` is set (`1` or `true`),
+//! and needs credentials (the GraphQL API doesn't accept anonymous requests, see [`github_auth`]).
+
+use anyhow::{bail, Result};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use tracing::warn;
+
+use super::activity::{ActivityMetrics, Commit, CommitDetails, Signature, ACTIVITY_DAYS};
+use super::github::RepositoryStats;
+use super::github_auth;
+use crate::http::{self, Transport};
+
+/// The environment variable selecting the GraphQL API (`1` or `true`).
+pub const GITHUB_GRAPHQL_VAR: &str = "GITHUB_GRAPHQL";
+
+/// The labels of the issues counted (see [`RepositoryStats::open_issues_by_label`]).
+pub const ISSUE_LABELS: &[&str] = &["bug", "security"];
+
+/// the number of repositories fetched per query (with up to 100 commits each)
+const BATCH_SIZE: usize = 20;
+
+const GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// Is the GraphQL API selected?
+pub fn enabled() -> bool {
+    std::env::var(GITHUB_GRAPHQL_VAR)
+        .map(|graphql| graphql == "1" || graphql.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+#[derive(Deserialize)]
+struct GraphQlResponse {
+    /// by alias of the repository (`r0`, `r1`, ...), none if it wasn't found
+    data: Option<HashMap<String, Option<Repository>>>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Repository {
+    name_with_owner: String,
+    stargazer_count: u64,
+    fork_count: u64,
+    is_archived: bool,
+    pushed_at: Option<String>,
+    issues: TotalCount,
+    pull_requests: TotalCount,
+    default_branch_ref: Option<BranchRef>,
+    /// the issues with each label, by alias (`label0`, `label1`, ...)
+    #[serde(flatten)]
+    labeled_issues: HashMap<String, TotalCount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TotalCount {
+    total_count: u64,
+}
+
+#[derive(Deserialize)]
+struct BranchRef {
+    name: String,
+    target: Option<BranchTarget>,
+}
+
+/// The commit a branch points to (empty for other objects).
+#[derive(Deserialize)]
+struct BranchTarget {
+    history: Option<History>,
+}
+
+#[derive(Deserialize)]
+struct History {
+    nodes: Vec<HistoryCommit>,
+}
+
+#[derive(Deserialize)]
+struct HistoryCommit {
+    #[serde(default)]
+    message: String,
+    author: Option<Actor>,
+    committer: Option<Actor>,
+    parents: TotalCount,
+}
+
+#[derive(Deserialize)]
+struct Actor {
+    email: Option<String>,
+}
+
+impl HistoryCommit {
+    /// Converts the commit to its REST representation (what the activity metrics are computed from).
+    fn into_commit(self) -> Commit {
+        let signature = |actor: Option<Actor>| {
+            actor.map(|actor| Signature {
+                email: actor.email.unwrap_or_default(),
+            })
+        };
+        Commit {
+            commit: CommitDetails {
+                author: signature(self.author),
+                committer: signature(self.committer),
+                message: self.message,
+            },
+            parents: vec![serde_json::Value::Null; self.parents.total_count as usize],
+        }
+    }
+}
+
+impl Repository {
+    fn into_stats(mut self) -> RepositoryStats {
+        let open_issues_by_label: BTreeMap<String, u64> = ISSUE_LABELS
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, label)| {
+                let count = self.labeled_issues.remove(&format!("label{}", idx))?;
+                Some((label.to_string(), count.total_count))
+            })
+            .collect();
+        let (default_branch, commits) = match self.default_branch_ref {
+            Some(branch) => {
+                let commits: Vec<Commit> = branch
+                    .target
+                    .and_then(|target| target.history)
+                    .map(|history| {
+                        history
+                            .nodes
+                            .into_iter()
+                            .map(HistoryCommit::into_commit)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (Some(branch.name), commits)
+            }
+            None => (None, Vec::new()),
+        };
+        RepositoryStats {
+            full_name: self.name_with_owner,
+            stargazers_count: self.stargazer_count,
+            forks_count: self.fork_count,
+            // (like the REST API, which counts the pull requests as issues)
+            open_issues_count: self.issues.total_count + self.pull_requests.total_count,
+            archived: self.is_archived,
+            pushed_at: self.pushed_at,
+            default_branch,
+            open_issues_by_label,
+            activity: Some(ActivityMetrics::from_commits(&commits)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Builds the query of a batch of repositories, whose owners and names are passed as variables.
+fn query(batch_len: usize) -> String {
+    let mut variables = String::from("$since: GitTimestamp!");
+    let mut repositories = String::new();
+    for idx in 0..batch_len {
+        let _ = write!(variables, ", $owner{0}: String!, $name{0}: String!", idx);
+        let _ = writeln!(
+            repositories,
+            "  r{0}: repository(owner: $owner{0}, name: $name{0}) {{ ...metrics }}",
+            idx
+        );
+    }
+    let mut labels = String::new();
+    for (idx, label) in ISSUE_LABELS.iter().enumerate() {
+        let _ = writeln!(
+            labels,
+            "  label{}: issues(states: OPEN, labels: [{:?}]) {{ totalCount }}",
+            idx, label
+        );
+    }
+    format!(
+        "query({variables}) {{
+{repositories}}}
+
+fragment metrics on Repository {{
+  nameWithOwner
+  stargazerCount
+  forkCount
+  isArchived
+  pushedAt
+  issues(states: OPEN) {{ totalCount }}
+  pullRequests(states: OPEN) {{ totalCount }}
+{labels}  defaultBranchRef {{
+    name
+    target {{
+      ... on Commit {{
+        history(first: 100, since: $since) {{
+          nodes {{ message author {{ email }} committer {{ email }} parents {{ totalCount }} }}
+        }}
+      }}
+    }}
+  }}
+}}
+",
+        variables = variables,
+        repositories = repositories,
+        labels = labels
+    )
+}
+
+/// Retrieves the statistics of GitHub repositories (given as owner and name), with their activity,
+/// by batches of [`BATCH_SIZE`]. The repositories that couldn't be fetched
+/// (or whose batch couldn't be) are missing from the result.
+pub async fn get_repositories_stats(
+    repositories: &[(String, String)],
+) -> Result<HashMap<(String, String), RepositoryStats>> {
+    let transport = http::default_transport("whackadep")?;
+    // (recorded responses are played back without credentials, see [`http::FIXTURES_VAR`])
+    if transport.is_network() && !github_auth::current().is_configured() {
+        bail!("the GitHub GraphQL API needs credentials");
+    }
+    Ok(get_repositories_stats_with(&*transport, repositories).await)
+}
+
+/// Same as [`get_repositories_stats`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn get_repositories_stats_with(
+    transport: &dyn Transport,
+    repositories: &[(String, String)],
+) -> HashMap<(String, String), RepositoryStats> {
+    let since = (Utc::now() - Duration::days(ACTIVITY_DAYS))
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string();
+    let mut stats = HashMap::new();
+    for batch in repositories.chunks(BATCH_SIZE) {
+        match get_batch_stats(transport, batch, &since).await {
+            Ok(batch_stats) => stats.extend(batch_stats),
+            Err(e) => warn!(
+                "couldn't look up a batch of {} repositories: {}",
+                batch.len(),
+                e
+            ),
+        }
+    }
+    stats
+}
+
+/// Retrieves the statistics of a batch of repositories, with their activity since the given date.
+async fn get_batch_stats(
+    transport: &dyn Transport,
+    batch: &[(String, String)],
+    since: &str,
+) -> Result<HashMap<(String, String), RepositoryStats>> {
+    let headers = vec![("Content-Type", "application/json".to_string())];
+    let mut variables = serde_json::Map::new();
+    variables.insert("since".to_string(), json!(since));
+    for (idx, (owner, name)) in batch.iter().enumerate() {
+        variables.insert(format!("owner{}", idx), json!(owner));
+        variables.insert(format!("name{}", idx), json!(name));
+    }
+    let body = json!({ "query": query(batch.len()), "variables": variables });
+    let response = github_auth::current()
+        .post(
+            transport,
+            GRAPHQL_URL,
+            &headers,
+            body.to_string().as_bytes(),
+        )
+        .await?
+        .error_for_status()?;
+    let response: GraphQlResponse =
+        serde_json::from_slice(&response.body).map_err(anyhow::Error::msg)?;
+    // (a repository that doesn't exist is an error, the others of the batch are still returned)
+    for error in &response.errors {
+        warn!("GitHub GraphQL error: {}", error.message);
+    }
+    let mut data = response.data.unwrap_or_default();
+    let mut stats = HashMap::new();
+    for (idx, repository) in batch.iter().enumerate() {
+        if let Some(Some(metrics)) = data.remove(&format!("r{}", idx)) {
+            stats.insert(repository.clone(), metrics.into_stats());
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{MockTransport, Response};
+
+    #[test]
+    fn test_query() {
+        let query = query(2);
+        assert!(query.starts_with(
+            "query($since: GitTimestamp!, $owner0: String!, $name0: String!, $owner1: String!, $name1: String!) {"
+        ));
+        assert!(query.contains("r1: repository(owner: $owner1, name: $name1) { ...metrics }"));
+        assert!(
+            query.contains("label1: issues(states: OPEN, labels: [\"security\"]) { totalCount }")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_repositories_stats() {
+        let transport = MockTransport::new().with_response(
+            GRAPHQL_URL,
+            Response::ok(
+                r#"{
+                    "data": {
+                        "r0": {
+                            "nameWithOwner": "servo/rust-smallvec",
+                            "stargazerCount": 780,
+                            "forkCount": 110,
+                            "isArchived": false,
+                            "pushedAt": "2021-01-08T18:19:33Z",
+                            "issues": { "totalCount": 30 },
+                            "pullRequests": { "totalCount": 5 },
+                            "label0": { "totalCount": 4 },
+                            "label1": { "totalCount": 0 },
+                            "defaultBranchRef": {
+                                "name": "master",
+                                "target": {
+                                    "history": {
+                                        "nodes": [
+                                            {
+                                                "message": "Fix a bug",
+                                                "author": { "email": "dev@mozilla.com" },
+                                                "committer": { "email": "noreply@github.com" },
+                                                "parents": { "totalCount": 1 }
+                                            }
+                                        ]
+                                    }
+                                }
+                            }
+                        },
+                        "r1": null
+                    },
+                    "errors": [{ "message": "Could not resolve to a Repository with the name 'a/gone'." }]
+                }"#,
+            ),
+        );
+        let repositories = vec![
+            ("servo".to_string(), "rust-smallvec".to_string()),
+            ("a".to_string(), "gone".to_string()),
+        ];
+        let stats = get_repositories_stats_with(&transport, &repositories).await;
+        assert_eq!(stats.len(), 1);
+        let smallvec = &stats[&repositories[0]];
+        assert_eq!(smallvec.full_name, "servo/rust-smallvec");
+        assert_eq!(smallvec.open_issues_count, 35);
+        assert_eq!(smallvec.default_branch.as_deref(), Some("master"));
+        assert_eq!(smallvec.open_issues_by_label["bug"], 4);
+        let activity = smallvec.activity.as_ref().unwrap();
+        assert_eq!(activity.commits, 1);
+        assert_eq!(activity.corporate, 1);
+        assert_eq!(activity.web_edits, 1);
+        assert_eq!(transport.requests(), vec![GRAPHQL_URL.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_get_repositories_stats_failed_batch() {
+        // (a batch that can't be fetched is skipped, not the whole lookup)
+        let transport = MockTransport::new().with_response(GRAPHQL_URL, Response::empty(502));
+        let repositories: Vec<(String, String)> = (0..BATCH_SIZE + 1)
+            .map(|idx| ("a".to_string(), format!("r{}", idx)))
+            .collect();
+        let stats = get_repositories_stats_with(&transport, &repositories).await;
+        assert!(stats.is_empty());
+        assert_eq!(transport.requests().len(), 2);
+    }
+}
@@ -1,5 +1,8 @@
 //! This module contains code that is useful for analyzing dependencies,
 //! and is language agnostic.
 
+pub mod activity;
 pub mod dependabot;
 pub mod github;
+pub mod github_auth;
+pub mod github_graphql;
@@ -0,0 +1,225 @@
+//! This module fetches the credentials of the service (GitHub tokens, the private key of a GitHub App,
+//! the tokens of private registries) from a secret provider, so that they don't have to be exported
+//! as plain environment variables in CI configurations. The secret `<NAME>` is looked up, in order:
+//!
+//! - in the environment variable `<NAME>`,
+//! - in the file whose path is in `<NAME>_FILE` (e.g. a Docker or Kubernetes secret mounted as a file),
+//! - in the output of the command in `<NAME>_COMMAND`, run with `sh -c`
+//!   (e.g. `vault kv get -field=token secret/github`, or `op read op://ci/github/token`).
+//!
+//! Applications embedding the library can plug their own provider instead (see [`SecretProvider`] and [`set_provider`]).
+//!
+//! Cargo reads the tokens of private registries from `CARGO_REGISTRIES_<NAME>_TOKEN`:
+//! the registries listed in `WHACKADEP_REGISTRIES` (comma-separated) get their token from the provider,
+//! set in the environment of the process only, so that the cargo commands of the analysis inherit it
+//! (see [`export_registry_tokens`]).
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::OnceCell;
+use std::fs;
+use std::process::Command;
+use tracing::{info, warn};
+
+use crate::audit;
+
+/// the variable listing the private registries whose tokens are fetched from the provider
+pub const REGISTRIES_VAR: &str = "WHACKADEP_REGISTRIES";
+
+/// A source of secrets.
+pub trait SecretProvider: Send + Sync {
+    /// Returns the secret with the given name (e.g. `GITHUB_TOKEN`), none if the provider doesn't have it.
+    fn secret(&self, name: &str) -> Result<Option<String>>;
+}
+
+/// Any function can be a provider (e.g. a closure calling the API of a secret manager).
+impl<F> SecretProvider for F
+where
+    F: Fn(&str) -> Result<Option<String>> + Send + Sync,
+{
+    fn secret(&self, name: &str) -> Result<Option<String>> {
+        self(name)
+    }
+}
+
+/// Reads the secret `<NAME>` from the environment variable `<NAME>`.
+pub struct EnvProvider;
+
+impl SecretProvider for EnvProvider {
+    fn secret(&self, name: &str) -> Result<Option<String>> {
+        Ok(std::env::var(name).ok())
+    }
+}
+
+/// Reads the secret `<NAME>` from the file whose path is in the environment variable `<NAME>_FILE`.
+pub struct FileProvider;
+
+impl SecretProvider for FileProvider {
+    fn secret(&self, name: &str) -> Result<Option<String>> {
+        let path = match std::env::var(format!("{}_FILE", name)) {
+            Ok(path) => path,
+            Err(_) => return Ok(None),
+        };
+        let secret = fs::read_to_string(&path)
+            .with_context(|| format!("couldn't read the secret {} from {}", name, path))?;
+        Ok(Some(secret))
+    }
+}
+
+/// Reads the secret `<NAME>` from the output of the command in the environment variable `<NAME>_COMMAND`.
+pub struct CommandProvider;
+
+impl SecretProvider for CommandProvider {
+    fn secret(&self, name: &str) -> Result<Option<String>> {
+        let command = match std::env::var(format!("{}_COMMAND", name)) {
+            Ok(command) => command,
+            Err(_) => return Ok(None),
+        };
+        audit::command(None, "sh", &["-c", &command]);
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .output()
+            .with_context(|| format!("couldn't run the command of the secret {}", name))?;
+        // (the output is the secret, only the error is worth showing)
+        if !output.status.success() {
+            bail!(
+                "the command of the secret {} failed: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(Some(String::from_utf8(output.stdout)?))
+    }
+}
+
+/// Asks several providers in turn, the first one having the secret wins.
+pub struct Chain(pub Vec<Box<dyn SecretProvider>>);
+
+impl SecretProvider for Chain {
+    fn secret(&self, name: &str) -> Result<Option<String>> {
+        for provider in &self.0 {
+            if let Some(secret) = provider.secret(name)? {
+                return Ok(Some(secret));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The provider used unless another one is set: the environment, then files, then commands.
+pub fn default_provider() -> Chain {
+    Chain(vec![
+        Box::new(EnvProvider),
+        Box::new(FileProvider),
+        Box::new(CommandProvider),
+    ])
+}
+
+/// The provider of the process.
+static PROVIDER: OnceCell<Box<dyn SecretProvider>> = OnceCell::new();
+
+/// Replaces the default provider, returning false if a provider is already in use
+/// (it has to be set before the first secret is read, e.g. at the start of the program).
+pub fn set_provider(provider: Box<dyn SecretProvider>) -> bool {
+    PROVIDER.set(provider).is_ok()
+}
+
+/// Returns the provider of the process (the default one if none was set).
+fn provider() -> &'static dyn SecretProvider {
+    PROVIDER
+        .get_or_init(|| Box::new(default_provider()))
+        .as_ref()
+}
+
+/// Returns a secret (trimmed, none if it is empty). Errors of the provider are logged.
+pub fn secret(name: &str) -> Option<String> {
+    from_provider(provider(), name)
+}
+
+fn from_provider(provider: &dyn SecretProvider, name: &str) -> Option<String> {
+    match provider.secret(name) {
+        Ok(secret) => secret
+            .map(|secret| secret.trim().to_string())
+            .filter(|secret| !secret.is_empty()),
+        Err(e) => {
+            warn!("couldn't get the secret {}: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Returns the name of the variable cargo reads the token of a registry from
+/// (e.g. `CARGO_REGISTRIES_MY_REGISTRY_TOKEN` for `my-registry`).
+pub fn registry_token_var(registry: &str) -> String {
+    format!(
+        "CARGO_REGISTRIES_{}_TOKEN",
+        registry.to_uppercase().replace('-', "_")
+    )
+}
+
+/// Sets the tokens of the registries listed in `WHACKADEP_REGISTRIES` in the environment of the process
+/// (not of the CI job), so that the cargo commands run by the analysis can fetch private crates.
+pub fn export_registry_tokens() {
+    let registries = std::env::var(REGISTRIES_VAR).unwrap_or_default();
+    for registry in registries
+        .split(',')
+        .map(str::trim)
+        .filter(|registry| !registry.is_empty())
+    {
+        let var = registry_token_var(registry);
+        match secret(&var) {
+            Some(token) => {
+                info!("using the token of the registry {}", registry);
+                std::env::set_var(var, token);
+            }
+            None => warn!("no token found for the registry {} ({})", registry, var),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_providers() {
+        // (the variables are unique to this test, as tests run concurrently)
+        std::env::set_var("WHACKADEP_TEST_SECRET", "from-env");
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret");
+        fs::write(&path, "from-file\n").unwrap();
+        std::env::set_var("WHACKADEP_TEST_SECRET_FILE", &path);
+        std::env::set_var("WHACKADEP_TEST_OTHER_FILE", &path);
+        std::env::set_var("WHACKADEP_TEST_COMMAND_COMMAND", "echo from-command");
+        std::env::set_var("WHACKADEP_TEST_FAILING_COMMAND", "exit 1");
+
+        let provider = default_provider();
+        let secret = |name| from_provider(&provider, name);
+        // (the environment comes first)
+        assert_eq!(secret("WHACKADEP_TEST_SECRET").as_deref(), Some("from-env"));
+        assert_eq!(secret("WHACKADEP_TEST_OTHER").as_deref(), Some("from-file"));
+        assert_eq!(
+            secret("WHACKADEP_TEST_COMMAND").as_deref(),
+            Some("from-command")
+        );
+        assert_eq!(secret("WHACKADEP_TEST_FAILING"), None);
+        assert_eq!(secret("WHACKADEP_TEST_MISSING"), None);
+
+        // a callback
+        let callback =
+            |name: &str| -> Result<Option<String>> { Ok(Some(format!("{} from the vault", name))) };
+        assert_eq!(
+            from_provider(&callback, "GITHUB_TOKEN").as_deref(),
+            Some("GITHUB_TOKEN from the vault")
+        );
+    }
+
+    #[test]
+    fn test_registry_token_var() {
+        assert_eq!(
+            registry_token_var("my-registry"),
+            "CARGO_REGISTRIES_MY_REGISTRY_TOKEN"
+        );
+    }
+}
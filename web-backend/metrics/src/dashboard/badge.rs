@@ -0,0 +1,125 @@
+//! This module renders a badge summarizing the health of the dependencies of an analysis
+//! (e.g. `deps | 3 advisories / 212 crates`), as a self-contained SVG in the style of shields.io,
+//! so that projects can embed it in their README from a CI artifact or from the `/badge` route.
+
+use semver::Version;
+use std::collections::BTreeSet;
+
+use super::{count_advisories, escape};
+use crate::analysis::Analysis;
+
+/// the label of the health badge
+const LABEL: &str = "deps";
+
+// the colors of shields.io
+const GREEN: &str = "#4c1";
+const YELLOW: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const GREY: &str = "#9f9f9f";
+
+/// Approximates the width of a text in Verdana 11px (the font of the badges), in pixels.
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 13 / 2
+}
+
+/// Renders a flat badge.
+pub fn svg(label: &str, message: &str, color: &str) -> String {
+    let label_width = text_width(label) + 10;
+    let message_width = text_width(message) + 10;
+    let width = label_width + message_width;
+    let (label, message, color) = (escape(label), escape(message), escape(color));
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+<title>{label}: {message}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text><text x="{label_x}" y="14">{label}</text>
+<text x="{message_x}" y="15" fill="#010101" fill-opacity=".3">{message}</text><text x="{message_x}" y="14">{message}</text>
+</g>
+</svg>
+"##,
+        width = width,
+        label_width = label_width,
+        message_width = message_width,
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+        label = label,
+        message = message,
+        color = color
+    )
+}
+
+/// Renders the health badge of a repository without analysis (or whose analysis can't be read).
+pub fn unknown() -> String {
+    svg(LABEL, "unknown", GREY)
+}
+
+/// Summarizes the health of dependencies in the message and the color of a badge:
+/// red if a vulnerability affects them, yellow if they only have warnings (unmaintained, yanked, etc.), green otherwise.
+fn summary(vulnerabilities: usize, advisories: usize, crates: usize) -> (String, &'static str) {
+    let message = format!(
+        "{} {} / {} crates",
+        advisories,
+        if advisories == 1 {
+            "advisory"
+        } else {
+            "advisories"
+        },
+        crates
+    );
+    let color = if vulnerabilities > 0 {
+        RED
+    } else if advisories > 0 {
+        YELLOW
+    } else {
+        GREEN
+    };
+    (message, color)
+}
+
+/// Renders the health badge of an analysis (e.g. `deps | 3 advisories / 212 crates`),
+/// counting the RUSTSEC advisories (vulnerabilities and warnings) and the distinct crate versions.
+pub fn health(analysis: &Analysis) -> String {
+    let rust_analysis = analysis.rust_dependencies();
+    let crates: BTreeSet<(&str, &Version)> = rust_analysis
+        .dependencies()
+        .iter()
+        .map(|dependency| (dependency.name(), dependency.version()))
+        .collect();
+    let (message, color) = summary(
+        rust_analysis.rustsec().vulnerabilities().len(),
+        count_advisories(analysis),
+        crates.len(),
+    );
+    svg(LABEL, &message, color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_badge() {
+        assert_eq!(
+            summary(1, 3, 212),
+            ("3 advisories / 212 crates".to_string(), RED)
+        );
+        assert_eq!(
+            summary(0, 1, 212),
+            ("1 advisory / 212 crates".to_string(), YELLOW)
+        );
+        assert_eq!(
+            summary(0, 0, 0),
+            ("0 advisories / 0 crates".to_string(), GREEN)
+        );
+
+        let badge = svg("deps", "3 advisories / 212 crates", RED);
+        assert!(badge.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"208\""));
+        assert!(badge.contains("<title>deps: 3 advisories / 212 crates</title>"));
+        assert!(badge.contains("fill=\"#e05d44\""));
+        // (the texts are escaped)
+        assert!(svg("a<b", "c", GREEN).contains("<title>a&lt;b: c</title>"));
+    }
+}
@@ -0,0 +1,127 @@
+//! This module renders simple line charts as SVG,
+//! so that trends can be displayed without any javascript library.
+
+use super::escape;
+use std::fmt::Write;
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 200.0;
+const MARGIN: f64 = 40.0;
+
+/// colors used for the different series of a chart
+const COLORS: [&str; 4] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728"];
+
+/// A named series of values to plot.
+pub struct Series<'a> {
+    pub name: &'a str,
+    pub values: Vec<f64>,
+}
+
+/// Renders a line chart of several series sharing the same x-axis labels.
+pub fn line_chart(title: &str, labels: &[String], series: &[Series]) -> String {
+    let max = series
+        .iter()
+        .flat_map(|series| series.values.iter().copied())
+        .fold(0.0_f64, f64::max);
+    // avoid dividing by zero with flat charts
+    let max = if max > 0.0 { max } else { 1.0 };
+
+    let points = labels.len().max(2) - 1;
+    let x = |index: usize| MARGIN + (WIDTH - 2.0 * MARGIN) * index as f64 / points as f64;
+    let y = |value: f64| HEIGHT - MARGIN - (HEIGHT - 2.0 * MARGIN) * value / max;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-size=\"10\">",
+        WIDTH, HEIGHT, WIDTH, HEIGHT
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{}\" y=\"15\" font-size=\"12\" font-weight=\"bold\">{}</text>",
+        MARGIN,
+        escape(title)
+    );
+
+    // axes
+    let _ = writeln!(
+        svg,
+        "<line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/><line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\" stroke=\"#999\"/>",
+        m = MARGIN,
+        b = HEIGHT - MARGIN,
+        r = WIDTH - MARGIN
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"5\" y=\"{}\">{}</text><text x=\"5\" y=\"{}\">0</text>",
+        MARGIN,
+        max,
+        HEIGHT - MARGIN
+    );
+
+    // first and last labels
+    if let (Some(first), Some(last)) = (labels.first(), labels.last()) {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\">{}</text><text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            MARGIN,
+            HEIGHT - MARGIN + 15.0,
+            escape(first),
+            WIDTH - MARGIN,
+            HEIGHT - MARGIN + 15.0,
+            escape(last)
+        );
+    }
+
+    // series
+    for (index, (series, color)) in series.iter().zip(COLORS.iter().cycle()).enumerate() {
+        let points: Vec<String> = series
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("{:.1},{:.1}", x(i), y(*value)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            color,
+            points.join(" ")
+        );
+        // legend
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" fill=\"{}\">{}</text>",
+            WIDTH - MARGIN - 150.0,
+            15.0 + 12.0 * index as f64,
+            color,
+            escape(series.name)
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_chart() {
+        let labels = vec!["2021-01-01".to_string(), "2021-02-01".to_string()];
+        let series = vec![Series {
+            name: "dependencies",
+            values: vec![10.0, 20.0],
+        }];
+        let svg = line_chart("trend", &labels, &series);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<polyline"));
+        assert!(svg.contains("dependencies"));
+    }
+
+    #[test]
+    fn test_line_chart_empty() {
+        let svg = line_chart("trend", &[], &[]);
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
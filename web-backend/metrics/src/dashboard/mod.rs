@@ -0,0 +1,675 @@
+//! This module generates a static HTML dashboard from one or several analyses.
+//! The result is a single self-contained `index.html` (no server, no database)
+//! that can be published from CI (e.g. on GitHub Pages),
+//! next to a badge summarizing the health of the dependencies (see [`badge`]).
+
+use anyhow::{bail, Result};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::analysis::Analysis;
+use crate::locale::Locale;
+use crate::rust::{manifest_lints::LintKind, versions, DependencyInfo, Update};
+use crate::verbosity::Verbosity;
+
+pub mod badge;
+pub mod chart;
+pub mod table;
+
+use chart::{line_chart, Series};
+use table::{Align, Cell, Table};
+
+/// The style of the dashboard
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+th { background: #333; color: #fff; cursor: pointer; }
+tr:nth-child(even) { background: #f4f4f4; }
+.stats { display: flex; gap: 1em; margin-bottom: 2em; }
+.stats div { background: #f4f4f4; padding: 1em 2em; }
+"#;
+
+/// A tiny script that makes every table sortable by clicking on its headers
+const SORT_SCRIPT: &str = r#"
+document.querySelectorAll("th").forEach((th) => th.addEventListener("click", () => {
+  const table = th.closest("table");
+  const index = Array.from(th.parentNode.children).indexOf(th);
+  const asc = !(th.dataset.asc === "true");
+  th.dataset.asc = asc;
+  const rows = Array.from(table.querySelectorAll("tbody tr"));
+  rows.sort((a, b) => {
+    const x = a.children[index].innerText, y = b.children[index].innerText;
+    const cmp = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+    return asc ? cmp : -cmp;
+  });
+  rows.forEach((row) => table.querySelector("tbody").appendChild(row));
+}));
+"#;
+
+/// Writes the dashboard of the given analyses in `out_dir/index.html`,
+/// and the health badge of the latest one in `out_dir/badge.svg`.
+/// The analyses are expected to be for the same repository,
+/// the latest one is displayed in details and the others are used for trends.
+/// Dates and numbers are formatted according to `locale`.
+pub fn generate(
+    analyses: &[Analysis],
+    out_dir: &Path,
+    locale: &Locale,
+    verbosity: Verbosity,
+) -> Result<()> {
+    let html = render(analyses, locale, verbosity)?;
+    fs::create_dir_all(out_dir)?;
+    fs::write(out_dir.join("index.html"), html)?;
+    if let Some(latest) = analyses.iter().max_by_key(|analysis| analysis.timestamp()) {
+        fs::write(out_dir.join("badge.svg"), badge::health(latest))?;
+    }
+    Ok(())
+}
+
+/// Renders the dashboard of the given analyses as an HTML page
+/// (without the history with [`Verbosity::Minimal`],
+/// and with every dependency and its raw metrics with [`Verbosity::Full`]).
+pub fn render(analyses: &[Analysis], locale: &Locale, verbosity: Verbosity) -> Result<String> {
+    // sort analyses by date
+    let mut analyses: Vec<&Analysis> = analyses.iter().collect();
+    analyses.sort_by_key(|analysis| analysis.timestamp());
+    let latest = match analyses.last() {
+        Some(latest) => *latest,
+        None => bail!("no analysis to render"),
+    };
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>")?;
+    writeln!(html, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(
+        html,
+        "<title>whackadep - {}</title>",
+        escape(latest.repository())
+    )?;
+    writeln!(html, "<style>{}</style></head><body>", STYLE)?;
+    writeln!(html, "<h1>{}</h1>", escape(latest.repository()))?;
+    writeln!(
+        html,
+        "<p>commit <code>{}</code> analyzed on {}</p>",
+        escape(latest.commit().trim()),
+        locale.datetime(latest.timestamp())
+    )?;
+
+    render_statistics(&mut html, latest, locale)?;
+    render_advisories(&mut html, latest)?;
+    render_updates(&mut html, latest)?;
+    if verbosity != Verbosity::Minimal {
+        render_slimming(&mut html, latest)?;
+        render_namespaces(&mut html, latest)?;
+        render_proc_macros(&mut html, latest)?;
+        render_manifest_lints(&mut html, latest)?;
+        render_version_conflicts(&mut html, latest)?;
+    }
+    if verbosity == Verbosity::Full {
+        render_dependencies(&mut html, latest)?;
+    }
+    if verbosity != Verbosity::Minimal {
+        render_history(&mut html, &analyses, locale)?;
+    }
+
+    writeln!(html, "<script>{}</script>", SORT_SCRIPT)?;
+    writeln!(html, "</body></html>")?;
+    Ok(html)
+}
+
+/// Counts (direct, transitive, dev) dependencies of an analysis.
+fn count_dependencies(analysis: &Analysis) -> (usize, usize, usize) {
+    let dependencies = analysis.rust_dependencies().dependencies();
+    let direct = dependencies
+        .iter()
+        .filter(|dep| !dep.dev() && dep.direct())
+        .count();
+    let transitive = dependencies
+        .iter()
+        .filter(|dep| !dep.dev() && !dep.direct())
+        .count();
+    let dev = dependencies
+        .iter()
+        .filter(|dep| dep.dev() && dep.direct())
+        .count();
+    (direct, transitive, dev)
+}
+
+/// Counts the RUSTSEC advisories (vulnerabilities and warnings) of an analysis.
+fn count_advisories(analysis: &Analysis) -> usize {
+    let rustsec = analysis.rust_dependencies().rustsec();
+    let warnings: usize = rustsec.warnings().values().map(Vec::len).sum();
+    rustsec.vulnerabilities().len() + warnings
+}
+
+/// Counts the `unsafe` keywords that the pending updates of an analysis would add and remove.
+/// (the analyses only scan the updated crates, not the whole dependency graph,
+/// so the history can't show the unsafe code of the dependencies itself)
+fn count_unsafe_changes(analysis: &Analysis) -> (usize, usize) {
+    let mut added = 0;
+    let mut removed = 0;
+    for dependency in analysis.rust_dependencies().dependencies() {
+        if let Some(update) = dependency.update() {
+            for change in update.unsafe_changes() {
+                added += change.unsafe_after.saturating_sub(change.unsafe_before);
+                removed += change.unsafe_before.saturating_sub(change.unsafe_after);
+            }
+        }
+    }
+    (added, removed)
+}
+
+fn render_statistics(html: &mut String, analysis: &Analysis, locale: &Locale) -> Result<()> {
+    let (direct, transitive, dev) = count_dependencies(analysis);
+    writeln!(html, "<div class=\"stats\">")?;
+    writeln!(
+        html,
+        "<div><strong>{}</strong> non-dev direct dependencies</div>",
+        locale.number(direct as u64)
+    )?;
+    writeln!(
+        html,
+        "<div><strong>{}</strong> non-dev transitive dependencies</div>",
+        locale.number(transitive as u64)
+    )?;
+    writeln!(
+        html,
+        "<div><strong>{}</strong> direct dev dependencies</div>",
+        locale.number(dev as u64)
+    )?;
+    writeln!(
+        html,
+        "<div><strong>{}</strong> RUSTSEC advisories</div>",
+        locale.number(count_advisories(analysis) as u64)
+    )?;
+    let build_time = analysis.rust_dependencies().build_time();
+    if build_time.crates > 0 {
+        writeln!(
+            html,
+            "<div title=\"{}\"><strong>{}</strong> crates running at build time</div>",
+            escape(&build_time.describe()),
+            locale.number(build_time.crates as u64)
+        )?;
+    }
+    writeln!(html, "</div>")?;
+    Ok(())
+}
+
+fn render_advisories(html: &mut String, analysis: &Analysis) -> Result<()> {
+    let rustsec = analysis.rust_dependencies().rustsec();
+    writeln!(html, "<h2>RUSTSEC advisories</h2>")?;
+    let mut table = Table::new()
+        .column("id", Align::Left)
+        .column("kind", Align::Left)
+        .column("crate", Align::Left)
+        .column("version", Align::Left)
+        .column("title", Align::Left)
+        .column("reachability", Align::Left)
+        .column("affected functions", Align::Left);
+    for vuln in rustsec.vulnerabilities() {
+        let id = vuln.advisory.id.to_string();
+        let reachability = analysis
+            .rust_dependencies()
+            .reachability()
+            .iter()
+            .find(|reachability| {
+                reachability.id == id && reachability.package == vuln.package.name.as_str()
+            })
+            .map(|reachability| Cell::text(reachability.reachability.as_str()))
+            .unwrap_or(Cell::Empty);
+        // (matched against the update of the vulnerable dependency, if there is one)
+        let functions: Vec<String> = analysis
+            .rust_dependencies()
+            .dependencies()
+            .iter()
+            .filter(|dependency| {
+                dependency.name() == vuln.package.name.as_str()
+                    && dependency.version().to_string() == vuln.package.version.to_string()
+            })
+            .filter_map(|dependency| dependency.update())
+            .flat_map(|update| update.advisory_functions())
+            .filter(|function| function.advisory == id)
+            .map(|function| function.describe())
+            .collect();
+        table.row(vec![
+            advisory_link(&id),
+            Cell::text("vulnerability"),
+            Cell::text(&vuln.package.name),
+            Cell::text(&vuln.package.version),
+            Cell::text(&vuln.advisory.title),
+            reachability,
+            Cell::text(&functions.join(", ")),
+        ]);
+    }
+    for (kind, warnings) in rustsec.warnings() {
+        let kind = format!("{:?}", kind).to_lowercase();
+        for warning in warnings {
+            let (id, title) = match &warning.advisory {
+                Some(advisory) => (
+                    advisory_link(&advisory.id.to_string()),
+                    Cell::text(&advisory.title),
+                ),
+                None => (Cell::Empty, Cell::Empty),
+            };
+            table.row(vec![
+                id,
+                Cell::text(&kind),
+                Cell::text(&warning.package.name),
+                Cell::text(&warning.package.version),
+                title,
+            ]);
+        }
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+fn render_updates(html: &mut String, analysis: &Analysis) -> Result<()> {
+    let updatable: Vec<(&DependencyInfo, &Update)> = analysis
+        .rust_dependencies()
+        .dependencies()
+        .iter()
+        .filter_map(|dep| dep.update().map(|update| (dep, update)))
+        .collect();
+    writeln!(html, "<h2>Updates available</h2>")?;
+    let mut table = Table::new()
+        .column("name", Align::Left)
+        .column("type", Align::Left)
+        .column("dev", Align::Center)
+        .column("version", Align::Left)
+        .column("latest version", Align::Left)
+        .column("build.rs changed", Align::Center)
+        .column("affects", Align::Left)
+        .column("tags", Align::Left);
+    for (dependency, update) in updatable {
+        let latest = update
+            .versions()
+            .last()
+            .map(versions::label)
+            .unwrap_or_default();
+        table.row(vec![
+            Cell::text(dependency.name()),
+            Cell::text(if dependency.direct() {
+                "direct"
+            } else {
+                "transitive"
+            }),
+            Cell::check(dependency.dev()),
+            Cell::text(dependency.version()),
+            Cell::text(latest),
+            Cell::check(update.build_rs()),
+            Cell::text(dependency.targets().join(", ")),
+            Cell::text(dependency.tags().join(", ")),
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+/// Renders the direct dependencies that could be declared with `default-features = false`.
+fn render_slimming(html: &mut String, analysis: &Analysis) -> Result<()> {
+    let dependencies: Vec<&DependencyInfo> = analysis
+        .rust_dependencies()
+        .dependencies()
+        .iter()
+        .filter(|dependency| dependency.slimming().is_some())
+        .collect();
+    if dependencies.is_empty() {
+        return Ok(());
+    }
+    writeln!(html, "<h2>Default features</h2>")?;
+    writeln!(
+        html,
+        "<p>Declaring these dependencies with <code>default-features = false</code> \
+        (and the features the workspace uses) would compile fewer crates.</p>"
+    )?;
+    let mut table = Table::new()
+        .column("name", Align::Left)
+        .column("version", Align::Left)
+        .column("unrequested default features", Align::Left)
+        .column("dropped crates", Align::Right)
+        .column("dropped lines", Align::Right)
+        .column("crates", Align::Left);
+    for dependency in dependencies {
+        let slimming = match dependency.slimming() {
+            Some(slimming) => slimming,
+            None => continue,
+        };
+        table.row(vec![
+            Cell::text(dependency.name()),
+            Cell::text(dependency.version()),
+            Cell::text(slimming.default_features.join(", ")),
+            Cell::text(slimming.dropped.len()),
+            Cell::text(slimming.dropped_lines),
+            Cell::text(slimming.dropped.join(", ")),
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+/// Renders the procedural macros, which run at build time with the privileges of the build.
+fn render_proc_macros(html: &mut String, analysis: &Analysis) -> Result<()> {
+    let proc_macros = analysis.rust_dependencies().proc_macros();
+    if proc_macros.is_empty() {
+        return Ok(());
+    }
+    writeln!(html, "<h2>Procedural macros</h2>")?;
+    writeln!(
+        html,
+        "<p>{} procedural macros run at build time, with full access to the build machine.</p>",
+        proc_macros.len()
+    )?;
+    let mut table = Table::new()
+        .column("name", Align::Left)
+        .column("version", Align::Left)
+        .column("type", Align::Left)
+        .column("dev", Align::Center)
+        .column("dependents", Align::Left)
+        .column("linked by", Align::Left)
+        .column("owners", Align::Left);
+    for proc_macro in proc_macros {
+        table.row(vec![
+            Cell::text(&proc_macro.name),
+            Cell::text(&proc_macro.version),
+            Cell::text(if proc_macro.direct {
+                "direct"
+            } else {
+                "transitive"
+            }),
+            Cell::check(proc_macro.dev),
+            Cell::text(proc_macro.dependents.join(", ")),
+            Cell::text(proc_macro.linked_by.join(", ")),
+            Cell::text(proc_macro.owners.join(", ")),
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+/// the number of namespaces shown on the dashboard (the ones with the most code)
+const NAMESPACES: usize = 10;
+
+/// Renders the upstream organizations and publishers the workspace depends on the most.
+fn render_namespaces(html: &mut String, analysis: &Analysis) -> Result<()> {
+    let namespaces = analysis.rust_dependencies().namespaces();
+    if namespaces.is_empty() {
+        return Ok(());
+    }
+    let total_lines: usize = namespaces.iter().map(|ns| ns.rust_lines).sum();
+    writeln!(html, "<h2>Upstream namespaces</h2>")?;
+    writeln!(
+        html,
+        "<p>The dependencies come from {} organizations or publishers, the ones publishing the most code are trusted the most.</p>",
+        namespaces.len()
+    )?;
+    let mut table = Table::new()
+        .column("namespace", Align::Left)
+        .column("kind", Align::Left)
+        .column("crates", Align::Right)
+        .column("lines", Align::Right)
+        .column("share of lines", Align::Right)
+        .column("unsafe", Align::Right);
+    for namespace in namespaces.iter().take(NAMESPACES) {
+        let share = if total_lines > 0 {
+            format!("{}%", namespace.rust_lines * 100 / total_lines)
+        } else {
+            String::new()
+        };
+        table.row(vec![
+            Cell::strong(&namespace.name),
+            Cell::text(namespace.kind.as_str()),
+            Cell::text(namespace.crates.len()),
+            Cell::text(namespace.rust_lines),
+            Cell::text(share),
+            Cell::text(namespace.unsafe_keywords),
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+/// Renders the dependency declarations of the manifests to fix, with the suggested fixes.
+fn render_manifest_lints(html: &mut String, analysis: &Analysis) -> Result<()> {
+    let lints = analysis.rust_dependencies().manifest_lints();
+    if lints.is_empty() {
+        return Ok(());
+    }
+    writeln!(html, "<h2>Manifests</h2>")?;
+    writeln!(
+        html,
+        "<p>These declarations make the build depend on when it happens \
+        (<code>/manifest_lints?format=patch</code> serves the fixes as patches).</p>"
+    )?;
+    let mut table = Table::new()
+        .column("manifest", Align::Left)
+        .column("dependency", Align::Left)
+        .column("finding", Align::Left)
+        .column("found", Align::Left)
+        .column("suggested", Align::Left);
+    for lint in lints {
+        let finding = match lint.kind {
+            LintKind::Wildcard => "wildcard requirement",
+            LintKind::UnpinnedGit => "git branch",
+            LintKind::DivergentRequirement => "divergent requirement",
+        };
+        table.row(vec![
+            Cell::text(&lint.manifest),
+            Cell::text(format!("{} [{}]", lint.dependency, lint.section)),
+            Cell::text(finding),
+            Cell::code(&lint.found),
+            match &lint.suggested {
+                Some(suggested) => Cell::code(suggested),
+                None => Cell::text(""),
+            },
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+/// Renders the crates used in conflicting versions or sources, with what each conflict means.
+fn render_version_conflicts(html: &mut String, analysis: &Analysis) -> Result<()> {
+    let conflicts = analysis.rust_dependencies().version_conflicts();
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    writeln!(html, "<h2>Version conflicts</h2>")?;
+    let mut table = Table::new()
+        .column("crate", Align::Left)
+        .column("conflict", Align::Left)
+        .column("versions", Align::Left)
+        .column("explanation", Align::Left);
+    for conflict in conflicts {
+        table.row(vec![
+            Cell::text(&conflict.name),
+            Cell::text(conflict.kind.as_str()),
+            Cell::text(conflict.versions.join(", ")),
+            Cell::text(&conflict.explanation),
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+fn render_dependencies(html: &mut String, analysis: &Analysis) -> Result<()> {
+    writeln!(html, "<h2>Dependencies</h2>")?;
+    let mut table = Table::new()
+        .column("name", Align::Left)
+        .column("version", Align::Left)
+        .column("type", Align::Left)
+        .column("dev", Align::Center)
+        .column("license", Align::Left)
+        .column("features", Align::Left)
+        .column("platforms", Align::Left)
+        .column("linked by", Align::Left)
+        .column("pulled in by", Align::Left)
+        .column("code owners", Align::Left)
+        .column("tags", Align::Left)
+        .column("security policy", Align::Left)
+        .column("fuzz targets", Align::Left)
+        .column("repository redirect", Align::Left);
+    for dependency in analysis.rust_dependencies().dependencies() {
+        table.row(vec![
+            Cell::text(dependency.name()),
+            Cell::text(dependency.version()),
+            Cell::text(if dependency.direct() {
+                "direct"
+            } else {
+                "transitive"
+            }),
+            Cell::check(dependency.dev()),
+            Cell::text(dependency.license().unwrap_or_default()),
+            Cell::text(dependency.features().join(", ")),
+            Cell::text(dependency.platforms().join(", ")),
+            Cell::text(dependency.linked_by().join(", ")),
+            Cell::text(dependency.introduced_by().join(", ")),
+            Cell::text(dependency.code_owners().join(", ")),
+            Cell::text(dependency.tags().join(", ")),
+            Cell::text(
+                dependency
+                    .security_policy()
+                    .map(|policy| policy.describe())
+                    .unwrap_or_default(),
+            ),
+            Cell::text(
+                dependency
+                    .fuzz_targets()
+                    .map(|targets| targets.join(", "))
+                    .unwrap_or_default(),
+            ),
+            Cell::text(
+                dependency
+                    .repository_redirect()
+                    .map(|redirect| redirect.describe())
+                    .unwrap_or_default(),
+            ),
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+fn render_history(html: &mut String, analyses: &[&Analysis], locale: &Locale) -> Result<()> {
+    writeln!(html, "<h2>History</h2>")?;
+
+    // charts
+    let labels: Vec<String> = analyses
+        .iter()
+        .map(|analysis| locale.date(analysis.timestamp().naive_utc().date()))
+        .collect();
+    let counts: Vec<(usize, usize, usize)> = analyses
+        .iter()
+        .map(|analysis| count_dependencies(analysis))
+        .collect();
+    let dependencies = [
+        Series {
+            name: "direct",
+            values: counts.iter().map(|c| c.0 as f64).collect(),
+        },
+        Series {
+            name: "transitive",
+            values: counts.iter().map(|c| c.1 as f64).collect(),
+        },
+        Series {
+            name: "dev",
+            values: counts.iter().map(|c| c.2 as f64).collect(),
+        },
+    ];
+    html.push_str(&line_chart("dependencies", &labels, &dependencies));
+    let advisories = [Series {
+        name: "advisories",
+        values: analyses
+            .iter()
+            .map(|analysis| count_advisories(analysis) as f64)
+            .collect(),
+    }];
+    html.push_str(&line_chart("RUSTSEC advisories", &labels, &advisories));
+    // (added and removed are charted apart, as the charts only show positive values)
+    let unsafe_changes: Vec<(usize, usize)> = analyses
+        .iter()
+        .map(|analysis| count_unsafe_changes(analysis))
+        .collect();
+    let unsafe_code = [
+        Series {
+            name: "added",
+            values: unsafe_changes.iter().map(|c| c.0 as f64).collect(),
+        },
+        Series {
+            name: "removed",
+            values: unsafe_changes.iter().map(|c| c.1 as f64).collect(),
+        },
+    ];
+    html.push_str(&line_chart(
+        "unsafe keywords in pending updates",
+        &labels,
+        &unsafe_code,
+    ));
+
+    // raw numbers
+    let mut table = Table::new()
+        .column("date", Align::Left)
+        .column("commit", Align::Left)
+        .column("direct", Align::Right)
+        .column("transitive", Align::Right)
+        .column("dev", Align::Right)
+        .column("advisories", Align::Right)
+        .column("unsafe added", Align::Right)
+        .column("unsafe removed", Align::Right);
+    for analysis in analyses.iter().rev() {
+        let (direct, transitive, dev) = count_dependencies(analysis);
+        let (unsafe_added, unsafe_removed) = count_unsafe_changes(analysis);
+        table.row(vec![
+            Cell::text(locale.datetime(analysis.timestamp())),
+            Cell::code(analysis.commit().trim()),
+            Cell::text(direct),
+            Cell::text(transitive),
+            Cell::text(dev),
+            Cell::text(count_advisories(analysis)),
+            Cell::text(unsafe_added),
+            Cell::text(unsafe_removed),
+        ]);
+    }
+    html.push_str(&table.to_html());
+    Ok(())
+}
+
+fn advisory_link(id: &str) -> Cell {
+    Cell::link(id, format!("https://rustsec.org/advisories/{}.html", id))
+}
+
+/// Escapes text to be included in HTML.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render_empty() {
+        assert!(render(&[], &Locale::default(), Verbosity::default()).is_err());
+    }
+}
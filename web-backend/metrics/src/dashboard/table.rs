@@ -0,0 +1,228 @@
+//! A small table builder rendering typed rows as markdown or HTML,
+//! so that the reports and the dashboard don't concatenate table markup by hand.
+//!
+//! ```
+//! use metrics::dashboard::table::{Align, Cell, Table};
+//!
+//! let mut table = Table::new()
+//!     .column("crate", Align::Left)
+//!     .column("downloads", Align::Right);
+//! table.row(vec![Cell::strong("serde"), Cell::text(42)]);
+//! assert!(table.to_markdown().starts_with("| crate | downloads |\n|:---|---:|\n"));
+//! ```
+
+use std::fmt::Write;
+
+use super::escape;
+
+/// The alignment of a column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Default for Align {
+    fn default() -> Self {
+        Align::Left
+    }
+}
+
+/// A cell of a table, rendered according to its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Strong(String),
+    Code(String),
+    Link {
+        text: String,
+        url: String,
+    },
+    /// a checkmark if true, nothing otherwise
+    Check(bool),
+    Empty,
+}
+
+impl Cell {
+    pub fn text(text: impl ToString) -> Self {
+        Cell::Text(text.to_string())
+    }
+
+    pub fn strong(text: impl ToString) -> Self {
+        Cell::Strong(text.to_string())
+    }
+
+    pub fn code(text: impl ToString) -> Self {
+        Cell::Code(text.to_string())
+    }
+
+    pub fn link(text: impl ToString, url: impl ToString) -> Self {
+        Cell::Link {
+            text: text.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    pub fn check(checked: bool) -> Self {
+        Cell::Check(checked)
+    }
+
+    fn to_markdown(&self) -> String {
+        // (pipes would end the cell, and new lines the row)
+        let clean = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+        match self {
+            Cell::Text(text) => clean(text),
+            Cell::Strong(text) => format!("**{}**", clean(text)),
+            Cell::Code(text) => format!("`{}`", clean(text)),
+            Cell::Link { text, url } => format!("[{}]({})", clean(text), url),
+            Cell::Check(true) => "✓".to_string(),
+            Cell::Check(false) | Cell::Empty => String::new(),
+        }
+    }
+
+    fn to_html(&self) -> String {
+        match self {
+            Cell::Text(text) => escape(text),
+            Cell::Strong(text) => format!("<strong>{}</strong>", escape(text)),
+            Cell::Code(text) => format!("<code>{}</code>", escape(text)),
+            Cell::Link { text, url } => {
+                format!("<a href=\"{}\">{}</a>", escape(url), escape(text))
+            }
+            Cell::Check(true) => "&#10003;".to_string(),
+            Cell::Check(false) | Cell::Empty => String::new(),
+        }
+    }
+}
+
+struct Column {
+    header: String,
+    align: Align,
+}
+
+/// A table with typed rows.
+/// Rows shorter than the header are padded with empty cells, longer rows are truncated.
+#[derive(Default)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a column.
+    pub fn column(mut self, header: impl ToString, align: Align) -> Self {
+        self.columns.push(Column {
+            header: header.to_string(),
+            align,
+        });
+        self
+    }
+
+    /// Adds a row.
+    pub fn row(&mut self, mut cells: Vec<Cell>) {
+        cells.resize(self.columns.len(), Cell::Empty);
+        self.rows.push(cells);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Renders the table as markdown (GitHub flavored).
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        markdown.push('|');
+        for column in &self.columns {
+            let _ = write!(
+                markdown,
+                " {} |",
+                Cell::Text(column.header.clone()).to_markdown()
+            );
+        }
+        markdown.push_str("\n|");
+        for column in &self.columns {
+            markdown.push_str(match column.align {
+                Align::Left => ":---|",
+                Align::Center => ":---:|",
+                Align::Right => "---:|",
+            });
+        }
+        markdown.push('\n');
+        for row in &self.rows {
+            markdown.push('|');
+            for cell in row {
+                let _ = write!(markdown, " {} |", cell.to_markdown());
+            }
+            markdown.push('\n');
+        }
+        markdown
+    }
+
+    /// Renders the table as HTML.
+    pub fn to_html(&self) -> String {
+        let style = |align: Align| match align {
+            Align::Left => "",
+            Align::Center => " style=\"text-align: center\"",
+            Align::Right => " style=\"text-align: right\"",
+        };
+        let mut html = String::new();
+        html.push_str("<table><thead><tr>");
+        for column in &self.columns {
+            let _ = write!(
+                html,
+                "<th{}>{}</th>",
+                style(column.align),
+                escape(&column.header)
+            );
+        }
+        html.push_str("</tr></thead><tbody>\n");
+        for row in &self.rows {
+            html.push_str("<tr>");
+            for (cell, column) in row.iter().zip(&self.columns) {
+                let _ = write!(html, "<td{}>{}</td>", style(column.align), cell.to_html());
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody></table>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let mut table = Table::new()
+            .column("crate", Align::Left)
+            .column("advisory", Align::Center)
+            .column("yanked", Align::Right);
+        table.row(vec![
+            Cell::code("a|b"),
+            Cell::link("RUSTSEC-2020-0001", "https://rustsec.org"),
+            Cell::check(true),
+        ]);
+        table.row(vec![Cell::strong("<c>")]);
+
+        assert_eq!(
+            table.to_markdown(),
+            "| crate | advisory | yanked |\n\
+             |:---|:---:|---:|\n\
+             | `a\\|b` | [RUSTSEC-2020-0001](https://rustsec.org) | ✓ |\n\
+             | **<c>** |  |  |\n"
+        );
+
+        let html = table.to_html();
+        assert!(html.starts_with(
+            "<table><thead><tr><th>crate</th><th style=\"text-align: center\">advisory</th>"
+        ));
+        assert!(html
+            .contains("<td><strong>&lt;c&gt;</strong></td><td style=\"text-align: center\"></td>"));
+        assert!(html.contains("<a href=\"https://rustsec.org\">RUSTSEC-2020-0001</a>"));
+    }
+}
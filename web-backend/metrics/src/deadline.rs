@@ -0,0 +1,115 @@
+//! This module provides a deadline that can be threaded through long analyses,
+//! so that they can be stopped (after some duration, or cancelled manually)
+//! and still return what they have obtained so far.
+
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{sleep_until, Instant};
+
+#[derive(Default)]
+struct Cancellation {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+/// A deadline after which (or a token with which) an analysis can be stopped.
+/// Cloning a deadline returns a handle to the same deadline,
+/// which can be used to cancel the analysis from somewhere else.
+#[derive(Clone, Default)]
+pub struct Deadline {
+    at: Option<Instant>,
+    cancellation: Arc<Cancellation>,
+}
+
+impl Deadline {
+    /// A deadline that never expires (unless cancelled).
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// A deadline that expires after the given duration.
+    pub fn after(duration: Duration) -> Self {
+        Self {
+            at: Some(Instant::now() + duration),
+            ..Default::default()
+        }
+    }
+
+    /// Cancels the analysis right away.
+    pub fn cancel(&self) {
+        self.cancellation.cancelled.store(true, Ordering::SeqCst);
+        self.cancellation.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the deadline has passed or if it was cancelled.
+    pub fn is_expired(&self) -> bool {
+        self.is_cancelled() || matches!(self.at, Some(at) if Instant::now() >= at)
+    }
+
+    /// Waits until the deadline expires.
+    pub async fn expired(&self) {
+        let notified = self.cancellation.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        match self.at {
+            Some(at) => {
+                tokio::select! {
+                    _ = sleep_until(at) => (),
+                    _ = notified => (),
+                }
+            }
+            None => notified.await,
+        }
+    }
+
+    /// Runs a future until it completes, or until the deadline expires.
+    /// Returns `None` if the future could not complete in time.
+    pub async fn run<F: Future>(&self, future: F) -> Option<F::Output> {
+        if self.is_expired() {
+            return None;
+        }
+        tokio::select! {
+            output = future => Some(output),
+            _ = self.expired() => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let deadline = Deadline::none();
+        assert_eq!(deadline.run(async { 1 }).await, Some(1));
+
+        let deadline = Deadline::after(Duration::from_millis(10));
+        let slow = tokio::time::sleep(Duration::from_secs(10));
+        assert!(deadline.run(slow).await.is_none());
+        assert!(deadline.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let deadline = Deadline::none();
+        let handle = deadline.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            handle.cancel();
+        });
+        let slow = tokio::time::sleep(Duration::from_secs(10));
+        assert!(deadline.run(slow).await.is_none());
+        assert!(deadline.is_cancelled());
+    }
+}
@@ -0,0 +1,300 @@
+//! This module renders a digest of the changes observed since the last analysis
+//! (new updates available, new RUSTSEC advisories, advisories withdrawn or amended), in a format suitable for emails:
+//! a plain text version and an HTML version with inline CSS (as most email clients strip stylesheets).
+//!
+//! Sending the digest via SMTP is only available with the `email` feature.
+
+use std::fmt::Write;
+
+use crate::analysis::Analysis;
+use crate::dashboard::escape;
+use crate::locale::Locale;
+use crate::rust::{ChangeSummary, RustSec};
+use crate::verbosity::Verbosity;
+
+const TABLE_STYLE: &str = "border-collapse:collapse;margin-bottom:16px;";
+const CELL_STYLE: &str = "border:1px solid #cccccc;padding:4px 8px;text-align:left;";
+const HEADER_STYLE: &str =
+    "border:1px solid #cccccc;padding:4px 8px;text-align:left;background:#333333;color:#ffffff;";
+
+/// A digest of the changes observed in an analysis.
+pub struct Digest<'a> {
+    analysis: &'a Analysis,
+    changes: Option<&'a ChangeSummary>,
+    locale: &'a Locale,
+    verbosity: Verbosity,
+}
+
+impl<'a> Digest<'a> {
+    /// With [`Verbosity::Minimal`], the digest only counts the changes (see [`Self::subject`]).
+    pub fn new(analysis: &'a Analysis, locale: &'a Locale, verbosity: Verbosity) -> Self {
+        Self {
+            analysis,
+            changes: analysis.rust_dependencies().change_summary(),
+            locale,
+            verbosity,
+        }
+    }
+
+    /// Returns true if nothing changed since the last analysis.
+    pub fn is_empty(&self) -> bool {
+        match self.changes {
+            Some(changes) => {
+                changes.new_updates().is_empty()
+                    && count_advisories(changes.new_rustsec()) == 0
+                    && changes.revised_rustsec().is_empty()
+            }
+            None => true,
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        let (updates, advisories, revised) = match self.changes {
+            Some(changes) => (
+                changes.new_updates().len(),
+                count_advisories(changes.new_rustsec()),
+                changes.revised_rustsec().len(),
+            ),
+            None => (0, 0, 0),
+        };
+        let mut subject = format!(
+            "[whackadep] {}: {} new updates, {} new RUSTSEC advisories",
+            self.analysis.repository(),
+            updates,
+            advisories
+        );
+        if revised > 0 {
+            subject.push_str(&format!(", {} revised", revised));
+        }
+        subject
+    }
+
+    /// Renders the digest as plain text.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "{}", self.subject());
+        let _ = writeln!(
+            text,
+            "commit {} analyzed on {}\n",
+            self.analysis.commit().trim(),
+            self.locale.datetime(self.analysis.timestamp())
+        );
+
+        let changes = match self.changes {
+            Some(changes) if !self.is_empty() => changes,
+            _ => {
+                text.push_str("Nothing changed since the last analysis.\n");
+                return text;
+            }
+        };
+        if self.verbosity == Verbosity::Minimal {
+            return text;
+        }
+
+        let advisories = advisory_rows(changes.new_rustsec());
+        if !advisories.is_empty() {
+            text.push_str("New RUSTSEC advisories:\n");
+            for (id, package, title) in advisories {
+                let _ = writeln!(text, "- {} ({}): {}", id, package, title);
+            }
+            text.push('\n');
+        }
+
+        if !changes.revised_rustsec().is_empty() {
+            text.push_str("Revised RUSTSEC advisories (withdrawn or amended):\n");
+            for revision in changes.revised_rustsec() {
+                let _ = writeln!(text, "- {}", revision.describe());
+            }
+            text.push('\n');
+        }
+
+        if !changes.new_updates().is_empty() {
+            text.push_str("New updates available:\n");
+            for dependency in changes.new_updates() {
+                let latest = dependency
+                    .update()
+                    .and_then(|update| update.versions().last())
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    text,
+                    "- {} {} -> {}",
+                    dependency.name(),
+                    dependency.version(),
+                    latest
+                );
+            }
+        }
+
+        text
+    }
+
+    /// Renders the digest as HTML with inline CSS.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str("<html><body style=\"font-family:sans-serif;color:#222222;\">\n");
+        let _ = writeln!(
+            html,
+            "<h2 style=\"margin:0 0 8px 0;\">{}</h2>",
+            escape(&self.subject())
+        );
+        let _ = writeln!(
+            html,
+            "<p style=\"color:#666666;\">commit <code>{}</code> analyzed on {}</p>",
+            escape(self.analysis.commit().trim()),
+            self.locale.datetime(self.analysis.timestamp())
+        );
+
+        let changes = match self.changes {
+            Some(changes) if !self.is_empty() => changes,
+            _ => {
+                html.push_str("<p>Nothing changed since the last analysis.</p>\n</body></html>\n");
+                return html;
+            }
+        };
+        if self.verbosity == Verbosity::Minimal {
+            html.push_str("</body></html>\n");
+            return html;
+        }
+
+        let advisories = advisory_rows(changes.new_rustsec());
+        if !advisories.is_empty() {
+            html.push_str("<h3>New RUSTSEC advisories</h3>\n");
+            let _ = writeln!(
+                html,
+                "<table style=\"{t}\"><tr><th style=\"{h}\">id</th><th style=\"{h}\">crate</th><th style=\"{h}\">title</th></tr>",
+                t = TABLE_STYLE,
+                h = HEADER_STYLE
+            );
+            for (id, package, title) in advisories {
+                let _ = writeln!(
+                    html,
+                    "<tr><td style=\"{c}\"><a href=\"https://rustsec.org/advisories/{id}.html\">{id}</a></td><td style=\"{c}\">{}</td><td style=\"{c}\">{}</td></tr>",
+                    escape(&package),
+                    escape(&title),
+                    c = CELL_STYLE,
+                    id = escape(&id)
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !changes.revised_rustsec().is_empty() {
+            html.push_str("<h3>Revised RUSTSEC advisories</h3>\n");
+            html.push_str(
+                "<p>These advisories were withdrawn or amended since they were reported.</p>\n<ul>\n",
+            );
+            for revision in changes.revised_rustsec() {
+                let _ = writeln!(
+                    html,
+                    "<li><a href=\"https://rustsec.org/advisories/{id}.html\">{id}</a>: {}</li>",
+                    escape(&revision.describe()),
+                    id = escape(&revision.finding.id)
+                );
+            }
+            html.push_str("</ul>\n");
+        }
+
+        if !changes.new_updates().is_empty() {
+            html.push_str("<h3>New updates available</h3>\n");
+            let _ = writeln!(
+                html,
+                "<table style=\"{t}\"><tr><th style=\"{h}\">crate</th><th style=\"{h}\">version</th><th style=\"{h}\">latest version</th></tr>",
+                t = TABLE_STYLE,
+                h = HEADER_STYLE
+            );
+            for dependency in changes.new_updates() {
+                let latest = dependency
+                    .update()
+                    .and_then(|update| update.versions().last())
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    html,
+                    "<tr><td style=\"{c}\">{}</td><td style=\"{c}\">{}</td><td style=\"{c}\">{}</td></tr>",
+                    escape(dependency.name()),
+                    dependency.version(),
+                    latest,
+                    c = CELL_STYLE
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body></html>\n");
+        html
+    }
+}
+
+/// Counts the advisories (vulnerabilities and warnings).
+fn count_advisories(rustsec: &RustSec) -> usize {
+    let warnings: usize = rustsec.warnings().values().map(Vec::len).sum();
+    rustsec.vulnerabilities().len() + warnings
+}
+
+/// Returns the (id, package, title) of every advisory.
+fn advisory_rows(rustsec: &RustSec) -> Vec<(String, String, String)> {
+    let vulnerabilities = rustsec.vulnerabilities().iter().map(|vuln| {
+        (
+            vuln.advisory.id.to_string(),
+            format!("{} {}", vuln.package.name, vuln.package.version),
+            vuln.advisory.title.clone(),
+        )
+    });
+    let warnings = rustsec
+        .warnings()
+        .values()
+        .flatten()
+        .map(|warning| match &warning.advisory {
+            Some(advisory) => (
+                advisory.id.to_string(),
+                format!("{} {}", warning.package.name, warning.package.version),
+                advisory.title.clone(),
+            ),
+            None => (
+                format!("{:?}", warning.kind).to_lowercase(),
+                format!("{} {}", warning.package.name, warning.package.version),
+                String::new(),
+            ),
+        });
+    vulnerabilities.chain(warnings).collect()
+}
+
+/// Sends digests via SMTP.
+/// The SMTP server is configured via the `SMTP_HOST`, `SMTP_USERNAME`, `SMTP_PASSWORD`
+/// and `SMTP_FROM` environment variables.
+#[cfg(feature = "email")]
+pub mod email {
+    use super::Digest;
+    use anyhow::{Context, Result};
+    use lettre::{
+        message::MultiPart, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+        AsyncTransport, Message, Tokio1Executor,
+    };
+    use std::env;
+
+    /// Sends a digest (plain text and HTML alternatives) to the given recipients.
+    pub async fn send(digest: &Digest<'_>, recipients: &[String]) -> Result<()> {
+        let host = env::var("SMTP_HOST").context("SMTP_HOST environment variable is missing")?;
+        let from = env::var("SMTP_FROM").context("SMTP_FROM environment variable is missing")?;
+
+        let mut builder = Message::builder()
+            .from(from.parse()?)
+            .subject(digest.subject());
+        for recipient in recipients {
+            builder = builder.to(recipient.parse()?);
+        }
+        let message = builder.multipart(MultiPart::alternative_plain_html(
+            digest.to_text(),
+            digest.to_html(),
+        ))?;
+
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?;
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD"))
+        {
+            transport = transport.credentials(Credentials::new(username, password));
+        }
+        transport.build().send(message).await?;
+        Ok(())
+    }
+}
@@ -3,12 +3,16 @@
 //! Ideally this would be implemented with a library (for example, git2),
 //! but ain't nobody got time for that!
 
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
+use chrono::{DateTime, Utc};
 use git2::Repository;
 use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tracing::debug;
 
+use crate::audit;
+use crate::timing::{self, Section};
+
 pub struct Repo {
     pub repo_folder: PathBuf,
 }
@@ -24,11 +28,15 @@ impl Repo {
 
     // clone
     pub async fn clone(url: &str, repo_folder: &Path) -> Result<Self> {
-        let output = Command::new("git")
-            .args(&["clone", "--depth", "1", url])
-            .arg(&repo_folder)
-            .output()
-            .await?;
+        audit::clone(url, repo_folder);
+        let output = timing::time(
+            Section::GitClone,
+            Command::new("git")
+                .args(&["clone", "--depth", "1", url])
+                .arg(&repo_folder)
+                .output(),
+        )
+        .await?;
         debug!("stdout: {}", String::from_utf8_lossy(&output.stdout));
         Ok(Self {
             repo_folder: repo_folder.to_path_buf(),
@@ -38,16 +46,21 @@ impl Repo {
     // performs a pull
     // TODO: since this might change the rust toolchain, do we want to do a rustup update here?
     pub async fn update(&self) -> Result<()> {
-        let output = Command::new("git")
-            .current_dir(&self.repo_folder)
-            .arg("pull")
-            .output()
-            .await?;
+        audit::command(Some(&self.repo_folder), "git", &["pull"]);
+        let output = timing::time(
+            Section::GitClone,
+            Command::new("git")
+                .current_dir(&self.repo_folder)
+                .arg("pull")
+                .output(),
+        )
+        .await?;
         debug!("stdout: {}", String::from_utf8_lossy(&output.stdout));
         Ok(())
     }
 
     pub async fn head(&self) -> Result<String> {
+        audit::command(Some(&self.repo_folder), "git", &["rev-parse", "HEAD"]);
         let output = Command::new("git")
             .current_dir(&self.repo_folder)
             .args(&["rev-parse", "HEAD"])
@@ -55,21 +68,114 @@ impl Repo {
             .await?;
         String::from_utf8(output.stdout).map_err(anyhow::Error::msg)
     }
+
+    // fetches the whole history if the repository was cloned shallowly (see [`Self::clone`])
+    pub async fn unshallow(&self) -> Result<()> {
+        if !self.repo_folder.join(".git").join("shallow").exists() {
+            return Ok(());
+        }
+        audit::command(Some(&self.repo_folder), "git", &["fetch", "--unshallow"]);
+        let output = timing::time(
+            Section::GitClone,
+            Command::new("git")
+                .current_dir(&self.repo_folder)
+                .args(&["fetch", "--unshallow"])
+                .output(),
+        )
+        .await?;
+        ensure!(
+            output.status.success(),
+            "couldn't fetch the history: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Ok(())
+    }
+
+    // lists the commits that changed a file (the oldest first), with their dates
+    pub async fn file_history(&self, path: &str) -> Result<Vec<(String, DateTime<Utc>)>> {
+        let args = ["log", "--reverse", "--format=%H %cI", "--", path];
+        audit::command(Some(&self.repo_folder), "git", &args);
+        let output = Command::new("git")
+            .current_dir(&self.repo_folder)
+            .args(&args)
+            .output()
+            .await?;
+        ensure!(
+            output.status.success(),
+            "couldn't get the history of {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut parts = line.splitn(2, ' ');
+                let commit = parts.next().unwrap_or_default().to_string();
+                let date = DateTime::parse_from_rfc3339(parts.next().unwrap_or_default().trim())
+                    .with_context(|| format!("invalid commit date in {}", line))?;
+                Ok((commit, date.with_timezone(&Utc)))
+            })
+            .collect()
+    }
+
+    // reads a file as of a commit (none if it didn't exist then)
+    pub async fn show_file(&self, commit: &str, path: &str) -> Result<Option<String>> {
+        let object = format!("{}:{}", commit, path);
+        audit::command(Some(&self.repo_folder), "git", &["show", &object]);
+        let output = Command::new("git")
+            .current_dir(&self.repo_folder)
+            .arg("show")
+            .arg(&object)
+            .output()
+            .await?;
+        if !output.status.success() {
+            debug!(
+                "couldn't read {} at {}: {}",
+                path,
+                commit,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return Ok(None);
+        }
+        String::from_utf8(output.stdout)
+            .map(Some)
+            .map_err(anyhow::Error::msg)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing;
     use tempfile::tempdir;
 
-    #[test]
-    fn test_t() {
+    #[tokio::test]
+    async fn test_t() {
+        let origin = tempdir().unwrap();
+        testing::git_repo(
+            origin.path(),
+            &[&[("Cargo.toml", "[package]\nname = \"disco\"\n")]],
+        )
+        .await
+        .unwrap();
         let dir = tempdir().unwrap();
 
         assert!(Repo::new(&dir.path()).is_err());
 
-        Repo::clone("https://github.com/mimoo/disco.git", dir.path());
+        Repo::clone(&origin.path().to_string_lossy(), dir.path())
+            .await
+            .unwrap();
 
         assert!(Repo::new(dir.path()).is_ok());
+        assert_eq!(
+            Repo::new(dir.path())
+                .unwrap()
+                .show_file("HEAD", "Cargo.toml")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("[package]\nname = \"disco\"\n")
+        );
     }
 }
@@ -0,0 +1,382 @@
+//! This module abstracts the HTTP requests made to crates.io and GitHub behind the [`Transport`] trait,
+//! so that tests (of this crate, or of its users) can run without network access or secrets:
+//! [`MockTransport`] serves canned responses,
+//! and [`FixtureTransport`] plays back responses recorded in a directory.
+//!
+//! Setting the `HTTP_FIXTURES` environment variable to a directory makes every request
+//! of an analysis played back from it. Fixtures have the layout of the response cache
+//! (see [`crate::cache`]), so they can be recorded by a prefetch
+//! (see [`crate::rust::RustAnalysis::prefetch`]) and copied from `cache/responses`.
+
+use anyhow::{anyhow, bail, Result};
+use crypto::{digest::Digest, md5::Md5};
+use futures::future::{BoxFuture, FutureExt};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::audit::{self, AuditedTransport};
+use crate::timing::TimedTransport;
+
+/// The environment variable pointing to a directory of recorded responses to play back.
+pub const FIXTURES_VAR: &str = "HTTP_FIXTURES";
+
+/// The response to a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// (names are lowercase)
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// A successful response.
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// A response with no body (e.g. a 404).
+    pub fn empty(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_lowercase(), value.to_string()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns an error if the status is not a success.
+    pub fn error_for_status(self) -> Result<Self> {
+        if (200..300).contains(&self.status) {
+            Ok(self)
+        } else {
+            bail!("HTTP status {}", self.status)
+        }
+    }
+
+    pub fn text(self) -> Result<String> {
+        String::from_utf8(self.body).map_err(anyhow::Error::msg)
+    }
+}
+
+/// Sends HTTP requests.
+pub trait Transport: Send + Sync {
+    /// Sends a GET request with the given headers.
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+    ) -> BoxFuture<'a, Result<Response>>;
+
+    /// Sends a POST request with the given headers and body
+    /// (only needed to authenticate as a GitHub App, see [`crate::common::github_auth`],
+    /// and for the GitHub GraphQL API, see [`crate::common::github_graphql`]).
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        _headers: &'a [(&'a str, String)],
+        _body: &'a [u8],
+    ) -> BoxFuture<'a, Result<Response>> {
+        async move { bail!("this transport can't send POST requests ({})", url) }.boxed()
+    }
+
+    /// Do the requests go over the network?
+    /// (if not, they are neither throttled nor cached)
+    fn is_network(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the transport used by default: the network,
+/// or the recorded responses if [`FIXTURES_VAR`] is set.
+/// Requests over the network are timed (see [`crate::timing`]),
+/// and recorded in the audit log if enabled (see [`crate::audit`]).
+pub fn default_transport(user_agent: &str) -> Result<Arc<dyn Transport>> {
+    if let Some(dir) = std::env::var_os(FIXTURES_VAR) {
+        return Ok(Arc::new(FixtureTransport::new(dir)));
+    }
+    let transport: Arc<dyn Transport> = Arc::new(TimedTransport::new(Arc::new(
+        ReqwestTransport::new(user_agent)?,
+    )));
+    if audit::log_path().is_some() {
+        return Ok(Arc::new(AuditedTransport::new(transport)));
+    }
+    Ok(transport)
+}
+
+/// The maximum length of a key (file names are limited to 255 bytes on most file systems).
+const MAX_KEY_LEN: usize = 200;
+
+/// The key under which the response of an URL is stored (in the cache or in fixtures).
+/// Bytes other than ASCII alphanumerics, `.` and `-` are escaped as `_XX` (their hex value),
+/// so that distinct URLs never share a key.
+/// Keys longer than [`MAX_KEY_LEN`] are truncated, and end with the MD5 hash of the whole URL instead.
+pub fn url_key(url: &str) -> String {
+    let mut key = String::new();
+    for byte in url.trim_start_matches("https://").bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'.' || byte == b'-' {
+            key.push(byte as char);
+        } else {
+            key.push_str(&format!("_{:02X}", byte));
+        }
+    }
+    if key.len() > MAX_KEY_LEN {
+        let mut md5 = Md5::new();
+        md5.input_str(url);
+        let hash = md5.result_str();
+        key.truncate(MAX_KEY_LEN - hash.len() - 1);
+        key.push('-');
+        key.push_str(&hash);
+    }
+    key
+}
+
+/// Sends requests over the network.
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(user_agent: &str) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().user_agent(user_agent).build()?,
+        })
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+    ) -> BoxFuture<'a, Result<Response>> {
+        async move {
+            let mut request = self.client.get(url);
+            for (name, value) in headers {
+                request = request.header(*name, value.as_str());
+            }
+            into_response(request.send().await?).await
+        }
+        .boxed()
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<Response>> {
+        async move {
+            let mut request = self.client.post(url).body(body.to_vec());
+            for (name, value) in headers {
+                request = request.header(*name, value.as_str());
+            }
+            into_response(request.send().await?).await
+        }
+        .boxed()
+    }
+}
+
+async fn into_response(response: reqwest::Response) -> Result<Response> {
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = value.to_str().ok()?;
+            Some((name.as_str().to_string(), value.to_string()))
+        })
+        .collect();
+    let body = response.bytes().await?.to_vec();
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Serves canned responses (to GET and POST requests alike), and records the URLs requested.
+/// Requesting an URL without a response is an error.
+#[derive(Default)]
+pub struct MockTransport {
+    responses: HashMap<String, Response>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_response(mut self, url: &str, response: Response) -> Self {
+        self.responses.insert(url.to_string(), response);
+        self
+    }
+
+    /// The URLs requested so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Transport for MockTransport {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        _headers: &'a [(&'a str, String)],
+    ) -> BoxFuture<'a, Result<Response>> {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(url.to_string());
+        }
+        let response = self
+            .responses
+            .get(url)
+            .cloned()
+            .ok_or_else(|| anyhow!("no mock response for {}", url));
+        async move { response }.boxed()
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        _body: &'a [u8],
+    ) -> BoxFuture<'a, Result<Response>> {
+        self.get(url, headers)
+    }
+
+    fn is_network(&self) -> bool {
+        false
+    }
+}
+
+/// Plays back responses recorded in a directory (one file per URL, see [`url_key`]).
+/// URLs without a recorded response get a 404.
+/// POST requests are played back by URL too (their body is ignored, e.g. a GraphQL query).
+pub struct FixtureTransport {
+    dir: PathBuf,
+}
+
+impl FixtureTransport {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl Transport for FixtureTransport {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        _headers: &'a [(&'a str, String)],
+    ) -> BoxFuture<'a, Result<Response>> {
+        let response = match fs::read(self.dir.join(url_key(url))) {
+            Ok(body) => Response::ok(body),
+            Err(_) => Response::empty(404),
+        };
+        async move { Ok(response) }.boxed()
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        _body: &'a [u8],
+    ) -> BoxFuture<'a, Result<Response>> {
+        self.get(url, headers)
+    }
+
+    fn is_network(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_mock_transport() {
+        let transport = MockTransport::new().with_response(
+            "https://example.com/a",
+            Response::ok("a").with_header("ETag", "\"1\""),
+        );
+        let response = transport.get("https://example.com/a", &[]).await.unwrap();
+        assert_eq!(response.header("etag"), Some("\"1\""));
+        assert_eq!(response.text().unwrap(), "a");
+        assert!(transport.get("https://example.com/b", &[]).await.is_err());
+        assert_eq!(
+            transport.requests(),
+            vec!["https://example.com/a", "https://example.com/b"]
+        );
+    }
+
+    #[test]
+    fn test_url_key() {
+        assert_eq!(
+            url_key("https://crates.io/api/v1/crates/a"),
+            "crates.io_2Fapi_2Fv1_2Fcrates_2Fa"
+        );
+        assert_ne!(
+            url_key("https://example.com/a/b_c"),
+            url_key("https://example.com/a_b/c")
+        );
+        assert_ne!(
+            url_key("https://example.com/a?b"),
+            url_key("https://example.com/a/b")
+        );
+
+        // (long URLs are truncated, and told apart by their hash)
+        let long = format!("https://api.github.com/search?q={}", "a%20b".repeat(100));
+        let key = url_key(&long);
+        assert_eq!(key.len(), MAX_KEY_LEN);
+        assert_ne!(key, url_key(&format!("{}c", long)));
+    }
+
+    #[tokio::test]
+    async fn test_fixture_transport() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path()
+                .join(url_key("https://crates.io/api/v1/crates/a")),
+            "{}",
+        )
+        .unwrap();
+        let transport = FixtureTransport::new(dir.path());
+        let response = transport
+            .get("https://crates.io/api/v1/crates/a", &[])
+            .await
+            .unwrap();
+        assert_eq!(response.text().unwrap(), "{}");
+        let response = transport
+            .post("https://crates.io/api/v1/crates/a", &[], b"query")
+            .await
+            .unwrap();
+        assert_eq!(response.text().unwrap(), "{}");
+        let response = transport
+            .get("https://crates.io/api/v1/crates/b", &[])
+            .await
+            .unwrap();
+        assert!(response.error_for_status().is_err());
+    }
+}
@@ -7,10 +7,24 @@ use std::sync::mpsc::Receiver;
 use tracing::{error, info};
 
 pub mod analysis;
+pub mod audit;
+pub mod cache;
 pub mod common;
+pub mod credentials;
+pub mod dashboard;
+pub mod deadline;
+pub mod digest;
 pub mod git;
+pub mod http;
+pub mod locale;
 pub mod model;
+pub mod redact;
+pub mod rpc;
 pub mod rust;
+pub mod sources;
+pub mod testing;
+pub mod timing;
+pub mod verbosity;
 
 use analysis::MetricsApp;
 
@@ -18,6 +32,18 @@ use analysis::MetricsApp;
 pub enum MetricsRequest {
     /// A request to refresh the list of rust dependencies, given a git repository.
     StartAnalysis { repo_url: String },
+    /// A request to review the dependency updates found in the history of a git repository.
+    Backfill { repo_url: String },
+    /// A request to refresh the analyses of several git repositories, one after the other
+    /// (see [`rust::batch`]).
+    Batch { repo_urls: Vec<String> },
+}
+
+/// The directory in which analyzed repositories are cloned.
+pub fn repos_dir() -> PathBuf {
+    let mut repo_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    repo_dir.push("repos");
+    repo_dir
 }
 
 /// Initializes a metrics service with a channel [`Receiver`] and wait for requests to process.
@@ -26,17 +52,25 @@ pub enum MetricsRequest {
 /// and will prevent any queries from being sent when busy.
 /// For this reason, you should call the sender with [`std::sync::mpsc::SyncSender::try_send()`].
 pub async fn start(receiver: Receiver<MetricsRequest>) -> Result<()> {
-    info!("initializing cargo tree");
-    rust::cargotree::CargoTree::init_cargo_tree().await?;
+    if sources::offline() {
+        // the cargo commands run by the analysis only use the local cargo cache,
+        // and the cargo tools are expected to be installed already
+        info!("offline mode enabled");
+        std::env::set_var("CARGO_NET_OFFLINE", "true");
+    } else {
+        info!("initializing cargo tree");
+        rust::cargotree::CargoTree::init_cargo_tree().await?;
+    }
 
-    info!("initializing cargo download");
-    rust::diff::init_cargo_download().await?;
+    if sources::sandbox() {
+        info!("sandbox mode enabled: no code of the analyzed repositories will run");
+    }
+    credentials::export_registry_tokens();
 
     let metrics = MetricsApp::new().await?;
 
     info!("metrics service started!");
-    let mut repo_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    repo_dir.push("repos");
+    let repo_dir = repos_dir();
 
     for request in receiver {
         match request {
@@ -49,6 +83,23 @@ pub async fn start(receiver: Receiver<MetricsRequest>) -> Result<()> {
                     }
                 };
             }
+            MetricsRequest::Backfill { repo_url } => {
+                match metrics.backfill(&repo_url, &repo_dir).await {
+                    Ok(backfill) => info!(
+                        "backfill finished successfuly ({} lockfile changes)",
+                        backfill.changes.len()
+                    ),
+                    Err(e) => error!("backfill failed to terminate: {}", e),
+                };
+            }
+            MetricsRequest::Batch { repo_urls } => {
+                let failed = metrics.batch(&repo_urls, &repo_dir).await;
+                info!(
+                    "batch finished ({} analyses, {} failed)",
+                    repo_urls.len(),
+                    failed.len()
+                );
+            }
         };
     }
     Ok(())
@@ -0,0 +1,166 @@
+//! This module formats the dates and numbers displayed by the renderers
+//! (reports, comparisons, dashboards, digests) according to a locale,
+//! so that international teams get coherent reports.
+//!
+//! The default locale (`iso`) writes dates as `YYYY-MM-DD` and numbers without separators.
+//! Other locales are selected with a language tag (e.g. `en-US`, `de-DE`, or just `de`);
+//! unknown tags fall back to the default.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The conventions of a language.
+struct Conventions {
+    /// the chrono format of a date
+    date: &'static str,
+    /// separates groups of three digits
+    thousands: Option<&'static str>,
+}
+
+const ISO: Conventions = Conventions {
+    date: "%Y-%m-%d",
+    thousands: None,
+};
+
+/// (language tag or language, conventions), the first matching tag wins
+const CONVENTIONS: &[(&str, Conventions)] = &[
+    (
+        "en-us",
+        Conventions {
+            date: "%m/%d/%Y",
+            thousands: Some(","),
+        },
+    ),
+    (
+        "en",
+        Conventions {
+            date: "%d/%m/%Y",
+            thousands: Some(","),
+        },
+    ),
+    (
+        "de",
+        Conventions {
+            date: "%d.%m.%Y",
+            thousands: Some("."),
+        },
+    ),
+    (
+        "fr",
+        Conventions {
+            date: "%d/%m/%Y",
+            // (a narrow no-break space)
+            thousands: Some("\u{202f}"),
+        },
+    ),
+    (
+        "ja",
+        Conventions {
+            date: "%Y/%m/%d",
+            thousands: Some(","),
+        },
+    ),
+];
+
+/// The locale used to format dates and numbers (see the module documentation).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Locale(String);
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self("iso".to_string())
+    }
+}
+
+impl Locale {
+    pub fn new(tag: &str) -> Self {
+        Self(tag.to_string())
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.0
+    }
+
+    fn conventions(&self) -> &'static Conventions {
+        let tag = self.0.to_lowercase().replace('_', "-");
+        let language = tag.split('-').next().unwrap_or_default().to_string();
+        CONVENTIONS
+            .iter()
+            .find(|(prefix, _)| *prefix == tag)
+            .or_else(|| CONVENTIONS.iter().find(|(prefix, _)| *prefix == language))
+            .map(|(_, conventions)| conventions)
+            .unwrap_or(&ISO)
+    }
+
+    /// Formats a date.
+    pub fn date(&self, date: NaiveDate) -> String {
+        date.format(self.conventions().date).to_string()
+    }
+
+    /// Formats the date of a timestamp, with its time (in UTC).
+    pub fn datetime(&self, datetime: DateTime<Utc>) -> String {
+        format!(
+            "{} {} UTC",
+            self.date(datetime.naive_utc().date()),
+            datetime.format("%H:%M")
+        )
+    }
+
+    /// Formats a date given as text (an RFC 3339 timestamp or a `YYYY-MM-DD` date, as returned by APIs),
+    /// which is returned as is if it can't be parsed.
+    pub fn date_str(&self, date: &str) -> String {
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(date) {
+            return self.date(datetime.with_timezone(&Utc).naive_utc().date());
+        }
+        match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(date) => self.date(date),
+            Err(_) => date.to_string(),
+        }
+    }
+
+    /// Formats a number, grouping its digits.
+    pub fn number(&self, number: u64) -> String {
+        let digits = number.to_string();
+        let separator = match self.conventions().thousands {
+            Some(separator) => separator,
+            None => return digits,
+        };
+        let mut formatted = String::with_capacity(digits.len() * 2);
+        for (idx, digit) in digits.chars().enumerate() {
+            if idx > 0 && (digits.len() - idx) % 3 == 0 {
+                formatted.push_str(separator);
+            }
+            formatted.push(digit);
+        }
+        formatted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale() {
+        let iso = Locale::default();
+        assert_eq!(
+            iso.date_str("2021-01-08T18:19:33.004838+00:00"),
+            "2021-01-08"
+        );
+        assert_eq!(iso.number(38_000_000), "38000000");
+
+        let us = Locale::new("en-US");
+        assert_eq!(us.date_str("2021-01-08"), "01/08/2021");
+        assert_eq!(us.number(38_000_000), "38,000,000");
+        assert_eq!(us.number(999), "999");
+
+        let de = Locale::new("de_AT");
+        assert_eq!(de.date_str("2021-01-08"), "08.01.2021");
+        assert_eq!(de.number(1234), "1.234");
+
+        // unknown languages and dates are left alone
+        assert_eq!(Locale::new("xx").number(1234), "1234");
+        assert_eq!(us.date_str("yesterday"), "yesterday");
+    }
+}
@@ -2,15 +2,71 @@
 //! by providing functions to read and write specific documents.
 
 use super::Db;
+use crate::locale::Locale;
+use crate::rust::{
+    guppy::ResolveOptions, reviewers::FindingCategory, risky_paths::RiskyPathOptions,
+    scope::ScopeOptions, tags::TagOptions, theme::MarkdownTheme,
+};
+use crate::verbosity::Verbosity;
 use anyhow::{anyhow, Result};
 use mongodb::bson::{self, doc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
 pub struct Repo {
     pub repo: String,
     pub trusted_crates: Vec<String>,
     pub snoozed_crates: Vec<String>,
+    /// how the dependency graph of the repository should be resolved
+    #[serde(default)]
+    pub resolve_options: ResolveOptions,
+    /// which dependencies should be analyzed
+    #[serde(default)]
+    pub scope: ScopeOptions,
+    /// crates that are internally forked, and thus intentionally differ from crates.io
+    /// (only their copies that don't come from crates.io are marked as forks)
+    #[serde(default)]
+    pub forked_crates: Vec<String>,
+    /// crates.io owners or github organizations (e.g. `rust-lang`, `tokio-rs`) that are trusted,
+    /// their patch-level updates get a condensed review
+    #[serde(default)]
+    pub trusted_publishers: Vec<String>,
+    /// email addresses that receive a digest of the changes observed by each analysis
+    /// (requires the `email` feature)
+    #[serde(default)]
+    pub digest_recipients: Vec<String>,
+    /// maximum duration of an analysis (in seconds),
+    /// after which the analysis is stored as is (and marked as truncated)
+    #[serde(default)]
+    pub max_duration: Option<u64>,
+    /// path patterns used to triage the files changed by updates
+    #[serde(default)]
+    pub risky_paths: RiskyPathOptions,
+    /// how the markdown reviews (e.g. posted as PR comments) look
+    #[serde(default)]
+    pub markdown_theme: MarkdownTheme,
+    /// clone the full history of the repository (instead of the latest commit only),
+    /// which is needed to find who introduced each dependency
+    #[serde(default)]
+    pub full_history: bool,
+    /// how dates and numbers are formatted in the dashboard and digests (e.g. `en-US`)
+    #[serde(default)]
+    pub locale: Locale,
+    /// how much the dashboard, digests and reviews say (`minimal`, `standard` or `full`)
+    #[serde(default)]
+    pub verbosity: Verbosity,
+    /// estimate whether the workspace calls into the functions affected by RUSTSEC advisories
+    /// (a deeper, slower analysis of the sources of the workspace)
+    #[serde(default)]
+    pub reachability: bool,
+    /// the tags of dependencies (e.g. `crypto`, `network`), and the review rules of the sensitive tags
+    #[serde(default)]
+    pub tags: TagOptions,
+    /// the reviewers to assign to the updates with a category of findings
+    /// (e.g. `unsafe: ["@acme/memory-safety"]`), listed in the reviews
+    #[serde(default)]
+    pub reviewers: BTreeMap<FindingCategory, Vec<String>>,
 }
 
 pub struct Config(Db);
@@ -35,8 +91,7 @@ impl Config {
         // if not, create it
         let repo = Repo {
             repo: repo.to_string(),
-            trusted_crates: Vec::new(),
-            snoozed_crates: Vec::new(),
+            ..Default::default()
         };
         let repo = bson::to_bson(&repo).unwrap();
         let document = repo.as_document().unwrap();
@@ -55,6 +110,37 @@ impl Config {
         Ok(result.is_some())
     }
 
+    /// obtain the configuration of a single repository
+    pub async fn get_repo(&self, repo: &str) -> Result<Option<Repo>> {
+        let filter = doc! {
+            "repo": repo.to_string(),
+        };
+        let document = self
+            .0
+            .find_one(Self::COLLECTION, Some(filter), None)
+            .await?;
+        match document {
+            Some(document) => bson::from_document(document)
+                .map(Some)
+                .map_err(anyhow::Error::msg),
+            None => Ok(None),
+        }
+    }
+
+    /// replace the configuration of an existing repository
+    pub async fn update_repo(&self, repo: &Repo) -> Result<()> {
+        let filter = doc! {
+            "repo": repo.repo.clone(),
+        };
+        let repo = bson::to_bson(repo)?;
+        let document = repo
+            .as_document()
+            .ok_or_else(|| anyhow!("couldn't convert repo config to document"))?;
+        self.0
+            .replace_one(Self::COLLECTION, filter, document.to_owned())
+            .await
+    }
+
     /// remove a repository configuration
     pub async fn remove_repo(&self, repo: &str) -> Result<()> {
         self.0
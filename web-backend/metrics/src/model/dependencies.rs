@@ -6,7 +6,7 @@ use crate::analysis::Analysis;
 use anyhow::{Context, Result};
 use mongodb::{
     bson::{self, doc, Document},
-    options::FindOneOptions,
+    options::{FindOneOptions, FindOptions},
 };
 
 pub struct Dependencies(Db);
@@ -19,9 +19,9 @@ impl Dependencies {
     }
 
     /// write an analysis to storage
-    pub async fn write_analysis(&self, analysis: Analysis) -> Result<()> {
+    pub async fn write_analysis(&self, analysis: &Analysis) -> Result<()> {
         let analysis =
-            bson::to_bson(&analysis).with_context(|| "couldn't BSON serialize an analysis")?;
+            bson::to_bson(analysis).with_context(|| "couldn't BSON serialize an analysis")?;
         let document = analysis
             .as_document()
             .with_context(|| "couldn't convert analysis to document")?;
@@ -64,4 +64,28 @@ impl Dependencies {
             .map(Some)
             .map_err(anyhow::Error::msg)
     }
+
+    /// get the `limit` last analyses for a specific repo (the most recent first)
+    pub async fn get_analyses(&self, repo: &str, limit: i64) -> Result<Vec<Analysis>> {
+        let filter = doc! {
+            "repository": repo,
+        };
+        let find_options = FindOptions::builder()
+            .sort(doc! {
+                "_id": -1,
+            })
+            .limit(limit)
+            .build();
+
+        let documents = self
+            .0
+            .find(Self::COLLECTION, Some(filter), Some(find_options))
+            .await?;
+
+        // deserialize
+        documents
+            .into_iter()
+            .map(|document| bson::from_document(document).map_err(anyhow::Error::msg))
+            .collect()
+    }
 }
@@ -0,0 +1,51 @@
+//! This module abstracts the database (mongodb)
+//! by providing functions to read and write specific documents.
+
+use super::Db;
+use crate::rust::backfill::Backfill;
+use anyhow::{anyhow, Result};
+use mongodb::bson::{self, doc};
+
+pub struct History(Db);
+
+impl History {
+    const COLLECTION: &'static str = "history";
+
+    pub fn new(db: Db) -> Self {
+        Self(db)
+    }
+
+    /// get the dependency updates found in the history of a repository
+    pub async fn get_backfill(&self, repo: &str) -> Result<Option<Backfill>> {
+        let filter = doc! {
+            "repository": repo,
+        };
+        let document = self
+            .0
+            .find_one(Self::COLLECTION, Some(filter), None)
+            .await?;
+        match document {
+            Some(document) => bson::from_document(document)
+                .map(Some)
+                .map_err(anyhow::Error::msg),
+            None => Ok(None),
+        }
+    }
+
+    /// write (or replace) the dependency updates found in the history of a repository
+    pub async fn save_backfill(&self, backfill: &Backfill) -> Result<()> {
+        let document = bson::to_bson(backfill)?;
+        let document = document
+            .as_document()
+            .ok_or_else(|| anyhow!("couldn't convert backfill to document"))?
+            .to_owned();
+        if self.get_backfill(&backfill.repository).await?.is_some() {
+            let filter = doc! {
+                "repository": backfill.repository.clone(),
+            };
+            self.0.replace_one(Self::COLLECTION, filter, document).await
+        } else {
+            self.0.write(Self::COLLECTION, document).await
+        }
+    }
+}
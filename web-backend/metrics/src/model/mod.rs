@@ -9,9 +9,15 @@ use tracing::info;
 
 mod config;
 mod dependencies;
+mod history;
+mod reviews;
+mod watches;
 
-pub use config::Config;
+pub use config::{Config, Repo as RepoConfig};
 pub use dependencies::Dependencies;
+pub use history::History;
+pub use reviews::Reviews;
+pub use watches::Watches;
 
 #[derive(Clone)]
 pub struct Db(Database);
@@ -91,6 +97,27 @@ impl Db {
         Ok(res)
     }
 
+    pub async fn replace_one(
+        &self,
+        collection: &str,
+        filter: Document,
+        replacement: Document,
+    ) -> Result<()> {
+        let res = self
+            .0
+            .collection(collection)
+            .replace_one(filter, replacement, None)
+            .await
+            .map_err(anyhow::Error::msg)?;
+        if res.matched_count != 1 {
+            return Err(anyhow!(
+                "replaced inconsistent number of documents: {}",
+                res.matched_count
+            ));
+        }
+        Ok(())
+    }
+
     pub async fn delete_one(
         &self,
         collection: &str,
@@ -0,0 +1,57 @@
+//! This module abstracts the database (mongodb)
+//! by providing functions to read and write specific documents.
+
+use super::Db;
+use crate::rust::review_diff::PostedReview;
+use anyhow::{anyhow, Result};
+use mongodb::bson::{self, doc};
+
+pub struct Reviews(Db);
+
+impl Reviews {
+    const COLLECTION: &'static str = "reviews";
+
+    pub fn new(db: Db) -> Self {
+        Self(db)
+    }
+
+    /// get the last review posted on a pull request of a repository
+    pub async fn get_review(&self, repo: &str, pull_request: &str) -> Result<Option<PostedReview>> {
+        let filter = doc! {
+            "repository": repo,
+            "pull_request": pull_request,
+        };
+        let document = self
+            .0
+            .find_one(Self::COLLECTION, Some(filter), None)
+            .await?;
+        match document {
+            Some(document) => bson::from_document(document)
+                .map(Some)
+                .map_err(anyhow::Error::msg),
+            None => Ok(None),
+        }
+    }
+
+    /// write (or replace) the last review posted on a pull request
+    pub async fn save_review(&self, review: &PostedReview) -> Result<()> {
+        let document = bson::to_bson(review)?;
+        let document = document
+            .as_document()
+            .ok_or_else(|| anyhow!("couldn't convert review to document"))?
+            .to_owned();
+        if self
+            .get_review(&review.repository, &review.pull_request)
+            .await?
+            .is_some()
+        {
+            let filter = doc! {
+                "repository": review.repository.clone(),
+                "pull_request": review.pull_request.clone(),
+            };
+            self.0.replace_one(Self::COLLECTION, filter, document).await
+        } else {
+            self.0.write(Self::COLLECTION, document).await
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! This module abstracts the database (mongodb)
+//! by providing functions to read and write specific documents.
+
+use super::Db;
+use crate::rust::watch::WatchState;
+use anyhow::{anyhow, Result};
+use mongodb::bson::{self, doc};
+
+pub struct Watches(Db);
+
+impl Watches {
+    const COLLECTION: &'static str = "watches";
+
+    pub fn new(db: Db) -> Self {
+        Self(db)
+    }
+
+    /// get the state of the last run of the release watcher on a repository
+    pub async fn get_state(&self, repo: &str) -> Result<Option<WatchState>> {
+        let filter = doc! {
+            "repository": repo,
+        };
+        let document = self
+            .0
+            .find_one(Self::COLLECTION, Some(filter), None)
+            .await?;
+        match document {
+            Some(document) => bson::from_document(document)
+                .map(Some)
+                .map_err(anyhow::Error::msg),
+            None => Ok(None),
+        }
+    }
+
+    /// write (or replace) the state of the release watcher for a repository
+    pub async fn save_state(&self, state: &WatchState) -> Result<()> {
+        let document = bson::to_bson(state)?;
+        let document = document
+            .as_document()
+            .ok_or_else(|| anyhow!("couldn't convert watch state to document"))?
+            .to_owned();
+        if self.get_state(&state.repository).await?.is_some() {
+            let filter = doc! {
+                "repository": state.repository.clone(),
+            };
+            self.0.replace_one(Self::COLLECTION, filter, document).await
+        } else {
+            self.0.write(Self::COLLECTION, document).await
+        }
+    }
+}
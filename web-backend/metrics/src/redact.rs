@@ -0,0 +1,393 @@
+//! This module redacts analyses, so that the reports produced from them (JSON, dashboard)
+//! can be shared with vendors or publicly without leaking the internal structure of an organization:
+//! the name of the analyzed repository, the paths of its checkout,
+//! the URLs of private registries and git hosts, the names of internal crates, workspace members, their targets and owners,
+//! the paths and contents of its manifests, and the authors and messages of its commits.
+//!
+//! Redaction works on the serialized analysis, so that every field (including the ones added later,
+//! and error messages) goes through it: internal URLs and paths are replaced wherever they appear,
+//! and the fields holding names (see [`NAME_FIELDS`]) are replaced by an alias if they are an internal name
+//! (other fields and keys are left alone, a member could be named like a field, e.g. `version`).
+
+use anyhow::Result;
+use guppy_summaries::SummarySource;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::iter;
+
+use crate::analysis::Analysis;
+use crate::rust::manifest_lints::ManifestLint;
+
+/// Replaces the name of the analyzed repository.
+pub const REDACTED_REPOSITORY: &str = "<redacted repository>";
+/// Replaces the paths of the checkout of the analyzed repository.
+pub const REDACTED_WORKSPACE: &str = "<workspace>";
+/// Replaces the authors of commits.
+pub const REDACTED_AUTHOR: &str = "<redacted author>";
+/// Replaces the messages of commits.
+pub const REDACTED_SUBJECT: &str = "<redacted commit message>";
+/// Replaces the patches of manifests (see [`crate::rust::manifest_lints`]), which show their content.
+pub const REDACTED_PATCH: &str = "<redacted patch>";
+/// Replaces the declarations found in manifests and the ones suggested instead
+/// (see [`crate::rust::manifest_lints`]), which show internal registries and git hosts.
+pub const REDACTED_DECLARATION: &str = "<redacted declaration>";
+/// Replaces private URLs (still a valid URL, as sources are parsed as such).
+pub const REDACTED_URL: &str = "https://redacted.invalid";
+
+/// The fields holding the names of crates, workspace members, targets, owners, manifests and commits
+/// (a string, a list of strings, or a map keyed by names like the tags of crates).
+const NAME_FIELDS: &[&str] = &[
+    "name",
+    "from",
+    "to",
+    "member",
+    "package",
+    "pkg",
+    "dependency",
+    "dependencies",
+    "new_dependencies",
+    "dependents",
+    "crates",
+    "workspace_members",
+    "linked_by",
+    "introduced_by",
+    "caused_by",
+    "targets",
+    "code_owners",
+    "manifest",
+    "found",
+    "suggested",
+    "patch",
+    "author",
+    "subject",
+];
+
+/// Returns a copy of an analysis that can be shared outside of the organization.
+pub fn redact(analysis: &Analysis) -> Result<Analysis> {
+    let rules = Rules::new(analysis);
+    let mut value = serde_json::to_value(analysis)?;
+    rules.apply(&mut value);
+    Ok(serde_json::from_value(value)?)
+}
+
+/// What gets replaced, and by what.
+#[derive(Default, Debug)]
+struct Rules {
+    /// substrings replaced wherever they appear (the longest first)
+    substrings: Vec<(String, String)>,
+    /// whole strings replaced (internal names)
+    names: HashMap<String, String>,
+}
+
+impl Rules {
+    fn new(analysis: &Analysis) -> Self {
+        let mut rules = Self::default();
+
+        // the repository, with and without its scheme and .git suffix
+        let repository = analysis.repository().trim_end_matches('/');
+        let without_git = repository.trim_end_matches(".git");
+        let without_scheme = without_git
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+        for url in &[repository, without_git, without_scheme] {
+            if !url.is_empty() {
+                rules.replace(url, REDACTED_REPOSITORY);
+            }
+        }
+
+        // the checkouts of the repositories
+        rules.replace(&crate::repos_dir().to_string_lossy(), REDACTED_WORKSPACE);
+
+        // the crates not published on crates.io, and where they come from
+        let rust_analysis = analysis.rust_dependencies();
+        let mut internal_crates = BTreeSet::new();
+        for dependency in rust_analysis.dependencies() {
+            if let SummarySource::External { source } = dependency.repo() {
+                rules.replace_source(source);
+                internal_crates.insert(dependency.name().to_string());
+            }
+        }
+        for (idx, name) in internal_crates.into_iter().enumerate() {
+            rules
+                .names
+                .insert(name, format!("internal-crate-{}", idx + 1));
+        }
+
+        // the workspace members
+        let mut members = BTreeSet::new();
+        for dependency in rust_analysis.dependencies() {
+            members.extend(dependency.linked_by().iter().cloned());
+        }
+        for exposure in rust_analysis.license_exposure() {
+            members.insert(exposure.member.clone());
+        }
+        for link in rust_analysis
+            .links()
+            .iter()
+            .filter(|link| link.from_workspace)
+        {
+            members.extend(link.from.split(' ').next().map(ToString::to_string));
+        }
+        for (idx, member) in members.into_iter().enumerate() {
+            rules
+                .names
+                .entry(member)
+                .or_insert_with(|| format!("workspace-member-{}", idx + 1));
+        }
+
+        // the ids of the internal packages in the dependency graph (`name version`)
+        for link in rust_analysis.links() {
+            for id in &[&link.from, &link.to] {
+                let mut parts = id.splitn(2, ' ');
+                let name = parts.next().unwrap_or_default();
+                let version = parts.next().unwrap_or_default();
+                if let Some(alias) = rules.names.get(name) {
+                    let alias = format!("{} {}", alias, version);
+                    rules.names.insert(id.to_string(), alias);
+                }
+            }
+        }
+
+        // the targets of the workspace members
+        let targets: BTreeSet<&String> = rust_analysis
+            .dependencies()
+            .iter()
+            .flat_map(|dependency| dependency.targets())
+            .collect();
+        for (idx, target) in targets.into_iter().enumerate() {
+            rules
+                .names
+                .entry(target.clone())
+                .or_insert_with(|| format!("workspace-target-{}", idx + 1));
+        }
+
+        // the owners of the workspace members
+        let owners: BTreeSet<&String> = rust_analysis
+            .dependencies()
+            .iter()
+            .flat_map(|dependency| dependency.code_owners())
+            .collect();
+        for (idx, owner) in owners.into_iter().enumerate() {
+            rules
+                .names
+                .entry(owner.clone())
+                .or_insert_with(|| format!("@owner-{}", idx + 1));
+        }
+
+        // the manifests of the workspace, and the declarations and patches showing their content
+        rules.redact_manifest_lints(rust_analysis.manifest_lints());
+
+        // the authors and messages of the commits that introduced dependencies
+        for dependency in rust_analysis.dependencies() {
+            if let Some(introduced) = dependency.introduced() {
+                rules
+                    .names
+                    .insert(introduced.author.clone(), REDACTED_AUTHOR.to_string());
+                rules
+                    .names
+                    .insert(introduced.subject.clone(), REDACTED_SUBJECT.to_string());
+            }
+        }
+
+        rules
+    }
+
+    /// Aliases the manifests of the findings, and redacts the declarations and patches quoting them.
+    fn redact_manifest_lints(&mut self, lints: &[ManifestLint]) {
+        let manifests: BTreeSet<&String> = lints.iter().map(|lint| &lint.manifest).collect();
+        for (idx, manifest) in manifests.into_iter().enumerate() {
+            self.names
+                .insert(manifest.clone(), format!("workspace-manifest-{}", idx + 1));
+        }
+        for lint in lints {
+            let declarations = iter::once(&lint.found).chain(lint.suggested.as_ref());
+            for declaration in declarations {
+                self.names
+                    .insert(declaration.clone(), REDACTED_DECLARATION.to_string());
+            }
+            if let Some(patch) = &lint.patch {
+                self.names.insert(patch.clone(), REDACTED_PATCH.to_string());
+            }
+        }
+    }
+
+    fn replace(&mut self, substring: &str, replacement: &str) {
+        if !self.substrings.iter().any(|(s, _)| s == substring) {
+            self.substrings
+                .push((substring.to_string(), replacement.to_string()));
+            self.substrings
+                .sort_by_key(|(substring, _)| std::cmp::Reverse(substring.len()));
+        }
+    }
+
+    /// Redacts the URL of a source (e.g. `registry+https://...`, `git+https://...?branch=main#<commit>`),
+    /// keeping its kind.
+    fn replace_source(&mut self, source: &str) {
+        let url = source.splitn(2, '+').nth(1).unwrap_or(source);
+        let url = url.split(|c| c == '?' || c == '#').next().unwrap_or(url);
+        if !url.is_empty() {
+            self.replace(url, REDACTED_URL);
+        }
+    }
+
+    /// Replaces the internal URLs and paths in a string.
+    fn redact_str(&self, string: &str) -> String {
+        let mut string = string.to_string();
+        for (substring, replacement) in &self.substrings {
+            if string.contains(substring.as_str()) {
+                string = string.replace(substring.as_str(), replacement);
+            }
+        }
+        string
+    }
+
+    /// Replaces an internal name by its alias (or redacts it like any string).
+    fn redact_name(&self, name: &str) -> String {
+        match self.names.get(name) {
+            Some(alias) => alias.clone(),
+            None => self.redact_str(name),
+        }
+    }
+
+    fn apply(&self, value: &mut Value) {
+        match value {
+            Value::String(string) => *string = self.redact_str(string),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.apply(value)),
+            Value::Object(map) => {
+                let entries = std::mem::take(map);
+                for (key, mut value) in entries {
+                    if NAME_FIELDS.contains(&key.as_str()) {
+                        self.apply_names(&mut value);
+                    } else {
+                        self.apply(&mut value);
+                    }
+                    map.insert(self.redact_str(&key), value);
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) => (),
+        }
+    }
+
+    /// Redacts the value of a field holding names.
+    fn apply_names(&self, value: &mut Value) {
+        match value {
+            Value::String(name) => *name = self.redact_name(name),
+            Value::Array(values) => {
+                for value in values {
+                    match value {
+                        Value::String(name) => *name = self.redact_name(name),
+                        value => self.apply(value),
+                    }
+                }
+            }
+            // (a map keyed by names)
+            Value::Object(map) => {
+                let entries = std::mem::take(map);
+                for (name, mut value) in entries {
+                    self.apply(&mut value);
+                    map.insert(self.redact_name(&name), value);
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rust::manifest_lints::LintKind;
+    use serde_json::json;
+
+    #[test]
+    fn test_rules() {
+        let mut rules = Rules::default();
+        rules.replace("https://github.com/acme/secret", REDACTED_REPOSITORY);
+        rules.replace("https://github.com/acme/secret.git", REDACTED_REPOSITORY);
+        rules.replace("github.com/acme/secret", REDACTED_REPOSITORY);
+        rules.replace("/srv/repos", REDACTED_WORKSPACE);
+        rules.replace_source("registry+https://registry.acme.corp/index?x=1");
+        rules
+            .names
+            .insert("acme-auth".to_string(), "internal-crate-1".to_string());
+
+        let mut value = json!({
+            "repository": "https://github.com/acme/secret.git",
+            "dependencies": [{
+                "name": "acme-auth",
+                "repo": {"External": {"source": "registry+https://registry.acme.corp/index"}},
+                "introduced_by": ["serde", "acme-auth"],
+            }],
+            "error": "couldn't read /srv/repos/abc/Cargo.toml",
+            "tags": {"crates": {"acme-auth": ["crypto"]}},
+            "count": 1,
+        });
+        rules.apply(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "repository": "<redacted repository>",
+                "dependencies": [{
+                    "name": "internal-crate-1",
+                    "repo": {"External": {"source": "registry+https://redacted.invalid"}},
+                    "introduced_by": ["serde", "internal-crate-1"],
+                }],
+                "error": "couldn't read <workspace>/abc/Cargo.toml",
+                "tags": {"crates": {"internal-crate-1": ["crypto"]}},
+                "count": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_names_like_fields() {
+        // (a member named like a field only gets replaced where names are)
+        let mut rules = Rules::default();
+        for (idx, member) in ["version", "dev", "serde"].iter().enumerate() {
+            rules
+                .names
+                .insert(member.to_string(), format!("workspace-member-{}", idx + 1));
+        }
+        let mut value = json!({
+            "name": "serde",
+            "version": "1.0.0",
+            "dev": false,
+            "description": "serde",
+            "linked_by": ["version", "dev"],
+        });
+        rules.apply(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "name": "workspace-member-3",
+                "version": "1.0.0",
+                "dev": false,
+                "description": "serde",
+                "linked_by": ["workspace-member-1", "workspace-member-2"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_manifest_lints() {
+        let lint = ManifestLint {
+            manifest: "auth/Cargo.toml".to_string(),
+            section: "dependencies".to_string(),
+            dependency: "acme-auth".to_string(),
+            kind: LintKind::Wildcard,
+            found: r#"acme-auth = { version = "*", registry = "corp" }"#.to_string(),
+            suggested: Some(r#"acme-auth = { version = "1.2.3", registry = "corp" }"#.to_string()),
+            patch: Some("--- a/auth/Cargo.toml\n+++ b/auth/Cargo.toml\n".to_string()),
+        };
+        let mut rules = Rules::default();
+        rules.redact_manifest_lints(&[lint.clone()]);
+
+        let mut value = serde_json::to_value(&lint).unwrap();
+        rules.apply(&mut value);
+        let redacted: ManifestLint = serde_json::from_value(value).unwrap();
+        assert_eq!(redacted.manifest, "workspace-manifest-1");
+        assert_eq!(redacted.found, REDACTED_DECLARATION);
+        assert_eq!(redacted.suggested.as_deref(), Some(REDACTED_DECLARATION));
+        assert_eq!(redacted.patch.as_deref(), Some(REDACTED_PATCH));
+    }
+}
@@ -0,0 +1,221 @@
+//! This module serves analysis requests sent as JSON lines (one JSON-RPC 2.0 request per line),
+//! for the tools that drive the analyses interactively (e.g. an IDE plugin) from a long-lived process:
+//! the caches warmed by a request (e.g. the advisory database, see [`cargoaudit::advisory_db`])
+//! are reused by the next ones.
+//!
+//! The methods are:
+//!
+//! - `analyze` (`repo_dir`, and optionally `max_duration` in seconds): analyzes the rust dependencies
+//!   of a local repository (see [`RustAnalysis`]),
+//! - `report` (`name`, and optionally `version`): reports on a crate version (see [`CrateReport`]),
+//! - `compare` (`names`): compares crates (see [`compare::compare_crates`]).
+//!
+//! Each request gets a response line with its `id`, in the order of the requests.
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::deadline::Deadline;
+use crate::model::RepoConfig;
+use crate::rust::{cargoaudit, compare, report::CrateReport, RustAnalysis};
+
+/// the line isn't JSON
+pub const PARSE_ERROR: i64 = -32700;
+/// the line isn't a request
+pub const INVALID_REQUEST: i64 = -32600;
+/// there is no such method
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// the parameters don't match the method
+pub const INVALID_PARAMS: i64 = -32602;
+/// the method failed
+pub const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct AnalyzeParams {
+    repo_dir: PathBuf,
+    #[serde(default)]
+    max_duration: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ReportParams {
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CompareParams {
+    names: Vec<String>,
+}
+
+/// The response to a request.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+/// Why a request failed.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+/// Handles a request line.
+pub async fn handle_line(line: &str) -> Response {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(e) => return Response::error(Value::Null, PARSE_ERROR, e.to_string()),
+    };
+    let request: Request = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(e) => return Response::error(Value::Null, INVALID_REQUEST, e.to_string()),
+    };
+    let id = request.id;
+    let result = match request.method.as_str() {
+        "analyze" => match params::<AnalyzeParams>(request.params) {
+            Ok(params) => analyze(params).await,
+            Err(e) => return Response::error(id, INVALID_PARAMS, e.to_string()),
+        },
+        "report" => match params::<ReportParams>(request.params) {
+            Ok(params) => CrateReport::new(&params.name, params.version.as_deref())
+                .await
+                .and_then(to_value),
+            Err(e) => return Response::error(id, INVALID_PARAMS, e.to_string()),
+        },
+        "compare" => match params::<CompareParams>(request.params) {
+            Ok(params) => compare::compare_crates(&params.names)
+                .await
+                .and_then(to_value),
+            Err(e) => return Response::error(id, INVALID_PARAMS, e.to_string()),
+        },
+        method => {
+            return Response::error(id, METHOD_NOT_FOUND, format!("unknown method {}", method))
+        }
+    };
+    match result {
+        Ok(result) => Response::result(id, result),
+        Err(e) => Response::error(id, SERVER_ERROR, format!("{:#}", e)),
+    }
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> serde_json::Result<T> {
+    serde_json::from_value(params)
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value> {
+    serde_json::to_value(value).map_err(anyhow::Error::msg)
+}
+
+async fn analyze(params: AnalyzeParams) -> Result<Value> {
+    let deadline = match params.max_duration {
+        Some(max_duration) => Deadline::after(Duration::from_secs(max_duration)),
+        None => Deadline::none(),
+    };
+    let analysis = RustAnalysis::get_dependencies(
+        &params.repo_dir,
+        None,
+        false,
+        &RepoConfig::default(),
+        &deadline,
+    )
+    .await?;
+    to_value(analysis)
+}
+
+/// Reads requests line by line until the input is closed, and writes a response line for each
+/// (empty lines are skipped). The advisory database is loaded first, so that it is warm for the first request.
+pub async fn serve<R, W>(reader: R, mut writer: W) -> Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    if let Err(e) = cargoaudit::advisory_db().await {
+        tracing::warn!("couldn't load the advisory database: {}", e);
+    }
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle_line(&line).await;
+        let mut response = serde_json::to_string(&response)?;
+        response.push('\n');
+        writer.write_all(response.as_bytes()).await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_handle_line() {
+        let response = handle_line("not json").await;
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+
+        let response = handle_line(r#"{"id": 1}"#).await;
+        assert_eq!(response.id, Value::Null);
+        assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+
+        let response = handle_line(r#"{"jsonrpc": "2.0", "id": 2, "method": "audit"}"#).await;
+        assert_eq!(
+            response,
+            Response::error(json!(2), METHOD_NOT_FOUND, "unknown method audit")
+        );
+
+        let response =
+            handle_line(r#"{"jsonrpc": "2.0", "id": "a", "method": "compare", "params": {}}"#)
+                .await;
+        assert_eq!(response.id, json!("a"));
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        let response = serde_json::to_value(Response::result(json!(3), json!([]))).unwrap();
+        assert_eq!(response, json!({"jsonrpc": "2.0", "id": 3, "result": []}));
+    }
+}
@@ -0,0 +1,317 @@
+//! This module analyzes the dependency updates found in the history of a repository,
+//! for retrospective studies (e.g. "how many advisories did we ship, and for how long?").
+//! Every commit that changed the `Cargo.lock` of the repository is reviewed with a cheap subset
+//! of the analysis: the packages updated, added and removed, and the RUSTSEC advisories
+//! affecting the lockfile (with today's advisory database, so that vulnerabilities discovered
+//! after a commit are attributed to the period during which they were shipped).
+//! Nothing of the repository is built or executed, only the lockfiles are read from git.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rustsec::{lockfile::Lockfile, Database};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    lockfile_format,
+    monitor::{self, Finding},
+};
+
+/// The state of the lockfile at a commit.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub commit: String,
+    pub date: DateTime<Utc>,
+    pub packages: BTreeMap<String, BTreeSet<Version>>,
+    /// the advisories affecting the lockfile
+    pub findings: Vec<Finding>,
+}
+
+impl Snapshot {
+    /// Reviews the lockfile of a commit.
+    pub fn new(commit: &str, date: DateTime<Utc>, lockfile: &str, db: &Database) -> Result<Self> {
+        let lockfile = lockfile_format::parse(lockfile)
+            .with_context(|| format!("couldn't parse the Cargo.lock of {}", commit))?;
+        Ok(Self {
+            commit: commit.to_string(),
+            date,
+            packages: packages(&lockfile),
+            findings: monitor::findings(db, &lockfile),
+        })
+    }
+}
+
+/// Returns the versions of the packages of a lockfile, by name.
+pub(crate) fn packages(lockfile: &Lockfile) -> BTreeMap<String, BTreeSet<Version>> {
+    let mut packages: BTreeMap<String, BTreeSet<Version>> = BTreeMap::new();
+    for package in &lockfile.packages {
+        packages
+            .entry(package.name.as_str().to_string())
+            .or_default()
+            .insert(package.version.clone());
+    }
+    packages
+}
+
+/// A package whose version changed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VersionChange {
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+    /// the package went back to a lower version (e.g. a rollback)
+    #[serde(default)]
+    pub downgrade: bool,
+}
+
+/// The changes made to the lockfile by a commit.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockfileChange {
+    pub commit: String,
+    pub date: DateTime<Utc>,
+    pub updated: Vec<VersionChange>,
+    /// the packages added (`name version`)
+    pub added: Vec<String>,
+    /// the packages removed (`name version`)
+    pub removed: Vec<String>,
+    /// the advisories that started affecting the lockfile with this commit
+    pub advisories_introduced: Vec<String>,
+    /// the advisories that stopped affecting the lockfile with this commit
+    pub advisories_fixed: Vec<String>,
+    /// the ones of the advisories introduced that had been fixed before (e.g. by a downgrade)
+    #[serde(default)]
+    pub advisories_reintroduced: Vec<String>,
+}
+
+/// A period during which an advisory affected the lockfile.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Exposure {
+    pub id: String,
+    pub package: String,
+    pub kind: String,
+    pub title: String,
+    pub introduced_in: String,
+    pub introduced_at: DateTime<Utc>,
+    /// none if the advisory still affects the latest lockfile
+    pub fixed_in: Option<String>,
+    pub fixed_at: Option<DateTime<Utc>>,
+    /// the duration of the exposure (until now if it is not fixed)
+    pub days: i64,
+}
+
+/// The dependency updates found in the history of a repository.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Backfill {
+    pub repository: String,
+    pub timestamp: DateTime<Utc>,
+    /// the changes of the lockfile (the oldest first)
+    pub changes: Vec<LockfileChange>,
+    pub exposures: Vec<Exposure>,
+}
+
+impl Backfill {
+    /// Compares consecutive snapshots of the lockfile (the oldest first).
+    pub fn new(repository: &str, snapshots: &[Snapshot], now: DateTime<Utc>) -> Self {
+        let mut changes = Vec::new();
+        let mut exposures: Vec<Exposure> = Vec::new();
+        // the index of the ongoing exposures, by (advisory, package)
+        let mut ongoing: BTreeMap<(String, String), usize> = BTreeMap::new();
+        let empty = BTreeMap::new();
+
+        for (idx, snapshot) in snapshots.iter().enumerate() {
+            let previous = match idx {
+                0 => &empty,
+                _ => &snapshots[idx - 1].packages,
+            };
+            let (updated, added, removed) = diff_packages(previous, &snapshot.packages);
+
+            // an advisory affects the lockfile as long as one version of the package is affected
+            let current: BTreeMap<(String, String), &Finding> = snapshot
+                .findings
+                .iter()
+                .map(|finding| ((finding.id.clone(), finding.package.clone()), finding))
+                .collect();
+            let mut advisories_introduced = Vec::new();
+            let mut advisories_reintroduced = Vec::new();
+            for (key, finding) in &current {
+                if !ongoing.contains_key(key) {
+                    if exposures
+                        .iter()
+                        .any(|exposure| exposure.id == key.0 && exposure.package == key.1)
+                    {
+                        advisories_reintroduced.push(finding.id.clone());
+                    }
+                    ongoing.insert(key.clone(), exposures.len());
+                    exposures.push(Exposure {
+                        id: finding.id.clone(),
+                        package: finding.package.clone(),
+                        kind: finding.kind.clone(),
+                        title: finding.title.clone(),
+                        introduced_in: snapshot.commit.clone(),
+                        introduced_at: snapshot.date,
+                        fixed_in: None,
+                        fixed_at: None,
+                        days: 0,
+                    });
+                    advisories_introduced.push(finding.id.clone());
+                }
+            }
+            let fixed: Vec<(String, String)> = ongoing
+                .keys()
+                .filter(|key| !current.contains_key(*key))
+                .cloned()
+                .collect();
+            let mut advisories_fixed = Vec::new();
+            for key in fixed {
+                if let Some(exposure_idx) = ongoing.remove(&key) {
+                    let exposure = &mut exposures[exposure_idx];
+                    exposure.fixed_in = Some(snapshot.commit.clone());
+                    exposure.fixed_at = Some(snapshot.date);
+                    advisories_fixed.push(key.0);
+                }
+            }
+
+            changes.push(LockfileChange {
+                commit: snapshot.commit.clone(),
+                date: snapshot.date,
+                updated,
+                added,
+                removed,
+                advisories_introduced,
+                advisories_fixed,
+                advisories_reintroduced,
+            });
+        }
+
+        for exposure in &mut exposures {
+            let end = exposure.fixed_at.unwrap_or(now);
+            exposure.days = (end - exposure.introduced_at).num_days();
+        }
+
+        Self {
+            repository: repository.to_string(),
+            timestamp: now,
+            changes,
+            exposures,
+        }
+    }
+}
+
+/// Returns the packages updated (or downgraded), added and removed between two lockfiles.
+/// (A package with a single version on both sides is updated, otherwise versions are added and removed.)
+pub(crate) fn diff_packages(
+    old: &BTreeMap<String, BTreeSet<Version>>,
+    new: &BTreeMap<String, BTreeSet<Version>>,
+) -> (Vec<VersionChange>, Vec<String>, Vec<String>) {
+    let mut updated = Vec::new();
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let no_versions = BTreeSet::new();
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for name in names {
+        let old_versions = old.get(name).unwrap_or(&no_versions);
+        let new_versions = new.get(name).unwrap_or(&no_versions);
+        if old_versions == new_versions {
+            continue;
+        }
+        if old_versions.len() == 1 && new_versions.len() == 1 {
+            if let (Some(from), Some(to)) = (old_versions.iter().next(), new_versions.iter().next())
+            {
+                updated.push(VersionChange {
+                    name: name.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                    downgrade: to < from,
+                });
+            }
+            continue;
+        }
+        for version in new_versions.difference(old_versions) {
+            added.push(format!("{} {}", name, version));
+        }
+        for version in old_versions.difference(new_versions) {
+            removed.push(format!("{} {}", name, version));
+        }
+    }
+    (updated, added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_backfill() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        let snapshot = |commit: &str, day: u32, smallvec: &str, affected: bool| {
+            let mut packages = BTreeMap::new();
+            packages.insert(
+                "smallvec".to_string(),
+                vec![v(smallvec)].into_iter().collect(),
+            );
+            let findings = if affected {
+                vec![Finding {
+                    id: "RUSTSEC-2021-0003".to_string(),
+                    package: "smallvec".to_string(),
+                    version: smallvec.to_string(),
+                    title: "Buffer overflow".to_string(),
+                    kind: "vulnerability".to_string(),
+                    patched: vec![">= 1.6.1".to_string()],
+                }]
+            } else {
+                Vec::new()
+            };
+            Snapshot {
+                commit: commit.to_string(),
+                date: Utc.ymd(2021, 1, day).and_hms(0, 0, 0),
+                packages,
+                findings,
+            }
+        };
+        let snapshots = vec![
+            snapshot("a", 1, "1.5.0", true),
+            snapshot("b", 5, "1.6.0", true),
+            snapshot("c", 11, "1.6.1", false),
+        ];
+        let backfill = Backfill::new("repo", &snapshots, Utc.ymd(2021, 2, 1).and_hms(0, 0, 0));
+
+        assert_eq!(backfill.changes.len(), 3);
+        assert_eq!(
+            backfill.changes[0].added,
+            vec!["smallvec 1.5.0".to_string()]
+        );
+        assert_eq!(
+            backfill.changes[1].updated,
+            vec![VersionChange {
+                name: "smallvec".to_string(),
+                from: v("1.5.0"),
+                to: v("1.6.0"),
+                downgrade: false,
+            }]
+        );
+        assert_eq!(backfill.changes[0].advisories_introduced.len(), 1);
+        assert!(backfill.changes[1].advisories_introduced.is_empty());
+        assert_eq!(backfill.changes[2].advisories_fixed.len(), 1);
+
+        // a single exposure, from the first to the last commit
+        assert_eq!(backfill.exposures.len(), 1);
+        assert_eq!(backfill.exposures[0].fixed_in.as_deref(), Some("c"));
+        assert_eq!(backfill.exposures[0].days, 10);
+
+        // rolling back reintroduces the advisory
+        let mut snapshots = snapshots;
+        snapshots.push(snapshot("d", 20, "1.6.0", true));
+        let backfill = Backfill::new("repo", &snapshots, Utc.ymd(2021, 2, 1).and_hms(0, 0, 0));
+        let rollback = &backfill.changes[3];
+        assert!(rollback.updated[0].downgrade);
+        assert!(!backfill.changes[2].updated[0].downgrade);
+        assert_eq!(
+            rollback.advisories_reintroduced,
+            vec!["RUSTSEC-2021-0003".to_string()]
+        );
+        assert!(backfill.changes[0].advisories_reintroduced.is_empty());
+        assert_eq!(backfill.exposures.len(), 2);
+    }
+}
@@ -0,0 +1,224 @@
+//! This module consolidates the latest analyses of several repositories (e.g. all the Rust services of an organization)
+//! into a single report ranking the riskiest dependencies across them.
+//! The repositories of a batch are analyzed one after the other by the metrics service
+//! (see [`crate::MetricsRequest::Batch`]), so that they share the cache (see [`crate::cache`])
+//! as well as the GitHub credentials and their rate limits (see [`crate::common::github_auth`]).
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::{graph::DependencyGraph, verdict::Verdict, RustAnalysis};
+use crate::analysis::Analysis;
+use crate::dashboard::table::{Align, Cell, Table};
+
+/// A repository of the batch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RepositoryStatus {
+    pub repository: String,
+    /// the commit of the latest analysis (none if the repository was never analyzed)
+    pub commit: Option<String>,
+    /// was the latest analysis stopped before completion?
+    pub truncated: bool,
+}
+
+/// A dependency at risk, across the repositories of the batch.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RiskyDependency {
+    pub name: String,
+    /// the versions in use
+    pub versions: BTreeSet<Version>,
+    /// the repositories using the dependency
+    pub repositories: BTreeSet<String>,
+    /// the RUSTSEC advisories affecting the versions in use
+    pub advisories: BTreeSet<String>,
+    /// the worst verdict of the reviews of its updates (see [`super::verdict`])
+    pub verdict: Option<Verdict>,
+}
+
+/// The consolidated report of a batch of repositories.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BatchReport {
+    pub repositories: Vec<RepositoryStatus>,
+    /// the dependencies affected by advisories or whose updates need a closer look, the riskiest first:
+    /// the ones with the most advisories, then the worst verdicts, then the most used
+    pub dependencies: Vec<RiskyDependency>,
+}
+
+impl BatchReport {
+    /// Builds the report out of the latest analysis of each repository (none if it was never analyzed).
+    pub fn new(analyses: &[(String, Option<Analysis>)]) -> Self {
+        let analyses: Vec<(&str, Option<(&str, &RustAnalysis)>)> = analyses
+            .iter()
+            .map(|(repository, analysis)| {
+                let analysis = analysis
+                    .as_ref()
+                    .map(|analysis| (analysis.commit(), analysis.rust_dependencies()));
+                (repository.as_str(), analysis)
+            })
+            .collect();
+        Self::from_rust_analyses(&analyses)
+    }
+
+    /// Same as [`Self::new`], with the commits and the rust analyses of the repositories.
+    fn from_rust_analyses(analyses: &[(&str, Option<(&str, &RustAnalysis)>)]) -> Self {
+        let mut dependencies: BTreeMap<String, RiskyDependency> = BTreeMap::new();
+        let mut repositories = Vec::new();
+        for (repository, analysis) in analyses {
+            let (commit, analysis) = match analysis {
+                Some(analysis) => analysis,
+                None => {
+                    repositories.push(RepositoryStatus {
+                        repository: repository.to_string(),
+                        commit: None,
+                        truncated: false,
+                    });
+                    continue;
+                }
+            };
+            repositories.push(RepositoryStatus {
+                repository: repository.to_string(),
+                commit: Some(commit.to_string()),
+                truncated: analysis.truncated,
+            });
+
+            let graph = DependencyGraph::new(analysis);
+            for node in graph.nodes.into_iter().filter(|node| !node.workspace) {
+                let at_risk = !node.advisories.is_empty()
+                    || node
+                        .verdict
+                        .map_or(false, |verdict| verdict > Verdict::Pass);
+                if !at_risk {
+                    continue;
+                }
+                let dependency =
+                    dependencies
+                        .entry(node.name.clone())
+                        .or_insert_with(|| RiskyDependency {
+                            name: node.name.clone(),
+                            ..Default::default()
+                        });
+                dependency.versions.insert(node.version);
+                dependency.repositories.insert(repository.to_string());
+                dependency.advisories.extend(node.advisories);
+                dependency.verdict = dependency.verdict.max(node.verdict);
+            }
+        }
+
+        let mut dependencies: Vec<RiskyDependency> =
+            dependencies.into_iter().map(|(_, dep)| dep).collect();
+        dependencies.sort_by(|a, b| {
+            (b.advisories.len(), b.verdict, b.repositories.len()).cmp(&(
+                a.advisories.len(),
+                a.verdict,
+                a.repositories.len(),
+            ))
+        });
+        Self {
+            repositories,
+            dependencies,
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        let analyzed = self
+            .repositories
+            .iter()
+            .filter(|status| status.commit.is_some())
+            .count();
+        let _ = writeln!(
+            markdown,
+            "# Dependencies at risk across {} repositories\n",
+            analyzed
+        );
+        for status in &self.repositories {
+            match &status.commit {
+                None => {
+                    let _ = writeln!(markdown, "* {} (never analyzed)", status.repository);
+                }
+                Some(commit) if status.truncated => {
+                    let _ = writeln!(
+                        markdown,
+                        "* {} at `{}` (partial analysis)",
+                        status.repository, commit
+                    );
+                }
+                Some(commit) => {
+                    let _ = writeln!(markdown, "* {} at `{}`", status.repository, commit);
+                }
+            }
+        }
+        markdown.push('\n');
+
+        if self.dependencies.is_empty() {
+            markdown.push_str("No dependency at risk.\n");
+            return markdown;
+        }
+        let mut table = Table::new()
+            .column("dependency", Align::Left)
+            .column("versions", Align::Left)
+            .column("advisories", Align::Left)
+            .column("verdict", Align::Left)
+            .column("repositories", Align::Left);
+        for dependency in &self.dependencies {
+            let versions: Vec<String> = dependency
+                .versions
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            let advisories: Vec<&str> = dependency.advisories.iter().map(String::as_str).collect();
+            let repositories: Vec<&str> =
+                dependency.repositories.iter().map(String::as_str).collect();
+            table.row(vec![
+                Cell::code(&dependency.name),
+                Cell::text(versions.join(", ")),
+                Cell::text(advisories.join(", ")),
+                Cell::text(dependency.verdict.map_or("", |verdict| verdict.as_str())),
+                Cell::text(repositories.join(", ")),
+            ]);
+        }
+        markdown.push_str(&table.to_markdown());
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rust::DependencyInfo;
+    use guppy_summaries::SummarySource;
+
+    #[test]
+    fn test_batch_report() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        let analysis = |dependencies: Vec<DependencyInfo>| RustAnalysis {
+            dependencies,
+            ..Default::default()
+        };
+        let dependency = |name: &str, version: &str| {
+            DependencyInfo::new(
+                name.to_string(),
+                v(version),
+                SummarySource::CratesIo,
+                false,
+                true,
+            )
+        };
+        // (without advisories nor updates, nothing is at risk)
+        let a = analysis(vec![dependency("smallvec", "1.6.0")]);
+        let b = analysis(vec![dependency("smallvec", "1.6.1")]);
+        let report = BatchReport::from_rust_analyses(&[
+            ("a", Some(("abc", &a))),
+            ("b", Some(("def", &b))),
+            ("c", None),
+        ]);
+        assert_eq!(report.repositories.len(), 3);
+        assert!(report.dependencies.is_empty());
+        let markdown = report.to_markdown();
+        assert!(markdown.starts_with("# Dependencies at risk across 2 repositories\n"));
+        assert!(markdown.contains("* c (never analyzed)"));
+        assert!(markdown.ends_with("No dependency at risk.\n"));
+    }
+}
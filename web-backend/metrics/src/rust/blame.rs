@@ -0,0 +1,169 @@
+//! This module finds when, and by whom, each dependency was introduced in a repository,
+//! to support ownership and cleanup conversations.
+//! It goes through the history of `Cargo.lock` (with a single `git log`),
+//! and records the first commit that added each package, with its author
+//! and the pull request it was merged with (when the commit message tells).
+//! This needs the full history of the repository (see [`crate::model::RepoConfig::full_history`]).
+
+use anyhow::{ensure, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tokio::process::Command;
+
+use super::crate_name::CrateName;
+use crate::audit;
+
+/// Separates the commits in the log.
+const COMMIT_MARKER: char = '\u{1}';
+
+/// The commit that introduced a dependency.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Introduction {
+    pub commit: String,
+    pub date: DateTime<Utc>,
+    /// `name <email>`
+    pub author: String,
+    /// the first line of the commit message
+    pub subject: String,
+    /// the number of the pull request, if the commit message mentions it
+    pub pull_request: Option<u64>,
+}
+
+/// Finds the commits that introduced the packages of the `Cargo.lock` of a repository.
+pub async fn introductions(repo_dir: &Path) -> Result<HashMap<CrateName, Introduction>> {
+    ensure!(
+        !repo_dir.join(".git").join("shallow").exists(),
+        "the repository is a shallow clone, its history is not available"
+    );
+    let args = [
+        "log",
+        "--reverse",
+        "--format=%x01%H%x00%cI%x00%an <%ae>%x00%s",
+        "--unified=0",
+        "-p",
+        "--",
+        "Cargo.lock",
+    ];
+    audit::command(Some(repo_dir), "git", &args);
+    let output = Command::new("git")
+        .current_dir(repo_dir)
+        .args(&args)
+        .output()
+        .await?;
+    ensure!(
+        output.status.success(),
+        "couldn't get the history of Cargo.lock: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses the patches of a `git log` of `Cargo.lock` (the oldest commit first).
+fn parse_log(log: &str) -> HashMap<CrateName, Introduction> {
+    let mut introductions = HashMap::new();
+    for commit in log.split(COMMIT_MARKER).filter(|commit| !commit.is_empty()) {
+        let mut lines = commit.lines();
+        let header: Vec<&str> = lines.next().unwrap_or_default().splitn(4, '\0').collect();
+        let date = header
+            .get(1)
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok());
+        let (hash, date, author, subject) = match (header.get(0), date, header.get(2)) {
+            (Some(hash), Some(date), Some(author)) => (
+                hash.to_string(),
+                date.with_timezone(&Utc),
+                author.to_string(),
+                header.get(3).unwrap_or(&"").to_string(),
+            ),
+            _ => continue,
+        };
+
+        // (diffs can move `name = ...` lines around, only the packages that appear count)
+        let mut added = HashSet::new();
+        let mut removed = HashSet::new();
+        for line in lines {
+            if line.starts_with("+++") || line.starts_with("---") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("+name = ") {
+                added.insert(CrateName::from(name.trim().trim_matches('"')));
+            } else if let Some(name) = line.strip_prefix("-name = ") {
+                removed.insert(CrateName::from(name.trim().trim_matches('"')));
+            }
+        }
+        for name in added.difference(&removed) {
+            introductions
+                .entry(name.clone())
+                .or_insert_with(|| Introduction {
+                    commit: hash.clone(),
+                    date,
+                    author: author.clone(),
+                    subject: subject.clone(),
+                    pull_request: pull_request(&subject),
+                });
+        }
+    }
+    introductions
+}
+
+/// Finds the pull request number in a commit message
+/// (`Merge pull request #123 from ...` or `... (#123)`).
+fn pull_request(subject: &str) -> Option<u64> {
+    let number = |s: &str| -> Option<u64> {
+        let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    };
+    if let Some(rest) = subject.strip_prefix("Merge pull request #") {
+        return number(rest);
+    }
+    let start = subject.trim_end().strip_suffix(')')?.rfind("(#")?;
+    number(&subject[start + 2..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log() {
+        let log = "\u{1}aaa\u{0}2021-01-01T10:00:00+01:00\u{0}Alice <alice@example.com>\u{0}Add serde (#12)
+
+diff --git a/Cargo.lock b/Cargo.lock
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -1,0 +2,8 @@
++[[package]]
++name = \"serde\"
++version = \"1.0.0\"
++
++[[package]]
++name = \"serde-derive\"
++version = \"1.0.0\"
+\u{1}bbb\u{0}2021-02-01T10:00:00+00:00\u{0}Bob <bob@example.com>\u{0}Merge pull request #34 from bob/rand
+@@ -3 +3 @@
+-name = \"serde-derive\"
++name = \"rand\"
+@@ -10 +10 @@
++name = \"serde-derive\"
+";
+        let introductions = parse_log(log);
+        assert_eq!(introductions.len(), 3);
+
+        let serde = &introductions[&CrateName::from("serde")];
+        assert_eq!(serde.commit, "aaa");
+        assert_eq!(serde.author, "Alice <alice@example.com>");
+        assert_eq!(serde.pull_request, Some(12));
+
+        // moving a package around doesn't introduce it again
+        assert_eq!(
+            introductions[&CrateName::from("serde_derive")].commit,
+            "aaa"
+        );
+        let rand = &introductions[&CrateName::from("rand")];
+        assert_eq!(rand.commit, "bbb");
+        assert_eq!(rand.pull_request, Some(34));
+
+        assert_eq!(pull_request("Bump rand"), None);
+    }
+}
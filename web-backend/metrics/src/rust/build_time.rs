@@ -0,0 +1,298 @@
+//! This module sizes the code that runs at build time: build scripts, procedural macros
+//! (see [`super::proc_macros`]) and the crates compiled into them (build-dependencies,
+//! dependencies of proc macros, transitively).
+//! That code executes on the build machines (developers' laptops, CI) with their access to files, network and credentials,
+//! so it is an attack surface of its own, whether or not it ends up in the artifacts.
+//! The surface of a workspace is summarized in the analysis (see [`BuildTimeSurface`]),
+//! and the updates growing it are flagged in the reviews (see [`BuildTimeChange`]).
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use super::{report::SourceInfo, DependencyInfo};
+
+/// The code running at build time in a workspace.
+/// A dependency used in several ways is counted once.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct BuildTimeSurface {
+    /// the crates running code at build time
+    pub crates: usize,
+    /// the crates with a build script
+    pub build_scripts: usize,
+    /// the procedural macros
+    pub proc_macros: usize,
+    /// the other crates, compiled into build scripts or proc macros
+    pub dependencies: usize,
+    /// the crates whose sources were available locally (the lines below only cover them)
+    pub measured: usize,
+    /// the lines of rust code of the crates
+    pub rust_lines: usize,
+    /// the distinct crates.io owners of the crates, who can publish code running on the build machines
+    pub publishers: usize,
+    /// the crates whose owners are unknown (not on crates.io, or not fetched)
+    pub unknown_publishers: usize,
+}
+
+impl BuildTimeSurface {
+    /// Sizes the build-time surface of dependencies,
+    /// `source_of` returning the source information of a dependency if available locally.
+    pub fn new(
+        dependencies: &[DependencyInfo],
+        source_of: impl Fn(&str, &Version) -> Option<SourceInfo>,
+    ) -> Self {
+        let crates: BTreeMap<(&str, &Version), &DependencyInfo> = dependencies
+            .iter()
+            .filter(|dependency| dependency.build_time())
+            .map(|dependency| ((dependency.name(), dependency.version()), dependency))
+            .collect();
+
+        let mut surface = Self {
+            crates: crates.len(),
+            ..Default::default()
+        };
+        let mut publishers: BTreeSet<&str> = BTreeSet::new();
+        for ((name, version), dependency) in crates {
+            if dependency.build_script() {
+                surface.build_scripts += 1;
+            } else if dependency.proc_macro() {
+                surface.proc_macros += 1;
+            } else {
+                surface.dependencies += 1;
+            }
+            if let Some(source) = source_of(name, version) {
+                surface.measured += 1;
+                surface.rust_lines += source.rust_lines;
+            }
+            if dependency.owners().is_empty() {
+                surface.unknown_publishers += 1;
+            }
+            publishers.extend(dependency.owners().iter().map(String::as_str));
+        }
+        surface.publishers = publishers.len();
+        surface
+    }
+
+    /// Describes the surface (e.g. `12 crates (3 build scripts, 2 proc macros, 7 dependencies), 40000 lines of rust, 9 publishers`).
+    pub fn describe(&self) -> String {
+        format!(
+            "{} crates ({} build scripts, {} proc macros, {} dependencies), {} lines of rust, {} publishers",
+            self.crates,
+            self.build_scripts,
+            self.proc_macros,
+            self.dependencies,
+            self.rust_lines,
+            self.publishers
+        )
+    }
+}
+
+/// How a new version of a crate grows the code running at build time.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct BuildTimeChange {
+    /// the new version adds a build script
+    pub build_script_added: bool,
+    /// the lines of the build script of the old and the new versions
+    pub build_script_lines: (usize, usize),
+    /// the new version is a procedural macro, the old one wasn't
+    pub proc_macro_added: bool,
+    /// the dependencies added to the build script or to the proc macro
+    pub new_dependencies: Vec<String>,
+}
+
+impl BuildTimeChange {
+    /// Describes the change (e.g. `adds a build script, new build-time dependencies cc`).
+    pub fn describe(&self) -> String {
+        let mut changes = Vec::new();
+        if self.build_script_added {
+            changes.push(format!(
+                "adds a build script ({} lines)",
+                self.build_script_lines.1
+            ));
+        } else if self.build_script_lines.1 > self.build_script_lines.0 {
+            changes.push(format!(
+                "build script grows from {} to {} lines",
+                self.build_script_lines.0, self.build_script_lines.1
+            ));
+        }
+        if self.proc_macro_added {
+            changes.push("becomes a procedural macro".to_string());
+        }
+        if !self.new_dependencies.is_empty() {
+            changes.push(format!(
+                "new build-time dependencies {}",
+                self.new_dependencies.join(", ")
+            ));
+        }
+        changes.join(", ")
+    }
+}
+
+/// What runs at build time in a crate, as found in its (crates.io) sources.
+#[derive(Default, Debug)]
+struct BuildTimeCode {
+    build_script_lines: Option<usize>,
+    proc_macro: bool,
+    dependencies: BTreeSet<String>,
+}
+
+impl BuildTimeCode {
+    fn new(crate_dir: &Path) -> Self {
+        let manifest = fs::read_to_string(crate_dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|manifest| toml::from_str::<toml::Value>(&manifest).ok())
+            .unwrap_or_else(|| toml::Value::Table(Default::default()));
+        let package = manifest.get("package");
+
+        // (`build = false` disables the build script, `build = "path"` moves it)
+        let build_script = match package.and_then(|package| package.get("build")) {
+            Some(toml::Value::Boolean(false)) => None,
+            Some(toml::Value::String(path)) => Some(path.as_str()),
+            _ => Some("build.rs"),
+        };
+        let build_script_lines = build_script
+            .and_then(|path| fs::read_to_string(crate_dir.join(path)).ok())
+            .map(|content| content.lines().count());
+
+        let lib = manifest.get("lib");
+        let proc_macro = ["proc-macro", "proc_macro"].iter().any(|key| {
+            lib.and_then(|lib| lib.get(key))
+                .and_then(toml::Value::as_bool)
+                .unwrap_or(false)
+        });
+
+        // the dependencies compiled into the build script (and into the crate, for a proc macro)
+        let mut tables = vec!["build-dependencies", "build_dependencies"];
+        if proc_macro {
+            tables.push("dependencies");
+        }
+        let mut dependencies = BTreeSet::new();
+        for table in &tables {
+            dependencies.extend(table_keys(manifest.get(table)));
+            if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+                for target in targets.values() {
+                    dependencies.extend(table_keys(target.get(table)));
+                }
+            }
+        }
+
+        Self {
+            build_script_lines,
+            proc_macro,
+            dependencies,
+        }
+    }
+}
+
+fn table_keys(value: Option<&toml::Value>) -> BTreeSet<String> {
+    value
+        .and_then(toml::Value::as_table)
+        .map(|table| table.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Compares the code running at build time in two versions of a crate,
+/// given their extracted sources, returning the change if the new version grows it.
+pub fn build_time_change(original_crate: &Path, new_crate: &Path) -> Option<BuildTimeChange> {
+    let old = BuildTimeCode::new(original_crate);
+    let new = BuildTimeCode::new(new_crate);
+    let change = BuildTimeChange {
+        build_script_added: old.build_script_lines.is_none() && new.build_script_lines.is_some(),
+        build_script_lines: (
+            old.build_script_lines.unwrap_or(0),
+            new.build_script_lines.unwrap_or(0),
+        ),
+        proc_macro_added: new.proc_macro && !old.proc_macro,
+        new_dependencies: new
+            .dependencies
+            .difference(&old.dependencies)
+            .cloned()
+            .collect(),
+    };
+    let grows = change.build_script_lines.1 > change.build_script_lines.0
+        || change.proc_macro_added
+        || !change.new_dependencies.is_empty();
+    if grows {
+        Some(change)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guppy_summaries::SummarySource;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_build_time_surface() {
+        let dependency = |name: &str, build_script: bool, proc_macro: bool, owner: &str| {
+            let mut dependency = DependencyInfo::new(
+                name.to_string(),
+                Version::parse("1.0.0").unwrap(),
+                SummarySource::CratesIo,
+                false,
+                false,
+            );
+            dependency.build_time = build_script || proc_macro || !owner.is_empty();
+            dependency.build_script = build_script;
+            dependency.proc_macro = proc_macro;
+            if !owner.is_empty() {
+                dependency.owners = vec![owner.to_string()];
+            }
+            dependency
+        };
+        let dependencies = vec![
+            dependency("libc", true, false, "alice"),
+            dependency("serde_derive", false, true, "bob"),
+            dependency("syn", false, false, "bob"),
+            dependency("smallvec", false, false, ""),
+        ];
+        let surface = BuildTimeSurface::new(&dependencies, |name, _| {
+            Some(SourceInfo {
+                rust_lines: if name == "syn" { 1000 } else { 100 },
+                ..Default::default()
+            })
+        });
+        assert_eq!(
+            surface,
+            BuildTimeSurface {
+                crates: 3,
+                build_scripts: 1,
+                proc_macros: 1,
+                dependencies: 1,
+                measured: 3,
+                rust_lines: 1200,
+                publishers: 2,
+                unknown_publishers: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_build_time_change() {
+        let old = tempdir().unwrap();
+        fs::write(
+            old.path().join("Cargo.toml"),
+            "[package]\nname = \"a\"\n[dependencies]\nsyn = \"1\"\n",
+        )
+        .unwrap();
+        let new = tempdir().unwrap();
+        fs::write(
+            new.path().join("Cargo.toml"),
+            "[package]\nname = \"a\"\n[lib]\nproc-macro = true\n[dependencies]\nsyn = \"1\"\nquote = \"1\"\n[build-dependencies]\ncc = \"1\"\n",
+        )
+        .unwrap();
+        fs::write(new.path().join("build.rs"), "fn main() {\n}\n").unwrap();
+
+        let change = build_time_change(old.path(), new.path()).unwrap();
+        assert_eq!(
+            change.describe(),
+            "adds a build script (2 lines), becomes a procedural macro, new build-time dependencies cc, quote, syn"
+        );
+        assert_eq!(build_time_change(new.path(), new.path()), None);
+    }
+}
@@ -4,31 +4,219 @@
 //! - there are versions that are unaffected
 
 use anyhow::{ensure, Context, Result};
-use rustsec::{advisory::Informational, lockfile::Lockfile, registry, warning, Report, Warning};
-use std::path::Path;
+use chrono::{DateTime, NaiveDate};
+use once_cell::sync::Lazy;
+use rustsec::{advisory::Informational, registry, warning, Advisory, Database, Report, Warning};
+use semver::{Version, VersionReq};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::process::Command;
-use tracing::info;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
 
-/// performs an audit of the Cargo.lock file with rustsec
-pub async fn audit(repo_path: &Path) -> Result<Report> {
+use super::{crate_name, cratesio, lockfile_format};
+use crate::timing::{self, Section};
+use crate::{audit, sources};
+
+/// The environment variable pointing to a local copy of the advisory database.
+pub const ADVISORY_DB_PATH_VAR: &str = "ADVISORY_DB_PATH";
+
+/// the path of the RUSTSEC advisory database (`$ADVISORY_DB_PATH`, or the default path of rustsec)
+pub fn advisory_db_path() -> PathBuf {
+    match std::env::var_os(ADVISORY_DB_PATH_VAR) {
+        Some(path) => PathBuf::from(path),
+        None => rustsec::GitRepository::default_path(),
+    }
+}
+
+/// fetches the latest version of the RUSTSEC advisory database
+/// (in offline mode, the local copy is used as is)
+pub fn fetch_advisory_db() -> Result<Database> {
     // config
     let advisory_db_url = rustsec::repository::git::DEFAULT_URL;
-    // TODO: do we want to use a custom path here?
-    let advisory_db_path = rustsec::GitRepository::default_path();
+    let advisory_db_path = advisory_db_path();
+
+    if sources::offline() {
+        info!("opening local RUSTSEC advisory database (offline mode)");
+        return rustsec::Database::open(&advisory_db_path).with_context(|| {
+            format!(
+                "couldn't open local RUSTSEC advisory database at {}",
+                advisory_db_path.display()
+            )
+        });
+    }
 
     // fetch latest changes from the advisory + load
     info!("fetching latest version of RUSTSEC advisory...");
-    let advisory_db_repo = rustsec::GitRepository::fetch(advisory_db_url, &advisory_db_path, true)
-        .with_context(|| "couldn't fetch RUSTSEC advisory database")?;
-    let advisory_db = rustsec::Database::load_from_repo(&advisory_db_repo)
-        .with_context(|| "couldn't open RUSTSEC repo")?;
+    audit::clone(advisory_db_url, &advisory_db_path);
+    let advisory_db_repo = timing::time_blocking(Section::GitClone, || {
+        rustsec::GitRepository::fetch(advisory_db_url, &advisory_db_path, true)
+    })
+    .with_context(|| "couldn't fetch RUSTSEC advisory database")?;
+    rustsec::Database::load_from_repo(&advisory_db_repo)
+        .with_context(|| "couldn't open RUSTSEC repo")
+}
+
+/// The environment variable setting how long (in seconds) the shared advisory database can be used
+/// before it is fetched again.
+pub const ADVISORY_DB_MAX_AGE_VAR: &str = "ADVISORY_DB_MAX_AGE";
+
+/// How long the shared advisory database is used by default before it is fetched again.
+const DEFAULT_ADVISORY_DB_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// the maximum age of the shared advisory database (`$ADVISORY_DB_MAX_AGE`, or an hour)
+pub fn advisory_db_max_age() -> Duration {
+    std::env::var(ADVISORY_DB_MAX_AGE_VAR)
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_ADVISORY_DB_MAX_AGE)
+}
+
+/// A value fetched on first use, and fetched again once it is older than a maximum age.
+struct Memoized<T> {
+    value: Mutex<Option<(Instant, Arc<T>)>>,
+}
+
+impl<T: Send + Sync + 'static> Memoized<T> {
+    fn new() -> Self {
+        Self {
+            value: Mutex::new(None),
+        }
+    }
+
+    /// Fetches the value on the blocking thread pool if needed
+    /// (the lock is held while fetching, so that concurrent callers wait for a single fetch).
+    /// A failed fetch returns the previous value, if there is one.
+    async fn get(
+        &self,
+        max_age: Duration,
+        fetch: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Result<Arc<T>> {
+        let mut value = self.value.lock().await;
+        if let Some((fetched, value)) = value.as_ref() {
+            if fetched.elapsed() <= max_age {
+                return Ok(Arc::clone(value));
+            }
+        }
+        let fetched = tokio::task::spawn_blocking(fetch)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|fetched| fetched);
+        match (fetched, value.as_ref()) {
+            (Ok(fetched), _) => {
+                let fetched = Arc::new(fetched);
+                *value = Some((Instant::now(), Arc::clone(&fetched)));
+                Ok(fetched)
+            }
+            (Err(e), Some((_, stale))) => {
+                warn!("couldn't refresh, using the previous copy: {:#}", e);
+                Ok(Arc::clone(stale))
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+}
+
+/// The advisory database shared by the process.
+fn shared_advisory_db() -> &'static Memoized<Database> {
+    static SHARED: Lazy<Memoized<Database>> = Lazy::new(Memoized::new);
+    &SHARED
+}
+
+/// returns the RUSTSEC advisory database shared by the process,
+/// fetching it on first use and once it is older than [`advisory_db_max_age`]
+/// (every feature reading advisories goes through it, so that a batch of analyses fetches it once,
+/// off the threads of the runtime, and keeps using the previous copy if a refresh fails)
+pub async fn advisory_db() -> Result<Arc<Database>> {
+    advisory_db_with_max_age(advisory_db_max_age()).await
+}
+
+/// returns the shared advisory database, fetching it again if it is older than `max_age`
+pub async fn advisory_db_with_max_age(max_age: Duration) -> Result<Arc<Database>> {
+    shared_advisory_db().get(max_age, fetch_advisory_db).await
+}
+
+/// returns all the advisories (past and present) of a crate
+pub fn advisories_for_crate<'a>(advisory_db: &'a Database, name: &str) -> Vec<&'a Advisory> {
+    advisory_db
+        .iter()
+        .filter(|advisory| crate_name::same_crate(advisory.metadata.package.as_str(), name))
+        .collect()
+}
+
+/// parses the release dates of crates.io versions
+pub fn release_dates(versions: &[cratesio::Version]) -> Vec<(Version, NaiveDate)> {
+    versions
+        .iter()
+        .filter_map(|version| {
+            let num = Version::parse(&version.num).ok()?;
+            let created_at = DateTime::parse_from_rfc3339(&version.created_at).ok()?;
+            Some((num, created_at.naive_utc().date()))
+        })
+        .collect()
+}
+
+/// returns the number of days between an advisory and the first release that fixed it
+pub fn days_to_fix(advisory: &Advisory, releases: &[(Version, NaiveDate)]) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(advisory.metadata.date.as_str(), "%Y-%m-%d").ok()?;
+    first_fix(date, &advisory.versions.patched, releases)
+}
+
+/// is a version affected by an advisory?
+/// (versions matching neither the patched nor the unaffected requirements are)
+pub fn is_affected(patched: &[VersionReq], unaffected: &[VersionReq], version: &Version) -> bool {
+    !patched
+        .iter()
+        .chain(unaffected)
+        .any(|req| req.matches(version))
+}
+
+/// returns the first of the (sorted) versions given that is not affected by an advisory,
+/// which is the smallest update fixing it
+pub fn first_fixed_version<'a>(
+    patched: &[VersionReq],
+    unaffected: &[VersionReq],
+    versions: &'a [Version],
+) -> Option<&'a Version> {
+    versions
+        .iter()
+        .find(|version| !is_affected(patched, unaffected, version))
+}
+
+/// returns the number of days between a date and the first release matching one of the patched requirements
+/// (a fix released before the advisory counts as 0 days)
+fn first_fix(
+    date: NaiveDate,
+    patched: &[VersionReq],
+    releases: &[(Version, NaiveDate)],
+) -> Option<i64> {
+    releases
+        .iter()
+        .filter(|(version, _)| patched.iter().any(|req| req.matches(version)))
+        .map(|(_, released)| (*released - date).num_days().max(0))
+        .min()
+}
+
+/// performs an audit of the Cargo.lock file with rustsec
+/// (with `lockfile_only`, the Cargo.lock file must already exist, as cargo is not invoked)
+pub async fn audit(repo_path: &Path, lockfile_only: bool) -> Result<Report> {
+    let advisory_db = advisory_db().await?;
 
     // make sure a Carg.lock file is there
-    generate_lockfile(repo_path).await?;
+    if lockfile_only {
+        ensure!(
+            repo_path.join("Cargo.lock").exists(),
+            "no Cargo.lock to audit"
+        );
+    } else {
+        generate_lockfile(repo_path).await?;
+    }
 
     // open Cargo.lock file
     let lockfile_path = repo_path.join("Cargo.lock");
-    let lockfile = Lockfile::load(&lockfile_path)?;
+    let lockfile = lockfile_format::load(&lockfile_path)?;
 
     // run audit
     info!("generating rustsec report...");
@@ -43,8 +231,13 @@ pub async fn audit(repo_path: &Path) -> Result<Report> {
 
     // check for yanked versions as well
     // TODO: move this elsewhere in priority engine? (especially as we are not leveraging guppy's results here)
+    if sources::offline() {
+        info!("skipping yanked versions check (offline mode)");
+        return Ok(report);
+    }
     info!("fetching latest crates.io index to check for yanked versions...");
-    let registry_index = registry::Index::fetch()?; // refresh crates.io index
+    // refresh crates.io index
+    let registry_index = timing::time_blocking(Section::GitClone, registry::Index::fetch)?;
 
     info!("finding yanked versions...");
     use std::collections::btree_map::Entry;
@@ -68,11 +261,20 @@ pub async fn audit(repo_path: &Path) -> Result<Report> {
 }
 
 pub async fn generate_lockfile(repo_path: &Path) -> Result<()> {
-    let output = Command::new("cargo")
-        .current_dir(repo_path)
-        .arg("generate-lockfile")
-        .output()
-        .await?;
+    // (offline, an existing lockfile is used as is)
+    let offline = sources::offline();
+    if offline && repo_path.join("Cargo.lock").exists() {
+        return Ok(());
+    }
+    let args: &[&str] = if offline {
+        &["generate-lockfile", "--offline"]
+    } else {
+        &["generate-lockfile"]
+    };
+    audit::command(Some(repo_path), "cargo", args);
+    let mut command = Command::new("cargo");
+    command.current_dir(repo_path).args(args);
+    let output = command.output().await?;
 
     ensure!(
         output.status.success(),
@@ -82,3 +284,88 @@ pub async fn generate_lockfile(repo_path: &Path) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memoized() {
+        let memoized = Memoized::new();
+        let hour = Duration::from_secs(60 * 60);
+        let stale = Duration::from_secs(0);
+        assert!(memoized
+            .get(hour, || Err(anyhow::anyhow!("offline")))
+            .await
+            .is_err());
+        assert_eq!(*memoized.get(hour, || Ok(1)).await.unwrap(), 1);
+        // fresh: not fetched again
+        assert_eq!(*memoized.get(hour, || Ok(2)).await.unwrap(), 1);
+        // stale: fetched again, and a failed fetch returns the previous value
+        assert_eq!(*memoized.get(stale, || Ok(3)).await.unwrap(), 3);
+        let failed = memoized.get(stale, || Err(anyhow::anyhow!("offline")));
+        assert_eq!(*failed.await.unwrap(), 3);
+        assert_eq!(*memoized.get(hour, || Ok(4)).await.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_first_fixed_version() {
+        let req = |req: &str| VersionReq::parse(req).unwrap();
+        let v = |v: &str| Version::parse(v).unwrap();
+        let patched = vec![req(">= 1.8.2")];
+        let unaffected = vec![req("< 1.0.0")];
+        assert!(!is_affected(&patched, &unaffected, &v("0.9.0")));
+        assert!(is_affected(&patched, &unaffected, &v("1.8.1")));
+        assert!(!is_affected(&patched, &unaffected, &v("1.8.2")));
+
+        let versions = vec![v("1.8.0"), v("1.8.2"), v("1.9.0")];
+        assert_eq!(
+            first_fixed_version(&patched, &unaffected, &versions),
+            Some(&v("1.8.2"))
+        );
+        assert_eq!(first_fixed_version(&[], &unaffected, &versions), None);
+    }
+
+    #[test]
+    fn test_first_fix() {
+        let releases = release_dates(&[
+            cratesio::Version {
+                num: "0.1.0".to_string(),
+                created_at: "2020-01-01T10:00:00.000000+00:00".to_string(),
+                crate_size: None,
+                license: None,
+                published_by: None,
+            },
+            cratesio::Version {
+                num: "0.1.1".to_string(),
+                created_at: "2020-03-11T10:00:00.000000+00:00".to_string(),
+                crate_size: None,
+                license: None,
+                published_by: None,
+            },
+            cratesio::Version {
+                num: "0.2.0".to_string(),
+                created_at: "2020-02-15T10:00:00.000000+00:00".to_string(),
+                crate_size: None,
+                license: None,
+                published_by: None,
+            },
+        ]);
+        assert_eq!(releases.len(), 3);
+
+        let date = NaiveDate::from_ymd(2020, 2, 1);
+        let patched = vec![
+            VersionReq::parse("^0.1.1").unwrap(),
+            VersionReq::parse(">= 0.2.0").unwrap(),
+        ];
+        assert_eq!(first_fix(date, &patched, &releases), Some(14));
+
+        // fixed before the advisory
+        let date = NaiveDate::from_ymd(2020, 4, 1);
+        assert_eq!(first_fix(date, &patched, &releases), Some(0));
+
+        // never fixed
+        let patched = vec![VersionReq::parse(">= 1.0.0").unwrap()];
+        assert_eq!(first_fix(date, &patched, &releases), None);
+    }
+}
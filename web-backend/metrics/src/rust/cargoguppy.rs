@@ -7,6 +7,8 @@ use tempfile::tempdir;
 use tokio::process::Command;
 use tracing::info;
 
+use crate::audit;
+
 pub struct CargoGuppy;
 
 impl CargoGuppy {
@@ -15,6 +17,16 @@ impl CargoGuppy {
 
         // 1. this will produce a json file containing no dev dependencies
         // (only transitive dependencies used in release)
+        audit::command(
+            Some(repo_dir),
+            "cargo",
+            &[
+                "x",
+                "generate-summaries",
+                &out_dir.to_string_lossy(),
+                "json",
+            ],
+        );
         let output = Command::new("cargo")
             .current_dir(repo_dir)
             .args(&["x", "generate-summaries"])
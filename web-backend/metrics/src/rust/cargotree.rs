@@ -2,12 +2,15 @@ use anyhow::{bail, Context, Result};
 use std::path::Path;
 use tokio::process::Command;
 
+use crate::audit;
+
 pub struct CargoTree;
 
 impl CargoTree {
     pub async fn init_cargo_tree() -> Result<()> {
         // make sure cargo-tree is installed
         // this seems necessary because cargo-audit might have had an update, or because of the rust-toolchain?
+        audit::command(None, "cargo", &["install", "cargo-tree"]);
         let output = Command::new("cargo")
             .args(&["install", "cargo-tree"]) // TODO: use --force to force upgrade?
             .output()
@@ -22,6 +25,7 @@ impl CargoTree {
     }
 
     pub async fn run_cargo_tree(repo_dir: &Path, package: String) -> Result<String> {
+        audit::command(Some(repo_dir), "cargo", &["tree", "-i", &package]);
         let output = Command::new("cargo")
             .current_dir(repo_dir)
             .args(&["tree", "-i"]) // -i, --invert <SPEC>...          Invert the tree direction and focus on the given package
@@ -0,0 +1,183 @@
+//! This module measures the churn (lines added and removed) between two versions of a crate.
+//! Vendored third-party code and generated code (e.g. bindgen output) can produce huge diffs
+//! that don't need the same scrutiny as hand-written code, so their churn is reported separately.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tokio::process::Command;
+
+use super::diff::relative_path;
+use crate::audit;
+
+/// directories containing vendored code
+const VENDORED_DIRS: &[&str] = &["vendor", "vendored", "third_party", "third-party"];
+/// markers found in the header of generated files
+const GENERATED_MARKERS: &[&str] = &["@generated", "automatically generated by rust-bindgen"];
+/// the number of lines of a file searched for a generated marker
+const HEADER_LINES: usize = 10;
+
+/// Lines added and removed.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub struct LineChanges {
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// The churn of an update, split between hand-written and vendored/generated code.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Churn {
+    pub hand_written: LineChanges,
+    pub generated: LineChanges,
+    /// the changed files detected as vendored or generated
+    pub generated_files: Vec<String>,
+}
+
+/// Computes the churn between two extracted versions of a crate.
+pub async fn churn(original_crate: &Path, new_crate: &Path) -> Result<Churn> {
+    audit::command(
+        None,
+        "git",
+        &[
+            "diff",
+            "--no-index",
+            "--numstat",
+            &original_crate.to_string_lossy(),
+            &new_crate.to_string_lossy(),
+        ],
+    );
+    let output = Command::new("git")
+        .args(&["diff", "--no-index", "--numstat"])
+        .arg(original_crate)
+        .arg(new_crate)
+        .output()
+        .await?;
+    // returns '1' if differences were found, '0' otherwise
+    if !matches!(output.status.code(), Some(1) | Some(0)) {
+        bail!(
+            "Error running git diff command: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let mut churn = Churn::default();
+    let numstat = String::from_utf8_lossy(&output.stdout);
+    for (added, removed, path) in parse_numstat(
+        &numstat,
+        &original_crate.to_string_lossy(),
+        &new_crate.to_string_lossy(),
+    ) {
+        // (the header of removed files is read from the original crate)
+        let header = fs::read_to_string(new_crate.join(&path))
+            .or_else(|_| fs::read_to_string(original_crate.join(&path)))
+            .unwrap_or_default();
+        let lines = if is_generated(&path, &header) {
+            churn.generated_files.push(path);
+            &mut churn.generated
+        } else {
+            &mut churn.hand_written
+        };
+        lines.added += added;
+        lines.removed += removed;
+    }
+    Ok(churn)
+}
+
+/// Parses the output of `git diff --no-index --numstat`
+/// into (lines added, lines removed, path relative to the crate root).
+/// Binary files are skipped.
+fn parse_numstat(
+    numstat: &str,
+    original_crate: &str,
+    new_crate: &str,
+) -> Vec<(usize, usize, String)> {
+    numstat
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let added = parts.next()?.parse().ok()?;
+            let removed = parts.next()?.parse().ok()?;
+            let path = new_path(parts.next()?);
+            let path = relative_path(&relative_path(&path, new_crate), original_crate);
+            Some((added, removed, path))
+        })
+        .collect()
+}
+
+/// As the two crates live in different directories, git reports the files as renamed
+/// (e.g. `/tmp/{old => new}/src/lib.rs`, or `/{tmp/old/src/lib.rs => dev/null}` for a removed file).
+/// This returns the path of the file in the new crate, or in the original crate if it was removed.
+fn new_path(path: &str) -> String {
+    let (old, new) = match (path.find('{'), path.find(" => "), path.find('}')) {
+        (Some(open), Some(arrow), Some(close)) if open < arrow && arrow < close => {
+            let (prefix, suffix) = (&path[..open], &path[close + 1..]);
+            (
+                format!("{}{}{}", prefix, &path[open + 1..arrow], suffix),
+                format!("{}{}{}", prefix, &path[arrow + " => ".len()..close], suffix),
+            )
+        }
+        _ => (path.to_string(), path.to_string()),
+    };
+    let new = new.replace("//", "/");
+    if new == "/dev/null" {
+        old.replace("//", "/")
+    } else {
+        new
+    }
+}
+
+/// Checks if a file (relative to the crate root) is vendored or generated,
+/// based on its path and on the content of its header.
+pub fn is_generated(path: &str, content: &str) -> bool {
+    let vendored = Path::new(path)
+        .parent()
+        .map(|dir| {
+            dir.components().any(|component| {
+                VENDORED_DIRS.contains(&component.as_os_str().to_string_lossy().as_ref())
+            })
+        })
+        .unwrap_or(false);
+    let generated_name = path.ends_with("_generated.rs");
+    let generated_header = content
+        .lines()
+        .take(HEADER_LINES)
+        .any(|line| GENERATED_MARKERS.iter().any(|marker| line.contains(marker)));
+    vendored || generated_name || generated_header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_generated() {
+        assert!(is_generated("vendor/zlib/inflate.c", ""));
+        assert!(is_generated("src/third_party/lib.rs", ""));
+        assert!(is_generated("src/schema_generated.rs", ""));
+        assert!(is_generated(
+            "src/bindings.rs",
+            "/* automatically generated by rust-bindgen 0.57.0 */\n"
+        ));
+        assert!(!is_generated("src/lib.rs", "pub fn f() {}\n"));
+        // a file named like a vendored directory
+        assert!(!is_generated("src/vendor", ""));
+    }
+
+    #[test]
+    fn test_parse_numstat() {
+        let numstat = "3\t1\t/tmp/x/{a-1.0.0 => a-1.1.0}/src/lib.rs
+120\t0\t/{dev/null => tmp/x/a-1.1.0/vendor/lib.c}
+0\t4\t/{tmp/x/a-1.0.0/old.rs => dev/null}
+-\t-\t/tmp/x/{a-1.0.0 => a-1.1.0}/blob.bin
+";
+        assert_eq!(
+            parse_numstat(numstat, "/tmp/x/a-1.0.0", "/tmp/x/a-1.1.0"),
+            vec![
+                (3, 1, "src/lib.rs".to_string()),
+                (120, 0, "vendor/lib.c".to_string()),
+                (0, 4, "old.rs".to_string()),
+            ]
+        );
+    }
+}
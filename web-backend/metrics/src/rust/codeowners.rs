@@ -0,0 +1,240 @@
+//! This module maps dependencies to the teams owning the workspace members that use them,
+//! as declared in the `CODEOWNERS` file of the repository
+//! (see [GitHub's documentation](https://docs.github.com/en/github/creating-cloning-and-archiving-repositories/about-code-owners)),
+//! so that dependency-health reports can be produced per team,
+//! and reviews can mention the right owners.
+//!
+//! Patterns follow the gitignore syntax (`*`, `**`, `?`, anchored and directory patterns),
+//! and the last matching pattern wins. They are matched together with a [`GlobSet`],
+//! whose matching time doesn't depend on the shape of the patterns.
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+use super::{crate_name::CrateName, lockfile::LocalPackage, RustAnalysis};
+
+/// Where GitHub looks for the `CODEOWNERS` file.
+const LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// The rules of a `CODEOWNERS` file.
+#[derive(Debug, Clone)]
+pub struct CodeOwners {
+    /// (pattern, owners), in the order of the file
+    rules: Vec<(String, Vec<String>)>,
+    /// the globs of the patterns (see [`globs`])
+    globs: GlobSet,
+    /// the rule of each glob
+    glob_rules: Vec<usize>,
+}
+
+impl Default for CodeOwners {
+    fn default() -> Self {
+        Self::parse("")
+    }
+}
+
+impl CodeOwners {
+    /// Reads the `CODEOWNERS` file of a repository, if it has one.
+    pub fn find(repo_dir: &Path) -> Option<Self> {
+        LOCATIONS
+            .iter()
+            .find_map(|location| fs::read_to_string(repo_dir.join(location)).ok())
+            .map(|content| Self::parse(&content))
+    }
+
+    /// Parses a `CODEOWNERS` file (invalid patterns are skipped).
+    pub fn parse(content: &str) -> Self {
+        let rules: Vec<(String, Vec<String>)> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let pattern = parts.next()?.to_string();
+                let owners = parts
+                    .take_while(|part| !part.starts_with('#'))
+                    .map(ToString::to_string)
+                    .collect();
+                Some((pattern, owners))
+            })
+            .collect();
+
+        let mut builder = GlobSetBuilder::new();
+        let mut glob_rules = Vec::new();
+        for (idx, (pattern, _)) in rules.iter().enumerate() {
+            for glob in globs(pattern) {
+                match GlobBuilder::new(&glob).literal_separator(true).build() {
+                    Ok(glob) => {
+                        builder.add(glob);
+                        glob_rules.push(idx);
+                    }
+                    Err(e) => debug!("ignoring CODEOWNERS pattern {}: {}", pattern, e),
+                }
+            }
+        }
+        let globs = builder.build().unwrap_or_else(|e| {
+            debug!("ignoring CODEOWNERS: {}", e);
+            glob_rules.clear();
+            GlobSet::empty()
+        });
+        Self {
+            rules,
+            globs,
+            glob_rules,
+        }
+    }
+
+    /// Returns the owners of a file (given relative to the root of the repository).
+    pub fn owners_of(&self, path: &str) -> &[String] {
+        self.globs
+            .matches(path)
+            .into_iter()
+            .map(|glob| self.glob_rules[glob])
+            .max()
+            .map(|rule| self.rules[rule].1.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the owners of the packages of a repository (the owners of their manifests).
+    pub(crate) fn owners_of_packages(
+        &self,
+        repo_dir: &Path,
+        packages: &[LocalPackage],
+    ) -> HashMap<CrateName, Vec<String>> {
+        packages
+            .iter()
+            .map(|package| {
+                let path = package
+                    .manifest_path
+                    .strip_prefix(repo_dir)
+                    .unwrap_or(&package.manifest_path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                (package.name.clone(), self.owners_of(&path).to_vec())
+            })
+            .collect()
+    }
+}
+
+/// Translates a `CODEOWNERS` pattern into globs matching the paths of files
+/// (a pattern matching a directory matches everything under it).
+fn globs(pattern: &str) -> Vec<String> {
+    let directory_only = pattern.ends_with('/');
+    let anchored = pattern.starts_with('/') || pattern.trim_end_matches('/').contains('/');
+    let pattern = pattern.trim_matches('/');
+    if pattern.is_empty() || pattern == "**" {
+        return vec!["**".to_string()];
+    }
+
+    let base = if anchored {
+        pattern.to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    let mut globs = vec![format!("{}/**", base)];
+    if !directory_only {
+        globs.push(base);
+    }
+    globs
+}
+
+/// The health of the dependencies used by the workspace members a team owns.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TeamReport {
+    /// the owner, as written in `CODEOWNERS` (e.g. `@org/team`)
+    pub owner: String,
+    pub dependencies: usize,
+    pub direct: usize,
+    /// the dependencies with an update available
+    pub updatable: Vec<String>,
+    /// the dependencies affected by a RUSTSEC vulnerability
+    pub vulnerable: Vec<String>,
+    /// the dependencies under a copyleft license
+    pub copyleft: Vec<String>,
+}
+
+/// Produces a report per owner (see [`super::DependencyInfo::code_owners`]).
+pub fn team_reports(analysis: &RustAnalysis) -> Vec<TeamReport> {
+    let vulnerable: BTreeSet<&str> = analysis
+        .rustsec()
+        .vulnerabilities()
+        .iter()
+        .map(|vuln| vuln.package.name.as_str())
+        .collect();
+    let mut reports: BTreeMap<&str, TeamReport> = BTreeMap::new();
+    for dependency in analysis.dependencies() {
+        for owner in dependency.code_owners() {
+            let report = reports.entry(owner).or_insert_with(|| TeamReport {
+                owner: owner.clone(),
+                ..Default::default()
+            });
+            let name = dependency.name().to_string();
+            report.dependencies += 1;
+            if dependency.direct() {
+                report.direct += 1;
+            }
+            if dependency.update().is_some() {
+                report.updatable.push(name.clone());
+            }
+            if vulnerable.contains(dependency.name()) {
+                report.vulnerable.push(name.clone());
+            }
+            if dependency.license_class().is_copyleft() {
+                report.copyleft.push(name);
+            }
+        }
+    }
+    reports.into_iter().map(|(_, report)| report).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_owners() {
+        let codeowners = CodeOwners::parse(
+            "
+# default owners
+*                @acme/core
+/crypto/         @acme/crypto @alice # inline comment
+network/**/*.toml @acme/network
+docs/            @acme/docs
+",
+        );
+        assert_eq!(codeowners.owners_of("Cargo.toml"), &["@acme/core"]);
+        assert_eq!(
+            codeowners.owners_of("crypto/ed25519/Cargo.toml"),
+            &["@acme/crypto", "@alice"]
+        );
+        assert_eq!(
+            codeowners.owners_of("network/peer/Cargo.toml"),
+            &["@acme/network"]
+        );
+        // unanchored directory patterns match at any depth
+        assert_eq!(codeowners.owners_of("sdk/docs/Cargo.toml"), &["@acme/docs"]);
+        // anchored ones don't
+        assert_eq!(
+            codeowners.owners_of("sdk/crypto/Cargo.toml"),
+            &["@acme/core"]
+        );
+        assert!(CodeOwners::default().owners_of("Cargo.toml").is_empty());
+    }
+
+    #[test]
+    fn test_pathological_pattern() {
+        // (a backtracking matcher takes exponential time on this one)
+        let pattern = format!("{}b", "*a".repeat(30));
+        let codeowners = CodeOwners::parse(&format!("{} @acme/a\n", pattern));
+        let path = format!("{}/Cargo.toml", "a".repeat(100));
+        assert!(codeowners.owners_of(&path).is_empty());
+        assert_eq!(
+            codeowners.owners_of(&format!("{}b/Cargo.toml", "a".repeat(100))),
+            &["@acme/a"]
+        );
+    }
+}
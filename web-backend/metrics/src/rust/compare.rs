@@ -0,0 +1,474 @@
+//! This module helps selecting a new dependency,
+//! by fetching metrics about candidate crates (that are not dependencies yet)
+//! and comparing them side by side.
+
+use anyhow::Result;
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use super::{
+    cargoaudit, crate_name,
+    cratesio::Crates,
+    discovery::{DiscoveryMethod, Links},
+    docsrs::{self, DocsStatus},
+    fuzzing,
+    provenance::{self, RepositoryRedirect},
+    report::SourceInfo,
+    CONCURRENCY,
+};
+use crate::common::github::{self, RepositoryStats};
+use crate::dashboard::table::{Align, Cell, Table};
+use crate::locale::Locale;
+use crate::sources::Source;
+
+/// a crate is a standard choice in a category if it is among its most downloaded crates
+const TOP_CRATES: usize = 20;
+
+/// Metrics about a candidate crate.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CrateComparison {
+    pub name: String,
+    pub description: Option<String>,
+    /// latest version published
+    pub latest_version: Option<String>,
+    /// number of versions published
+    pub versions: usize,
+    /// date of the first release
+    pub created_at: Option<String>,
+    /// date of the latest release
+    pub updated_at: Option<String>,
+    /// total number of downloads
+    pub downloads: u64,
+    /// number of downloads in the last 90 days
+    pub recent_downloads: Option<u64>,
+    /// owners on crates.io
+    pub owners: Vec<String>,
+    pub repository: Option<String>,
+    /// how the repository was found (see [`Links::discover`])
+    #[serde(default)]
+    pub repository_discovery: Option<DiscoveryMethod>,
+    /// statistics of the GitHub repository (if hosted on GitHub)
+    pub github: Option<RepositoryStats>,
+    /// all RUSTSEC advisories ever published for the crate
+    pub advisories: Vec<String>,
+    /// the crates.io categories of the crate (slugs, e.g. `data-structures`)
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// the categories in which the crate is among the most downloaded (see [`TOP_CRATES`])
+    #[serde(default)]
+    pub top_in_categories: Vec<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// the documentation of the latest version on docs.rs
+    #[serde(default)]
+    pub docs: Option<DocsStatus>,
+    /// the GitHub repository that the declared one redirects to (renamed or transferred)
+    #[serde(default)]
+    pub repository_redirect: Option<RepositoryRedirect>,
+    /// the source of the latest version (build script, `unsafe` usages, see [`SourceInfo`])
+    #[serde(default)]
+    pub code: Option<SourceInfo>,
+}
+
+/// Fetches metrics for a list of candidate crates.
+pub async fn compare_crates(names: &[String]) -> Result<Vec<CrateComparison>> {
+    let advisory_db = if Source::Advisories.is_enabled() {
+        Some(cargoaudit::advisory_db().await?)
+    } else {
+        None
+    };
+
+    let comparisons: Vec<CrateComparison> = stream::iter(names)
+        .map(|name| async move {
+            let mut comparison = CrateComparison {
+                name: name.clone(),
+                ..Default::default()
+            };
+
+            // crates.io
+            if !Source::CratesIo.is_enabled() {
+                return comparison;
+            }
+            match Crates::get_all_versions(name).await {
+                Ok(crate_) => {
+                    // (the README is not read, it would need to download the package)
+                    let discovered = Links::from_crate_info(&crate_.crate_info).discover(|| None);
+                    comparison.description = crate_.crate_info.description;
+                    comparison.latest_version = crate_.crate_info.max_version;
+                    comparison.versions = crate_.versions.len();
+                    comparison.created_at = crate_.crate_info.created_at;
+                    comparison.updated_at = crate_.crate_info.updated_at;
+                    comparison.downloads = crate_.crate_info.downloads;
+                    comparison.recent_downloads = crate_.crate_info.recent_downloads;
+                    comparison.repository = discovered.as_ref().map(|(url, _)| url.clone());
+                    comparison.repository_discovery = discovered.map(|(_, method)| method);
+                    comparison.categories = crate_.crate_info.categories;
+                    comparison.keywords = crate_.crate_info.keywords;
+                }
+                Err(e) => error!("couldn't get {} from crates.io: {}", name, e),
+            };
+            if let Some(latest_version) = &comparison.latest_version {
+                match docsrs::get_docs_status(name, latest_version).await {
+                    Ok(docs) => comparison.docs = Some(docs),
+                    Err(e) => error!("couldn't get the docs.rs status of {}: {}", name, e),
+                }
+                match SourceInfo::new(name, latest_version).await {
+                    Ok(code) => comparison.code = Some(code),
+                    Err(e) => error!("couldn't analyze the source of {}: {}", name, e),
+                }
+            }
+            for category in &comparison.categories {
+                match Crates::get_top_crates(category, TOP_CRATES).await {
+                    Ok(top) if top.iter().any(|top| crate_name::same_crate(top, name)) => {
+                        comparison.top_in_categories.push(category.clone())
+                    }
+                    Ok(_) => (),
+                    Err(e) => error!("couldn't get the top crates of {}: {}", category, e),
+                }
+            }
+            match Crates::get_owners(name).await {
+                Ok(owners) => {
+                    comparison.owners = owners.into_iter().map(|owner| owner.login).collect()
+                }
+                Err(e) => error!("couldn't get owners of {}: {}", name, e),
+            };
+
+            // github
+            if let Some((owner, repo)) = comparison
+                .repository
+                .as_deref()
+                .and_then(github::repository_path)
+                .filter(|_| Source::GitHub.is_enabled())
+            {
+                match github::get_repository_stats(owner, repo).await {
+                    Ok(mut stats) => {
+                        comparison.repository_redirect =
+                            comparison.repository.as_deref().and_then(|declared| {
+                                provenance::repository_redirect(declared, &stats.full_name)
+                            });
+                        // (the other lookups only need the repository, so they run concurrently)
+                        let (policy, manifest, activity) = futures::join!(
+                            github::get_security_policy(owner, repo),
+                            github::get_file(owner, repo, fuzzing::FUZZ_MANIFEST),
+                            github::get_activity(owner, repo)
+                        );
+                        match policy {
+                            Ok(policy) => stats.security_policy = Some(policy),
+                            Err(e) => error!("couldn't get the security policy of {}: {}", name, e),
+                        };
+                        match manifest {
+                            Ok(manifest) => {
+                                stats.fuzz_targets = Some(
+                                    manifest
+                                        .map(|manifest| fuzzing::fuzz_targets(&manifest))
+                                        .unwrap_or_default(),
+                                )
+                            }
+                            Err(e) => error!("couldn't get the fuzz targets of {}: {}", name, e),
+                        };
+                        match activity {
+                            Ok(activity) => stats.activity = Some(activity),
+                            Err(e) => error!("couldn't get the activity of {}: {}", name, e),
+                        };
+                        comparison.github = Some(stats);
+                    }
+                    Err(e) => error!("couldn't get github stats of {}: {}", name, e),
+                };
+            }
+
+            comparison
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await;
+
+    // advisories
+    let comparisons = comparisons
+        .into_iter()
+        .map(|mut comparison| {
+            if let Some(advisory_db) = &advisory_db {
+                comparison.advisories =
+                    cargoaudit::advisories_for_crate(advisory_db, &comparison.name)
+                        .iter()
+                        .map(|advisory| advisory.metadata.id.to_string())
+                        .collect();
+            }
+            comparison
+        })
+        .collect();
+
+    Ok(comparisons)
+}
+
+/// Renders a comparison as a markdown table (one column per crate).
+pub fn render_markdown(comparisons: &[CrateComparison], locale: &Locale) -> String {
+    // header
+    let mut table = Table::new().column("", Align::Left);
+    for comparison in comparisons {
+        table = table.column(format!("**{}**", comparison.name), Align::Left);
+    }
+
+    // rows
+    let unknown = || "?".to_string();
+    let mut rows: Vec<(&str, Box<dyn Fn(&CrateComparison) -> String + '_>)> = vec![
+        (
+            "latest version",
+            Box::new(|c: &CrateComparison| c.latest_version.clone().unwrap_or_else(unknown)),
+        ),
+        (
+            "versions published",
+            Box::new(|c: &CrateComparison| locale.number(c.versions as u64)),
+        ),
+        (
+            "first release",
+            Box::new(|c: &CrateComparison| {
+                c.created_at
+                    .as_deref()
+                    .map(|date| locale.date_str(date))
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "latest release",
+            Box::new(|c: &CrateComparison| {
+                c.updated_at
+                    .as_deref()
+                    .map(|date| locale.date_str(date))
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "downloads",
+            Box::new(|c: &CrateComparison| locale.number(c.downloads)),
+        ),
+        (
+            "recent downloads",
+            Box::new(|c: &CrateComparison| {
+                c.recent_downloads
+                    .map(|downloads| locale.number(downloads))
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "categories",
+            Box::new(|c: &CrateComparison| {
+                if c.categories.is_empty() {
+                    return "none".to_string();
+                }
+                let categories: Vec<String> = c
+                    .categories
+                    .iter()
+                    .map(|category| {
+                        if c.top_in_categories.contains(category) {
+                            format!("{} (top {})", category, TOP_CRATES)
+                        } else {
+                            category.clone()
+                        }
+                    })
+                    .collect();
+                categories.join(", ")
+            }),
+        ),
+        (
+            "keywords",
+            Box::new(|c: &CrateComparison| c.keywords.join(", ")),
+        ),
+        (
+            "docs.rs",
+            Box::new(|c: &CrateComparison| {
+                c.docs
+                    .as_ref()
+                    .map(|docs| docs.describe())
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "owners",
+            Box::new(|c: &CrateComparison| c.owners.join(", ")),
+        ),
+        (
+            "stars",
+            Box::new(|c: &CrateComparison| {
+                c.github
+                    .as_ref()
+                    .map(|github| locale.number(github.stargazers_count))
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "open issues",
+            Box::new(|c: &CrateComparison| {
+                c.github
+                    .as_ref()
+                    .map(|github| locale.number(github.open_issues_count))
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "archived",
+            Box::new(|c: &CrateComparison| {
+                c.github
+                    .as_ref()
+                    .map(|github| github.archived.to_string())
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "security policy",
+            Box::new(|c: &CrateComparison| {
+                c.github
+                    .as_ref()
+                    .and_then(|github| github.security_policy.as_ref())
+                    .map(|policy| policy.describe())
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "fuzzing",
+            Box::new(|c: &CrateComparison| {
+                let github = match &c.github {
+                    Some(github) => github,
+                    None => return unknown(),
+                };
+                let targets = match &github.fuzz_targets {
+                    Some(targets) if targets.is_empty() => "no fuzz targets".to_string(),
+                    Some(targets) => format!("fuzz targets: {}", targets.join(", ")),
+                    None => unknown(),
+                };
+                match &github.security_policy {
+                    Some(policy) if policy.oss_fuzz => format!("{}, OSS-Fuzz", targets),
+                    _ => targets,
+                }
+            }),
+        ),
+        (
+            "recent commits",
+            Box::new(|c: &CrateComparison| {
+                c.github
+                    .as_ref()
+                    .and_then(|github| github.activity.as_ref())
+                    .map(|activity| activity.describe())
+                    .unwrap_or_else(unknown)
+            }),
+        ),
+        (
+            "RUSTSEC advisories",
+            Box::new(|c: &CrateComparison| {
+                if c.advisories.is_empty() {
+                    "none".to_string()
+                } else {
+                    c.advisories.join(", ")
+                }
+            }),
+        ),
+    ];
+    // (the source is left out of crate reports, which detail it)
+    if comparisons.iter().any(|c| c.code.is_some()) {
+        rows.push((
+            "build script",
+            Box::new(|c: &CrateComparison| {
+                c.code
+                    .as_ref()
+                    .map(|code| code.build_rs.to_string())
+                    .unwrap_or_else(unknown)
+            }),
+        ));
+        rows.push((
+            "rust code",
+            Box::new(|c: &CrateComparison| {
+                c.code
+                    .as_ref()
+                    .map(|code| {
+                        format!(
+                            "{} lines in {} files",
+                            locale.number(code.rust_lines as u64),
+                            locale.number(code.rust_files as u64)
+                        )
+                    })
+                    .unwrap_or_else(unknown)
+            }),
+        ));
+        rows.push((
+            "`unsafe` usages",
+            Box::new(|c: &CrateComparison| {
+                c.code
+                    .as_ref()
+                    .map(|code| {
+                        format!(
+                            "{} (counted by {})",
+                            locale.number(code.unsafe_keywords as u64),
+                            code.unsafe_scanner
+                        )
+                    })
+                    .unwrap_or_else(unknown)
+            }),
+        ));
+    }
+    for (label, value) in rows {
+        let mut cells = vec![Cell::text(label)];
+        cells.extend(comparisons.iter().map(|c| Cell::text(value(c))));
+        table.row(cells);
+    }
+
+    table.to_markdown()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_markdown() {
+        let comparisons = vec![
+            CrateComparison {
+                name: "a".to_string(),
+                versions: 3,
+                advisories: vec!["RUSTSEC-2020-0001".to_string()],
+                ..Default::default()
+            },
+            CrateComparison {
+                name: "b".to_string(),
+                ..Default::default()
+            },
+        ];
+        let markdown = render_markdown(&comparisons, &Locale::default());
+        assert!(markdown.starts_with("|  | **a** | **b** |\n|:---|:---|:---|\n"));
+        assert!(markdown.contains("| versions published | 3 | 0 |"));
+        assert!(markdown.contains("| RUSTSEC advisories | RUSTSEC-2020-0001 | none |"));
+        assert!(!markdown.contains("build script"));
+
+        let comparisons = vec![
+            CrateComparison {
+                name: "a".to_string(),
+                code: Some(SourceInfo {
+                    build_rs: true,
+                    rust_files: 2,
+                    rust_lines: 120,
+                    unsafe_keywords: 3,
+                    unsafe_scanner: "internal".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            CrateComparison {
+                name: "b".to_string(),
+                ..Default::default()
+            },
+        ];
+        let markdown = render_markdown(&comparisons, &Locale::default());
+        assert!(markdown.contains("| build script | true | ? |"));
+        assert!(markdown.contains("| rust code | 120 lines in 2 files | ? |"));
+        assert!(markdown.contains("| `unsafe` usages | 3 (counted by internal) | ? |"));
+    }
+
+    #[tokio::test]
+    #[ignore] // (queries crates.io, docs.rs and GitHub, run with `cargo test -- --ignored`)
+    async fn test_compare_crates() {
+        let comparisons = compare_crates(&["serde".to_string(), "miniserde".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(comparisons.len(), 2);
+        assert!(comparisons[0].versions > 0);
+    }
+}
@@ -0,0 +1,326 @@
+//! This module detects the version conflicts of the dependencies of a repository,
+//! which make a build larger and harder to audit than its manifests suggest, or its lockfile lie:
+//!
+//! - a crate used in several semver-incompatible versions (e.g. `rand 0.7` and `rand 0.8`),
+//!   each compiled separately and whose types can't be mixed,
+//! - a direct dependency used in another (compatible) version transitively,
+//! - a crate coming from several sources (e.g. crates.io and a git repository), distinct crates to cargo,
+//! - a requirement of a manifest that no version of `Cargo.lock` matches
+//!   (the lockfile is stale, or the dependency is patched).
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tracing::debug;
+
+use super::{graph, lockfile_format, manifest_lints, DependencyInfo};
+
+/// The kinds of conflicts.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictKind {
+    /// the crate is used in several semver-incompatible versions
+    IncompatibleVersions,
+    /// a direct dependency is also used in another version transitively
+    DirectTransitive,
+    /// the crate comes from several sources
+    MultipleSources,
+    /// a requirement of a manifest matches no version of `Cargo.lock`
+    UnmatchedRequirement,
+}
+
+impl ConflictKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::IncompatibleVersions => "incompatible versions",
+            Self::DirectTransitive => "direct and transitive versions",
+            Self::MultipleSources => "multiple sources",
+            Self::UnmatchedRequirement => "unmatched requirement",
+        }
+    }
+}
+
+/// A version conflict of a crate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VersionConflict {
+    pub name: String,
+    pub kind: ConflictKind,
+    /// the versions involved (for an unmatched requirement, the locked ones)
+    pub versions: Vec<String>,
+    /// what the conflict is and why it matters
+    pub explanation: String,
+}
+
+/// The semver-compatible range of a version (e.g. `1` for `1.2.3`, `0.2` for `0.2.3`).
+fn compatibility(version: &Version) -> String {
+    match (version.major, version.minor) {
+        (0, 0) => format!("0.0.{}", version.patch),
+        (0, minor) => format!("0.{}", minor),
+        (major, _) => major.to_string(),
+    }
+}
+
+fn join(versions: &BTreeSet<&Version>) -> String {
+    versions
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Detects the conflicts between the versions and sources of the dependencies.
+pub fn detect(dependencies: &[DependencyInfo]) -> Vec<VersionConflict> {
+    let mut crates: BTreeMap<&str, Vec<&DependencyInfo>> = BTreeMap::new();
+    for dependency in dependencies {
+        crates
+            .entry(dependency.name())
+            .or_default()
+            .push(dependency);
+    }
+
+    let mut conflicts = Vec::new();
+    for (name, dependencies) in crates {
+        let versions: BTreeSet<&Version> = dependencies.iter().map(|dep| dep.version()).collect();
+        let ranges: BTreeSet<String> = versions.iter().map(|v| compatibility(*v)).collect();
+        let direct: BTreeSet<&Version> = dependencies
+            .iter()
+            .filter(|dep| dep.direct())
+            .map(|dep| dep.version())
+            .collect();
+        let transitive: BTreeSet<&Version> = dependencies
+            .iter()
+            .filter(|dep| !dep.direct())
+            .map(|dep| dep.version())
+            .collect();
+
+        if ranges.len() > 1 {
+            conflicts.push(VersionConflict {
+                name: name.to_string(),
+                kind: ConflictKind::IncompatibleVersions,
+                versions: versions.iter().map(ToString::to_string).collect(),
+                explanation: format!(
+                    "{} is used in {} semver-incompatible versions ({}): each is compiled, \
+                    and their types can't be mixed",
+                    name,
+                    ranges.len(),
+                    join(&versions)
+                ),
+            });
+        } else if !direct.is_empty() && transitive.iter().any(|v| !direct.contains(v)) {
+            let others: BTreeSet<&Version> = transitive.difference(&direct).cloned().collect();
+            conflicts.push(VersionConflict {
+                name: name.to_string(),
+                kind: ConflictKind::DirectTransitive,
+                versions: versions.iter().map(ToString::to_string).collect(),
+                explanation: format!(
+                    "{} is required directly in {} but used transitively in {}: both are compiled, \
+                    aligning the direct requirement would unify them",
+                    name,
+                    join(&direct),
+                    join(&others)
+                ),
+            });
+        }
+
+        let sources: BTreeSet<String> = dependencies
+            .iter()
+            .map(|dep| graph::source_name(dep.repo()))
+            .collect();
+        // (an internal fork is expected to come from another source than crates.io)
+        if sources.len() > 1 {
+            let explanation = if dependencies.iter().any(|dep| dep.forked()) {
+                format!(
+                    "{} is an internal fork, also used from {} other source(s) ({}): \
+                    expected, but they are distinct crates to cargo",
+                    name,
+                    sources.len() - 1,
+                    sources.into_iter().collect::<Vec<_>>().join(", ")
+                )
+            } else {
+                format!(
+                    "{} comes from {} sources ({}): they are distinct crates to cargo, \
+                    and the advisories of one don't tell about the others",
+                    name,
+                    sources.len(),
+                    sources.into_iter().collect::<Vec<_>>().join(", ")
+                )
+            };
+            conflicts.push(VersionConflict {
+                name: name.to_string(),
+                kind: ConflictKind::MultipleSources,
+                versions: versions.iter().map(ToString::to_string).collect(),
+                explanation,
+            });
+        }
+    }
+    conflicts
+}
+
+/// Detects the requirements of the manifests of a repository that no version of its `Cargo.lock` matches.
+pub fn unmatched_requirements(repo_dir: &Path) -> Vec<VersionConflict> {
+    let lockfile = match lockfile_format::load(&repo_dir.join("Cargo.lock")) {
+        Ok(lockfile) => lockfile,
+        Err(e) => {
+            debug!("no Cargo.lock to check the requirements against: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut locked: BTreeMap<&str, Vec<&Version>> = BTreeMap::new();
+    for package in &lockfile.packages {
+        locked
+            .entry(package.name.as_str())
+            .or_default()
+            .push(&package.version);
+    }
+
+    let manifests = manifest_lints::read_manifests(repo_dir);
+    let mut conflicts = Vec::new();
+    for (path, content, manifest) in &manifests {
+        for declaration in manifest_lints::declarations(path, content, manifest) {
+            let requirement = match declaration.requirement() {
+                Some(requirement) => requirement,
+                None => continue,
+            };
+            let (accepted, versions) = match (
+                VersionReq::parse(requirement),
+                locked.get(declaration.name()),
+            ) {
+                (Ok(parsed), Some(versions)) => (parsed, versions),
+                // (a dependency that is not locked is not built, e.g. an optional or platform-specific one)
+                _ => continue,
+            };
+            if versions.iter().any(|v| accepted.matches(v)) {
+                continue;
+            }
+            let versions: BTreeSet<&Version> = versions.iter().cloned().collect();
+            conflicts.push(VersionConflict {
+                name: declaration.name().to_string(),
+                kind: ConflictKind::UnmatchedRequirement,
+                versions: versions.iter().map(ToString::to_string).collect(),
+                explanation: format!(
+                    "{} requires {} {} in [{}], which the locked versions ({}) can't match: \
+                    Cargo.lock is stale, or the dependency is patched",
+                    declaration.manifest,
+                    declaration.name(),
+                    requirement,
+                    declaration.section,
+                    join(&versions)
+                ),
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guppy_summaries::SummarySource;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_detect() {
+        let dependency = |name: &str, version: &str, direct: bool, source: SummarySource| {
+            DependencyInfo::new(
+                name.to_string(),
+                Version::parse(version).unwrap(),
+                source,
+                false,
+                direct,
+            )
+        };
+        let git = || SummarySource::External {
+            source: "git+https://github.com/acme/serde?rev=1#1".to_string(),
+        };
+        let dependencies = vec![
+            dependency("rand", "0.7.3", true, SummarySource::CratesIo),
+            dependency("rand", "0.8.3", false, SummarySource::CratesIo),
+            dependency("log", "0.4.8", true, SummarySource::CratesIo),
+            dependency("log", "0.4.14", false, SummarySource::CratesIo),
+            dependency("serde", "1.0.123", true, SummarySource::CratesIo),
+            dependency("serde", "1.0.123", false, git()),
+            dependency("libc", "0.2.86", true, SummarySource::CratesIo),
+            dependency("libc", "0.2.86", false, SummarySource::CratesIo),
+        ];
+        let conflicts = detect(&dependencies);
+        let kinds: Vec<(&str, ConflictKind)> = conflicts
+            .iter()
+            .map(|conflict| (conflict.name.as_str(), conflict.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("log", ConflictKind::DirectTransitive),
+                ("rand", ConflictKind::IncompatibleVersions),
+                ("serde", ConflictKind::MultipleSources),
+            ]
+        );
+        assert_eq!(
+            conflicts[0].explanation,
+            "log is required directly in 0.4.8 but used transitively in 0.4.14: both are compiled, \
+            aligning the direct requirement would unify them"
+        );
+        assert_eq!(conflicts[1].versions, vec!["0.7.3", "0.8.3"]);
+
+        // (the other source of an internal fork is annotated)
+        let mut fork = dependency("serde", "1.0.123", false, git());
+        fork.forked = true;
+        let conflicts = detect(&[
+            dependency("serde", "1.0.123", true, SummarySource::CratesIo),
+            fork,
+        ]);
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0]
+            .explanation
+            .starts_with("serde is an internal fork, also used from 1 other source(s)"));
+    }
+
+    #[test]
+    fn test_unmatched_requirements() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+serde = "1.0.130"
+log = "0.4"
+optional = { version = "1", optional = true }
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("Cargo.lock"),
+            r#"[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["log", "serde"]
+
+[[package]]
+name = "log"
+version = "0.4.14"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.123"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let conflicts = unmatched_requirements(dir.path());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].name, "serde");
+        assert_eq!(conflicts[0].versions, vec!["1.0.123"]);
+        assert_eq!(
+            conflicts[0].explanation,
+            "Cargo.toml requires serde 1.0.130 in [dependencies], which the locked versions (1.0.123) can't match: \
+            Cargo.lock is stale, or the dependency is patched"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! This module handles the naming variants of crates.
+//! crates.io treats `foo-bar`, `foo_bar` and `Foo-Bar` as the same crate
+//! (only one of them can be published), so names obtained from different places
+//! (a lockfile, a configuration, an advisory, a user) must be compared in their canonical form.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Returns the canonical form of a crate name (lowercase, with underscores instead of hyphens).
+pub fn canonical(name: &str) -> String {
+    canonical_chars(name).collect()
+}
+
+/// Are the two names the same crate?
+pub fn same_crate(name: &str, other: &str) -> bool {
+    canonical_chars(name).eq(canonical_chars(other))
+}
+
+/// Is the name a possible crate name (ASCII alphanumerics, `-` and `_`)?
+/// Names received from users must be checked before they end up in paths or URLs.
+pub fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn canonical_chars(name: &str) -> impl Iterator<Item = char> + '_ {
+    name.chars().map(|c| match c {
+        '-' => '_',
+        c => c.to_ascii_lowercase(),
+    })
+}
+
+/// The name of a crate, as written, that compares (and hashes) by its canonical form,
+/// so that it can key maps and sets without missing naming variants.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct CrateName(String);
+
+impl CrateName {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    /// The name as written.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The canonical form of the name (see [`canonical`]).
+    pub fn canonical(&self) -> String {
+        canonical(&self.0)
+    }
+}
+
+impl PartialEq for CrateName {
+    fn eq(&self, other: &Self) -> bool {
+        same_crate(&self.0, &other.0)
+    }
+}
+
+impl Eq for CrateName {}
+
+impl PartialEq<str> for CrateName {
+    fn eq(&self, other: &str) -> bool {
+        same_crate(&self.0, other)
+    }
+}
+
+impl PartialEq<&str> for CrateName {
+    fn eq(&self, other: &&str) -> bool {
+        same_crate(&self.0, other)
+    }
+}
+
+impl Hash for CrateName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for c in canonical_chars(&self.0) {
+            c.hash(state);
+        }
+    }
+}
+
+impl PartialOrd for CrateName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CrateName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        canonical_chars(&self.0).cmp(canonical_chars(&other.0))
+    }
+}
+
+impl fmt::Display for CrateName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for CrateName {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for CrateName {
+    fn from(name: String) -> Self {
+        Self(name)
+    }
+}
+
+impl AsRef<str> for CrateName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_crate_name() {
+        assert_eq!(canonical("Foo-Bar_baz"), "foo_bar_baz");
+        assert!(same_crate("serde-json", "serde_json"));
+        assert!(!same_crate("serde", "serde_json"));
+
+        assert!(is_valid("serde_json"));
+        assert!(is_valid("tokio-util2"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("../serde"));
+        assert!(!is_valid("serde==1.0.0"));
+
+        let name = CrateName::from("foo-bar");
+        assert_eq!(name, CrateName::from("Foo_Bar"));
+        assert_eq!(name, "foo_bar");
+        assert_eq!(name.to_string(), "foo-bar");
+        assert_eq!(name.canonical(), "foo_bar");
+
+        let names: HashSet<CrateName> = vec!["foo-bar".into(), "foo_bar".into(), "baz".into()]
+            .into_iter()
+            .collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&CrateName::from("FOO-BAR")));
+
+        assert_eq!(
+            serde_json::to_string(&name).unwrap(),
+            "\"foo-bar\"".to_string()
+        );
+    }
+}
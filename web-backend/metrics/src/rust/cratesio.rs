@@ -1,5 +1,126 @@
+//! This module talks to crates.io.
+//! Every request (API calls and crate downloads) goes through [`Client`],
+//! which follows the crawler policy of crates.io (https://crates.io/policies#crawlers)
+//! so that large analyses don't get banned.
+//! Tests can give a client a mock transport (see [`crate::http`]).
+
 use anyhow::Result;
 use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+use super::crate_name::canonical;
+use crate::cache;
+use crate::http::{self, Transport};
+use crate::sources;
+
+/// the minimum interval between two requests to crates.io (at most 1 request per second)
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// identifies the tool to crates.io
+const USER_AGENT: &str = concat!(
+    "whackadep/",
+    env!("CARGO_PKG_VERSION"),
+    " (dependency review dashboard)"
+);
+
+/// the time (in milliseconds since the epoch) at which the next request can be sent,
+/// shared by every client
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(0);
+
+/// A crates.io client. Requests are throttled (shared between every client):
+/// they are spaced by at least [`REQUEST_INTERVAL`], concurrent requests being queued
+/// rather than sent in bursts. API responses are cached and revalidated with conditional requests
+/// (see [`cache::get`]).
+pub struct Client {
+    transport: Arc<dyn Transport>,
+}
+
+impl Client {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            transport: http::default_transport(USER_AGENT)?,
+        })
+    }
+
+    /// Creates a client sending its requests through the given transport (e.g. a mock).
+    pub fn with_transport(transport: Arc<dyn Transport>) -> Self {
+        Self { transport }
+    }
+
+    /// Gets a response of the crates.io API.
+    pub async fn get(&self, url: &str) -> Result<String> {
+        // (offline, the response comes from the cache)
+        if self.transport.is_network() && !sources::offline() {
+            throttle().await;
+        }
+        cache::get(&*self.transport, url).await
+    }
+
+    /// Retrieves all versions published on crates.io for a given crate.
+    pub async fn get_crate(&self, name: &str) -> Result<Crates> {
+        // (crates.io accepts any naming variant, the canonical one keeps a single cache entry)
+        let url = format!("https://crates.io/api/v1/crates/{}", canonical(name));
+        let body = self.get(&url).await?;
+        serde_json::from_str(&body).map_err(anyhow::Error::msg)
+    }
+
+    /// Retrieves the users and teams that own a given crate on crates.io.
+    pub async fn get_owners(&self, name: &str) -> Result<Vec<Owner>> {
+        let url = format!("https://crates.io/api/v1/crates/{}/owners", canonical(name));
+        let body = self.get(&url).await?;
+        let owners: Owners = serde_json::from_str(&body).map_err(anyhow::Error::msg)?;
+        Ok(owners.users)
+    }
+
+    /// Retrieves the names of the most downloaded crates of a category (given by its slug, e.g. `encoding`).
+    pub async fn get_top_crates(&self, category: &str, count: usize) -> Result<Vec<String>> {
+        let url = format!(
+            "https://crates.io/api/v1/crates?category={}&sort=downloads&per_page={}",
+            category, count
+        );
+        let body = self.get(&url).await?;
+        let page: CratePage = serde_json::from_str(&body).map_err(anyhow::Error::msg)?;
+        Ok(page
+            .crates
+            .into_iter()
+            .map(|summary| summary.name)
+            .collect())
+    }
+
+    /// Downloads the package (`.crate` file) of a crate version.
+    pub async fn download(&self, name: &str, version: &str) -> Result<Vec<u8>> {
+        let url = format!(
+            "https://crates.io/api/v1/crates/{}/{}/download",
+            name, version
+        );
+        if self.transport.is_network() {
+            throttle().await;
+        }
+        let response = self.transport.get(&url, &[]).await?.error_for_status()?;
+        Ok(response.body)
+    }
+}
+
+/// Waits for the next request slot.
+async fn throttle() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or(0);
+    let interval = REQUEST_INTERVAL.as_millis() as u64;
+    // reserve the next slot
+    let previous = NEXT_REQUEST
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |next| {
+            Some(next.max(now) + interval)
+        })
+        .unwrap_or(now);
+    let slot = previous.max(now);
+    if slot > now {
+        sleep(Duration::from_millis(slot - now)).await;
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct Crates {
@@ -10,24 +131,107 @@ pub struct Crates {
 
 #[derive(Deserialize, Debug)]
 pub struct CrateInfo {
-    pub repository: String,
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    #[serde(default)]
+    pub documentation: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub downloads: u64,
+    #[serde(default)]
+    pub recent_downloads: Option<u64>,
+    #[serde(default)]
+    pub max_version: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    /// the slugs of the categories of the crate (e.g. `data-structures`)
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+/// A page of a crate search.
+#[derive(Deserialize, Debug)]
+struct CratePage {
+    crates: Vec<CrateSummary>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CrateSummary {
+    name: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Version {
     pub num: String,
     pub created_at: String,
+    /// size of the compressed package, in bytes
+    #[serde(default)]
+    pub crate_size: Option<u64>,
+    /// the license declared by the version (an SPDX expression)
+    #[serde(default)]
+    pub license: Option<String>,
+    /// the user who published the version (not recorded for old versions)
+    #[serde(default)]
+    pub published_by: Option<Publisher>,
+}
+
+/// A crates.io user who published a version.
+#[derive(Deserialize, Debug)]
+pub struct Publisher {
+    pub login: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Owners {
+    pub users: Vec<Owner>,
+}
+
+/// An owner of a crate on crates.io, which can be a user or a team.
+#[derive(Deserialize, Debug)]
+pub struct Owner {
+    /// the login of a user (e.g. `dtolnay`),
+    /// or of a team (e.g. `github:rust-lang:libs`)
+    pub login: String,
+    pub kind: Option<String>,
+}
+
+impl Owner {
+    /// returns the github organization of a team, or the login of a user
+    pub fn organization(&self) -> &str {
+        organization(&self.login)
+    }
+}
+
+/// returns the github organization of a team login (e.g. `github:rust-lang:libs`),
+/// or the login of a user
+pub fn organization(login: &str) -> &str {
+    let mut parts = login.split(':');
+    match (parts.next(), parts.next()) {
+        (Some("github"), Some(org)) => org,
+        _ => login,
+    }
 }
 
 impl Crates {
     /// retrieves all versions published on crates.io for a given dependency
     pub async fn get_all_versions(name: &str) -> Result<Self> {
-        let url = format!("https://crates.io/api/v1/crates/{}", name);
+        Client::new()?.get_crate(name).await
+    }
 
-        let client = reqwest::Client::builder().user_agent("whackadep").build()?;
+    /// retrieves the users and teams that own a given crate on crates.io
+    pub async fn get_owners(name: &str) -> Result<Vec<Owner>> {
+        Client::new()?.get_owners(name).await
+    }
 
-        let body = client.get(&url).send().await?.text().await?;
-        serde_json::from_str(&body).map_err(anyhow::Error::msg)
+    /// retrieves the names of the most downloaded crates of a category
+    pub async fn get_top_crates(category: &str, count: usize) -> Result<Vec<String>> {
+        Client::new()?.get_top_crates(category, count).await
     }
 }
 
@@ -35,14 +239,79 @@ impl Crates {
 mod tests {
 
     use super::*;
+    use crate::testing;
+
+    /// A client playing back the responses of crates.io for serde.
+    fn serde_client() -> Client {
+        let cassette = testing::cassette("crates-io-serde").unwrap();
+        Client::with_transport(Arc::new(cassette.transport()))
+    }
 
     #[tokio::test]
     async fn test_get_all_versions() {
-        let creates_io = Crates::get_all_versions("serde").await.unwrap();
+        let creates_io = serde_client().get_crate("serde").await.unwrap();
 
         let version_found = creates_io.versions.iter().find(|version| {
             version.num == "1.0.121" && version.created_at == "2021-01-23T21:17:54.177776+00:00"
         });
         assert!(version_found.is_some());
     }
+
+    #[tokio::test]
+    async fn test_get_owners() {
+        let owners = serde_client().get_owners("serde").await.unwrap();
+        assert!(owners.iter().any(|owner| owner.login == "dtolnay"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_client() {
+        let transport = http::MockTransport::new().with_response(
+            "https://crates.io/api/v1/crates/serde/owners",
+            http::Response::ok(r#"{"users": [{"login": "dtolnay", "kind": "user"}]}"#),
+        );
+        let transport = Arc::new(transport);
+        let client = Client::with_transport(transport.clone());
+        let owners = client.get_owners("serde").await.unwrap();
+        assert_eq!(owners[0].login, "dtolnay");
+        assert!(client.get_crate("serde").await.is_err());
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_top_crates() {
+        let transport = http::MockTransport::new().with_response(
+            "https://crates.io/api/v1/crates?category=encoding&sort=downloads&per_page=2",
+            http::Response::ok(
+                r#"{"crates": [{"name": "serde_json", "downloads": 1}, {"name": "base64"}], "meta": {"total": 1000}}"#,
+            ),
+        );
+        let client = Client::with_transport(Arc::new(transport));
+        let top = client.get_top_crates("encoding", 2).await.unwrap();
+        assert_eq!(top, vec!["serde_json", "base64"]);
+    }
+
+    #[tokio::test]
+    async fn test_throttle() {
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            throttle().await;
+        }
+        // (the first request might not wait)
+        assert!(start.elapsed() >= 2 * REQUEST_INTERVAL - Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_owner_organization() {
+        let team = Owner {
+            login: "github:rust-lang:libs".to_string(),
+            kind: Some("team".to_string()),
+        };
+        assert_eq!(team.organization(), "rust-lang");
+
+        let user = Owner {
+            login: "dtolnay".to_string(),
+            kind: Some("user".to_string()),
+        };
+        assert_eq!(user.organization(), "dtolnay");
+    }
 }
@@ -7,30 +7,234 @@
 use anyhow::{bail, ensure, Result};
 use regex::Regex;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tempfile::tempdir;
 use tokio::process::Command;
 use tracing::info;
 
-async fn download_cargo_crate(crate_with_version: &str, extract_dir: &Path) -> Result<()> {
-    // return path to downloaded crate
-    // cargo download cargo-download==0.1.2
+use crate::sources::{self, Source};
+use crate::timing::{self, Section};
+use crate::{audit, cache};
+
+use super::{
+    build_time::{self, BuildTimeChange},
+    cargo_home,
+    churn::{self, Churn},
+    crate_name, cratesio,
+    geiger::{self, UnsafeDelta},
+    provenance::{self, RepositoryChange},
+    reachability,
+    risky_paths::{RiskyFile, RiskyPaths},
+    secrets, toolchain,
+    unsafety::{self, UnsafeChange},
+};
+
+/// The signals obtained by diffing two versions of a crate.
+#[derive(Debug, Default)]
+pub struct CrateDiff {
+    /// build.rs changed
+    pub build_rs: bool,
+    /// new toolchain requirements (see [`toolchain::toolchain_changes`])
+    pub toolchain_changes: Vec<String>,
+    /// the growth of the code running at build time (see [`build_time::build_time_change`])
+    pub build_time_change: Option<BuildTimeChange>,
+    /// the (uncompressed size in bytes, number of files) of the original and the new crate
+    pub sizes: ((u64, usize), (u64, usize)),
+    /// possible secrets and blobs added (see [`secrets::scan`])
+    pub secrets: Vec<secrets::SecretFinding>,
+    /// the files changed that match a risky path pattern
+    pub risky_files: Vec<RiskyFile>,
+    /// the rust files changed that contain unsafe code (see [`unsafety::unsafe_changes`])
+    pub unsafe_changes: Vec<UnsafeChange>,
+    /// the unsafe usages counted by cargo-geiger in both versions, if it is available (see [`geiger::unsafe_delta`])
+    pub unsafe_delta: Option<UnsafeDelta>,
+    /// the lines changed, hand-written and vendored/generated code apart (see [`churn::churn`])
+    pub churn: Churn,
+    /// the repository declared by the manifest changed (see [`provenance::repository_change`])
+    pub repository_change: Option<RepositoryChange>,
+    /// the functions affected by advisories whose definition changed (see [`reachability::changed_functions`])
+    pub changed_functions: Vec<String>,
+}
+
+/// A crate version found in the local cargo cache.
+#[derive(Debug, PartialEq)]
+enum CachedCrate {
+    /// the sources extracted by cargo (`~/.cargo/registry/src/<registry>/<name>-<version>`)
+    Src(PathBuf),
+    /// the package downloaded by cargo (`~/.cargo/registry/cache/<registry>/<name>-<version>.crate`)
+    Package(PathBuf),
+}
+
+/// Looks for a crate version in the cargo cache, preferring the extracted sources
+/// (unless `packages_only` is set: build scripts can write in the extracted sources).
+fn find_cached_crate(
+    cargo_home: &Path,
+    name: &str,
+    version: &str,
+    packages_only: bool,
+) -> Option<CachedCrate> {
+    let registry = cargo_home.join("registry");
+    // (the name might be a naming variant of the one cargo used, see [`crate_name`])
+    let in_registries = |dir: &str, file_name: String| -> Option<PathBuf> {
+        fs::read_dir(registry.join(dir))
+            .ok()?
+            .filter_map(|registry| registry.ok())
+            .find_map(|registry| {
+                let path = registry.path().join(&file_name);
+                if path.exists() {
+                    return Some(path);
+                }
+                fs::read_dir(registry.path())
+                    .ok()?
+                    .filter_map(|entry| entry.ok())
+                    .find(|entry| {
+                        crate_name::same_crate(&entry.file_name().to_string_lossy(), &file_name)
+                    })
+                    .map(|entry| entry.path())
+            })
+    };
+    in_registries("src", format!("{}-{}", name, version))
+        .filter(|crate_dir| !packages_only && crate_dir.join("Cargo.toml").exists())
+        .map(CachedCrate::Src)
+        .or_else(|| {
+            in_registries("cache", format!("{}-{}.crate", name, version)).map(CachedCrate::Package)
+        })
+}
+
+/// Returns the sources of a crate version available locally (prefetched, or extracted by cargo),
+/// without downloading or extracting anything.
+/// In sandbox mode, the sources extracted by cargo are not used (see [`find_cached_crate`]).
+pub(crate) fn local_sources(name: &str, version: &str) -> Option<PathBuf> {
+    let prefetched = cache::crates_dir().join(format!("{}=={}", name, version));
+    if prefetched.exists() {
+        return Some(prefetched);
+    }
+    match find_cached_crate(&cargo_home()?, name, version, sources::sandbox())? {
+        CachedCrate::Src(crate_dir) => Some(crate_dir),
+        CachedCrate::Package(_) => None,
+    }
+}
+
+/// Copies a directory recursively (skipping the `.cargo-ok` marker left by cargo).
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name() == ".cargo-ok" {
+            continue;
+        }
+        if path.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()))?;
+        } else {
+            fs::copy(&path, to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Downloads and extracts a crate version (e.g. `cargo-download==0.1.2`) in `extract_dir/<crate_with_version>`,
+/// with the crates.io client (see [`cratesio::Client`]).
+/// Versions already prefetched or in the local cargo cache are not downloaded again
+/// (and offline, only these versions can be obtained).
+/// In sandbox mode, only packages are used (see [`sources::sandbox`]).
+pub(crate) async fn download_cargo_crate(
+    crate_with_version: &str,
+    extract_dir: &Path,
+) -> Result<()> {
     let extract_path = extract_dir.join(crate_with_version);
     let extract_path = extract_path.as_path();
+
+    // look in the prefetched crates and in the cargo cache first
+    let prefetched = cache::crates_dir().join(crate_with_version);
+    if prefetched.exists() && prefetched != extract_path {
+        info!("using prefetched {}", crate_with_version);
+        return copy_dir(&prefetched, extract_path);
+    }
+    let mut name_version = crate_with_version.splitn(2, "==");
+    let name_version_parts = (name_version.next(), name_version.next());
+    let cached = match (name_version_parts, cargo_home()) {
+        ((Some(name), Some(version)), Some(cargo_home)) => {
+            find_cached_crate(&cargo_home, name, version, sources::sandbox())
+        }
+        _ => None,
+    };
+    match cached {
+        Some(CachedCrate::Src(crate_dir)) => {
+            info!("using {} from the cargo cache", crate_dir.display());
+            return copy_dir(&crate_dir, extract_path);
+        }
+        Some(CachedCrate::Package(package)) => {
+            info!("extracting {} from the cargo cache", package.display());
+            match extract_package(&package, extract_path).await {
+                Ok(()) => return Ok(()),
+                // (a corrupted package is downloaded again)
+                Err(e) => info!("{}", e),
+            }
+        }
+        None => (),
+    }
+    ensure!(
+        Source::CratesIo.is_enabled() && !sources::offline(),
+        "crates.io can't be used and {} is not in the cargo cache",
+        crate_with_version
+    );
+
+    // download the package from crates.io
+    let (name, version) = match (name_version_parts.0, name_version_parts.1) {
+        (Some(name), Some(version)) => (name, version),
+        _ => bail!("invalid crate version: {}", crate_with_version),
+    };
+    download_package(&cratesio::Client::new()?, name, version, extract_path).await
+}
+
+/// Downloads the package of a crate version from crates.io and extracts it.
+async fn download_package(
+    client: &cratesio::Client,
+    name: &str,
+    version: &str,
+    extract_path: &Path,
+) -> Result<()> {
+    let package = client.download(name, version).await?;
+    let package_dir = tempdir()?;
+    let package_path = package_dir
+        .path()
+        .join(format!("{}-{}.crate", name, version));
+    fs::write(&package_path, package)?;
+    extract_package(&package_path, extract_path).await
+}
+
+/// Extracts a package (`.crate` file, a gzipped tarball containing a `<name>-<version>` directory).
+pub(super) async fn extract_package(package: &Path, extract_path: &Path) -> Result<()> {
     fs::create_dir_all(extract_path)?;
-    let output = Command::new("cargo")
-        .current_dir(extract_dir)
-        .args(&["download", "-x", "-o"])
+    audit::command(
+        None,
+        "tar",
+        &[
+            "-xzf",
+            &package.to_string_lossy(),
+            "--strip-components",
+            "1",
+            "-C",
+            &extract_path.to_string_lossy(),
+        ],
+    );
+    let output = Command::new("tar")
+        .args(&["-xzf"])
+        .arg(package)
+        .args(&["--strip-components", "1", "-C"])
         .arg(extract_path)
-        .arg(crate_with_version)
         .output()
         .await?;
-
-    ensure!(
-        output.status.success(),
-        "Couldn't run cargo download: {}",
-        String::from_utf8_lossy(&output.stderr)
-    );
+    if !output.status.success() {
+        let _ = fs::remove_dir_all(extract_path);
+        bail!(
+            "couldn't extract {}: {}",
+            package.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
     Ok(())
 }
 
@@ -38,6 +242,17 @@ async fn diff_cargo_crates(
     path_to_original_crate: &Path,
     path_to_new_crate: &Path,
 ) -> Result<bool> {
+    audit::command(
+        None,
+        "git",
+        &[
+            "diff",
+            "--no-index",
+            "--name-only",
+            &path_to_original_crate.to_string_lossy(),
+            &path_to_new_crate.to_string_lossy(),
+        ],
+    );
     let diff_output = Command::new("git")
         .args(&["diff", "--no-index", "--name-only"])
         .arg(path_to_original_crate)
@@ -63,19 +278,54 @@ async fn diff_cargo_crates(
     Ok(pattern.is_match(&String::from_utf8(diff_output.stdout)?))
 }
 
-pub async fn init_cargo_download() -> Result<()> {
-    //! install cargo-download crate
-    info!("Installing cargo-download crate");
-    let output = Command::new("cargo")
-        .args(&["install", "cargo-download"])
+/// Lists the files (relative to the crate root) that differ between two extracted crates.
+async fn changed_files(
+    path_to_original_crate: &Path,
+    path_to_new_crate: &Path,
+) -> Result<Vec<String>> {
+    audit::command(
+        None,
+        "git",
+        &[
+            "diff",
+            "--no-index",
+            "--name-only",
+            &path_to_original_crate.to_string_lossy(),
+            &path_to_new_crate.to_string_lossy(),
+        ],
+    );
+    let diff_output = Command::new("git")
+        .args(&["diff", "--no-index", "--name-only"])
+        .arg(path_to_original_crate)
+        .arg(path_to_new_crate)
         .output()
         .await?;
-    ensure!(
-        output.status.success(),
-        "couldn't install cargo-download: {}",
-        String::from_utf8_lossy(&output.stderr)
-    );
-    Ok(())
+
+    // returns '1' if no difference found, '0' if difference found
+    if !matches!(diff_output.status.code(), Some(1) | Some(0)) {
+        bail!(
+            "Error running git diff command: {}",
+            String::from_utf8_lossy(&diff_output.stderr)
+        );
+    }
+
+    // (removed files are listed with the path of the original crate)
+    let original_crate = path_to_original_crate.to_string_lossy();
+    let new_crate = path_to_new_crate.to_string_lossy();
+    Ok(String::from_utf8(diff_output.stdout)?
+        .lines()
+        .map(|path| relative_path(&relative_path(path, &new_crate), &original_crate))
+        .collect())
+}
+
+/// Strips the `b/` prefix and the crate directory from a path of the diff.
+pub(crate) fn relative_path(path: &str, crate_dir: &str) -> String {
+    let path = path.trim_start_matches("a/").trim_start_matches("b/");
+    let crate_dir = crate_dir.trim_start_matches('/');
+    path.trim_start_matches('/')
+        .trim_start_matches(crate_dir)
+        .trim_start_matches('/')
+        .to_string()
 }
 
 pub async fn is_diff_in_buildrs(
@@ -99,16 +349,217 @@ pub async fn is_diff_in_buildrs(
     diff_cargo_crates(original_crate, latest_crate).await
 }
 
+pub async fn diff_crate_versions(
+    cargo_crate_original_version: &str,
+    cargo_crate_new_version: &str,
+    risky_paths: &RiskyPaths,
+    affected_functions: &[String],
+) -> Result<CrateDiff> {
+    //! Download two versions of a crate and returns the signals obtained by diffing them
+    //! (including whether the given functions, affected by advisories, changed)
+
+    let out_dir = tempdir()?;
+    let out_dir = out_dir.path();
+
+    download_cargo_crate(cargo_crate_original_version, &out_dir).await?;
+    download_cargo_crate(cargo_crate_new_version, &out_dir).await?;
+
+    let original_crate = out_dir.join(cargo_crate_original_version);
+    let latest_crate = out_dir.join(cargo_crate_new_version);
+
+    let versions = match (
+        split_crate_version(cargo_crate_original_version),
+        split_crate_version(cargo_crate_new_version),
+    ) {
+        (Some((name, original_version)), Some((_, new_version))) => {
+            Some((name, original_version, new_version))
+        }
+        _ => None,
+    };
+    diff_crate_dirs(
+        &original_crate,
+        &latest_crate,
+        versions,
+        risky_paths,
+        affected_functions,
+    )
+    .await
+}
+
+/// Returns the signals obtained by diffing two extracted crates.
+/// cargo-geiger only runs if the (name, original version, new version) of published crates are given,
+/// as its counts are cached per version.
+pub(super) async fn diff_crate_dirs(
+    original_crate: &Path,
+    latest_crate: &Path,
+    published: Option<(&str, &str, &str)>,
+    risky_paths: &RiskyPaths,
+    affected_functions: &[String],
+) -> Result<CrateDiff> {
+    // (the time spent in cargo-geiger is not counted in the diff)
+    let start = Instant::now();
+    let changed_files = changed_files(original_crate, latest_crate).await?;
+    let diff_time = start.elapsed();
+
+    // cargo-geiger only runs on the two versions of the crate (and only if it changed unsafe code)
+    let geiger_version = if published.is_some()
+        && Source::Geiger.is_enabled()
+        && unsafety::unsafe_changes(original_crate, latest_crate, &changed_files)
+            .iter()
+            .any(|change| change.unsafe_before != change.unsafe_after || change.unsafe_code_changed)
+    {
+        geiger::version().await
+    } else {
+        None
+    };
+    let unsafe_delta = match (geiger_version, published) {
+        (Some(scanner), Some((name, original_version, new_version))) => match geiger::unsafe_delta(
+            name,
+            (original_version, original_crate),
+            (new_version, latest_crate),
+            &scanner,
+        )
+        .await
+        {
+            Ok(delta) => Some(delta),
+            Err(e) => {
+                tracing::warn!("cargo geiger failed on {}: {}", name, e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    let start = Instant::now();
+    let crate_diff = CrateDiff {
+        build_rs: diff_cargo_crates(original_crate, latest_crate).await?,
+        toolchain_changes: toolchain::toolchain_changes(original_crate, latest_crate),
+        build_time_change: build_time::build_time_change(original_crate, latest_crate),
+        sizes: (package_size(original_crate)?, package_size(latest_crate)?),
+        secrets: secrets::scan(original_crate, latest_crate).await?,
+        risky_files: risky_paths.triage(&changed_files),
+        unsafe_changes: unsafety::unsafe_changes(original_crate, latest_crate, &changed_files),
+        unsafe_delta,
+        churn: churn::churn(original_crate, latest_crate).await?,
+        repository_change: provenance::repository_change(original_crate, latest_crate),
+        changed_functions: reachability::changed_functions(
+            original_crate,
+            latest_crate,
+            &changed_files,
+            affected_functions,
+        ),
+    };
+    timing::record(Section::Diff, diff_time + start.elapsed());
+    Ok(crate_diff)
+}
+
+/// Splits a `<name>==<version>` crate into its name and version.
+fn split_crate_version(crate_with_version: &str) -> Option<(&str, &str)> {
+    let mut parts = crate_with_version.splitn(2, "==");
+    Some((parts.next()?, parts.next()?))
+}
+
+/// Returns the total size (in bytes) and the number of files of an extracted crate.
+fn package_size(crate_dir: &Path) -> Result<(u64, usize)> {
+    let mut size = 0;
+    let mut files = 0;
+    for entry in fs::read_dir(crate_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            let (dir_size, dir_files) = package_size(&entry.path())?;
+            size += dir_size;
+            files += dir_files;
+        } else {
+            size += metadata.len();
+            files += 1;
+        }
+    }
+    Ok((size, files))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::{MockTransport, Response};
+    use crate::testing;
+    use std::sync::Arc;
 
     #[tokio::test]
-    async fn test_download_cargo_crate() {
+    async fn test_download_package() {
+        let package = testing::package(
+            "cargo-download",
+            "0.1.2",
+            &[
+                ("Cargo.toml", "[package]\nname = \"cargo-download\"\n"),
+                ("src/main.rs", "fn main() {}\n"),
+            ],
+        )
+        .await
+        .unwrap();
+        let transport = MockTransport::new().with_response(
+            "https://crates.io/api/v1/crates/cargo-download/0.1.2/download",
+            Response::ok(package),
+        );
+        let client = cratesio::Client::with_transport(Arc::new(transport));
+
         let out_dir = tempdir().unwrap();
-        let out_dir = out_dir.path();
-        download_cargo_crate("cargo-download==0.1.2", &out_dir).await;
-        assert!(out_dir.join("cargo-download==0.1.2").exists());
+        let extract_path = out_dir.path().join("cargo-download==0.1.2");
+        download_package(&client, "cargo-download", "0.1.2", &extract_path)
+            .await
+            .unwrap();
+        assert!(extract_path.join("src/main.rs").exists());
+        assert!(
+            download_package(&client, "cargo-download", "0.1.3", &extract_path)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cargo_cache() {
+        let cargo_home = tempdir().unwrap();
+        let registry = cargo_home.path().join("registry");
+        let src = registry.join("src/github.com-1ecc6299db9ec823/foo-1.0.0");
+        fs::create_dir_all(src.join("src")).unwrap();
+        fs::write(src.join("Cargo.toml"), "[package]\nname = \"foo\"\n").unwrap();
+        fs::write(src.join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+        fs::write(src.join(".cargo-ok"), "").unwrap();
+        let cache = registry.join("cache/github.com-1ecc6299db9ec823");
+        fs::create_dir_all(&cache).unwrap();
+        fs::write(cache.join("bar-0.1.0.crate"), "").unwrap();
+
+        assert_eq!(
+            find_cached_crate(cargo_home.path(), "foo", "1.0.0", false),
+            Some(CachedCrate::Src(src.clone()))
+        );
+        assert_eq!(
+            find_cached_crate(cargo_home.path(), "bar", "0.1.0", false),
+            Some(CachedCrate::Package(cache.join("bar-0.1.0.crate")))
+        );
+        assert_eq!(
+            find_cached_crate(cargo_home.path(), "foo", "2.0.0", false),
+            None
+        );
+        // (naming variants are found too)
+        assert_eq!(
+            find_cached_crate(cargo_home.path(), "Bar", "0.1.0", false),
+            Some(CachedCrate::Package(cache.join("bar-0.1.0.crate")))
+        );
+        // (in sandbox mode, the extracted sources are not trusted)
+        assert_eq!(
+            find_cached_crate(cargo_home.path(), "foo", "1.0.0", true),
+            None
+        );
+        assert_eq!(
+            find_cached_crate(cargo_home.path(), "bar", "0.1.0", true),
+            Some(CachedCrate::Package(cache.join("bar-0.1.0.crate")))
+        );
+
+        let out_dir = tempdir().unwrap();
+        copy_dir(&src, out_dir.path()).unwrap();
+        assert!(out_dir.path().join("src/lib.rs").exists());
+        assert!(!out_dir.path().join(".cargo-ok").exists());
     }
 
     #[tokio::test]
@@ -118,15 +569,25 @@ mod tests {
         // tiny-keccak-2.0.0 does not have build.rs
         // tiny-keccak-2.0.1 does have build.rs
         // tiny-keccak-2.0.2 has diff from 2.0.1
-        download_cargo_crate("tiny-keccak==2.0.0", &out_dir)
-            .await
-            .unwrap();
-        download_cargo_crate("tiny-keccak==2.0.1", &out_dir)
-            .await
-            .unwrap();
-        download_cargo_crate("tiny-keccak==2.0.2", &out_dir)
-            .await
-            .unwrap();
+        let lib = ("src/lib.rs", "pub fn keccak() {}\n");
+        let versions: [(&str, &[(&str, &str)]); 3] = [
+            ("2.0.0", &[lib]),
+            ("2.0.1", &[lib, ("build.rs", "fn main() {}\n")]),
+            (
+                "2.0.2",
+                &[
+                    lib,
+                    (
+                        "build.rs",
+                        "fn main() { println!(\"cargo:rustc-cfg=f\"); }\n",
+                    ),
+                ],
+            ),
+        ];
+        for (version, files) in &versions {
+            testing::write_crate(&out_dir.join(format!("tiny-keccak=={}", version)), files)
+                .unwrap();
+        }
 
         let t_k_0 = out_dir.join("tiny-keccak==2.0.0");
         let t_k_0 = t_k_0.as_path();
@@ -143,6 +604,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore] // (downloads crates from crates.io, run with `cargo test -- --ignored`)
     async fn test_is_diff_in_buildrs() {
         assert!(
             is_diff_in_buildrs("tiny-keccak==2.0.0", "tiny-keccak==2.0.1")
@@ -156,8 +618,12 @@ mod tests {
         );
     }
 
-    #[tokio::test]
-    async fn test_init_cargo_download() {
-        assert!(init_cargo_download().await.is_ok());
+    #[test]
+    fn test_package_size() {
+        let crate_dir = tempdir().unwrap();
+        fs::create_dir(crate_dir.path().join("src")).unwrap();
+        fs::write(crate_dir.path().join("Cargo.toml"), "0123456789").unwrap();
+        fs::write(crate_dir.path().join("src/lib.rs"), "01234").unwrap();
+        assert_eq!(package_size(crate_dir.path()).unwrap(), (15, 2));
     }
 }
@@ -0,0 +1,195 @@
+//! This module finds the repository of crates that don't declare one,
+//! so that the repository metrics (GitHub, provenance, security policies) cover more of the graph.
+//! The candidates are tried in order:
+//!
+//! 1. the `repository` reported by crates.io (the field of the manifest),
+//! 2. the homepage, if it points to a code host (or to GitHub Pages),
+//! 3. the documentation, likewise,
+//! 4. the badges (CI, coverage, ...) of the README of the package, which usually link to the repository.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::cratesio::CrateInfo;
+
+/// the hosts whose URLs are repositories (`https://<host>/<owner>/<name>`)
+const CODE_HOSTS: &[&str] = &["github.com", "gitlab.com", "bitbucket.org", "codeberg.org"];
+/// the READMEs read to find badges
+const READMES: &[&str] = &["README.md", "README", "readme.md", "README.markdown"];
+
+/// Where the repository of a crate was found.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiscoveryMethod {
+    Repository,
+    Homepage,
+    Documentation,
+    ReadmeBadge,
+}
+
+impl DiscoveryMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscoveryMethod::Repository => "repository field",
+            DiscoveryMethod::Homepage => "homepage",
+            DiscoveryMethod::Documentation => "documentation",
+            DiscoveryMethod::ReadmeBadge => "README badge",
+        }
+    }
+}
+
+/// The links of a crate, as reported by crates.io.
+#[derive(Debug, Default, Clone)]
+pub struct Links {
+    pub repository: Option<String>,
+    pub homepage: Option<String>,
+    pub documentation: Option<String>,
+}
+
+impl Links {
+    pub fn from_crate_info(crate_info: &CrateInfo) -> Self {
+        Self {
+            repository: crate_info.repository.clone(),
+            homepage: crate_info.homepage.clone(),
+            documentation: crate_info.documentation.clone(),
+        }
+    }
+
+    /// Finds the repository of the crate (see the module documentation).
+    /// The README is only read if the links don't point to a repository.
+    pub fn discover(
+        &self,
+        readme: impl FnOnce() -> Option<String>,
+    ) -> Option<(String, DiscoveryMethod)> {
+        if let Some(repository) = self
+            .repository
+            .as_deref()
+            .filter(|url| !url.trim().is_empty())
+        {
+            return Some((repository.to_string(), DiscoveryMethod::Repository));
+        }
+        let candidates = [
+            (&self.homepage, DiscoveryMethod::Homepage),
+            (&self.documentation, DiscoveryMethod::Documentation),
+        ];
+        for (url, method) in &candidates {
+            if let Some(repository) = url.as_deref().and_then(repository_url) {
+                return Some((repository, *method));
+            }
+        }
+        readme_badge(&readme()?).map(|repository| (repository, DiscoveryMethod::ReadmeBadge))
+    }
+}
+
+/// Reads the README of an extracted crate.
+pub fn readme(crate_dir: &Path) -> Option<String> {
+    READMES
+        .iter()
+        .find_map(|readme| fs::read_to_string(crate_dir.join(readme)).ok())
+}
+
+/// Returns the repository an URL points to (e.g. `https://github.com/a/b/tree/master/c`
+/// or `https://a.github.io/b/` give `https://github.com/a/b`).
+fn repository_url(url: &str) -> Option<String> {
+    let path = url
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.");
+    let mut parts = path.split('/').filter(|part| !part.is_empty());
+    let host = parts.next()?.to_lowercase();
+
+    // GitHub Pages (`<owner>.github.io/<name>`)
+    if let Some(owner) = host.strip_suffix(".github.io") {
+        let name = parts.next()?;
+        return Some(format!("https://github.com/{}/{}", owner, name));
+    }
+
+    if !CODE_HOSTS.contains(&host.as_str()) {
+        return None;
+    }
+    let owner = parts.next()?;
+    let name = parts.next()?.trim_end_matches(".git");
+    if name.is_empty() {
+        return None;
+    }
+    Some(format!("https://{}/{}/{}", host, owner, name))
+}
+
+/// Finds the repository linked by the badges of a README
+/// (the first repository URL of the lines with images or badges, other links could point anywhere).
+fn readme_badge(readme: &str) -> Option<String> {
+    let link = Regex::new(r#"https?://[^\s)"'>\]]+"#).expect("valid regex");
+    readme
+        .lines()
+        .filter(|line| line.contains("![") || line.to_lowercase().contains("badge"))
+        .flat_map(|line| link.find_iter(line))
+        .find_map(|url| repository_url(url.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover() {
+        let no_readme = || None;
+        let links = Links {
+            repository: Some("https://github.com/a/declared".to_string()),
+            homepage: Some("https://github.com/a/homepage".to_string()),
+            documentation: None,
+        };
+        assert_eq!(
+            links.discover(no_readme),
+            Some((
+                "https://github.com/a/declared".to_string(),
+                DiscoveryMethod::Repository
+            ))
+        );
+
+        // homepages that are not repositories are skipped
+        let links = Links {
+            repository: None,
+            homepage: Some("https://serde.rs".to_string()),
+            documentation: Some("https://a.github.io/lib/".to_string()),
+        };
+        assert_eq!(
+            links.discover(no_readme),
+            Some((
+                "https://github.com/a/lib".to_string(),
+                DiscoveryMethod::Documentation
+            ))
+        );
+
+        let links = Links {
+            repository: None,
+            homepage: None,
+            documentation: Some("https://docs.rs/lib".to_string()),
+        };
+        let readme = "# lib\n\n[![Build Status](https://img.shields.io/github/workflow/status/b/lib/CI)](https://github.com/b/lib/actions)\n";
+        assert_eq!(
+            links.discover(|| Some(readme.to_string())),
+            Some((
+                "https://github.com/b/lib".to_string(),
+                DiscoveryMethod::ReadmeBadge
+            ))
+        );
+        assert_eq!(links.discover(no_readme), None);
+    }
+
+    #[test]
+    fn test_repository_url() {
+        assert_eq!(
+            repository_url("https://gitlab.com/a/b.git").as_deref(),
+            Some("https://gitlab.com/a/b")
+        );
+        assert_eq!(
+            repository_url("https://www.github.com/a/b/tree/master/c").as_deref(),
+            Some("https://github.com/a/b")
+        );
+        assert_eq!(repository_url("https://github.com/a"), None);
+        assert_eq!(repository_url("https://docs.rs/a"), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! This module talks to [docs.rs](https://docs.rs), to know whether the documentation of a crate version
+//! was built, and how much of the crate is documented (a usability and maintenance signal).
+//! The build status comes from the `status.json` endpoint, the coverage from the page of the crate
+//! (docs.rs doesn't expose it in JSON).
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::http::{self, Transport};
+
+/// The documentation of a crate version on docs.rs.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct DocsStatus {
+    /// did docs.rs build the documentation? (none if docs.rs doesn't know the version yet)
+    pub build_succeeded: Option<bool>,
+    /// the percentage of the items of the crate that are documented
+    pub documented_percentage: Option<f64>,
+}
+
+impl DocsStatus {
+    /// Describes the status (e.g. `built, 87.5% documented`).
+    pub fn describe(&self) -> String {
+        let build = match self.build_succeeded {
+            Some(true) => "built",
+            Some(false) => "build failed",
+            None => "not built yet",
+        };
+        match self.documented_percentage {
+            Some(percentage) => format!("{}, {:.1}% documented", build, percentage),
+            None => build.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BuildStatus {
+    doc_status: bool,
+}
+
+/// Retrieves the docs.rs status of a crate version.
+pub async fn get_docs_status(name: &str, version: &str) -> Result<DocsStatus> {
+    let transport = http::default_transport("whackadep")?;
+    get_docs_status_with(&*transport, name, version).await
+}
+
+/// Same as [`get_docs_status`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn get_docs_status_with(
+    transport: &dyn Transport,
+    name: &str,
+    version: &str,
+) -> Result<DocsStatus> {
+    let url = format!("https://docs.rs/crate/{}/{}/status.json", name, version);
+    let response = transport.get(&url, &[]).await?;
+    let build_succeeded = if response.status == 404 {
+        None
+    } else {
+        let status: BuildStatus = serde_json::from_slice(&response.error_for_status()?.body)
+            .map_err(anyhow::Error::msg)?;
+        Some(status.doc_status)
+    };
+
+    // (only successful builds have a coverage)
+    let documented_percentage = if build_succeeded == Some(true) {
+        let url = format!("https://docs.rs/crate/{}/{}", name, version);
+        let page = transport.get(&url, &[]).await?.error_for_status()?.text()?;
+        documented_percentage(&page)
+    } else {
+        None
+    };
+
+    Ok(DocsStatus {
+        build_succeeded,
+        documented_percentage,
+    })
+}
+
+/// Reads the coverage in the page of a crate (e.g. `<b>87.5%</b> of the crate is documented`).
+fn documented_percentage(page: &str) -> Option<f64> {
+    let coverage =
+        Regex::new(r"([0-9]+(?:\.[0-9]+)?)%(?:\s*</[a-z]+>)?\s*of the crate is documented")
+            .expect("valid regex");
+    coverage
+        .captures(page)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{MockTransport, Response};
+
+    #[tokio::test]
+    async fn test_get_docs_status() {
+        let transport = MockTransport::new()
+            .with_response(
+                "https://docs.rs/crate/smallvec/1.6.1/status.json",
+                Response::ok(r#"{"doc_status":true,"version":"1.6.1"}"#),
+            )
+            .with_response(
+                "https://docs.rs/crate/smallvec/1.6.1",
+                Response::ok(
+                    "<div class=\"pure-menu\"><span><b>87.5%</b>\n of the crate is documented</span></div>",
+                ),
+            )
+            .with_response(
+                "https://docs.rs/crate/broken/0.1.0/status.json",
+                Response::ok(r#"{"doc_status":false,"version":"0.1.0"}"#),
+            )
+            .with_response(
+                "https://docs.rs/crate/new/0.1.0/status.json",
+                Response::empty(404),
+            );
+
+        let status = get_docs_status_with(&transport, "smallvec", "1.6.1")
+            .await
+            .unwrap();
+        assert_eq!(status.build_succeeded, Some(true));
+        assert_eq!(status.describe(), "built, 87.5% documented");
+
+        let status = get_docs_status_with(&transport, "broken", "0.1.0")
+            .await
+            .unwrap();
+        assert_eq!(status.describe(), "build failed");
+
+        let status = get_docs_status_with(&transport, "new", "0.1.0")
+            .await
+            .unwrap();
+        assert_eq!(status, DocsStatus::default());
+    }
+}
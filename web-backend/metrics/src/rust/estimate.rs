@@ -0,0 +1,128 @@
+//! This module estimates the cost of an analysis before running it (dry-run),
+//! so that one can decide to reduce its scope (or give it a deadline) beforehand.
+//!
+//! Only the dependency graph is resolved (which is done locally),
+//! the number of network calls and downloads is then derived from its size,
+//! from the previous analysis (if any), and from what is already cached on the machine.
+
+use anyhow::Result;
+use guppy_summaries::SummarySource;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+use super::{cargo_home, RustAnalysis, CONCURRENCY};
+use crate::common::github_auth;
+use crate::model::RepoConfig;
+
+/// approximate duration of a call to the crates.io API
+const CRATESIO_CALL: Duration = Duration::from_millis(500);
+/// approximate duration of a dependabot run (which queries GitHub)
+const DEPENDABOT_RUN: Duration = Duration::from_secs(5);
+/// approximate duration of a crate download (from crates.io)
+const CRATE_DOWNLOAD: Duration = Duration::from_secs(2);
+/// approximate duration of fetching the RUSTSEC advisory database for the first time
+const ADVISORY_DB_CLONE: Duration = Duration::from_secs(30);
+/// approximate duration of fetching the crates.io index for the first time
+const CRATESIO_INDEX_CLONE: Duration = Duration::from_secs(120);
+
+/// The estimated cost of an analysis.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CostEstimate {
+    /// number of dependencies found in the graph
+    pub dependencies: usize,
+    /// number of dependencies found in the graph, that are hosted on crates.io
+    pub cratesio_dependencies: usize,
+    /// estimated number of dependencies that have an update
+    /// (taken from the previous analysis if there is one, upper bound otherwise)
+    pub estimated_updates: usize,
+    /// estimated number of calls to the crates.io API
+    pub cratesio_calls: usize,
+    /// estimated number of dependabot runs (each one queries GitHub)
+    pub dependabot_runs: usize,
+    /// estimated number of crates to download
+    pub crate_downloads: usize,
+    /// is the RUSTSEC advisory database already cloned on this machine?
+    pub advisory_db_cached: bool,
+    /// is the crates.io index already cloned on this machine?
+    pub cratesio_index_cached: bool,
+    /// a (very rough) estimation of how long the analysis will take
+    pub estimated_duration: Duration,
+}
+
+impl RustAnalysis {
+    /// Estimates the cost of [`RustAnalysis::get_dependencies`] without running it.
+    pub async fn estimate(
+        repo_dir: &Path,
+        previous_analysis: Option<&Self>,
+        is_diem: bool,
+        config: &RepoConfig,
+    ) -> Result<CostEstimate> {
+        // the graph is obtained locally (cargo metadata, or reading the manifests)
+        let (mut rust_analysis, package_graph) =
+            Self::fetch(repo_dir, is_diem, &config.resolve_options).await?;
+        rust_analysis.mark_forks(&config.forked_crates);
+        rust_analysis.restrict(package_graph.as_ref(), &config.scope)?;
+
+        let cratesio_entries = rust_analysis
+            .dependencies
+            .iter()
+            .filter(|dep| matches!(dep.repo, SummarySource::CratesIo))
+            .count();
+        let mut cratesio_dependencies: Vec<&str> = rust_analysis
+            .dependencies
+            .iter()
+            .filter(|dep| matches!(dep.repo, SummarySource::CratesIo))
+            .map(|dep| dep.name.as_str())
+            .collect();
+        cratesio_dependencies.dedup();
+
+        // estimate the number of updates
+        let estimated_updates = match previous_analysis {
+            Some(previous) => previous
+                .dependencies
+                .iter()
+                .filter(|dep| dep.update.is_some())
+                .count(),
+            None => cratesio_entries,
+        };
+
+        // trusted publishers require fetching the owners of every update
+        let trust_calls =
+            if config.trusted_crates.is_empty() && config.trusted_publishers.is_empty() {
+                0
+            } else {
+                estimated_updates
+            };
+
+        let github_token = github_auth::current().is_configured();
+        let mut estimate = CostEstimate {
+            dependencies: rust_analysis.dependencies.len(),
+            cratesio_dependencies: cratesio_dependencies.len(),
+            estimated_updates,
+            cratesio_calls: cratesio_dependencies.len() + trust_calls,
+            dependabot_runs: if github_token { estimated_updates } else { 0 },
+            crate_downloads: 2 * estimated_updates,
+            advisory_db_cached: rustsec::GitRepository::default_path().exists(),
+            cratesio_index_cached: cargo_home()
+                .map(|home| home.join("registry").join("index").exists())
+                .unwrap_or(false),
+            ..Default::default()
+        };
+
+        // rough estimation of the duration (requests are done concurrently)
+        let concurrency = CONCURRENCY as u32;
+        let mut duration = CRATESIO_CALL * estimate.cratesio_calls as u32 / concurrency
+            + DEPENDABOT_RUN * estimate.dependabot_runs as u32 / concurrency
+            + CRATE_DOWNLOAD * estimate.crate_downloads as u32 / concurrency;
+        if !estimate.advisory_db_cached {
+            duration += ADVISORY_DB_CLONE;
+        }
+        if !estimate.cratesio_index_cached {
+            duration += CRATESIO_INDEX_CLONE;
+        }
+        estimate.estimated_duration = duration;
+
+        Ok(estimate)
+    }
+}
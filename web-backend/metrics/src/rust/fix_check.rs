@@ -0,0 +1,127 @@
+//! This module checks that the advisories an update claims to fix are fixed once the lockfile is resolved.
+//! Bumping the requirement of a direct dependency (what dependabot does) doesn't always move every copy of the crate:
+//! another dependency can still pin a vulnerable version, in which case the `Cargo.lock` of the update
+//! keeps it next to the patched one. The versions actually resolved in the lockfile are what counts.
+
+use rustsec::lockfile::Lockfile;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// The resolution of an advisory that an update claims to fix.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FixCheck {
+    /// the RUSTSEC identifier
+    pub advisory: String,
+    pub package: String,
+    /// the versions of the package in the lockfile (none if the package is no longer used)
+    pub resolved: Vec<Version>,
+    /// the resolved versions still affected by the advisory
+    pub vulnerable: Vec<Version>,
+}
+
+impl FixCheck {
+    /// Checks an advisory on a package against a lockfile,
+    /// `is_vulnerable` telling if a version of the package is affected by the advisory.
+    pub fn new(
+        advisory: &str,
+        package: &str,
+        is_vulnerable: impl Fn(&Version) -> bool,
+        lockfile: &Lockfile,
+    ) -> Self {
+        let resolved: Vec<Version> = lockfile
+            .packages
+            .iter()
+            .filter(|p| p.name.as_str() == package)
+            .map(|p| p.version.clone())
+            .collect();
+        let vulnerable = resolved
+            .iter()
+            .filter(|version| is_vulnerable(version))
+            .cloned()
+            .collect();
+        Self {
+            advisory: advisory.to_string(),
+            package: package.to_string(),
+            resolved,
+            vulnerable,
+        }
+    }
+
+    pub fn fixed(&self) -> bool {
+        self.vulnerable.is_empty()
+    }
+
+    /// Describes the check (e.g. `RUSTSEC-2021-0003 is not fixed: Cargo.lock still resolves smallvec to 1.6.0`).
+    pub fn describe(&self) -> String {
+        let versions = |versions: &[Version]| {
+            let versions: Vec<String> = versions.iter().map(ToString::to_string).collect();
+            versions.join(", ")
+        };
+        if !self.fixed() {
+            format!(
+                "{} is not fixed: Cargo.lock still resolves {} to {}",
+                self.advisory,
+                self.package,
+                versions(&self.vulnerable)
+            )
+        } else if self.resolved.is_empty() {
+            format!(
+                "{} is fixed in Cargo.lock ({} is no longer used)",
+                self.advisory, self.package
+            )
+        } else {
+            format!(
+                "{} is fixed in Cargo.lock ({} {})",
+                self.advisory,
+                self.package,
+                versions(&self.resolved)
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fix_check() {
+        let lockfile: Lockfile = r#"[[package]]
+name = "service"
+version = "0.1.0"
+dependencies = [
+ "smallvec 1.6.1",
+ "smallvec 1.6.0",
+]
+
+[[package]]
+name = "smallvec"
+version = "1.6.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "smallvec"
+version = "1.6.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#
+        .parse()
+        .unwrap();
+        let patched = Version::parse("1.6.1").unwrap();
+        let is_vulnerable = |version: &Version| version < &patched;
+
+        // the direct dependency was bumped, but a transitive pin remains
+        let check = FixCheck::new("RUSTSEC-2021-0003", "smallvec", is_vulnerable, &lockfile);
+        assert!(!check.fixed());
+        assert_eq!(
+            check.describe(),
+            "RUSTSEC-2021-0003 is not fixed: Cargo.lock still resolves smallvec to 1.6.0"
+        );
+
+        let check = FixCheck::new("RUSTSEC-2021-0003", "smallvec", |_| false, &lockfile);
+        assert!(check.fixed());
+        assert_eq!(
+            check.describe(),
+            "RUSTSEC-2021-0003 is fixed in Cargo.lock (smallvec 1.6.0, 1.6.1)"
+        );
+    }
+}
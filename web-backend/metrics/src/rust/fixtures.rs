@@ -0,0 +1,188 @@
+//! Deterministic fixture data (no network, no dates relative to now) to test renderers.
+//! The renderers of this crate are snapshot-tested against these fixtures
+//! (run `cargo insta review` after changing a renderer to review the output as a text diff),
+//! and downstream users can use them to test their own renderers.
+
+use semver::Version;
+
+use super::{
+    compare::CrateComparison,
+    discovery::DiscoveryMethod,
+    docsrs::DocsStatus,
+    report::{AdvisorySummary, CrateReport, Release, SourceInfo},
+    verdict::{GroupVerdict, UpdateDetails, UpdateVerdict, Verdict},
+};
+use crate::common::activity::ActivityMetrics;
+use crate::common::github::{RepositoryStats, SecurityPolicy};
+
+/// A review of three updates: one passing, one needing a look, one failing.
+pub fn group_verdict() -> GroupVerdict {
+    let update =
+        |name: &str, from: &str, to: &str, verdict: Verdict, reasons: &[&str]| UpdateVerdict {
+            name: name.to_string(),
+            from: Version::parse(from).expect("valid fixture version"),
+            to: Version::parse(to).expect("valid fixture version"),
+            verdict,
+            reasons: reasons.iter().map(ToString::to_string).collect(),
+            owners: Vec::new(),
+            affects: Vec::new(),
+            details: UpdateDetails::default(),
+            build_time_change: None,
+            tags: Vec::new(),
+            categories: Vec::new(),
+            violations: Vec::new(),
+        };
+    GroupVerdict::from_updates(vec![
+        update("itoa", "0.4.7", "0.4.8", Verdict::Pass, &[]),
+        update(
+            "libc",
+            "0.2.86",
+            "0.2.87",
+            Verdict::Warn,
+            &["build.rs changed", "unsafe code changed in src/unix/mod.rs"],
+        ),
+        update(
+            "smallvec",
+            "1.6.0",
+            "1.6.1",
+            Verdict::Fail,
+            &["RUSTSEC-2021-0003 still affects the new version"],
+        ),
+    ])
+}
+
+/// A report about a crate version, with every section filled.
+pub fn crate_report() -> CrateReport {
+    CrateReport {
+        version: "1.6.1".to_string(),
+        metrics: CrateComparison {
+            name: "smallvec".to_string(),
+            description: Some("'Small vector' optimization".to_string()),
+            latest_version: Some("1.6.1".to_string()),
+            versions: 42,
+            created_at: Some("2015-09-15T02:11:08.543405+00:00".to_string()),
+            updated_at: Some("2021-01-08T18:19:33.004838+00:00".to_string()),
+            downloads: 38_000_000,
+            recent_downloads: Some(5_000_000),
+            owners: vec![
+                "mbrubeck".to_string(),
+                "github:servo:cargo-publish".to_string(),
+            ],
+            repository: Some("https://github.com/servo/rust-smallvec".to_string()),
+            repository_discovery: Some(DiscoveryMethod::Repository),
+            github: Some(RepositoryStats {
+                full_name: "servo/rust-smallvec".to_string(),
+                stargazers_count: 780,
+                forks_count: 110,
+                open_issues_count: 35,
+                archived: false,
+                pushed_at: Some("2021-01-08T18:19:33Z".to_string()),
+                default_branch: Some("master".to_string()),
+                open_issues_by_label: vec![("bug".to_string(), 4)].into_iter().collect(),
+                security_policy: Some(SecurityPolicy {
+                    security_md: true,
+                    disclosure_contact: Some(
+                        "https://github.com/servo/rust-smallvec/security/advisories/new"
+                            .to_string(),
+                    ),
+                    bug_bounty: false,
+                    oss_fuzz: true,
+                }),
+                fuzz_targets: Some(vec!["smallvec_ops".to_string()]),
+                activity: Some(ActivityMetrics {
+                    commits: 12,
+                    corporate: 6,
+                    personal: 3,
+                    noreply: 3,
+                    corporate_domains: vec![("mozilla.com".to_string(), 6)].into_iter().collect(),
+                    web_edits: 1,
+                }),
+            }),
+            advisories: vec![
+                "RUSTSEC-2019-0009".to_string(),
+                "RUSTSEC-2021-0003".to_string(),
+            ],
+            categories: vec!["data-structures".to_string()],
+            top_in_categories: vec!["data-structures".to_string()],
+            keywords: vec![
+                "small".to_string(),
+                "vec".to_string(),
+                "vector".to_string(),
+                "stack".to_string(),
+                "no_std".to_string(),
+            ],
+            docs: Some(DocsStatus {
+                build_succeeded: Some(true),
+                documented_percentage: Some(87.5),
+            }),
+            repository_redirect: None,
+            code: None,
+        },
+        advisories: vec![
+            AdvisorySummary {
+                id: "RUSTSEC-2019-0009".to_string(),
+                title: "Double-free and use-after-free in SmallVec::grow()".to_string(),
+                date: "2019-06-06".to_string(),
+                patched: vec![">= 0.6.10".to_string()],
+                unaffected: Vec::new(),
+                affects_version: false,
+            },
+            AdvisorySummary {
+                id: "RUSTSEC-2021-0003".to_string(),
+                title: "Buffer overflow in SmallVec::insert_many".to_string(),
+                date: "2021-01-08".to_string(),
+                patched: vec![">= 1.6.1".to_string()],
+                unaffected: Vec::new(),
+                affects_version: false,
+            },
+        ],
+        recent_releases: vec![
+            Release {
+                version: "1.6.1".to_string(),
+                created_at: "2021-01-08T18:19:33.004838+00:00".to_string(),
+            },
+            Release {
+                version: "1.6.0".to_string(),
+                created_at: "2020-12-18T19:58:22.231512+00:00".to_string(),
+            },
+        ],
+        source: Some(SourceInfo {
+            vcs_commit: Some("0a3ff2c3e0d1b9c4e4c9d3a1d4b5e6f7a8b9c0d1".to_string()),
+            build_rs: false,
+            rust_files: 4,
+            rust_lines: 3100,
+            unsafe_keywords: 60,
+            unsafe_scanner: "internal".to_string(),
+            fuzz_targets: Vec::new(),
+            vcs_path: None,
+            unsafe_hotspots: Vec::new(),
+        }),
+        disabled_sources: Vec::new(),
+        publisher_trust: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::Locale;
+    use crate::rust::theme::MarkdownTheme;
+    use crate::verbosity::Verbosity;
+
+    #[test]
+    fn test_snapshots() {
+        insta::assert_snapshot!(
+            "group_verdict_markdown",
+            group_verdict().to_markdown(&MarkdownTheme::default(), Verbosity::Standard)
+        );
+        let locale = Locale::default();
+        insta::assert_snapshot!(
+            "crate_report_markdown",
+            crate_report().to_markdown(&locale, Verbosity::Standard)
+        );
+        insta::assert_snapshot!(
+            "crate_report_html",
+            crate_report().to_html(&locale, Verbosity::Standard)
+        );
+    }
+}
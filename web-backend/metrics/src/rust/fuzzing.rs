@@ -0,0 +1,80 @@
+//! This module detects the fuzz targets of a crate, a robustness signal:
+//! [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz) keeps them in a `fuzz` crate
+//! (a manifest with `cargo-fuzz = true` in its metadata, and one binary per target),
+//! in the published package or in the repository of the crate.
+//! Enrollment in OSS-Fuzz is detected with the security policy (see [`crate::common::github`]).
+
+use std::fs;
+use std::path::Path;
+
+/// Where cargo-fuzz puts its crate.
+pub const FUZZ_MANIFEST: &str = "fuzz/Cargo.toml";
+
+/// Returns the fuzz targets declared by the manifest of a cargo-fuzz crate
+/// (empty if the manifest is not the one of a cargo-fuzz crate).
+pub fn fuzz_targets(manifest: &str) -> Vec<String> {
+    let manifest: toml::Value = match toml::from_str(manifest) {
+        Ok(manifest) => manifest,
+        Err(_) => return Vec::new(),
+    };
+    let is_cargo_fuzz = manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("cargo-fuzz"))
+        .and_then(toml::Value::as_bool)
+        .unwrap_or(false);
+    if !is_cargo_fuzz {
+        return Vec::new();
+    }
+    manifest
+        .get("bin")
+        .and_then(toml::Value::as_array)
+        .map(|bins| {
+            bins.iter()
+                .filter_map(|bin| bin.get("name").and_then(toml::Value::as_str))
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the fuzz targets of an extracted crate (or of a checkout of its repository).
+pub fn fuzz_targets_in_dir(crate_dir: &Path) -> Vec<String> {
+    fs::read_to_string(crate_dir.join(FUZZ_MANIFEST))
+        .map(|manifest| fuzz_targets(&manifest))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_targets() {
+        let manifest = r#"
+[package]
+name = "smallvec-fuzz"
+version = "0.0.0"
+publish = false
+
+[package.metadata]
+cargo-fuzz = true
+
+[dependencies]
+libfuzzer-sys = "0.3"
+
+[[bin]]
+name = "smallvec_ops"
+path = "fuzz_targets/smallvec_ops.rs"
+
+[[bin]]
+name = "insert_many"
+path = "fuzz_targets/insert_many.rs"
+"#;
+        assert_eq!(fuzz_targets(manifest), vec!["smallvec_ops", "insert_many"]);
+
+        // a crate that happens to live in a fuzz directory
+        assert!(fuzz_targets("[package]\nname = \"fuzz\"\n\n[[bin]]\nname = \"a\"").is_empty());
+        assert!(fuzz_targets("not toml").is_empty());
+    }
+}
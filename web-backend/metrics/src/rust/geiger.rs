@@ -0,0 +1,302 @@
+//! This module counts the unsafe code of a crate with [cargo-geiger](https://github.com/rust-secure-code/cargo-geiger).
+//! cargo-geiger is an optional external tool: it might not be installed, it can panic,
+//! or it can emit output we don't understand. Callers are expected to fall back to
+//! the internal scanner (see [`super::unsafety`]) when this module returns an error.
+//!
+//! Running cargo-geiger builds a crate, so reviews of updates only run it on the two versions
+//! of each updated crate (see [`unsafe_delta`]), never on the whole workspace,
+//! and the counts are cached by crate version (a published version never changes).
+//!
+//! cargo-geiger can't run more than once at a time (concurrent runs share the cargo build directory
+//! and corrupt each other's reports), so runs are queued: by a mutex within the process,
+//! and by a lock file in the cache directory between the processes of a machine (see [`lock`]).
+
+use anyhow::{anyhow, bail, Context, Result};
+use fs2::FileExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::time::{sleep, timeout};
+use tracing::warn;
+
+use crate::timing::{self, Section};
+use crate::{audit, cache};
+
+/// cargo-geiger builds the crate, which can take a while
+const GEIGER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// how often a process waiting for the lock file checks it again
+const LOCK_POLL: Duration = Duration::from_millis(500);
+
+/// Returns the mutex queuing the runs of the process.
+fn process_lock() -> &'static Mutex<()> {
+    static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+    &LOCK
+}
+
+/// The file queuing the runs of the processes of the machine.
+fn lock_path() -> PathBuf {
+    cache::cache_dir().join("geiger.lock")
+}
+
+/// An exclusive (advisory) lock on a file, released when dropped.
+/// The file itself is left in place: deleting it would let another process lock a new file
+/// while a third one still holds the old one.
+struct FileLock {
+    file: File,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            warn!("couldn't release the geiger lock: {}", e);
+        }
+    }
+}
+
+/// Waits until the lock file can be locked (it then contains the id of the process holding it).
+/// The lock of a process that died is released by the OS, so it can't go stale.
+async fn lock_file(path: &Path) -> Result<FileLock> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("couldn't open the lock {}", path.display()))?;
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(()) => break,
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => sleep(LOCK_POLL).await,
+            Err(e) => return Err(e).with_context(|| format!("couldn't lock {}", path.display())),
+        }
+    }
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    Ok(FileLock { file })
+}
+
+/// The right to run cargo-geiger, the next run starts when it is dropped.
+pub struct Lock {
+    _file: FileLock,
+    _guard: MutexGuard<'static, ()>,
+}
+
+/// Waits for the runs of cargo-geiger of this process, then of the other processes of the machine, to end.
+pub async fn lock() -> Result<Lock> {
+    let guard = process_lock().lock().await;
+    let file = lock_file(&lock_path()).await?;
+    Ok(Lock {
+        _file: file,
+        _guard: guard,
+    })
+}
+
+/// Returns the version of cargo-geiger (e.g. `cargo-geiger 0.11.0`), if it is installed.
+pub async fn version() -> Option<String> {
+    audit::command(None, "cargo", &["geiger", "--version"]);
+    let output = Command::new("cargo")
+        .args(&["geiger", "--version"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if version.is_empty() {
+        None
+    } else {
+        Some(version)
+    }
+}
+
+/// Runs cargo-geiger on an extracted crate and returns the number of unsafe usages
+/// (functions, expressions, impls, traits and methods) of the crate itself.
+pub async fn count_unsafe(crate_dir: &Path, name: &str) -> Result<u64> {
+    let _lock = lock().await?;
+    audit::command(
+        None,
+        "cargo",
+        &[
+            "geiger",
+            "--output-format",
+            "Json",
+            "--manifest-path",
+            &crate_dir.join("Cargo.toml").to_string_lossy(),
+        ],
+    );
+    let output = timing::time(
+        Section::Geiger,
+        timeout(
+            GEIGER_TIMEOUT,
+            Command::new("cargo")
+                .args(&["geiger", "--output-format", "Json", "--manifest-path"])
+                .arg(crate_dir.join("Cargo.toml"))
+                .kill_on_drop(true)
+                .output(),
+        ),
+    )
+    .await
+    .map_err(|_| anyhow!("cargo geiger timed out"))??;
+    // (cargo-geiger can exit with an error when the crate forbids unsafe code,
+    // so the output is checked rather than the status)
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        bail!(
+            "cargo geiger produced no output: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    parse_report(&stdout, name)
+}
+
+/// The unsafe usages counted by cargo-geiger in the current and the new versions of a crate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnsafeDelta {
+    /// the version of cargo-geiger (e.g. `cargo-geiger 0.11.0`)
+    pub scanner: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+/// A count cached for a crate version.
+#[derive(Serialize, Deserialize)]
+struct CachedCount {
+    scanner: String,
+    unsafe_usages: u64,
+}
+
+/// The file caching the count of a crate version.
+fn cache_path(name: &str, version: &str) -> PathBuf {
+    cache::cache_dir()
+        .join("geiger")
+        .join(format!("{}=={}", name, version))
+}
+
+/// Counts the unsafe usages of an extracted crate version,
+/// reusing the count of a previous run with the same version of cargo-geiger.
+pub async fn count_unsafe_cached(
+    crate_dir: &Path,
+    name: &str,
+    version: &str,
+    scanner: &str,
+) -> Result<u64> {
+    let path = cache_path(name, version);
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|cached| serde_json::from_str::<CachedCount>(&cached).ok());
+    if let Some(cached) = cached.filter(|cached| cached.scanner == scanner) {
+        return Ok(cached.unsafe_usages);
+    }
+
+    let unsafe_usages = count_unsafe(crate_dir, name).await?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let cached = CachedCount {
+        scanner: scanner.to_string(),
+        unsafe_usages,
+    };
+    fs::write(&path, serde_json::to_string(&cached)?)?;
+    Ok(unsafe_usages)
+}
+
+/// Counts the unsafe usages of the two versions of an updated crate (extracted in `original_crate` and `new_crate`).
+pub async fn unsafe_delta(
+    name: &str,
+    (original_version, original_crate): (&str, &Path),
+    (new_version, new_crate): (&str, &Path),
+    scanner: &str,
+) -> Result<UnsafeDelta> {
+    Ok(UnsafeDelta {
+        scanner: scanner.to_string(),
+        before: count_unsafe_cached(original_crate, name, original_version, scanner).await?,
+        after: count_unsafe_cached(new_crate, name, new_version, scanner).await?,
+    })
+}
+
+/// Extracts the number of unsafe usages of a package from the JSON report of cargo-geiger.
+fn parse_report(report: &str, name: &str) -> Result<u64> {
+    let report: serde_json::Value =
+        serde_json::from_str(report).context("invalid JSON output from cargo geiger")?;
+    let package = report["packages"]
+        .as_array()
+        .ok_or_else(|| anyhow!("no packages in the cargo geiger report"))?
+        .iter()
+        .find(|package| package["package"]["id"]["name"].as_str() == Some(name))
+        .ok_or_else(|| anyhow!("{} not found in the cargo geiger report", name))?;
+    let unsafety = &package["unsafety"];
+    if !unsafety.is_object() {
+        bail!("no unsafety data for {} in the cargo geiger report", name);
+    }
+    // counts are split between code used by the build and unused code
+    let count = ["used", "unused"]
+        .iter()
+        .flat_map(|usage| {
+            ["functions", "exprs", "item_impls", "item_traits", "methods"]
+                .iter()
+                .map(move |kind| unsafety[usage][kind]["unsafe_"].as_u64().unwrap_or(0))
+        })
+        .sum();
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_report() {
+        let report = r#"{
+            "packages": [
+                {
+                    "package": {"id": {"name": "dep", "version": "1.0.0"}},
+                    "unsafety": {"used": {"exprs": {"safe": 1, "unsafe_": 100}}}
+                },
+                {
+                    "package": {"id": {"name": "mycrate", "version": "0.1.0"}},
+                    "unsafety": {
+                        "used": {
+                            "functions": {"safe": 10, "unsafe_": 1},
+                            "exprs": {"safe": 50, "unsafe_": 3}
+                        },
+                        "unused": {"methods": {"safe": 2, "unsafe_": 2}},
+                        "forbids_unsafe": false
+                    }
+                }
+            ]
+        }"#;
+        assert_eq!(parse_report(report, "mycrate").unwrap(), 6);
+        assert!(parse_report(report, "other").is_err());
+        assert!(parse_report("thread 'main' panicked", "mycrate").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lock_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("geiger.lock");
+
+        // a lock file left behind (e.g. by a killed process) doesn't hold anything
+        fs::write(&path, "1").unwrap();
+        let lock = lock_file(&path).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        // another process waits while the lock is held
+        assert!(timeout(LOCK_POLL * 2, lock_file(&path)).await.is_err());
+        drop(lock);
+        let _lock = timeout(LOCK_POLL * 2, lock_file(&path))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+//! This module exports the dependency graph of an analysis, annotated with its results
+//! (verdict of the available update, RUSTSEC advisories, unsafe code changed by the update),
+//! so that other tools can consume the enriched graph without running the analysis again.
+//! Nodes are identified like guppy and cargo identify packages (`name version`),
+//! and carry the guppy summary source of the package.
+//!
+//! Besides JSON, the graph can be exported as Cypher statements (for Neo4j and other graph databases)
+//! or as GraphML. In Cypher, the packages are shared by the graphs of all the repositories,
+//! so that an organization can ingest the graphs of all its repositories in a single database:
+//! what is specific to a repository (verdict of the update, direct or dev dependency) is recorded
+//! on the `USES` relationship between the repository and the package, and the workspace members
+//! are scoped to their repository.
+
+use guppy_summaries::SummarySource;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::{verdict::UpdateVerdict, verdict::Verdict, RustAnalysis};
+use crate::dashboard::escape;
+
+/// A dependency of a package on another one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Link {
+    /// the id of the dependent package (see [`node_id`])
+    pub from: String,
+    /// the id of the dependency
+    pub to: String,
+    /// is it only a dev-dependency?
+    #[serde(default)]
+    pub dev_only: bool,
+    /// is the dependent package a member of the workspace?
+    #[serde(default)]
+    pub from_workspace: bool,
+}
+
+/// Returns the id of the node of a package (e.g. `serde 1.0.123`).
+pub fn node_id(name: &str, version: &impl fmt::Display) -> String {
+    format!("{} {}", name, version)
+}
+
+/// A package of the graph, with the results of the analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Node {
+    pub id: String,
+    pub name: String,
+    pub version: Version,
+    /// where the package comes from (none for the workspace members, which are not analyzed)
+    pub source: Option<SummarySource>,
+    /// is it a member of the workspace?
+    pub workspace: bool,
+    pub direct: bool,
+    pub dev: bool,
+    /// the latest version available, if the package can be updated
+    pub update: Option<Version>,
+    /// the verdict of the review of the update (see [`super::verdict`])
+    pub verdict: Option<Verdict>,
+    /// the RUSTSEC advisories affecting the version in use (vulnerabilities and warnings)
+    pub advisories: Vec<String>,
+    /// the number of `unsafe` in the files changed by the update, before and after it
+    pub unsafe_changed: Option<(usize, usize)>,
+}
+
+/// The annotated dependency graph of an analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DependencyGraph {
+    pub nodes: Vec<Node>,
+    pub links: Vec<Link>,
+}
+
+impl DependencyGraph {
+    /// Builds the graph of an analysis.
+    /// The links are only recorded by analyses resolved with guppy, otherwise the graph has no links.
+    pub fn new(analysis: &RustAnalysis) -> Self {
+        // the RUSTSEC advisories of every package
+        let mut advisories: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for vuln in &analysis.rustsec.vulnerabilities {
+            advisories
+                .entry(node_id(vuln.package.name.as_str(), &vuln.package.version))
+                .or_default()
+                .push(vuln.advisory.id.to_string());
+        }
+        for warning in analysis.rustsec.warnings.values().flatten() {
+            if let Some(advisory) = &warning.advisory {
+                advisories
+                    .entry(node_id(
+                        warning.package.name.as_str(),
+                        &warning.package.version,
+                    ))
+                    .or_default()
+                    .push(advisory.id.to_string());
+            }
+        }
+
+        let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
+        for dependency in &analysis.dependencies {
+            let id = node_id(&dependency.name, &dependency.version);
+            let update = dependency.update.as_ref();
+            let unsafe_changed = update
+                .filter(|update| !update.unsafe_changes.is_empty())
+                .map(|update| {
+                    update
+                        .unsafe_changes
+                        .iter()
+                        .fold((0, 0), |(before, after), change| {
+                            (before + change.unsafe_before, after + change.unsafe_after)
+                        })
+                });
+            // (the same package can be listed twice, as a dev and a normal dependency)
+            let node = nodes.entry(id.clone()).or_insert_with(|| Node {
+                id: id.clone(),
+                name: dependency.name.clone(),
+                version: dependency.version.clone(),
+                source: Some(dependency.repo.clone()),
+                workspace: false,
+                direct: false,
+                dev: true,
+                update: update.and_then(|update| update.versions.last().cloned()),
+                verdict: UpdateVerdict::new(analysis, dependency).map(|review| review.verdict),
+                advisories: advisories.get(&id).cloned().unwrap_or_default(),
+                unsafe_changed,
+            });
+            node.direct |= dependency.direct;
+            node.dev &= dependency.dev;
+        }
+
+        // the workspace members
+        for link in analysis.links.iter().filter(|link| link.from_workspace) {
+            let id = &link.from;
+            if !nodes.contains_key(id) {
+                let mut parts = id.splitn(2, ' ');
+                let name = parts.next().unwrap_or_default();
+                let version = match parts.next().map(Version::parse) {
+                    Some(Ok(version)) => version,
+                    _ => continue,
+                };
+                nodes.insert(
+                    id.clone(),
+                    Node {
+                        id: id.clone(),
+                        name: name.to_string(),
+                        version,
+                        source: None,
+                        workspace: true,
+                        direct: false,
+                        dev: false,
+                        update: None,
+                        verdict: None,
+                        advisories: Vec::new(),
+                        unsafe_changed: None,
+                    },
+                );
+            }
+        }
+
+        // (only the links between packages of the graph are kept,
+        // the analysis can be restricted to some dependencies, see [`super::scope`])
+        let links = analysis
+            .links
+            .iter()
+            .filter(|link| nodes.contains_key(&link.from) && nodes.contains_key(&link.to))
+            .cloned()
+            .collect();
+        Self {
+            nodes: nodes.into_iter().map(|(_, node)| node).collect(),
+            links,
+        }
+    }
+
+    /// Exports the graph as Cypher statements, to be run on a graph database (e.g. Neo4j).
+    /// Running them again (or with the graph of another repository) merges the graphs.
+    pub fn to_cypher(&self, repository: &str) -> String {
+        let mut cypher = format!(
+            "// dependency graph of {}\nMERGE (r:Repository {{name: {}}});\n",
+            repository,
+            cypher_str(repository)
+        );
+        let repository = cypher_str(repository);
+        let null = || "null".to_string();
+        for node in &self.nodes {
+            let id = cypher_str(&node.id);
+            if node.workspace {
+                cypher.push_str(&format!(
+                    "MATCH (r:Repository {{name: {repository}}}) \
+                     MERGE (m:Member {{repository: {repository}, id: {id}}}) \
+                     SET m.name = {}, m.version = {} \
+                     MERGE (r)-[:CONTAINS]->(m);\n",
+                    cypher_str(&node.name),
+                    cypher_str(&node.version.to_string()),
+                    repository = repository,
+                    id = id,
+                ));
+                continue;
+            }
+            let source = node
+                .source
+                .as_ref()
+                .map(|source| cypher_str(&source_name(source)))
+                .unwrap_or_else(null);
+            let advisories: Vec<String> = node
+                .advisories
+                .iter()
+                .map(|advisory| cypher_str(advisory))
+                .collect();
+            let update = node
+                .update
+                .as_ref()
+                .map(|update| cypher_str(&update.to_string()))
+                .unwrap_or_else(null);
+            let verdict = node
+                .verdict
+                .map(|verdict| cypher_str(verdict.as_str()))
+                .unwrap_or_else(null);
+            let unsafe_changed = match node.unsafe_changed {
+                Some((before, after)) => format!("[{}, {}]", before, after),
+                None => null(),
+            };
+            cypher.push_str(&format!(
+                "MERGE (p:Package {{id: {id}}}) \
+                 SET p.name = {}, p.version = {}, p.source = {}, p.advisories = [{}];\n\
+                 MATCH (r:Repository {{name: {repository}}}), (p:Package {{id: {id}}}) \
+                 MERGE (r)-[u:USES]->(p) \
+                 SET u.direct = {}, u.dev = {}, u.update = {}, u.verdict = {}, u.unsafe_changed = {};\n",
+                cypher_str(&node.name),
+                cypher_str(&node.version.to_string()),
+                source,
+                advisories.join(", "),
+                node.direct,
+                node.dev,
+                update,
+                verdict,
+                unsafe_changed,
+                repository = repository,
+                id = id,
+            ));
+        }
+
+        // (the workspace members are scoped to the repository)
+        let workspace: Vec<&str> = self
+            .nodes
+            .iter()
+            .filter(|node| node.workspace)
+            .map(|node| node.id.as_str())
+            .collect();
+        let pattern = |variable: &str, id: &str| {
+            if workspace.contains(&id) {
+                format!(
+                    "({}:Member {{repository: {}, id: {}}})",
+                    variable,
+                    repository,
+                    cypher_str(id)
+                )
+            } else {
+                format!("({}:Package {{id: {}}})", variable, cypher_str(id))
+            }
+        };
+        for link in &self.links {
+            cypher.push_str(&format!(
+                "MATCH {}, {} MERGE (a)-[:DEPENDS_ON {{dev_only: {}}}]->(b);\n",
+                pattern("a", &link.from),
+                pattern("b", &link.to),
+                link.dev_only
+            ));
+        }
+        cypher
+    }
+
+    /// Exports the graph as GraphML, which most graph tools can import.
+    pub fn to_graphml(&self) -> String {
+        let keys: &[(&str, &str, &str)] = &[
+            ("name", "node", "string"),
+            ("version", "node", "string"),
+            ("source", "node", "string"),
+            ("workspace", "node", "boolean"),
+            ("direct", "node", "boolean"),
+            ("dev", "node", "boolean"),
+            ("update", "node", "string"),
+            ("verdict", "node", "string"),
+            ("advisories", "node", "string"),
+            ("unsafe_before", "node", "int"),
+            ("unsafe_after", "node", "int"),
+            ("dev_only", "edge", "boolean"),
+        ];
+        let mut graphml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+        for (name, domain, kind) in keys {
+            graphml.push_str(&format!(
+                "  <key id=\"{name}\" for=\"{}\" attr.name=\"{name}\" attr.type=\"{}\"/>\n",
+                domain,
+                kind,
+                name = name
+            ));
+        }
+        graphml.push_str("  <graph id=\"dependencies\" edgedefault=\"directed\">\n");
+        for node in &self.nodes {
+            let mut data = vec![
+                ("name", node.name.clone()),
+                ("version", node.version.to_string()),
+                ("workspace", node.workspace.to_string()),
+                ("direct", node.direct.to_string()),
+                ("dev", node.dev.to_string()),
+                ("advisories", node.advisories.join(",")),
+            ];
+            if let Some(source) = &node.source {
+                data.push(("source", source_name(source)));
+            }
+            if let Some(update) = &node.update {
+                data.push(("update", update.to_string()));
+            }
+            if let Some(verdict) = node.verdict {
+                data.push(("verdict", verdict.as_str().to_string()));
+            }
+            if let Some((before, after)) = node.unsafe_changed {
+                data.push(("unsafe_before", before.to_string()));
+                data.push(("unsafe_after", after.to_string()));
+            }
+            graphml.push_str(&format!("    <node id=\"{}\">\n", escape(&node.id)));
+            for (key, value) in data {
+                graphml.push_str(&format!(
+                    "      <data key=\"{}\">{}</data>\n",
+                    key,
+                    escape(&value)
+                ));
+            }
+            graphml.push_str("    </node>\n");
+        }
+        for link in &self.links {
+            graphml.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\">\n      <data key=\"dev_only\">{}</data>\n    </edge>\n",
+                escape(&link.from),
+                escape(&link.to),
+                link.dev_only
+            ));
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
+
+/// Returns where a package comes from (`crates-io`, the URL of a registry or git repository, or `path`).
+pub(super) fn source_name(source: &SummarySource) -> String {
+    match source {
+        SummarySource::CratesIo => "crates-io".to_string(),
+        SummarySource::External { source } => source.clone(),
+        _ => "path".to_string(),
+    }
+}
+
+/// Quotes a Cypher string.
+fn cypher_str(string: &str) -> String {
+    format!("'{}'", string.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rust::{DependencyInfo, Update};
+
+    #[test]
+    fn test_dependency_graph() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        let mut smallvec = DependencyInfo::new(
+            "smallvec".to_string(),
+            v("1.6.0"),
+            SummarySource::CratesIo,
+            false,
+            false,
+        );
+        smallvec.update = Some(Update {
+            versions: vec![v("1.6.1")],
+            ..Default::default()
+        });
+        let parking_lot = DependencyInfo::new(
+            "parking_lot".to_string(),
+            v("0.11.1"),
+            SummarySource::CratesIo,
+            false,
+            true,
+        );
+        let link = |from: &str, to: &str, from_workspace: bool| Link {
+            from: from.to_string(),
+            to: to.to_string(),
+            dev_only: false,
+            from_workspace,
+        };
+        let analysis = RustAnalysis {
+            dependencies: vec![smallvec, parking_lot],
+            links: vec![
+                link("service 0.1.0", "parking_lot 0.11.1", true),
+                link("parking_lot 0.11.1", "smallvec 1.6.0", false),
+                // out of the scope of the analysis
+                link("parking_lot 0.11.1", "cfg-if 1.0.0", false),
+            ],
+            ..Default::default()
+        };
+
+        let graph = DependencyGraph::new(&analysis);
+        let ids: Vec<&str> = graph.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec!["parking_lot 0.11.1", "service 0.1.0", "smallvec 1.6.0"]
+        );
+        assert!(graph.nodes[1].workspace);
+        assert_eq!(graph.nodes[2].update, Some(v("1.6.1")));
+        assert_eq!(graph.nodes[2].verdict, Some(Verdict::Pass));
+        assert_eq!(graph.links.len(), 2);
+
+        let cypher = graph.to_cypher("https://github.com/acme/o'brien");
+        assert!(
+            cypher.contains("MERGE (r:Repository {name: 'https://github.com/acme/o\\'brien'});")
+        );
+        assert!(cypher.contains("MERGE (p:Package {id: 'smallvec 1.6.0'}) SET p.name = 'smallvec', p.version = '1.6.0', p.source = 'crates-io', p.advisories = [];"));
+        assert!(cypher.contains("SET u.direct = false, u.dev = false, u.update = '1.6.1', u.verdict = 'pass', u.unsafe_changed = null;"));
+        assert!(cypher.contains("MATCH (a:Member {repository: 'https://github.com/acme/o\\'brien', id: 'service 0.1.0'}), (b:Package {id: 'parking_lot 0.11.1'}) MERGE (a)-[:DEPENDS_ON {dev_only: false}]->(b);"));
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<node id=\"smallvec 1.6.0\">"));
+        assert!(graphml.contains("<data key=\"verdict\">pass</data>"));
+        assert!(graphml.contains("<edge source=\"parking_lot 0.11.1\" target=\"smallvec 1.6.0\">"));
+    }
+}
@@ -1,48 +1,129 @@
-use anyhow::Result;
+//! This module resolves the dependency graph of a workspace with guppy (running `cargo metadata`).
+//!
+//! The graph is built once ([`package_graph`]) and the other functions work on that [`PackageGraph`],
+//! which the caller may already have (e.g. a tool built on guppy), so that downstream tooling can reuse
+//! the classification of the analysis: direct or transitive dependencies ([`classify`]),
+//! how the workspace depends on each package ([`kinds`]),
+//! and the packages that only a direct dependency pulls in ([`exclusive_dependencies`]).
+//! Packages are identified by their name, version and source (a [`SummaryId`], as in the summaries of guppy),
+//! since a crate can be pulled in several times with the same version (e.g. from crates.io and from a git fork).
+//! The guppy types are re-exported, for downstream crates to use the same version of guppy.
+
+use anyhow::{Context, Result};
+pub use guppy::graph::{summaries::SummaryId, PackageGraph, PackageMetadata};
 use guppy::{
     graph::{
         cargo::{CargoOptions, CargoResolverVersion},
-        feature::StandardFeatures,
+        feature::{feature_filter, FeatureId, StandardFeatures},
         summaries::Summary,
-        PackageGraph,
+        BuildTargetId, BuildTargetKind, DependencyDirection, EnabledTernary, PackageLink,
+        PlatformStatus,
     },
-    MetadataCommand,
+    MetadataCommand, PackageId,
 };
+use guppy_summaries::SummarySource;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs;
 use std::path::Path;
 use target_spec::{Platform, TargetFeatures};
 use tracing::{debug, info};
 
+use super::graph::{self, Link};
+use crate::audit;
+
+/// Options used to resolve the dependency graph of a workspace.
+/// By default, the graph is resolved like a `cargo build` of the whole workspace would,
+/// on any platform, with default features.
+/// Setting these options allows an analysis to reflect what actually gets compiled
+/// into a given artifact instead of the union of every possible build.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ResolveOptions {
+    /// The target triple to resolve the graph for (e.g. `x86_64-unknown-linux-gnu`).
+    /// If not set, dependencies for every platform are included.
+    pub target: Option<String>,
+    /// Additional features to enable on the workspace packages (like `--features`).
+    pub features: Vec<String>,
+    /// Do not enable the default features of the workspace packages (like `--no-default-features`).
+    pub no_default_features: bool,
+    /// Forces the use of the version 2 of the feature resolver.
+    /// Note that the resolver is also switched to v2 if the workspace manifest specifies `resolver = "2"`.
+    pub v2_resolver: bool,
+    /// Builds the dependency list from `Cargo.lock` and the manifests, without invoking cargo
+    /// (see [`super::lockfile`]), so that untrusted repositories can be analyzed without executing
+    /// their build machinery. In this degraded mode, the other options are ignored,
+    /// and the depth, introducers, licenses, targets and platforms of dependencies are not available.
+    pub lockfile_only: bool,
+}
+
 /// Obtains all dependencies (normal/build/dev and direct/transitive)
-/// that get imported when default features are used.
-pub fn get_guppy_summaries(manifest_path: &Path) -> Result<(Summary, Summary)> {
+/// that get imported when building the workspace with the given options,
+/// without and with dev-dependencies.
+pub fn summaries(
+    package_graph: &PackageGraph,
+    manifest_path: &Path,
+    options: &ResolveOptions,
+) -> Result<(Summary, Summary)> {
     info!("obtaining dependencies from {:?}", manifest_path);
-    let no_dev_summary = get_dependencies_inner(manifest_path, false)?;
-    let all_summary = get_dependencies_inner(manifest_path, true)?;
+    let no_dev_summary = resolve_summary(package_graph, manifest_path, false, options)?;
+    let all_summary = resolve_summary(package_graph, manifest_path, true, options)?;
     //
     Ok((no_dev_summary, all_summary))
 }
 
-/// Obtains all dependencies (normal/build/dev and direct/transitive)
-/// that get imported when default features are used.
-pub fn get_dependencies_inner(manifest_path: &Path, include_dev: bool) -> Result<Summary> {
-    // obtain metadata from manifest_path
+/// Returns the `cargo metadata` command of a workspace, recorded in the audit log (see [`crate::audit`]).
+fn metadata_command(manifest_path: &Path) -> MetadataCommand {
+    audit::command(
+        None,
+        "cargo",
+        &[
+            "metadata",
+            "--format-version",
+            "1",
+            "--manifest-path",
+            &manifest_path.to_string_lossy(),
+        ],
+    );
     let mut cmd = MetadataCommand::new();
     cmd.manifest_path(manifest_path);
+    cmd
+}
 
-    // construct graph with guppy
-    let package_graph = PackageGraph::from_command(&mut cmd).map_err(anyhow::Error::msg)?;
+/// Builds the graph of a workspace (running `cargo metadata`).
+pub fn package_graph(manifest_path: &Path) -> Result<PackageGraph> {
+    let mut cmd = metadata_command(manifest_path);
+    PackageGraph::from_command(&mut cmd).map_err(anyhow::Error::msg)
+}
 
+/// Obtains all dependencies (normal/build/dev and direct/transitive)
+/// that get imported when building the workspace with the given options.
+pub fn get_dependencies_inner(
+    manifest_path: &Path,
+    include_dev: bool,
+    options: &ResolveOptions,
+) -> Result<Summary> {
+    let package_graph = package_graph(manifest_path)?;
+
+    resolve_summary(&package_graph, manifest_path, include_dev, options)
+}
+
+/// Resolves the graph of a workspace with the given options, like [`get_dependencies_inner`].
+fn resolve_summary(
+    package_graph: &PackageGraph,
+    manifest_path: &Path,
+    include_dev: bool,
+    options: &ResolveOptions,
+) -> Result<Summary> {
     // cargo options
-    let mut opts = CargoOptions::new();
+    let opts = cargo_options(manifest_path, include_dev, options)?;
     info!("guppy cargo settings: {:#?}", opts);
 
-    // TODO: we have to switch to v2 when rust will do the switch?
-    opts.set_version(CargoResolverVersion::V1)
-        .set_include_dev(include_dev);
-
     // we're simulating a build on all workspace crates
     let package_set = package_graph.resolve_workspace();
-    let feature_set = package_set.to_feature_set(StandardFeatures::Default); // standard cargo build
+    let features = options.features.iter().map(String::as_str);
+    let feature_set =
+        package_set.to_feature_set(feature_filter(standard_features(options), features));
     let cargo_set = feature_set.into_cargo_set(&opts)?;
 
     // produce summary
@@ -53,54 +134,629 @@ pub fn get_dependencies_inner(manifest_path: &Path, include_dev: bool) -> Result
     Ok(summary)
 }
 
-/// Obtains all dependencies (normal/build/dev and direct/transitive)
-/// that get imported when default features are used.
-pub fn get_dependencies_inner_custom(
+/// Finds the license of every (non-workspace) package in the graph of a workspace,
+/// as well as the workspace members linking against it (transitively, ignoring dev-dependencies).
+pub fn licenses(
+    package_graph: &PackageGraph,
+) -> HashMap<SummaryId, (Option<String>, BTreeSet<String>)> {
+    // depth-first search from each workspace member
+    let mut licenses: HashMap<SummaryId, (Option<String>, BTreeSet<String>)> = HashMap::new();
+    for member in package_graph.workspace().iter() {
+        let mut visited: HashSet<&PackageId> = HashSet::new();
+        visited.insert(member.id());
+        let mut stack = vec![member];
+        while let Some(package) = stack.pop() {
+            for link in package.direct_links() {
+                let dependency = link.to();
+                if link.dev_only() || !visited.insert(dependency.id()) {
+                    continue;
+                }
+                if !dependency.in_workspace() {
+                    licenses
+                        .entry(dependency.to_summary_id())
+                        .or_insert_with(|| {
+                            (
+                                dependency.license().map(ToString::to_string),
+                                BTreeSet::new(),
+                            )
+                        })
+                        .1
+                        .insert(member.name().to_string());
+                }
+                stack.push(dependency);
+            }
+        }
+    }
+
+    licenses
+}
+
+/// Finds the library and binary targets of every workspace member
+/// (e.g. `core (lib)`, `service-a (bin)`), which are the artifacts built out of the workspace.
+/// All the targets of a package share its (non-dev) dependencies.
+pub fn targets(package_graph: &PackageGraph) -> HashMap<String, Vec<String>> {
+    let mut targets = HashMap::new();
+    for member in package_graph.workspace().iter() {
+        let member_targets = member
+            .build_targets()
+            .filter_map(|target| match target.id() {
+                BuildTargetId::Library => Some(format!("{} (lib)", target.name())),
+                BuildTargetId::Binary(name) => Some(format!("{} (bin)", name)),
+                _ => None,
+            })
+            .collect();
+        targets.insert(member.name().to_string(), member_targets);
+    }
+
+    targets
+}
+
+/// Finds the (non-workspace) packages of the graph of a workspace that are procedural macros,
+/// which are compiled for and executed on the host at build time (see [`super::proc_macros`]).
+pub fn proc_macros(package_graph: &PackageGraph) -> HashSet<SummaryId> {
+    package_graph
+        .packages()
+        .filter(|package| !package.in_workspace())
+        .filter(|package| {
+            package
+                .build_targets()
+                .any(|target| matches!(target.kind(), BuildTargetKind::ProcMacro))
+        })
+        .map(|package| package.to_summary_id())
+        .collect()
+}
+
+/// Finds the (non-workspace) packages of the graph of a workspace whose code runs at build time
+/// (see [`super::build_time`]), and whether they have a build script:
+/// the packages with a build script, the procedural macros,
+/// and what they are compiled with (build-dependencies, dependencies of proc macros, transitively).
+pub fn build_time_packages(package_graph: &PackageGraph) -> HashMap<SummaryId, bool> {
+    let mut build_time = HashMap::new();
+    // the packages executed at build time (as proc macros, or compiled into build scripts)
+    let mut executed: Vec<PackageMetadata> = Vec::new();
+    for package in package_graph.packages() {
+        let build_script = package
+            .build_targets()
+            .any(|target| matches!(target.id(), BuildTargetId::BuildScript));
+        let proc_macro = package
+            .build_targets()
+            .any(|target| matches!(target.kind(), BuildTargetKind::ProcMacro));
+        if build_script {
+            // (the build-dependencies of the workspace members run at build time too)
+            for link in package.direct_links() {
+                if link.build().is_present() {
+                    executed.push(link.to());
+                }
+            }
+        }
+        if package.in_workspace() {
+            continue;
+        }
+        if build_script {
+            build_time.insert(package.to_summary_id(), true);
+        }
+        if proc_macro {
+            executed.push(package);
+        }
+    }
+
+    // depth-first search from the executed packages
+    let mut visited: HashSet<&PackageId> = HashSet::new();
+    while let Some(package) = executed.pop() {
+        if !visited.insert(package.id()) {
+            continue;
+        }
+        if !package.in_workspace() {
+            build_time.entry(package.to_summary_id()).or_insert(false);
+        }
+        for link in package.direct_links() {
+            if !link.dev_only() {
+                executed.push(link.to());
+            }
+        }
+    }
+
+    build_time
+}
+
+/// Lists the links between the packages of the graph of a workspace
+/// (every dependency of every package, for any platform and feature).
+pub fn links(package_graph: &PackageGraph) -> Vec<Link> {
+    let mut links = Vec::new();
+    for package in package_graph.packages() {
+        for link in package.direct_links() {
+            let dependency = link.to();
+            links.push(Link {
+                from: graph::node_id(package.name(), package.version()),
+                to: graph::node_id(dependency.name(), dependency.version()),
+                dev_only: link.dev_only(),
+                from_workspace: package.in_workspace(),
+            });
+        }
+    }
+    links.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+    links.dedup();
+
+    links
+}
+
+/// Finds the direct dependencies of a workspace whose default features pull in packages
+/// (see [`super::slimming`]), with the default features the workspace doesn't request explicitly
+/// and the (non-workspace) packages that would not be compiled anymore without them.
+/// For each candidate, the features of the graph are resolved again without following the links
+/// from the workspace members to its `default` feature (the features they request are still followed).
+/// Dependencies whose default features are also enabled by other packages are not reported,
+/// as nothing would be dropped.
+pub fn default_features_bloat(
+    package_graph: &PackageGraph,
     manifest_path: &Path,
-    include_dev: bool,
-    v2resolver: bool,
-    features: Vec<&str>,
-    platform_triplet: &str,
-    ignored_packages: Vec<&str>,
-) -> Result<Summary> {
-    // obtain metadata from manifest_path
-    let mut cmd = MetadataCommand::new();
-    cmd.manifest_path(manifest_path);
+    options: &ResolveOptions,
+) -> Result<HashMap<SummaryId, (Vec<String>, Vec<SummaryId>)>> {
+    let packages = resolve_packages(package_graph, manifest_path, options)?;
+    let platform = platform(options)?;
 
-    // construct graph with guppy
-    let package_graph = PackageGraph::from_command(&mut cmd).map_err(anyhow::Error::msg)?;
+    let mut bloat = HashMap::new();
+    for (dependency, default_features) in default_features_candidates(package_graph) {
+        let slimmed_packages =
+            resolve_without_default(package_graph, options, platform.as_ref(), dependency.id());
+        let dropped: Vec<SummaryId> = packages
+            .iter()
+            .filter(|package| !slimmed_packages.contains(package))
+            .cloned()
+            .collect();
+        if !dropped.is_empty() {
+            bloat.insert(dependency.to_summary_id(), (default_features, dropped));
+        }
+    }
 
-    // cargo options
+    Ok(bloat)
+}
+
+/// Resolves the graph of a workspace (without dev-dependencies) into its non-workspace packages.
+fn resolve_packages(
+    package_graph: &PackageGraph,
+    manifest_path: &Path,
+    options: &ResolveOptions,
+) -> Result<BTreeSet<SummaryId>> {
+    let summary = resolve_summary(package_graph, manifest_path, false, options)?;
+    Ok(summary
+        .target_packages
+        .iter()
+        .chain(summary.host_packages.iter())
+        .map(|(summary_id, _)| summary_id)
+        .filter(|summary_id| {
+            !matches!(
+                summary_id.source,
+                SummarySource::Workspace { .. } | SummarySource::Path { .. }
+            )
+        })
+        .cloned()
+        .collect())
+}
+
+/// Resolves the features of a workspace (without dev-dependencies) into its non-workspace packages,
+/// without following the links from the workspace members to the `default` feature of a dependency.
+/// Unlike [`resolve_packages`], the features requested on a package are unified across the build
+/// (like the version 1 of the resolver), which can only keep more packages.
+fn resolve_without_default(
+    package_graph: &PackageGraph,
+    options: &ResolveOptions,
+    platform: Option<&Platform>,
+    dependency: &PackageId,
+) -> HashSet<SummaryId> {
+    let enabled = |status: PlatformStatus| match platform {
+        Some(platform) => status.enabled_on(platform) != EnabledTernary::Disabled,
+        None => status.is_present(),
+    };
+    let features = options.features.iter().map(String::as_str);
+    package_graph
+        .feature_graph()
+        .query_workspace(feature_filter(standard_features(options), features))
+        .resolve_with_fn(|_, link| {
+            let skipped = link.from().package().in_workspace()
+                && link.to().package_id() == dependency
+                && link.to().feature_id().feature() == Some("default");
+            !skipped && (enabled(link.normal()) || enabled(link.build()))
+        })
+        .to_package_set()
+        .packages(DependencyDirection::Forward)
+        .filter(|package| !package.in_workspace())
+        .map(|package| package.to_summary_id())
+        .collect()
+}
+
+/// Lists the dependencies declared by the workspace members with their default features
+/// (other than as dev-dependencies, and not by path),
+/// with the default features that the members don't request explicitly (if any).
+fn default_features_candidates(
+    package_graph: &PackageGraph,
+) -> Vec<(PackageMetadata<'_>, Vec<String>)> {
+    // the features requested by the members, per dependency
+    let mut requested: BTreeMap<&PackageId, (PackageMetadata, BTreeSet<&str>)> = BTreeMap::new();
+    for member in package_graph.workspace().iter() {
+        for link in member.direct_links() {
+            let dependency = link.to();
+            if link.dev_only() || dependency.source().is_local() {
+                continue;
+            }
+            for req in [link.normal(), link.build()].iter() {
+                if req.default_features().enabled_on_any() {
+                    requested
+                        .entry(dependency.id())
+                        .or_insert_with(|| (dependency, BTreeSet::new()))
+                        .1
+                        .extend(req.features());
+                }
+            }
+        }
+    }
+
+    // the default features of these dependencies
+    let feature_graph = package_graph.feature_graph();
+    let mut candidates = Vec::new();
+    for (id, (dependency, requested)) in requested {
+        if !dependency.has_default_feature() {
+            continue;
+        }
+        let default_features: BTreeSet<&str> = dependency
+            .named_features()
+            .filter(|feature| *feature != "default" && !requested.contains(feature))
+            .filter(|feature| {
+                feature_graph
+                    .is_default_feature(FeatureId::new(id, feature))
+                    .unwrap_or(false)
+            })
+            .collect();
+        if !default_features.is_empty() {
+            candidates.push((
+                dependency,
+                default_features
+                    .into_iter()
+                    .map(ToString::to_string)
+                    .collect(),
+            ));
+        }
+    }
+    candidates
+}
+
+/// Returns the base features of the workspace members for the given options.
+fn standard_features(options: &ResolveOptions) -> StandardFeatures {
+    if options.no_default_features {
+        StandardFeatures::None
+    } else {
+        StandardFeatures::Default // standard cargo build
+    }
+}
+
+/// Returns the platform to resolve the graph for, if the options set a target.
+fn platform(options: &ResolveOptions) -> Result<Option<Platform<'static>>> {
+    options
+        .target
+        .as_ref()
+        .map(|target| {
+            Platform::new(target, TargetFeatures::Unknown)
+                .with_context(|| format!("unknown target triple {}", target))
+        })
+        .transpose()
+}
+
+/// Converts our resolve options into guppy's cargo options.
+fn cargo_options(
+    manifest_path: &Path,
+    include_dev: bool,
+    options: &ResolveOptions,
+) -> Result<CargoOptions<'static>> {
     let mut opts = CargoOptions::new();
 
-    let target_features = TargetFeatures::Unknown;
-    let platform = Platform::new(platform_triplet, target_features)?;
-    opts.set_platform(Some(platform));
+    if let Some(platform) = platform(options)? {
+        opts.set_platform(Some(platform));
+    }
 
-    let resolver = if v2resolver {
+    let resolver = if options.v2_resolver || uses_v2_resolver(manifest_path) {
         CargoResolverVersion::V2
     } else {
         CargoResolverVersion::V1
     };
     opts.set_version(resolver).set_include_dev(include_dev);
 
-    // we're simulating a build on all workspace crates
-    let package_set = package_graph.resolve_workspace();
-    let feature_set = package_set.to_feature_set(StandardFeatures::Default); // standard cargo build
-    let cargo_set = feature_set.into_cargo_set(&opts)?;
+    Ok(opts)
+}
 
-    // produce summary
-    let summary = cargo_set.to_summary(&opts)?;
-    debug!("summary obtained: {:?}", summary);
+/// Checks if the root manifest of a workspace opts into the version 2 of the feature resolver.
+fn uses_v2_resolver(manifest_path: &Path) -> bool {
+    let manifest = match fs::read_to_string(manifest_path) {
+        Ok(manifest) => manifest,
+        Err(_) => return false,
+    };
+    let manifest: toml::Value = match toml::from_str(&manifest) {
+        Ok(manifest) => manifest,
+        Err(_) => return false,
+    };
+    ["workspace", "package"].iter().any(|table| {
+        manifest
+            .get(table)
+            .and_then(|table| table.get("resolver"))
+            .and_then(toml::Value::as_str)
+            == Some("2")
+    })
+}
 
-    //
-    Ok(summary)
+//
+// Graph helpers
+//
+
+/// How a workspace depends on a package.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    /// a workspace member depends on it
+    Direct,
+    /// only other dependencies depend on it
+    Transitive,
+}
+
+/// The ways a workspace depends on a package (transitively), a package can be depended on in several ways.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DependencyKinds {
+    /// compiled into the workspace members
+    pub normal: bool,
+    /// compiled into build scripts (or procedural macros) only, running on the build machine
+    pub build: bool,
+    /// compiled into tests, examples and benchmarks only
+    pub dev: bool,
+}
+
+/// The kind of the paths from the workspace to a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Normal,
+    Build,
+    Dev,
+}
+
+/// Lists the (non-workspace) packages that the workspace members depend on directly.
+pub fn direct_dependencies(package_graph: &PackageGraph) -> Vec<PackageMetadata<'_>> {
+    let mut direct_dependencies: Vec<PackageMetadata> = Vec::new();
+    let mut seen: HashSet<&PackageId> = HashSet::new();
+    for package in package_graph.workspace().iter() {
+        for link in package.direct_links() {
+            let dependency = link.to();
+            if !dependency.in_workspace() && seen.insert(dependency.id()) {
+                direct_dependencies.push(dependency);
+            }
+        }
+    }
+    direct_dependencies
+}
+
+/// Classifies the (non-workspace) packages of a graph as direct or transitive dependencies of the workspace.
+pub fn classify(package_graph: &PackageGraph) -> HashMap<SummaryId, Relation> {
+    let direct: HashSet<&PackageId> = direct_dependencies(package_graph)
+        .iter()
+        .map(PackageMetadata::id)
+        .collect();
+    package_graph
+        .packages()
+        .filter(|package| !package.in_workspace())
+        .map(|package| {
+            let relation = if direct.contains(package.id()) {
+                Relation::Direct
+            } else {
+                Relation::Transitive
+            };
+            (package.to_summary_id(), relation)
+        })
+        .collect()
+}
+
+/// Returns the kind of a link followed on a path of the given kind
+/// (a build-dependency of a normal dependency runs at build time).
+fn link_kind(kind: Kind, link: &PackageLink) -> Kind {
+    if kind == Kind::Normal && !link.normal().is_present() && link.build().is_present() {
+        Kind::Build
+    } else {
+        kind
+    }
+}
+
+/// Finds how the workspace depends on every (non-workspace) package of a graph:
+/// as a normal, build or dev dependency (transitively, e.g. the dependencies of a build-dependency are build dependencies).
+pub fn kinds(package_graph: &PackageGraph) -> HashMap<SummaryId, DependencyKinds> {
+    let mut stack: Vec<(PackageMetadata, Kind)> = Vec::new();
+    for member in package_graph.workspace().iter() {
+        for link in member.direct_links() {
+            if link.to().in_workspace() {
+                continue;
+            }
+            if link.normal().is_present() {
+                stack.push((link.to(), Kind::Normal));
+            }
+            if link.build().is_present() {
+                stack.push((link.to(), Kind::Build));
+            }
+            if link.dev().is_present() {
+                stack.push((link.to(), Kind::Dev));
+            }
+        }
+    }
+
+    // depth-first search, once per kind
+    let mut kinds: HashMap<SummaryId, DependencyKinds> = HashMap::new();
+    let mut visited: HashSet<(&PackageId, Kind)> = HashSet::new();
+    while let Some((package, kind)) = stack.pop() {
+        if !visited.insert((package.id(), kind)) {
+            continue;
+        }
+        let package_kinds = kinds.entry(package.to_summary_id()).or_default();
+        match kind {
+            Kind::Normal => package_kinds.normal = true,
+            Kind::Build => package_kinds.build = true,
+            Kind::Dev => package_kinds.dev = true,
+        }
+        for link in package.direct_links() {
+            if !link.dev_only() && !link.to().in_workspace() {
+                stack.push((link.to(), link_kind(kind, &link)));
+            }
+        }
+    }
+    kinds
+}
+
+/// The direct dependencies of the workspace pulling in a package (transitively).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reachers<'g> {
+    /// only this one
+    One(&'g PackageId),
+    /// several of them
+    Many,
+}
+
+impl<'g> Reachers<'g> {
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Reachers::One(a), Reachers::One(b)) if a == b => Reachers::One(a),
+            _ => Reachers::Many,
+        }
+    }
+}
+
+/// Finds, for every direct dependency of the workspace, the (non-workspace) packages that only it pulls in,
+/// which would not be compiled anymore without it.
+/// Packages that are also direct dependencies are not exclusive to another.
+pub fn exclusive_dependencies(
+    package_graph: &PackageGraph,
+) -> HashMap<SummaryId, BTreeSet<SummaryId>> {
+    let follow = |link: &PackageLink| !link.dev_only() && !link.to().in_workspace();
+    let direct_dependencies = direct_dependencies(package_graph);
+    let direct: HashSet<&PackageId> = direct_dependencies
+        .iter()
+        .map(PackageMetadata::id)
+        .collect();
+
+    // the number of links to each package pulled in by the direct dependencies
+    let mut in_degrees: HashMap<&PackageId, usize> = HashMap::new();
+    let mut visited = direct.clone();
+    let mut stack = direct_dependencies.clone();
+    while let Some(package) = stack.pop() {
+        for link in package.direct_links().filter(follow) {
+            let dependency = link.to();
+            *in_degrees.entry(dependency.id()).or_default() += 1;
+            if visited.insert(dependency.id()) {
+                stack.push(dependency);
+            }
+        }
+    }
+
+    // propagate the direct dependencies reaching each package, in topological order
+    // (cargo only resolves the dev-dependencies of the workspace members, so the other links have no cycle)
+    let mut reachers: HashMap<&PackageId, Reachers> = HashMap::new();
+    let mut ready: Vec<PackageMetadata> = direct_dependencies
+        .iter()
+        .filter(|package| !in_degrees.contains_key(package.id()))
+        .copied()
+        .collect();
+    while let Some(package) = ready.pop() {
+        let mut passed = reachers.get(package.id()).copied();
+        if direct.contains(package.id()) {
+            let own = Reachers::One(package.id());
+            passed = Some(passed.map_or(own, |passed| passed.merge(own)));
+        }
+        for link in package.direct_links().filter(follow) {
+            let dependency = link.to();
+            if let Some(passed) = passed {
+                reachers
+                    .entry(dependency.id())
+                    .and_modify(|reachers| *reachers = reachers.merge(passed))
+                    .or_insert(passed);
+            }
+            let in_degree = in_degrees.entry(dependency.id()).or_default();
+            *in_degree -= 1;
+            if *in_degree == 0 {
+                ready.push(dependency);
+            }
+        }
+    }
+
+    let mut exclusive: HashMap<SummaryId, BTreeSet<SummaryId>> = direct_dependencies
+        .iter()
+        .map(|package| (package.to_summary_id(), BTreeSet::new()))
+        .collect();
+    for (id, reachers) in reachers {
+        let (package, direct_dependency) = match (package_graph.metadata(id), reachers) {
+            (Ok(package), Reachers::One(direct_dependency)) if !direct.contains(id) => {
+                (package, direct_dependency)
+            }
+            _ => continue,
+        };
+        if let Ok(direct_dependency) = package_graph.metadata(direct_dependency) {
+            exclusive
+                .entry(direct_dependency.to_summary_id())
+                .or_default()
+                .insert(package.to_summary_id());
+        }
+    }
+    exclusive
+}
+
+/// Computes the depth of every (non-workspace) package in a graph,
+/// which is the minimum number of hops from a workspace member (direct dependencies are at depth 1).
+pub fn depths(package_graph: &PackageGraph) -> HashMap<SummaryId, usize> {
+    // breadth-first search from the workspace members
+    let mut depths = HashMap::new();
+    let mut visited: HashSet<&PackageId> = HashSet::new();
+    let mut queue: VecDeque<(PackageMetadata, usize)> = VecDeque::new();
+    for package in package_graph.workspace().iter() {
+        visited.insert(package.id());
+        queue.push_back((package, 0));
+    }
+    while let Some((package, depth)) = queue.pop_front() {
+        for link in package.direct_links() {
+            let dependency = link.to();
+            if visited.insert(dependency.id()) {
+                let depth = depth + 1;
+                depths.insert(dependency.to_summary_id(), depth);
+                queue.push_back((dependency, depth));
+            }
+        }
+    }
+
+    depths
+}
+
+/// Finds, for every (non-workspace) package in a graph,
+/// the direct dependencies of the workspace that pull it in (transitively).
+/// Direct dependencies are not attributed to themselves.
+pub fn introducers(package_graph: &PackageGraph) -> HashMap<SummaryId, BTreeSet<String>> {
+    // depth-first search from each direct dependency
+    let mut introducers: HashMap<SummaryId, BTreeSet<String>> = HashMap::new();
+    for direct_dependency in direct_dependencies(package_graph) {
+        let mut visited: HashSet<&PackageId> = HashSet::new();
+        visited.insert(direct_dependency.id());
+        let mut stack = vec![direct_dependency];
+        while let Some(package) = stack.pop() {
+            for link in package.direct_links() {
+                let dependency = link.to();
+                if dependency.in_workspace() || !visited.insert(dependency.id()) {
+                    continue;
+                }
+                introducers
+                    .entry(dependency.to_summary_id())
+                    .or_default()
+                    .insert(direct_dependency.name().to_string());
+                stack.push(dependency);
+            }
+        }
+    }
+
+    introducers
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::git::Repo;
+    use crate::testing::{git_repo, write_crate};
     use std::path::PathBuf;
     use tempfile::tempdir;
 
@@ -109,7 +765,8 @@ mod tests {
         let mut manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         manifest_path.push("resources/test/sample_repo/Cargo.toml");
 
-        let summary = get_dependencies_inner(&manifest_path, true).unwrap();
+        let summary =
+            get_dependencies_inner(&manifest_path, true, &ResolveOptions::default()).unwrap();
 
         println!("{:#?}", summary);
         assert!(summary
@@ -123,4 +780,325 @@ mod tests {
             .find(|p| p.0.name == "optional_dep")
             .is_some());
     }
+
+    #[test]
+    fn test_introducers() {
+        let mut manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        manifest_path.push("resources/test/sample_repo/Cargo.toml");
+
+        let introducers = introducers(&package_graph(&manifest_path).unwrap());
+
+        // bitvec is a direct dependency, and pulls in radium
+        assert!(introducers.keys().all(|id| id.name != "bitvec"));
+        let (_, radium) = introducers
+            .iter()
+            .find(|(id, _)| id.name == "radium")
+            .unwrap();
+        assert!(radium.contains("bitvec"));
+    }
+
+    #[test]
+    fn test_graph_helpers() {
+        let mut manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        manifest_path.push("resources/test/sample_repo/Cargo.toml");
+        let package_graph = package_graph(&manifest_path).unwrap();
+        let find = |name: &str| {
+            package_graph
+                .packages()
+                .find(|package| package.name() == name)
+                .map(|package| package.to_summary_id())
+                .unwrap()
+        };
+
+        // bitvec is a direct dependency, and the only one pulling in radium
+        let relations = classify(&package_graph);
+        assert_eq!(relations[&find("bitvec")], Relation::Direct);
+        assert_eq!(relations[&find("radium")], Relation::Transitive);
+        let exclusive = exclusive_dependencies(&package_graph);
+        assert!(exclusive[&find("bitvec")].contains(&find("radium")));
+        assert!(exclusive[&find("optional_dep")].is_empty());
+        assert_eq!(
+            exclusive,
+            exclusive_dependencies_by_traversal(&package_graph)
+        );
+        assert_eq!(
+            kinds(&package_graph)[&find("radium")],
+            DependencyKinds {
+                normal: true,
+                build: false,
+                dev: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_exclusive_dependencies_of_transitive_direct_dependency() {
+        // app depends on a and b, b depends on a too, and only a depends on c
+        let dir = tempdir().unwrap();
+        let crates: &[(&str, &[&str])] = &[
+            ("app", &["a", "b"]),
+            ("a", &["c"]),
+            ("b", &["a"]),
+            ("c", &[]),
+        ];
+        for (name, dependencies) in crates {
+            let crate_dir = dir.path().join(name);
+            fs::create_dir_all(crate_dir.join("src")).unwrap();
+            fs::write(crate_dir.join("src/lib.rs"), "").unwrap();
+            let mut manifest = format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n",
+                name
+            );
+            for dependency in dependencies.iter() {
+                manifest.push_str(&format!(
+                    "{} = {{ path = \"../{}\" }}\n",
+                    dependency, dependency
+                ));
+            }
+            fs::write(crate_dir.join("Cargo.toml"), manifest).unwrap();
+        }
+        let package_graph = package_graph(&dir.path().join("app/Cargo.toml")).unwrap();
+        let key = |name: &str| {
+            package_graph
+                .packages()
+                .find(|package| package.name() == name)
+                .map(|package| package.to_summary_id())
+                .unwrap()
+        };
+
+        // without the declaration of a, b still pulls in a and c (and the other way around)
+        let exclusive = exclusive_dependencies(&package_graph);
+        assert!(exclusive[&key("a")].is_empty());
+        assert!(exclusive[&key("b")].is_empty());
+        assert_eq!(
+            exclusive,
+            exclusive_dependencies_by_traversal(&package_graph)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_same_version_from_two_sources() {
+        // app depends on a and b, and b depends on a fork of a in a git repository (same name and version)
+        let dir = tempdir().unwrap();
+        let manifest = |name: &str, dependencies: &str| {
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                name, dependencies
+            )
+        };
+        let fork = dir.path().join("fork");
+        fs::create_dir_all(&fork).unwrap();
+        let fork_manifest = manifest("a", "");
+        git_repo(
+            &fork,
+            &[&[("Cargo.toml", &fork_manifest), ("src/lib.rs", "")]],
+        )
+        .await
+        .unwrap();
+        let fork_dependency = format!("a = {{ git = \"file://{}\" }}\n", fork.display());
+        let crates = &[
+            (
+                "app",
+                manifest("app", "a = { path = \"../a\" }\nb = { path = \"../b\" }\n"),
+            ),
+            ("a", manifest("a", "")),
+            ("b", manifest("b", &fork_dependency)),
+        ];
+        for (name, manifest) in crates {
+            write_crate(
+                &dir.path().join(name),
+                &[("Cargo.toml", manifest), ("src/lib.rs", "")],
+            )
+            .unwrap();
+        }
+        let package_graph = package_graph(&dir.path().join("app/Cargo.toml")).unwrap();
+
+        // the two copies of a are told apart
+        let relations = classify(&package_graph);
+        let copies: Vec<&Relation> = relations
+            .iter()
+            .filter(|(id, _)| id.name == "a")
+            .map(|(_, relation)| relation)
+            .collect();
+        assert_eq!(copies.len(), 2);
+        assert!(copies.contains(&&Relation::Direct));
+        assert!(copies.contains(&&Relation::Transitive));
+        let introducers = introducers(&package_graph);
+        let (fork, introduced_by) = introducers.iter().find(|(id, _)| id.name == "a").unwrap();
+        assert!(matches!(fork.source, SummarySource::External { .. }));
+        assert!(introduced_by.contains("b"));
+    }
+
+    /// A reference implementation of [`exclusive_dependencies`] (a traversal per direct dependency),
+    /// to check the results and compare the durations.
+    fn exclusive_dependencies_by_traversal(
+        package_graph: &PackageGraph,
+    ) -> HashMap<SummaryId, BTreeSet<SummaryId>> {
+        let direct_dependencies = direct_dependencies(package_graph);
+        let direct: HashSet<&PackageId> = direct_dependencies
+            .iter()
+            .map(PackageMetadata::id)
+            .collect();
+
+        // the direct dependencies pulling in each package
+        let mut reached_by: HashMap<&PackageId, Vec<usize>> = HashMap::new();
+        for (idx, direct_dependency) in direct_dependencies.iter().enumerate() {
+            let mut visited: HashSet<&PackageId> = HashSet::new();
+            visited.insert(direct_dependency.id());
+            let mut stack = vec![*direct_dependency];
+            while let Some(package) = stack.pop() {
+                for link in package.direct_links() {
+                    let dependency = link.to();
+                    if link.dev_only()
+                        || dependency.in_workspace()
+                        || !visited.insert(dependency.id())
+                    {
+                        continue;
+                    }
+                    reached_by.entry(dependency.id()).or_default().push(idx);
+                    stack.push(dependency);
+                }
+            }
+        }
+
+        let mut exclusive: HashMap<SummaryId, BTreeSet<SummaryId>> = direct_dependencies
+            .iter()
+            .map(|package| (package.to_summary_id(), BTreeSet::new()))
+            .collect();
+        for (id, reachers) in reached_by {
+            let (package, direct_dependency) =
+                match (package_graph.metadata(id), reachers.as_slice()) {
+                    (Ok(package), [idx]) if !direct.contains(id) => {
+                        (package, &direct_dependencies[*idx])
+                    }
+                    _ => continue,
+                };
+            exclusive
+                .entry(direct_dependency.to_summary_id())
+                .or_default()
+                .insert(package.to_summary_id());
+        }
+        exclusive
+    }
+
+    #[tokio::test]
+    #[ignore] // (clones diem, run with `cargo test --release -- --ignored bench_exclusive_dependencies --nocapture`)
+    async fn bench_exclusive_dependencies() {
+        let temp_dir = tempdir().unwrap();
+        let repo = Repo::clone("https://github.com/diem/diem.git", temp_dir.path())
+            .await
+            .unwrap();
+        let package_graph = package_graph(&repo.repo_folder.join("Cargo.toml")).unwrap();
+
+        let start = std::time::Instant::now();
+        let exclusive = exclusive_dependencies(&package_graph);
+        let single_pass = start.elapsed();
+        let start = std::time::Instant::now();
+        let by_traversal = exclusive_dependencies_by_traversal(&package_graph);
+        let traversal = start.elapsed();
+        println!(
+            "{} packages, {} direct dependencies: {:?} in a single pass, {:?} with traversals",
+            package_graph.package_count(),
+            exclusive.len(),
+            single_pass,
+            traversal
+        );
+        assert_eq!(exclusive, by_traversal);
+    }
+
+    #[test]
+    fn test_targets() {
+        let mut manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        manifest_path.push("resources/test/sample_repo/Cargo.toml");
+
+        let targets = targets(&package_graph(&manifest_path).unwrap());
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets["thing"], vec!["thing (bin)".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_default_features_bloat() {
+        // app depends on d (from git), whose default feature enables its optional dependency on e
+        let dir = tempdir().unwrap();
+        let git_crate = |name: &str, manifest: String| {
+            let crate_dir = dir.path().join(name);
+            async move {
+                fs::create_dir_all(&crate_dir).unwrap();
+                git_repo(
+                    &crate_dir,
+                    &[&[("Cargo.toml", &manifest), ("src/lib.rs", "")]],
+                )
+                .await
+                .unwrap();
+                format!("file://{}", crate_dir.display())
+            }
+        };
+        let e = git_crate(
+            "e",
+            "[package]\nname = \"e\"\nversion = \"0.1.0\"\n".to_string(),
+        )
+        .await;
+        let d = git_crate(
+            "d",
+            format!(
+                "[package]\nname = \"d\"\nversion = \"0.1.0\"\n\n[dependencies]\ne = {{ git = \"{}\", optional = true }}\n\n[features]\ndefault = [\"extra\"]\nextra = [\"e\"]\n",
+                e
+            ),
+        )
+        .await;
+        let bloat = |declaration: &str| {
+            let app_dir = dir.path().join("app");
+            write_crate(
+                &app_dir,
+                &[
+                    (
+                        "Cargo.toml",
+                        &format!(
+                            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nd = {{ git = \"{}\"{} }}\n",
+                            d, declaration
+                        ),
+                    ),
+                    ("src/lib.rs", ""),
+                ],
+            )
+            .unwrap();
+            let manifest_path = app_dir.join("Cargo.toml");
+            let package_graph = package_graph(&manifest_path).unwrap();
+            default_features_bloat(&package_graph, &manifest_path, &ResolveOptions::default())
+                .unwrap()
+        };
+
+        // without its default features, d would not pull in e
+        let bloat_of_d = bloat("");
+        assert_eq!(bloat_of_d.len(), 1);
+        let (id, (default_features, dropped)) = bloat_of_d.iter().next().unwrap();
+        assert_eq!(id.name, "d");
+        assert!(default_features.contains(&"extra".to_string()));
+        assert_eq!(
+            dropped
+                .iter()
+                .map(|id| id.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["e"]
+        );
+
+        // (the features requested explicitly are kept)
+        assert!(bloat(", features = [\"extra\"]").is_empty());
+    }
+
+    #[test]
+    fn test_uses_v2_resolver() {
+        let dir = tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+
+        fs::write(&manifest_path, "[workspace]\nmembers = [\"a\"]\n").unwrap();
+        assert!(!uses_v2_resolver(&manifest_path));
+
+        fs::write(
+            &manifest_path,
+            "[workspace]\nmembers = [\"a\"]\nresolver = \"2\"\n",
+        )
+        .unwrap();
+        assert!(uses_v2_resolver(&manifest_path));
+    }
 }
@@ -0,0 +1,231 @@
+//! This module produces the inventory of the third-party crates used across an organization,
+//! out of the `Cargo.lock` of its repositories: every crate version in use and the repositories using it,
+//! and the repositories lagging on versions affected by RUSTSEC advisories
+//! (with the repositories that already moved to a version that is not affected, if any).
+//! Like the backfill (see [`super::backfill`]), nothing is built or executed, only the lockfiles are read.
+
+use anyhow::{Context, Result};
+use rustsec::Database;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::{
+    lockfile_format,
+    monitor::{self, Finding},
+};
+use crate::dashboard::{
+    escape,
+    table::{Align, Cell, Table},
+};
+
+/// A third-party crate, and who uses which of its versions.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CrateUsage {
+    pub name: String,
+    /// the repositories using each version
+    pub versions: BTreeMap<Version, BTreeSet<String>>,
+}
+
+/// A repository using a version affected by an advisory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Lagging {
+    pub repository: String,
+    /// the advisory (see [`monitor::findings`])
+    pub finding: Finding,
+    /// the repositories using the same crate without being affected by the advisory
+    pub fixed_elsewhere: Vec<String>,
+}
+
+/// The crates used across the repositories of an organization.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+    pub repositories: Vec<String>,
+    /// the third-party crates (the ones with a source: crates.io, other registries, git)
+    pub crates: Vec<CrateUsage>,
+    /// the repositories using versions affected by advisories (without an advisory database, none)
+    pub lagging: Vec<Lagging>,
+}
+
+impl Inventory {
+    /// Builds the inventory of the lockfiles of repositories (`(repository, content of its Cargo.lock)`).
+    pub fn new(lockfiles: &[(String, String)], advisory_db: Option<&Database>) -> Result<Self> {
+        let mut crates: BTreeMap<String, CrateUsage> = BTreeMap::new();
+        let mut findings: Vec<(String, Finding)> = Vec::new();
+        for (repository, lockfile) in lockfiles {
+            let lockfile = lockfile_format::parse(lockfile)
+                .with_context(|| format!("couldn't parse the Cargo.lock of {}", repository))?;
+            for package in lockfile.packages.iter().filter(|p| p.source.is_some()) {
+                let name = package.name.as_str();
+                crates
+                    .entry(name.to_string())
+                    .or_insert_with(|| CrateUsage {
+                        name: name.to_string(),
+                        ..Default::default()
+                    })
+                    .versions
+                    .entry(package.version.clone())
+                    .or_default()
+                    .insert(repository.clone());
+            }
+            if let Some(advisory_db) = advisory_db {
+                findings.extend(
+                    monitor::findings(advisory_db, &lockfile)
+                        .into_iter()
+                        .map(|finding| (repository.clone(), finding)),
+                );
+            }
+        }
+
+        let lagging = findings
+            .iter()
+            .map(|(repository, finding)| {
+                let users: BTreeSet<&String> = crates
+                    .get(&finding.package)
+                    .map(|usage| usage.versions.values().flatten().collect())
+                    .unwrap_or_default();
+                let fixed_elsewhere = users
+                    .into_iter()
+                    .filter(|user| {
+                        !findings.iter().any(|(other, other_finding)| {
+                            &other == user
+                                && other_finding.id == finding.id
+                                && other_finding.package == finding.package
+                        })
+                    })
+                    .cloned()
+                    .collect();
+                Lagging {
+                    repository: repository.clone(),
+                    finding: finding.clone(),
+                    fixed_elsewhere,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            repositories: lockfiles
+                .iter()
+                .map(|(repository, _)| repository.clone())
+                .collect(),
+            crates: crates.into_iter().map(|(_, usage)| usage).collect(),
+            lagging,
+        })
+    }
+
+    /// Renders a summary of the inventory as a standalone HTML page:
+    /// the lagging repositories, then the crates used in several versions.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>crate inventory</title></head><body>\n");
+        let versions: usize = self.crates.iter().map(|usage| usage.versions.len()).sum();
+        let _ = writeln!(
+            html,
+            "<h1>Crate inventory</h1>\n<p>{} repositories use {} third-party crates ({} versions).</p>",
+            self.repositories.len(),
+            self.crates.len(),
+            versions
+        );
+
+        html.push_str("<h2>Repositories lagging on versions with advisories</h2>\n");
+        if self.lagging.is_empty() {
+            html.push_str("<p>None.</p>\n");
+        } else {
+            let mut table = Table::new()
+                .column("repository", Align::Left)
+                .column("crate", Align::Left)
+                .column("advisory", Align::Left)
+                .column("patched", Align::Left)
+                .column("fixed elsewhere", Align::Left);
+            for lagging in &self.lagging {
+                let finding = &lagging.finding;
+                table.row(vec![
+                    Cell::text(&lagging.repository),
+                    Cell::code(format!("{} {}", finding.package, finding.version)),
+                    Cell::link(
+                        format!("{} ({})", finding.id, finding.kind),
+                        format!("https://rustsec.org/advisories/{}.html", finding.id),
+                    ),
+                    Cell::text(finding.patched.join(", ")),
+                    Cell::text(lagging.fixed_elsewhere.join(", ")),
+                ]);
+            }
+            html.push_str(&table.to_html());
+        }
+
+        html.push_str("<h2>Crates used in several versions</h2>\n");
+        let mut table = Table::new()
+            .column("crate", Align::Left)
+            .column("version", Align::Left)
+            .column("repositories", Align::Left);
+        for usage in self.crates.iter().filter(|usage| usage.versions.len() > 1) {
+            for (version, repositories) in &usage.versions {
+                let repositories: Vec<&str> = repositories.iter().map(String::as_str).collect();
+                table.row(vec![
+                    Cell::code(&usage.name),
+                    Cell::text(version),
+                    Cell::text(repositories.join(", ")),
+                ]);
+            }
+        }
+        if table.is_empty() {
+            html.push_str("<p>None.</p>\n");
+        } else {
+            html.push_str(&table.to_html());
+        }
+
+        let _ = writeln!(
+            html,
+            "<p>Repositories: {}</p>\n</body></html>",
+            escape(&self.repositories.join(", "))
+        );
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inventory() {
+        let lockfile = |smallvec: &str| {
+            format!(
+                r#"[[package]]
+name = "service"
+version = "0.1.0"
+dependencies = [
+ "smallvec",
+]
+
+[[package]]
+name = "smallvec"
+version = "{}"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+                smallvec
+            )
+        };
+        let inventory = Inventory::new(
+            &[
+                ("a".to_string(), lockfile("1.6.0")),
+                ("b".to_string(), lockfile("1.6.1")),
+                ("c".to_string(), lockfile("1.6.1")),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(inventory.crates.len(), 1);
+        let smallvec = &inventory.crates[0];
+        assert_eq!(smallvec.versions.len(), 2);
+        assert_eq!(
+            smallvec.versions[&Version::parse("1.6.1").unwrap()],
+            vec!["b".to_string(), "c".to_string()].into_iter().collect()
+        );
+        assert!(inventory.lagging.is_empty());
+        assert!(inventory
+            .to_html()
+            .contains("<p>3 repositories use 1 third-party crates (2 versions).</p>"));
+    }
+}
@@ -0,0 +1,286 @@
+//! This module classifies the licenses of dependencies by how much they restrict
+//! the code linking against them (permissive, weak copyleft, strong copyleft),
+//! so that an analysis can report which workspace members are exposed to copyleft code,
+//! and which updates change the license of a dependency.
+//!
+//! Licenses are read as SPDX expressions, as used in Cargo manifests:
+//! `OR` (and the legacy `/`) lets the user pick the least restrictive alternative,
+//! `AND` imposes the most restrictive term and binds tighter than `OR`,
+//! and parentheses group sub-expressions.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::DependencyInfo;
+
+/// The class of a license (sorted from least to most restrictive).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum LicenseClass {
+    /// no obligation beyond attribution (MIT, Apache-2.0, BSD, ...)
+    Permissive,
+    /// modifications of the licensed files must be shared (MPL, LGPL, EPL, ...)
+    WeakCopyleft,
+    /// the whole program linking against the code must be shared (GPL, AGPL, ...)
+    StrongCopyleft,
+    /// the license is missing or not recognized
+    Unknown,
+}
+
+impl Default for LicenseClass {
+    fn default() -> Self {
+        LicenseClass::Unknown
+    }
+}
+
+impl LicenseClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LicenseClass::Permissive => "permissive",
+            LicenseClass::WeakCopyleft => "weak copyleft",
+            LicenseClass::StrongCopyleft => "strong copyleft",
+            LicenseClass::Unknown => "unknown",
+        }
+    }
+
+    pub fn is_copyleft(&self) -> bool {
+        matches!(
+            self,
+            LicenseClass::WeakCopyleft | LicenseClass::StrongCopyleft
+        )
+    }
+}
+
+/// SPDX identifiers of the common permissive licenses.
+const PERMISSIVE: &[&str] = &[
+    "0BSD",
+    "Apache-2.0",
+    "BSD-1-Clause",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "BSL-1.0",
+    "CC0-1.0",
+    "ISC",
+    "MIT",
+    "MIT-0",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "WTFPL",
+    "Zlib",
+];
+
+/// Prefixes of the SPDX identifiers of weak copyleft licenses.
+const WEAK_COPYLEFT: &[&str] = &["LGPL-", "MPL-", "EPL-", "CDDL-", "OSL-"];
+
+/// Prefixes of the SPDX identifiers of strong copyleft licenses.
+const STRONG_COPYLEFT: &[&str] = &["GPL-", "AGPL-", "EUPL-", "SSPL-"];
+
+/// Classifies a license expression (as found in the `license` field of a Cargo manifest).
+/// Malformed expressions are classified as unknown.
+pub fn classify(license: Option<&str>) -> LicenseClass {
+    let license = match license {
+        Some(license) if !license.trim().is_empty() => license,
+        _ => return LicenseClass::Unknown,
+    };
+
+    let license = license
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .replace('/', " OR ");
+    let mut tokens = license.split_whitespace().peekable();
+    match classify_or(&mut tokens) {
+        Some(class) if tokens.next().is_none() => class,
+        _ => LicenseClass::Unknown,
+    }
+}
+
+type Tokens<'a> = std::iter::Peekable<std::str::SplitWhitespace<'a>>;
+
+/// Parses alternatives (`OR` binds the loosest), and returns the least restrictive one.
+fn classify_or(tokens: &mut Tokens) -> Option<LicenseClass> {
+    let mut class = classify_and(tokens)?;
+    while tokens.peek() == Some(&"OR") {
+        tokens.next();
+        class = class.min(classify_and(tokens)?);
+    }
+    Some(class)
+}
+
+/// Parses conjunctions (`AND` binds tighter than `OR`), and returns the most restrictive term.
+fn classify_and(tokens: &mut Tokens) -> Option<LicenseClass> {
+    let mut class = classify_term(tokens)?;
+    while tokens.peek() == Some(&"AND") {
+        tokens.next();
+        class = class.max(classify_term(tokens)?);
+    }
+    Some(class)
+}
+
+/// Parses a parenthesized expression, or a single license
+/// possibly with an exception (e.g. `GPL-2.0 WITH Classpath-exception-2.0`).
+fn classify_term(tokens: &mut Tokens) -> Option<LicenseClass> {
+    match tokens.next()? {
+        "(" => {
+            let class = classify_or(tokens)?;
+            if tokens.next()? != ")" {
+                return None;
+            }
+            Some(class)
+        }
+        ")" | "OR" | "AND" | "WITH" => None,
+        id => {
+            let has_exception = tokens.peek() == Some(&"WITH");
+            if has_exception {
+                tokens.next();
+                tokens.next()?;
+            }
+            Some(classify_license(id.trim_end_matches('+'), has_exception))
+        }
+    }
+}
+
+/// Classifies a single license identifier.
+fn classify_license(id: &str, has_exception: bool) -> LicenseClass {
+    if PERMISSIVE
+        .iter()
+        .any(|permissive| permissive.eq_ignore_ascii_case(id))
+    {
+        LicenseClass::Permissive
+    } else if WEAK_COPYLEFT.iter().any(|prefix| id.starts_with(prefix)) {
+        LicenseClass::WeakCopyleft
+    } else if STRONG_COPYLEFT.iter().any(|prefix| id.starts_with(prefix)) {
+        // (linking exceptions, like the classpath exception, lift the obligation on the linking code)
+        if has_exception {
+            LicenseClass::WeakCopyleft
+        } else {
+            LicenseClass::StrongCopyleft
+        }
+    } else {
+        LicenseClass::Unknown
+    }
+}
+
+/// A change of license between the current and the latest version of a dependency.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LicenseChange {
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl LicenseChange {
+    /// Returns a change if the licenses differ.
+    pub fn new(old: Option<String>, new: Option<String>) -> Option<Self> {
+        if old == new {
+            None
+        } else {
+            Some(Self { old, new })
+        }
+    }
+
+    pub fn old_class(&self) -> LicenseClass {
+        classify(self.old.as_deref())
+    }
+
+    pub fn new_class(&self) -> LicenseClass {
+        classify(self.new.as_deref())
+    }
+
+    /// Does the new license impose more obligations than the old one?
+    pub fn is_more_restrictive(&self) -> bool {
+        self.new_class() > self.old_class()
+    }
+}
+
+/// The copyleft dependencies linked into a workspace member.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LicenseExposure {
+    /// the workspace member
+    pub member: String,
+    /// the most restrictive class of license linked into the member
+    pub class: LicenseClass,
+    /// the copyleft dependencies (`<name> <version> (<license>)`)
+    pub dependencies: Vec<String>,
+}
+
+/// Computes, for every workspace member, the copyleft dependencies it links against
+/// (dev-dependencies are not linked into the member, so they are ignored).
+pub fn exposure(dependencies: &[DependencyInfo]) -> Vec<LicenseExposure> {
+    let mut exposure: BTreeMap<&str, LicenseExposure> = BTreeMap::new();
+    for dependency in dependencies {
+        if !dependency.license_class.is_copyleft() {
+            continue;
+        }
+        for member in &dependency.linked_by {
+            let entry = exposure
+                .entry(member.as_str())
+                .or_insert_with(|| LicenseExposure {
+                    member: member.clone(),
+                    class: dependency.license_class,
+                    dependencies: Vec::new(),
+                });
+            entry.class = entry.class.max(dependency.license_class);
+            entry.dependencies.push(format!(
+                "{} {} ({})",
+                dependency.name,
+                dependency.version,
+                dependency.license.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+    exposure.into_iter().map(|(_, exposure)| exposure).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(Some("MIT")), LicenseClass::Permissive);
+        assert_eq!(
+            classify(Some("MIT OR Apache-2.0")),
+            LicenseClass::Permissive
+        );
+        assert_eq!(classify(Some("MIT/Apache-2.0")), LicenseClass::Permissive);
+        assert_eq!(classify(Some("GPL-3.0 OR MIT")), LicenseClass::Permissive);
+        assert_eq!(
+            classify(Some("(MIT OR Apache-2.0) AND MPL-2.0")),
+            LicenseClass::WeakCopyleft
+        );
+        assert_eq!(
+            classify(Some("(MIT OR Apache-2.0) AND GPL-3.0")),
+            LicenseClass::StrongCopyleft
+        );
+        assert_eq!(
+            classify(Some("MIT OR Apache-2.0 AND GPL-3.0")),
+            LicenseClass::Permissive
+        );
+        assert_eq!(classify(Some("(MIT OR Apache-2.0")), LicenseClass::Unknown);
+        assert_eq!(
+            classify(Some("LGPL-2.1-or-later")),
+            LicenseClass::WeakCopyleft
+        );
+        assert_eq!(classify(Some("GPL-3.0-only")), LicenseClass::StrongCopyleft);
+        assert_eq!(classify(Some("AGPL-3.0+")), LicenseClass::StrongCopyleft);
+        assert_eq!(
+            classify(Some("GPL-2.0 WITH Classpath-exception-2.0")),
+            LicenseClass::WeakCopyleft
+        );
+        assert_eq!(
+            classify(Some("LicenseRef-Proprietary")),
+            LicenseClass::Unknown
+        );
+        assert_eq!(classify(None), LicenseClass::Unknown);
+    }
+
+    #[test]
+    fn test_license_change() {
+        assert!(LicenseChange::new(Some("MIT".to_string()), Some("MIT".to_string())).is_none());
+        let change =
+            LicenseChange::new(Some("MIT".to_string()), Some("GPL-3.0".to_string())).unwrap();
+        assert!(change.is_more_restrictive());
+        let change =
+            LicenseChange::new(Some("GPL-3.0".to_string()), Some("MIT".to_string())).unwrap();
+        assert!(!change.is_more_restrictive());
+    }
+}
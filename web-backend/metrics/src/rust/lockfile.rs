@@ -0,0 +1,416 @@
+//! This module builds the list of dependencies of a workspace out of its `Cargo.lock` and manifests,
+//! without invoking cargo (see [`super::guppy::ResolveOptions::lockfile_only`]).
+//! Nothing of the analyzed repository gets built or executed (no `cargo metadata`, no build script),
+//! which makes it possible to analyze untrusted repositories (or ones that don't resolve on the analysis machine).
+//! The result is less precise than with guppy:
+//! the lockfile contains the dependencies of every platform and every feature,
+//! and doesn't record which features are enabled.
+
+use anyhow::{ensure, Context, Result};
+use guppy_summaries::SummarySource;
+use semver::Version;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use super::{crate_name::CrateName, lockfile_format};
+
+/// A dependency found in a lockfile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedDependency {
+    pub name: String,
+    pub version: Version,
+    pub source: SummarySource,
+    /// only reachable through the dev-dependencies of the workspace packages
+    pub dev: bool,
+    /// depended on by a workspace package
+    pub direct: bool,
+}
+
+/// The dependencies declared by the manifest of a workspace package (by package name).
+#[derive(Default, Debug)]
+pub(crate) struct ManifestDependencies {
+    /// normal and build dependencies
+    pub(crate) normal: HashSet<CrateName>,
+    pub(crate) dev: HashSet<CrateName>,
+    /// the dependencies inherited from the workspace (`dep = { workspace = true }`),
+    /// whose requirements are changed in the manifest of the workspace root
+    pub(crate) inherited: HashSet<CrateName>,
+}
+
+/// Obtains all dependencies (normal/build/dev and direct/transitive) of a workspace
+/// from its `Cargo.lock`, which must exist.
+pub fn get_dependencies(repo_dir: &Path) -> Result<Vec<LockedDependency>> {
+    let lockfile_path = repo_dir.join("Cargo.lock");
+    ensure!(
+        lockfile_path.exists(),
+        "no Cargo.lock in {:?} (it can't be generated without invoking cargo)",
+        repo_dir
+    );
+    info!("obtaining dependencies from {:?}", lockfile_path);
+    let lockfile = lockfile_format::load(&lockfile_path)
+        .with_context(|| format!("couldn't parse {:?}", lockfile_path))?;
+    let manifests = find_manifests(repo_dir);
+
+    // packages without a source are the workspace (or path) packages
+    let index: HashMap<(&str, &Version), usize> = lockfile
+        .packages
+        .iter()
+        .enumerate()
+        .map(|(idx, package)| ((package.name.as_str(), &package.version), idx))
+        .collect();
+    let is_local = |idx: usize| lockfile.packages[idx].source.is_none();
+
+    // the dependencies of the workspace packages, as roots
+    let mut direct = HashSet::new();
+    let mut normal_roots = Vec::new();
+    let mut dev_roots = Vec::new();
+    for package in lockfile.packages.iter().filter(|p| p.source.is_none()) {
+        let declared = manifests.get(&CrateName::from(package.name.as_str()));
+        for dependency in &package.dependencies {
+            let idx = match index.get(&(dependency.name.as_str(), &dependency.version)) {
+                Some(idx) => *idx,
+                None => continue,
+            };
+            if is_local(idx) {
+                continue;
+            }
+            direct.insert(idx);
+            // (without a manifest to tell, the dependency is assumed to be a normal one)
+            let name = CrateName::from(dependency.name.as_str());
+            let dev_only = declared.map_or(false, |declared| {
+                !declared.normal.contains(&name) && declared.dev.contains(&name)
+            });
+            if dev_only {
+                dev_roots.push(idx);
+            } else {
+                normal_roots.push(idx);
+            }
+        }
+    }
+
+    // everything reachable from the normal dependencies gets built in release,
+    // what is only reachable from the dev-dependencies doesn't
+    let reachable = |roots: Vec<usize>| -> HashSet<usize> {
+        let mut visited: HashSet<usize> = HashSet::new();
+        let mut queue: VecDeque<usize> = roots.into_iter().collect();
+        while let Some(idx) = queue.pop_front() {
+            if !visited.insert(idx) {
+                continue;
+            }
+            for dependency in &lockfile.packages[idx].dependencies {
+                if let Some(dep_idx) = index.get(&(dependency.name.as_str(), &dependency.version)) {
+                    if !is_local(*dep_idx) && !visited.contains(dep_idx) {
+                        queue.push_back(*dep_idx);
+                    }
+                }
+            }
+        }
+        visited
+    };
+    let no_dev = reachable(normal_roots.clone());
+    let mut all_roots = normal_roots;
+    all_roots.extend(dev_roots);
+    let mut all: Vec<usize> = reachable(all_roots).into_iter().collect();
+    all.sort_unstable();
+
+    let dependencies = all
+        .into_iter()
+        .map(|idx| {
+            let package = &lockfile.packages[idx];
+            let source = match &package.source {
+                Some(source) if source.is_default_registry() => SummarySource::CratesIo,
+                Some(source) => SummarySource::External {
+                    source: lockfile_format::normalize_source(&source.to_string()),
+                },
+                None => unreachable!("local packages are not traversed"),
+            };
+            LockedDependency {
+                name: package.name.as_str().to_string(),
+                version: package.version.clone(),
+                source,
+                dev: !no_dev.contains(&idx),
+                direct: direct.contains(&idx),
+            }
+        })
+        .collect();
+    Ok(dependencies)
+}
+
+/// Finds the manifests of the packages of a repository (by package name).
+fn find_manifests(repo_dir: &Path) -> HashMap<CrateName, ManifestDependencies> {
+    local_packages(repo_dir)
+        .into_iter()
+        .map(|package| (package.name, package.dependencies))
+        .collect()
+}
+
+/// A package of a repository (a workspace member, or a path dependency).
+#[derive(Debug)]
+pub(crate) struct LocalPackage {
+    pub(crate) name: CrateName,
+    /// the path of its manifest
+    pub(crate) manifest_path: PathBuf,
+    pub(crate) dependencies: ManifestDependencies,
+    /// the manifest of the workspace root declaring the inherited dependencies, if any
+    pub(crate) workspace_manifest: Option<PathBuf>,
+}
+
+/// Finds the packages of a repository by reading their manifests,
+/// skipping build outputs and hidden directories.
+/// The dependencies inherited from the workspace (`dep = { workspace = true }`) are resolved
+/// with the `[workspace.dependencies]` of the closest workspace root.
+pub(crate) fn local_packages(repo_dir: &Path) -> Vec<LocalPackage> {
+    let manifests = read_manifests(repo_dir);
+
+    // the dependencies declared by the workspace roots, by manifest
+    let workspaces: Vec<(&PathBuf, &toml::value::Table)> = manifests
+        .iter()
+        .filter_map(|(path, manifest)| {
+            let dependencies = manifest
+                .get("workspace")
+                .and_then(|workspace| workspace.get("dependencies"))
+                .and_then(toml::Value::as_table)?;
+            Some((path, dependencies))
+        })
+        .collect();
+
+    let mut packages = Vec::new();
+    for (path, manifest) in &manifests {
+        let name = manifest
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(|name| name.as_str());
+        let name = match name {
+            Some(name) => name,
+            None => continue,
+        };
+        // (the closest root is the deepest one containing the package)
+        let workspace = workspaces
+            .iter()
+            .filter(|(root, _)| root.parent().map_or(false, |dir| path.starts_with(dir)))
+            .max_by_key(|(root, _)| root.components().count());
+        let dependencies =
+            manifest_dependencies(manifest, workspace.map(|(_, dependencies)| *dependencies));
+        packages.push(LocalPackage {
+            name: name.into(),
+            workspace_manifest: workspace
+                .filter(|_| !dependencies.inherited.is_empty())
+                .map(|(root, _)| (*root).clone()),
+            dependencies,
+            manifest_path: path.clone(),
+        });
+    }
+    packages
+}
+
+/// Reads the manifests of a repository, skipping build outputs and hidden directories.
+pub(crate) fn read_manifests(repo_dir: &Path) -> Vec<(PathBuf, toml::Value)> {
+    let mut manifests = Vec::new();
+    let mut dirs = vec![repo_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() {
+                if file_name != "target" && !file_name.starts_with('.') {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            if file_name != "Cargo.toml" {
+                continue;
+            }
+            let manifest = match fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| toml::from_str::<toml::Value>(&content).map_err(Into::into))
+            {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    debug!("couldn't parse {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            manifests.push((path, manifest));
+        }
+    }
+    manifests
+}
+
+/// Lists the dependencies declared by a manifest, including the platform-specific ones,
+/// by package name (a dependency can be renamed with `package = "..."`),
+/// given the dependencies of the workspace the inherited ones come from.
+fn manifest_dependencies(
+    manifest: &toml::Value,
+    inherited: Option<&toml::value::Table>,
+) -> ManifestDependencies {
+    let mut tables = vec![manifest];
+    if let Some(targets) = manifest.get("target").and_then(|t| t.as_table()) {
+        tables.extend(targets.values());
+    }
+
+    let mut dependencies = ManifestDependencies::default();
+    for table in tables {
+        for (section, dev) in &[
+            ("dependencies", false),
+            ("build-dependencies", false),
+            ("dev-dependencies", true),
+        ] {
+            let section = match table.get(*section).and_then(|s| s.as_table()) {
+                Some(section) => section,
+                None => continue,
+            };
+            for (key, value) in section {
+                // (an inherited dependency is declared by the workspace, which can rename it)
+                let is_inherited =
+                    value.get("workspace").and_then(toml::Value::as_bool) == Some(true);
+                let value = if is_inherited {
+                    inherited
+                        .and_then(|inherited| inherited.get(key))
+                        .unwrap_or(value)
+                } else {
+                    value
+                };
+                let name = value
+                    .get("package")
+                    .and_then(|package| package.as_str())
+                    .unwrap_or(key);
+                let name = CrateName::from(name);
+                if is_inherited {
+                    dependencies.inherited.insert(name.clone());
+                }
+                if *dev {
+                    dependencies.dev.insert(name);
+                } else {
+                    dependencies.normal.insert(name);
+                }
+            }
+        }
+    }
+    dependencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const REGISTRY: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    #[test]
+    fn test_lockfile_dependencies() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+a = "1"
+
+[target.'cfg(unix)'.dependencies]
+d = { package = "delta", version = "1" }
+
+[dev-dependencies]
+b = "1"
+"#,
+        )
+        .unwrap();
+        let package = |name: &str, dependencies: &[&str]| {
+            let dependencies: Vec<String> = dependencies
+                .iter()
+                .map(|dep| format!(" \"{} 1.0.0 ({})\",\n", dep, REGISTRY))
+                .collect();
+            format!(
+                "[[package]]\nname = \"{}\"\nversion = \"1.0.0\"\nsource = \"{}\"\ndependencies = [\n{}]\n\n",
+                name,
+                REGISTRY,
+                dependencies.concat()
+            )
+        };
+        let lockfile = format!(
+            "[[package]]\nname = \"app\"\nversion = \"0.1.0\"\ndependencies = [\n \"a 1.0.0 ({registry})\",\n \"b 1.0.0 ({registry})\",\n \"delta 1.0.0 ({registry})\",\n]\n\n{}{}{}{}{}",
+            package("a", &["c"]),
+            package("b", &["c", "e"]),
+            package("c", &[]),
+            package("delta", &[]),
+            package("e", &[]),
+            registry = REGISTRY,
+        );
+        fs::write(dir.path().join("Cargo.lock"), lockfile).unwrap();
+
+        let dependencies = get_dependencies(dir.path()).unwrap();
+        let get = |name: &str| {
+            dependencies
+                .iter()
+                .find(|dependency| dependency.name == name)
+                .map(|dependency| (dependency.dev, dependency.direct))
+                .unwrap()
+        };
+        assert_eq!(dependencies.len(), 5);
+        assert!(dependencies
+            .iter()
+            .all(|dependency| dependency.source == SummarySource::CratesIo));
+        assert_eq!(get("a"), (false, true));
+        assert_eq!(get("b"), (true, true));
+        assert_eq!(get("c"), (false, false));
+        assert_eq!(get("delta"), (false, true));
+        assert_eq!(get("e"), (true, false));
+
+        // no lockfile, no analysis
+        let dir = tempdir().unwrap();
+        assert!(get_dependencies(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_workspace_inheritance() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            r#"
+[workspace]
+members = ["app"]
+
+[workspace.dependencies]
+d = { package = "delta", version = "1" }
+b = "1"
+"#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("app")).unwrap();
+        fs::write(
+            dir.path().join("app/Cargo.toml"),
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+d = { workspace = true, features = ["std"] }
+
+[dev-dependencies]
+b.workspace = true
+"#,
+        )
+        .unwrap();
+
+        let packages = local_packages(dir.path());
+        assert_eq!(packages.len(), 1);
+        let dependencies = &packages[0].dependencies;
+        assert!(dependencies.normal.contains(&CrateName::from("delta")));
+        assert!(!dependencies.normal.contains(&CrateName::from("d")));
+        assert!(dependencies.dev.contains(&CrateName::from("b")));
+        assert_eq!(dependencies.inherited.len(), 2);
+        assert_eq!(
+            packages[0].workspace_manifest,
+            Some(dir.path().join("Cargo.toml"))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! This module reads `Cargo.lock` files whatever the version of their format,
+//! and every feature reading a lockfile goes through it (instead of parsing with [`Lockfile`] directly).
+//! The formats only differ in how they encode sources and checksums:
+//!
+//! - v1 lists the checksums in a `[metadata]` table,
+//! - v2 moves them to the packages (and has no `version` marker),
+//! - v3 (`version = 3`) records `branch = "master"` git dependencies explicitly,
+//! - v4 (`version = 4`) percent-encodes the query of git sources (e.g. `?branch=feat%2Fx`).
+//!
+//! The parser we use predates the `version` marker, so it is stripped before parsing,
+//! and the git sources are normalized (see [`normalize_source`]) so that migrating a lockfile to a newer format
+//! doesn't look like a change of source.
+//! A lockfile in a format newer than the ones known is parsed as the latest known one (with a warning),
+//! as new formats so far kept the same structure.
+
+use anyhow::{Context, Result};
+use rustsec::lockfile::Lockfile;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+/// The latest version of the format of `Cargo.lock` known.
+pub const LATEST_KNOWN_VERSION: u32 = 4;
+
+/// Returns the version of the format of a lockfile, as declared by its `version` marker
+/// (none before v3, which has no marker).
+pub fn format_version(content: &str) -> Option<u32> {
+    content
+        .lines()
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .find_map(version_marker)
+}
+
+/// Returns the version of a `version = N` line (package versions are quoted strings, not numbers).
+fn version_marker(line: &str) -> Option<u32> {
+    let mut parts = line.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) if key.trim() == "version" => value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses the content of a lockfile of any format.
+pub fn parse(content: &str) -> Result<Lockfile> {
+    let version = format_version(content);
+    if let Some(version) = version.filter(|version| *version > LATEST_KNOWN_VERSION) {
+        warn!(
+            "Cargo.lock format version {} is newer than the latest known ({}), parsing it as such",
+            version, LATEST_KNOWN_VERSION
+        );
+    }
+    let mut in_header = true;
+    let stripped: Vec<&str> = content
+        .lines()
+        .filter(|line| {
+            in_header &= !line.trim_start().starts_with('[');
+            !(in_header && version_marker(line).is_some())
+        })
+        .collect();
+    stripped.join("\n").parse().with_context(|| match version {
+        Some(version) => format!("couldn't parse a Cargo.lock (format version {})", version),
+        None => "couldn't parse a Cargo.lock".to_string(),
+    })
+}
+
+/// Reads and parses a lockfile of any format.
+pub fn load(path: &Path) -> Result<Lockfile> {
+    let content = fs::read_to_string(path).with_context(|| format!("couldn't read {:?}", path))?;
+    parse(&content).with_context(|| format!("couldn't parse {:?}", path))
+}
+
+/// Normalizes the source of a locked package, decoding the percent-encoded query of the git sources of v4.
+pub fn normalize_source(source: &str) -> String {
+    let (start, end) = match (source.find('?'), source.find('#')) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        (Some(start), None) => (start, source.len()),
+        _ => return source.to_string(),
+    };
+    let query = source[start..end].as_bytes();
+    let mut decoded = Vec::with_capacity(query.len());
+    let mut idx = 0;
+    while idx < query.len() {
+        let escaped = query
+            .get(idx + 1..idx + 3)
+            .filter(|_| query[idx] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            None => {
+                decoded.push(query[idx]);
+                idx += 1;
+            }
+        }
+    }
+    format!(
+        "{}{}{}",
+        &source[..start],
+        String::from_utf8_lossy(&decoded),
+        &source[end..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGES: &str = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "serde",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.123"
+source = "git+https://github.com/serde-rs/serde?branch=feat%2Fderive#0123456789abcdef0123456789abcdef01234567"
+"#;
+
+    #[test]
+    fn test_parse() {
+        for marker in &["", "version = 3\n", "version = 4\n", "version = 42\n"] {
+            let content = format!(
+                "# This file is automatically @generated by Cargo.\n# It is not intended for manual editing.\n{}{}",
+                marker, PACKAGES
+            );
+            let lockfile = parse(&content).unwrap();
+            assert_eq!(lockfile.packages.len(), 2);
+            assert_eq!(lockfile.packages[1].version.to_string(), "1.0.123");
+        }
+        assert_eq!(format_version(PACKAGES), None);
+        assert_eq!(
+            format_version(&format!("version = 4\n{}", PACKAGES)),
+            Some(4)
+        );
+        assert!(parse("[[package]]\nname = 3").is_err());
+    }
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(
+            normalize_source("git+https://github.com/acme/lib?branch=feat%2Fx#0123abc"),
+            "git+https://github.com/acme/lib?branch=feat/x#0123abc"
+        );
+        assert_eq!(
+            normalize_source("git+https://github.com/acme/lib?branch=feat/x#0123abc"),
+            "git+https://github.com/acme/lib?branch=feat/x#0123abc"
+        );
+        assert_eq!(
+            normalize_source("registry+https://github.com/rust-lang/crates.io-index"),
+            "registry+https://github.com/rust-lang/crates.io-index"
+        );
+        assert_eq!(
+            normalize_source("git+https://a/b?rev=100%"),
+            "git+https://a/b?rev=100%"
+        );
+    }
+}
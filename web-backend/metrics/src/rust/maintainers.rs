@@ -0,0 +1,210 @@
+//! This module looks for changes of maintainers between the current and the latest version of a crate.
+//! New owners, or a release published by someone who isn't an owner (or who never published before),
+//! are the classic precursors of a hostile takeover of a crate.
+//! So is a release cut by someone who only started publishing the crate recently.
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+/// the number of days after which a publisher is no longer considered new to a crate
+pub const NEW_PUBLISHER_DAYS: i64 = 90;
+
+/// Who published the current and the latest version of a crate, and what changed.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct MaintainerChange {
+    /// the crates.io user who published the current version
+    pub old_publisher: Option<String>,
+    /// the crates.io user who published the latest version
+    pub new_publisher: Option<String>,
+    /// the new publisher never published any version up to the current one
+    pub first_time_publisher: bool,
+    /// the new publisher is not an owner of the crate
+    pub publisher_not_owner: bool,
+    /// the owners added since the previous analysis
+    pub new_owners: Vec<String>,
+    /// when the new publisher published this crate for the first time
+    #[serde(default)]
+    pub publisher_since: Option<String>,
+    /// the number of days between the first release and the latest version by the new publisher
+    #[serde(default)]
+    pub publisher_tenure_days: Option<i64>,
+}
+
+impl MaintainerChange {
+    /// Compares the publisher of the latest version with the publishers of the previous versions
+    /// (up to the current one).
+    pub fn new(
+        old_publisher: Option<String>,
+        new_publisher: Option<String>,
+        previous_publishers: &[String],
+    ) -> Self {
+        // (old versions don't record their publisher, in which case nothing can be said)
+        let first_time_publisher = match &new_publisher {
+            Some(new_publisher) if !previous_publishers.is_empty() => {
+                !previous_publishers.contains(new_publisher)
+            }
+            _ => false,
+        };
+        Self {
+            old_publisher,
+            new_publisher,
+            first_time_publisher,
+            ..Default::default()
+        }
+    }
+
+    /// Records how long the new publisher has been publishing the crate,
+    /// given the release dates of all the versions with their publishers, and the release date of the latest version.
+    pub fn check_tenure(&mut self, releases: &[(String, String)], released_at: Option<&str>) {
+        let new_publisher = match &self.new_publisher {
+            Some(new_publisher) => new_publisher,
+            None => return,
+        };
+        let first_release = releases
+            .iter()
+            .filter(|(publisher, _)| publisher == new_publisher)
+            .filter_map(|(_, date)| DateTime::parse_from_rfc3339(date).ok().map(|d| (d, date)))
+            .min_by_key(|(datetime, _)| *datetime);
+        let (since, since_str) = match first_release {
+            Some(first_release) => first_release,
+            None => return,
+        };
+        self.publisher_since = Some(since_str.clone());
+        self.publisher_tenure_days = released_at
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .map(|released_at| (released_at - since).num_days());
+    }
+
+    /// Compares the publisher with the current owners of the crate,
+    /// and the owners with the ones seen by the previous analysis (if any).
+    pub fn check_owners(&mut self, owners: &[String], previous_owners: &[String]) {
+        if owners.is_empty() {
+            return;
+        }
+        self.publisher_not_owner = match &self.new_publisher {
+            Some(new_publisher) => !owners.contains(new_publisher),
+            None => false,
+        };
+        if !previous_owners.is_empty() {
+            self.new_owners = owners
+                .iter()
+                .filter(|owner| !previous_owners.contains(owner))
+                .cloned()
+                .collect();
+        }
+    }
+
+    /// Returns the reasons to take a closer look at the maintainers of the update.
+    pub fn reasons(&self) -> Vec<String> {
+        let publisher = self.new_publisher.as_deref().unwrap_or_default();
+        let mut reasons = Vec::new();
+        if self.first_time_publisher {
+            reasons.push(format!(
+                "published by {}, who never published this crate before",
+                publisher
+            ));
+        }
+        // (a first-time publisher is already flagged)
+        match (self.publisher_tenure_days, &self.publisher_since) {
+            (Some(days), Some(since)) if days < NEW_PUBLISHER_DAYS && !self.first_time_publisher => {
+                reasons.push(format!(
+                    "published by {}, who has only been publishing this crate since {} ({} days before the release)",
+                    publisher,
+                    since.get(..10).unwrap_or(since),
+                    days
+                ))
+            }
+            _ => (),
+        }
+        if self.publisher_not_owner {
+            reasons.push(format!(
+                "published by {}, who is not an owner of the crate",
+                publisher
+            ));
+        }
+        if !self.new_owners.is_empty() {
+            reasons.push(format!("new owners: {}", self.new_owners.join(", ")));
+        }
+        reasons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintainer_change() {
+        let owners = vec!["alice".to_string(), "github:org:team".to_string()];
+
+        // same publisher, same owners
+        let mut change = MaintainerChange::new(
+            Some("alice".to_string()),
+            Some("alice".to_string()),
+            &["alice".to_string()],
+        );
+        change.check_owners(&owners, &owners);
+        assert!(change.reasons().is_empty());
+
+        // takeover: a new owner publishes
+        let mut change = MaintainerChange::new(
+            Some("alice".to_string()),
+            Some("mallory".to_string()),
+            &["alice".to_string()],
+        );
+        let mut new_owners = owners.clone();
+        new_owners.push("mallory".to_string());
+        change.check_owners(&new_owners, &owners);
+        assert!(change.first_time_publisher);
+        assert!(!change.publisher_not_owner);
+        assert_eq!(change.new_owners, vec!["mallory".to_string()]);
+        assert_eq!(change.reasons().len(), 2);
+
+        // publisher removed from the owners, no previous analysis
+        let mut change = MaintainerChange::new(None, Some("bob".to_string()), &[]);
+        change.check_owners(&owners, &[]);
+        assert!(change.publisher_not_owner);
+        assert!(change.new_owners.is_empty());
+    }
+
+    #[test]
+    fn test_check_tenure() {
+        let releases = vec![
+            (
+                "alice".to_string(),
+                "2019-01-01T10:00:00.000000+00:00".to_string(),
+            ),
+            (
+                "bob".to_string(),
+                "2021-01-01T10:00:00.000000+00:00".to_string(),
+            ),
+            (
+                "bob".to_string(),
+                "2021-01-31T10:00:00.000000+00:00".to_string(),
+            ),
+        ];
+        let released_at = Some("2021-01-31T10:00:00.000000+00:00");
+
+        // bob published a first version a month before the release
+        let mut change = MaintainerChange::new(
+            Some("bob".to_string()),
+            Some("bob".to_string()),
+            &["bob".to_string()],
+        );
+        change.check_tenure(&releases, released_at);
+        assert_eq!(change.publisher_tenure_days, Some(30));
+        assert_eq!(
+            change.reasons(),
+            vec!["published by bob, who has only been publishing this crate since 2021-01-01 (30 days before the release)"]
+        );
+
+        // alice has been around for years
+        let mut change = MaintainerChange::new(
+            Some("alice".to_string()),
+            Some("alice".to_string()),
+            &["alice".to_string()],
+        );
+        change.check_tenure(&releases, released_at);
+        assert!(change.reasons().is_empty());
+    }
+}
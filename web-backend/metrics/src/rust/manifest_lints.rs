@@ -0,0 +1,469 @@
+//! This module lints the dependency declarations of the manifests of a repository
+//! that make the build depend on when it happens:
+//! wildcard requirements (`dep = "*"`), git dependencies following a branch instead of a commit,
+//! and a crate required differently by several manifests of the workspace.
+//! Every finding comes with a replacement declaration, using the versions and commits resolved in `Cargo.lock`,
+//! and a patch of the manifest that can be reviewed and applied with `git apply`.
+//! The manifests are only read, nothing of the repository gets executed.
+
+use rustsec::lockfile::Lockfile;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+use super::{lockfile, lockfile_format};
+
+/// The kinds of findings.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintKind {
+    /// any version of the crate is accepted
+    Wildcard,
+    /// a git dependency following a branch (or a tag) instead of a commit (`rev`)
+    UnpinnedGit,
+    /// the crate is required differently (but compatibly) by other manifests of the workspace
+    DivergentRequirement,
+}
+
+/// A dependency declaration to fix.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestLint {
+    /// the manifest, relative to the repository (e.g. `app/Cargo.toml`)
+    pub manifest: String,
+    /// the table of the declaration (e.g. `dependencies`, `target.cfg(unix).dev-dependencies`)
+    pub section: String,
+    /// the key of the declaration (the crate name, unless it is renamed)
+    pub dependency: String,
+    pub kind: LintKind,
+    /// the declaration found (e.g. `serde = "*"`)
+    pub found: String,
+    /// the suggested declaration (none if `Cargo.lock` doesn't tell what to use)
+    pub suggested: Option<String>,
+    /// the patch of the manifest replacing the declaration
+    /// (none if the declaration doesn't fit on a line, e.g. a `[dependencies.serde]` table)
+    pub patch: Option<String>,
+}
+
+impl ManifestLint {
+    /// Describes the finding (e.g. `app/Cargo.toml: serde in [dependencies] accepts any version`).
+    pub fn describe(&self) -> String {
+        let problem = match self.kind {
+            LintKind::Wildcard => "accepts any version",
+            LintKind::UnpinnedGit => "follows a git branch instead of a commit",
+            LintKind::DivergentRequirement => {
+                "is required differently by other manifests of the workspace"
+            }
+        };
+        format!(
+            "{}: {} in [{}] {}",
+            self.manifest, self.dependency, self.section, problem
+        )
+    }
+}
+
+/// What `Cargo.lock` resolved: the versions of the packages, and the commits of the git ones.
+#[derive(Default, Debug)]
+struct Locked {
+    versions: HashMap<String, Vec<Version>>,
+    commits: HashMap<String, String>,
+}
+
+impl Locked {
+    fn new(lockfile: &Lockfile) -> Self {
+        let mut locked = Self::default();
+        for package in &lockfile.packages {
+            let name = package.name.as_str().to_string();
+            locked
+                .versions
+                .entry(name.clone())
+                .or_default()
+                .push(package.version.clone());
+            // (a git source is locked as `git+<url>?branch=<branch>#<commit>`)
+            let source = package
+                .source
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            if let (true, Some(index)) = (source.starts_with("git+"), source.rfind('#')) {
+                locked.commits.insert(name, source[index + 1..].to_string());
+            }
+        }
+        locked
+    }
+
+    fn latest(&self, name: &str) -> Option<&Version> {
+        self.versions.get(name)?.iter().max()
+    }
+}
+
+/// A dependency declaration of a manifest.
+pub(super) struct Declaration<'a> {
+    pub(super) manifest: String,
+    content: &'a str,
+    pub(super) section: String,
+    key: &'a str,
+    value: &'a toml::Value,
+}
+
+impl Declaration<'_> {
+    /// The name of the package (a dependency can be renamed with `package = "..."`).
+    pub(super) fn name(&self) -> &str {
+        self.value
+            .get("package")
+            .and_then(toml::Value::as_str)
+            .unwrap_or(self.key)
+    }
+
+    pub(super) fn requirement(&self) -> Option<&str> {
+        match self.value {
+            toml::Value::String(requirement) => Some(requirement.as_str()),
+            value => value.get("version").and_then(toml::Value::as_str),
+        }
+    }
+
+    fn lint(&self, kind: LintKind, suggested: Option<toml::Value>) -> ManifestLint {
+        let suggested = suggested.map(|value| format!("{} = {}", self.key, inline(&value)));
+        ManifestLint {
+            manifest: self.manifest.clone(),
+            section: self.section.clone(),
+            dependency: self.key.to_string(),
+            kind,
+            found: format!("{} = {}", self.key, inline(self.value)),
+            patch: suggested.as_ref().and_then(|suggested| {
+                patch(
+                    &self.manifest,
+                    self.content,
+                    &self.section,
+                    self.key,
+                    suggested,
+                )
+            }),
+            suggested,
+        }
+    }
+
+    /// The declaration with another version requirement.
+    fn with_requirement(&self, requirement: &str) -> toml::Value {
+        match self.value {
+            toml::Value::Table(table) => {
+                let mut table = table.clone();
+                table.insert("version".into(), requirement.into());
+                toml::Value::Table(table)
+            }
+            _ => requirement.into(),
+        }
+    }
+}
+
+/// Lints the manifests of a repository.
+pub fn lint(repo_dir: &Path) -> Vec<ManifestLint> {
+    let locked = match lockfile_format::load(&repo_dir.join("Cargo.lock")) {
+        Ok(lockfile) => Locked::new(&lockfile),
+        Err(e) => {
+            debug!("no suggestion from Cargo.lock: {}", e);
+            Locked::default()
+        }
+    };
+
+    let manifests = read_manifests(repo_dir);
+    let declarations: Vec<Declaration> = manifests
+        .iter()
+        .flat_map(|(path, content, manifest)| declarations(path, content, manifest))
+        .collect();
+
+    let mut lints = lint_declarations(&declarations, &locked);
+    lints.sort_by(|a, b| {
+        (&a.manifest, &a.section, &a.dependency).cmp(&(&b.manifest, &b.section, &b.dependency))
+    });
+    lints
+}
+
+/// Reads the manifests of a repository: their path (relative to the repository), content and value.
+pub(super) fn read_manifests(repo_dir: &Path) -> Vec<(String, String, toml::Value)> {
+    lockfile::read_manifests(repo_dir)
+        .into_iter()
+        .filter_map(|(path, manifest)| {
+            let content = fs::read_to_string(&path).ok()?;
+            let path = path
+                .strip_prefix(repo_dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            Some((path, content, manifest))
+        })
+        .collect()
+}
+
+/// Lists the dependency declarations of a manifest,
+/// skipping the ones inherited from the workspace (they are declared, and linted, in the workspace root).
+pub(super) fn declarations<'a>(
+    manifest_path: &str,
+    content: &'a str,
+    manifest: &'a toml::Value,
+) -> Vec<Declaration<'a>> {
+    let mut tables = vec![(String::new(), manifest)];
+    if let Some(workspace) = manifest.get("workspace") {
+        tables.push(("workspace.".to_string(), workspace));
+    }
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        for (target, table) in targets {
+            tables.push((format!("target.{}.", target), table));
+        }
+    }
+
+    let mut declarations = Vec::new();
+    for (prefix, table) in tables {
+        for section in &["dependencies", "build-dependencies", "dev-dependencies"] {
+            let dependencies = match table.get(*section).and_then(toml::Value::as_table) {
+                Some(dependencies) => dependencies,
+                None => continue,
+            };
+            for (key, value) in dependencies {
+                if value.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+                    continue;
+                }
+                declarations.push(Declaration {
+                    manifest: manifest_path.to_string(),
+                    content,
+                    section: format!("{}{}", prefix, section),
+                    key,
+                    value,
+                });
+            }
+        }
+    }
+    declarations
+}
+
+fn lint_declarations(declarations: &[Declaration], locked: &Locked) -> Vec<ManifestLint> {
+    let mut lints = Vec::new();
+
+    // the requirements of the same package, by semver-compatible range
+    let mut requirements: BTreeMap<(&str, String), Vec<(&Declaration, Version)>> = BTreeMap::new();
+
+    for declaration in declarations {
+        let name = declaration.name();
+        let requirement = declaration.requirement();
+
+        if requirement.map(str::trim) == Some("*") {
+            let suggested = locked
+                .latest(name)
+                .map(|version| declaration.with_requirement(&version.to_string()));
+            lints.push(declaration.lint(LintKind::Wildcard, suggested));
+            continue;
+        }
+
+        if let toml::Value::Table(table) = declaration.value {
+            if table.contains_key("git") && !table.contains_key("rev") {
+                let suggested = locked.commits.get(name).map(|commit| {
+                    let mut table = table.clone();
+                    table.remove("branch");
+                    table.remove("tag");
+                    table.insert("rev".into(), commit.as_str().into());
+                    toml::Value::Table(table)
+                });
+                lints.push(declaration.lint(LintKind::UnpinnedGit, suggested));
+                continue;
+            }
+        }
+
+        if let Some(lower_bound) = requirement.and_then(lower_bound) {
+            let compatible = if lower_bound.major > 0 {
+                lower_bound.major.to_string()
+            } else {
+                format!("0.{}", lower_bound.minor)
+            };
+            requirements
+                .entry((name, compatible))
+                .or_default()
+                .push((declaration, lower_bound));
+        }
+    }
+
+    // the divergent requirements are aligned on the highest one
+    for declarations in requirements.values() {
+        let highest = match declarations.iter().max_by(|a, b| a.1.cmp(&b.1)) {
+            Some((declaration, _)) => declaration.requirement().unwrap_or_default(),
+            None => continue,
+        };
+        for (declaration, _) in declarations {
+            if declaration.requirement() != Some(highest) {
+                let suggested = declaration.with_requirement(highest);
+                lints.push(declaration.lint(LintKind::DivergentRequirement, Some(suggested)));
+            }
+        }
+    }
+    lints
+}
+
+/// The lowest version accepted by a requirement (e.g. `1.2` for `^1.2`, `>=1.2, <2`).
+fn lower_bound(requirement: &str) -> Option<Version> {
+    let first = requirement.split(',').next()?.trim();
+    let version = first.trim_start_matches(|c: char| matches!(c, '^' | '~' | '=' | '>' | ' '));
+    if first.starts_with('<') || version.contains('*') {
+        return None;
+    }
+    let mut parts: Vec<&str> = version.split('.').collect();
+    while parts.len() < 3 {
+        parts.push("0");
+    }
+    Version::parse(&parts.join(".")).ok()
+}
+
+/// Formats a value as it is written on a line of a manifest (tables are inline).
+fn inline(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Table(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, inline(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+/// Produces the patch replacing the line declaring a dependency in a manifest (with a line of context).
+fn patch(
+    manifest: &str,
+    content: &str,
+    section: &str,
+    key: &str,
+    replacement: &str,
+) -> Option<String> {
+    let normalize = |header: &str| -> String {
+        header
+            .chars()
+            .filter(|c| !matches!(c, '\'' | '"' | ' '))
+            .collect()
+    };
+    let section = normalize(section);
+
+    let lines: Vec<&str> = content.lines().collect();
+    let mut current = String::new();
+    let mut found = None;
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            current = normalize(trimmed.trim_matches(|c| c == '[' || c == ']'));
+            continue;
+        }
+        let declared_key = trimmed.splitn(2, '=').next().unwrap_or_default().trim();
+        if current == section && trimmed.contains('=') && declared_key.trim_matches('"') == key {
+            found = Some(index);
+            break;
+        }
+    }
+    let index = found?;
+
+    let start = index.saturating_sub(1);
+    let end = (index + 2).min(lines.len());
+    let mut hunk = Vec::new();
+    for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+        if i == index {
+            hunk.push(format!("-{}", line));
+            hunk.push(format!("+{}", replacement));
+        } else {
+            hunk.push(format!(" {}", line));
+        }
+    }
+    Some(format!(
+        "--- a/{manifest}\n+++ b/{manifest}\n@@ -{line},{count} +{line},{count} @@\n{hunk}\n",
+        manifest = manifest,
+        line = start + 1,
+        count = end - start,
+        hunk = hunk.join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lint() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+serde = "*"
+log = "0.4.8"
+fork = { git = "https://github.com/acme/fork", branch = "main" }
+
+[workspace]
+members = ["lib"]
+"#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("lib")).unwrap();
+        fs::write(
+            dir.path().join("lib/Cargo.toml"),
+            r#"[package]
+name = "lib"
+version = "0.1.0"
+
+[dependencies]
+log = { version = "0.4.14", features = ["std"] }
+rand = "0.8"
+
+[dev-dependencies]
+rand = "0.7"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("Cargo.lock"),
+            r#"[[package]]
+name = "serde"
+version = "1.0.123"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let lints = lint(dir.path());
+        // (rand 0.7 and 0.8 are not compatible, they are not divergent)
+        assert_eq!(lints.len(), 3);
+
+        assert_eq!(lints[0].kind, LintKind::UnpinnedGit);
+        assert_eq!(lints[0].dependency, "fork");
+        // no commit in Cargo.lock, nothing to suggest
+        assert_eq!(lints[0].suggested, None);
+
+        assert_eq!(lints[1].kind, LintKind::DivergentRequirement);
+        assert_eq!(lints[1].dependency, "log");
+        assert_eq!(lints[1].suggested.as_deref(), Some(r#"log = "0.4.14""#));
+        assert_eq!(
+            lints[1].patch.as_deref(),
+            Some(
+                "--- a/Cargo.toml\n+++ b/Cargo.toml\n@@ -6,3 +6,3 @@\n serde = \"*\"\n-log = \"0.4.8\"\n+log = \"0.4.14\"\n fork = { git = \"https://github.com/acme/fork\", branch = \"main\" }\n"
+            )
+        );
+
+        assert_eq!(lints[2].kind, LintKind::Wildcard);
+        assert_eq!(
+            lints[2].describe(),
+            "Cargo.toml: serde in [dependencies] accepts any version"
+        );
+        assert_eq!(lints[2].suggested.as_deref(), Some(r#"serde = "1.0.123""#));
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        let version = |version: &str| Some(Version::parse(version).unwrap());
+        assert_eq!(lower_bound("1.2"), version("1.2.0"));
+        assert_eq!(lower_bound("^0.4.8"), version("0.4.8"));
+        assert_eq!(lower_bound(">= 1.2, < 2"), version("1.2.0"));
+        assert_eq!(lower_bound("<2"), None);
+        assert_eq!(lower_bound("1.*"), None);
+    }
+}
@@ -9,29 +9,120 @@
 //! so this might not matter...
 //!
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use futures::{stream, StreamExt};
-use guppy_summaries::{PackageStatus, SummarySource};
+use guppy_summaries::{PackageStatus, SummaryId, SummarySource};
 use rustsec::{report::WarningInfo, Vulnerability, Warning};
-use semver::Version;
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use tracing::{error, info};
 
 //
 // Modules
 //
 
+pub mod backfill;
+pub mod batch;
+pub mod blame;
+pub mod build_time;
 pub mod cargoaudit;
 pub mod cargoguppy;
 pub mod cargotree;
+pub mod churn;
+pub mod codeowners;
+pub mod compare;
+pub mod conflicts;
+pub mod crate_name;
 pub mod cratesio;
 pub mod diff;
+pub mod discovery;
+pub mod docsrs;
+pub mod estimate;
+pub mod fix_check;
+pub mod fixtures;
+pub mod fuzzing;
+pub mod geiger;
+pub mod graph;
 pub mod guppy;
+pub mod inventory;
+pub mod licenses;
+pub mod lockfile;
+pub mod lockfile_format;
+pub mod maintainers;
+pub mod manifest_lints;
+pub mod monitor;
+pub mod namespaces;
+pub mod platforms;
+pub mod prefetch;
+pub mod prepublish;
+pub mod proc_macros;
+pub mod provenance;
+pub mod publisher_trust;
+pub mod reachability;
+pub mod report;
+pub mod review_diff;
+pub mod reviewers;
+pub mod risky_paths;
+pub mod scope;
+pub mod secrets;
+pub mod self_review;
+pub mod slimming;
+pub mod statistics;
+pub mod tags;
+pub mod theme;
+pub mod toolchain;
+pub mod unsafety;
+pub mod verdict;
+pub mod versions;
+pub mod watch;
 
-use crate::common::dependabot::{self, UpdateMetadata};
+use self::guppy::{PackageGraph, ResolveOptions};
+use crate::common::{
+    dependabot::{self, UpdateMetadata},
+    github::{self, SecurityPolicy},
+    github_auth, github_graphql,
+};
+use crate::deadline::Deadline;
+use crate::model::RepoConfig;
+use crate::sources::Source;
+use blame::Introduction;
+use build_time::{BuildTimeChange, BuildTimeSurface};
 use cargoguppy::CargoGuppy;
+use churn::Churn;
+use codeowners::CodeOwners;
+use conflicts::VersionConflict;
+use crate_name::CrateName;
+use discovery::{DiscoveryMethod, Links};
+use geiger::UnsafeDelta;
+use graph::Link;
+use licenses::{LicenseChange, LicenseClass, LicenseExposure};
+use maintainers::MaintainerChange;
+use manifest_lints::ManifestLint;
+use monitor::Revision;
+use namespaces::Namespace;
+use proc_macros::ProcMacro;
+use provenance::{RepositoryChange, RepositoryRedirect};
+use reachability::{AdvisoryReachability, FunctionMatch};
+use risky_paths::{RiskyFile, RiskyPaths};
+use scope::{Scope, ScopeOptions};
+use secrets::SecretFinding;
+use slimming::Slimming;
+use statistics::Statistics;
+use tags::TagOptions;
+use unsafety::UnsafeChange;
+
+/// The number of concurrent requests made to external services (crates.io, GitHub, etc.)
+const CONCURRENCY: usize = 10;
+
+/// Returns the cargo home directory (`$CARGO_HOME`, or `~/.cargo` by default).
+pub(crate) fn cargo_home() -> Option<PathBuf> {
+    match std::env::var_os("CARGO_HOME") {
+        Some(cargo_home) => Some(PathBuf::from(cargo_home)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")),
+    }
+}
 
 //
 // Structures
@@ -40,6 +131,10 @@ use cargoguppy::CargoGuppy;
 /// RustAnalysis contains the result of the analysis of a rust workspace
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct RustAnalysis {
+    /// Aggregate statistics of the analysis (first, for the readers of the JSON output)
+    #[serde(default)]
+    statistics: Statistics,
+
     /// Note that we do not use a map because the same dependency can be seen several times.
     /// This is due to different versions being used or/and being used directly and indirectly (transitively).
     dependencies: Vec<DependencyInfo>,
@@ -49,6 +144,70 @@ pub struct RustAnalysis {
 
     /// A summary of the changes since last analysis
     change_summary: Option<ChangeSummary>,
+
+    /// The options used to resolve the dependency graph
+    #[serde(default)]
+    resolve_options: ResolveOptions,
+
+    /// The dependencies that were analyzed
+    #[serde(default)]
+    scope: ScopeOptions,
+
+    /// The tags of the dependencies, and the review rules of each tag (see [`tags`])
+    #[serde(default)]
+    tags: TagOptions,
+
+    /// Set if the analysis was stopped before completion (deadline expired, or cancelled),
+    /// in which case some results are partial.
+    #[serde(default)]
+    truncated: bool,
+
+    /// The steps that did not complete before the deadline
+    #[serde(default)]
+    skipped_steps: Vec<String>,
+
+    /// The external data sources that were disabled (see [`crate::sources`]),
+    /// the results depending on them are missing
+    #[serde(default)]
+    disabled_sources: Vec<Source>,
+
+    /// Set if the analysis only used local data (see [`crate::sources::offline`])
+    #[serde(default)]
+    offline: bool,
+
+    /// The workspace members linking against copyleft dependencies
+    #[serde(default)]
+    license_exposure: Vec<LicenseExposure>,
+
+    /// The analyses skipped because they would have run code of the analyzed repository
+    /// or of its dependencies (see [`crate::sources::sandbox`])
+    #[serde(default)]
+    skipped_for_safety: Vec<String>,
+
+    /// Whether the workspace references the functions affected by the RUSTSEC vulnerabilities
+    /// (only with [`RepoConfig::reachability`])
+    #[serde(default)]
+    reachability: Vec<AdvisoryReachability>,
+
+    /// The links between the packages of the dependency graph (only with guppy, see [`graph`])
+    #[serde(default)]
+    links: Vec<Link>,
+
+    /// The code running at build time (see [`build_time`])
+    #[serde(default)]
+    build_time: BuildTimeSurface,
+
+    /// The dependencies grouped by upstream organization or publisher, the largest first (see [`namespaces`])
+    #[serde(default)]
+    namespaces: Vec<Namespace>,
+
+    /// The dependency declarations of the manifests to fix (see [`manifest_lints`])
+    #[serde(default)]
+    manifest_lints: Vec<ManifestLint>,
+
+    /// The crates used in conflicting versions or sources, or required in versions not locked (see [`conflicts`])
+    #[serde(default)]
+    version_conflicts: Vec<VersionConflict>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
@@ -73,6 +232,102 @@ pub struct DependencyInfo {
     direct: bool,
     /// An optional update available for the dependency.
     update: Option<Update>,
+    /// Is it an internal fork of a crate? (see [`RepoConfig::forked_crates`])
+    #[serde(default)]
+    forked: bool,
+    /// The repository declared on crates.io.
+    #[serde(default)]
+    repository: Option<String>,
+    /// How the repository was found, when the crate doesn't declare one (see [`discovery`]).
+    #[serde(default)]
+    repository_discovery: Option<DiscoveryMethod>,
+    /// Is it published by a trusted publisher? (see [`RepoConfig::trusted_publishers`])
+    #[serde(default)]
+    trusted: bool,
+    /// All the RUSTSEC advisories ever published for the dependency
+    /// (not only the ones affecting the current version).
+    #[serde(default)]
+    advisory_history: Vec<AdvisoryRecord>,
+    /// The direct dependencies that pull this transitive dependency in.
+    /// Updating one of them is likely to bump this dependency as well.
+    #[serde(default)]
+    introduced_by: Vec<String>,
+    /// The features of the dependency that are enabled in the build.
+    #[serde(default)]
+    features: Vec<String>,
+    /// The platforms (cfg expressions or target triples) the dependency is compiled for,
+    /// empty if it is compiled for every platform.
+    #[serde(default)]
+    platforms: Vec<String>,
+    /// The license of the dependency (an SPDX expression, as declared in its manifest).
+    #[serde(default)]
+    license: Option<String>,
+    /// The class of the license (permissive, weak or strong copyleft).
+    #[serde(default)]
+    license_class: LicenseClass,
+    /// The workspace members that link against the dependency
+    /// (i.e. that depend on it, directly or transitively, other than through dev-dependencies).
+    #[serde(default)]
+    linked_by: Vec<String>,
+    /// The crates.io owners (logins of users and teams),
+    /// only fetched for dependencies with an update and for the ones running at build time.
+    #[serde(default)]
+    owners: Vec<String>,
+    /// The commit that introduced the dependency in the repository
+    /// (only available with the full history, see [`RepoConfig::full_history`]).
+    #[serde(default)]
+    introduced: Option<Introduction>,
+    /// The owners (as declared in `CODEOWNERS`) of the workspace members using the dependency.
+    #[serde(default)]
+    code_owners: Vec<String>,
+    /// The library and binary targets of the workspace that include the dependency
+    /// (e.g. `service-a (bin)`), i.e. the artifacts to rebuild and release when it is updated.
+    #[serde(default)]
+    targets: Vec<String>,
+    /// How vulnerabilities are handled by the GitHub repository of the dependency
+    /// (only fetched for dependencies hosted on GitHub).
+    #[serde(default)]
+    security_policy: Option<SecurityPolicy>,
+    /// The cargo-fuzz targets in the GitHub repository of the dependency (see [`fuzzing`]).
+    #[serde(default)]
+    fuzz_targets: Option<Vec<String>>,
+    /// The GitHub repository that the declared one redirects to, if it was renamed or transferred.
+    #[serde(default)]
+    repository_redirect: Option<RepositoryRedirect>,
+    /// What declaring the (direct) dependency with `default-features = false` would save,
+    /// if its default features pull in crates (see [`slimming`]).
+    #[serde(default)]
+    slimming: Option<Slimming>,
+    /// Is it a procedural macro (executed at build time, see [`proc_macros`])?
+    #[serde(default)]
+    proc_macro: bool,
+    /// Does it have a build script?
+    #[serde(default)]
+    build_script: bool,
+    /// Does its code run at build time (build script, proc macro, or compiled into one, see [`build_time`])?
+    #[serde(default)]
+    build_time: bool,
+    /// The tags given to the dependency by the configuration (see [`RepoConfig::tags`]).
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// AdvisoryRecord describes a RUSTSEC advisory published for a dependency in the past,
+/// which helps evaluating how a crate has been dealing with security issues.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct AdvisoryRecord {
+    /// The RUSTSEC identifier (e.g. `RUSTSEC-2020-0001`).
+    id: String,
+    /// The date of the advisory.
+    date: String,
+    /// The versions that are not affected by the advisory.
+    patched: Vec<String>,
+    /// The versions that were never affected by the advisory (e.g. released before the bug).
+    #[serde(default)]
+    unaffected: Vec<String>,
+    /// The number of days it took for a fix to be released on crates.io
+    /// (none if no fix was released).
+    days_to_fix: Option<i64>,
 }
 
 /// Update should contain any interesting information (red flags, etc.) about the changes observed in the new version
@@ -85,6 +340,461 @@ pub struct Update {
     update_metadata: UpdateMetadata,
     /// build.rs changed
     build_rs: bool,
+    /// new toolchain requirements (rust-version, build dependencies, nightly features)
+    #[serde(default)]
+    toolchain_changes: Vec<String>,
+    /// sizes of the crates.io packages of the current and the latest versions
+    #[serde(default)]
+    size_change: Option<SizeChange>,
+    /// possible secrets and blobs added by the new version, which need a human to take a look
+    #[serde(default)]
+    secrets: Vec<SecretFinding>,
+    /// files changed that match the risky path patterns of the repository
+    /// (see [`RepoConfig::risky_paths`])
+    #[serde(default)]
+    risky_files: Vec<RiskyFile>,
+    /// changed rust files that contain unsafe code, and whether the unsafe code itself changed
+    #[serde(default)]
+    unsafe_changes: Vec<UnsafeChange>,
+    /// the unsafe usages counted by cargo-geiger in the current and the new versions (see [`geiger::unsafe_delta`])
+    #[serde(default)]
+    unsafe_delta: Option<UnsafeDelta>,
+    /// lines changed by the update, with vendored and generated code reported apart
+    #[serde(default)]
+    churn: Option<Churn>,
+    /// the license declared by the latest version, if it differs from the current one
+    #[serde(default)]
+    license_change: Option<LicenseChange>,
+    /// the repository declared by the manifest changed (the project moved, or was hijacked)
+    #[serde(default)]
+    repository_change: Option<RepositoryChange>,
+    /// who published the current and the latest versions, and changes of owners
+    #[serde(default)]
+    maintainer_change: Option<MaintainerChange>,
+    /// the functions affected by advisories on the dependency, matched against the update and the workspace
+    #[serde(default)]
+    advisory_functions: Vec<FunctionMatch>,
+    /// how the new version grows the code running at build time (see [`build_time`])
+    #[serde(default)]
+    build_time_change: Option<BuildTimeChange>,
+}
+
+/// SizeChange compares the crates.io packages of two versions of a crate.
+/// A sudden jump in size (e.g. a bundled binary or a vendored C library) is worth a look.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct SizeChange {
+    old: PackageSize,
+    new: PackageSize,
+}
+
+/// The size of a crates.io package.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct PackageSize {
+    /// size of the compressed package (as published on crates.io), in bytes
+    compressed: Option<u64>,
+    /// size of the extracted package, in bytes
+    uncompressed: u64,
+    /// number of files in the package
+    files: usize,
+}
+
+//
+// Accessors
+//
+
+impl RustAnalysis {
+    pub fn dependencies(&self) -> &[DependencyInfo] {
+        &self.dependencies
+    }
+
+    pub fn rustsec(&self) -> &RustSec {
+        &self.rustsec
+    }
+
+    pub fn change_summary(&self) -> Option<&ChangeSummary> {
+        self.change_summary.as_ref()
+    }
+
+    pub fn disabled_sources(&self) -> &[Source] {
+        &self.disabled_sources
+    }
+
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn license_exposure(&self) -> &[LicenseExposure] {
+        &self.license_exposure
+    }
+
+    pub fn reachability(&self) -> &[AdvisoryReachability] {
+        &self.reachability
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// The procedural macros of the dependency graph (see [`proc_macros`]).
+    pub fn proc_macros(&self) -> Vec<ProcMacro> {
+        proc_macros::census(&self.dependencies, &self.links)
+    }
+
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    pub fn build_time(&self) -> &BuildTimeSurface {
+        &self.build_time
+    }
+
+    pub fn namespaces(&self) -> &[Namespace] {
+        &self.namespaces
+    }
+
+    pub fn manifest_lints(&self) -> &[ManifestLint] {
+        &self.manifest_lints
+    }
+
+    pub fn version_conflicts(&self) -> &[VersionConflict] {
+        &self.version_conflicts
+    }
+
+    pub fn skipped_for_safety(&self) -> &[String] {
+        &self.skipped_for_safety
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl RustSec {
+    pub fn vulnerabilities(&self) -> &[Vulnerability] {
+        &self.vulnerabilities
+    }
+
+    pub fn warnings(&self) -> &WarningInfo {
+        &self.warnings
+    }
+}
+
+impl DependencyInfo {
+    /// A dependency as fetched, before any other step of the analysis.
+    fn new(name: String, version: Version, repo: SummarySource, dev: bool, direct: bool) -> Self {
+        Self {
+            name,
+            version,
+            repo,
+            update: None,
+            dev,
+            direct,
+            forked: false,
+            repository: None,
+            repository_discovery: None,
+            trusted: false,
+            advisory_history: Vec::new(),
+            introduced_by: Vec::new(),
+            features: Vec::new(),
+            platforms: Vec::new(),
+            license: None,
+            license_class: LicenseClass::Unknown,
+            linked_by: Vec::new(),
+            owners: Vec::new(),
+            introduced: None,
+            code_owners: Vec::new(),
+            targets: Vec::new(),
+            security_policy: None,
+            fuzz_targets: None,
+            repository_redirect: None,
+            slimming: None,
+            proc_macro: false,
+            build_script: false,
+            build_time: false,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Where the dependency comes from (crates.io, another registry, a git repository).
+    pub fn repo(&self) -> &SummarySource {
+        &self.repo
+    }
+
+    /// Identifies the dependency by its name, version and source, like the graph helpers of [`guppy`].
+    pub fn summary_id(&self) -> SummaryId {
+        SummaryId::new(self.name.clone(), self.version.clone(), self.repo.clone())
+    }
+
+    pub fn dev(&self) -> bool {
+        self.dev
+    }
+
+    pub fn direct(&self) -> bool {
+        self.direct
+    }
+
+    pub fn forked(&self) -> bool {
+        self.forked
+    }
+
+    pub fn update(&self) -> Option<&Update> {
+        self.update.as_ref()
+    }
+
+    pub fn advisory_history(&self) -> &[AdvisoryRecord] {
+        &self.advisory_history
+    }
+
+    pub fn introduced_by(&self) -> &[String] {
+        &self.introduced_by
+    }
+
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    pub fn platforms(&self) -> &[String] {
+        &self.platforms
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    pub fn license_class(&self) -> LicenseClass {
+        self.license_class
+    }
+
+    pub fn linked_by(&self) -> &[String] {
+        &self.linked_by
+    }
+
+    pub fn owners(&self) -> &[String] {
+        &self.owners
+    }
+
+    pub fn introduced(&self) -> Option<&Introduction> {
+        self.introduced.as_ref()
+    }
+
+    pub fn code_owners(&self) -> &[String] {
+        &self.code_owners
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn targets(&self) -> &[String] {
+        &self.targets
+    }
+
+    pub fn security_policy(&self) -> Option<&SecurityPolicy> {
+        self.security_policy.as_ref()
+    }
+
+    pub fn fuzz_targets(&self) -> Option<&[String]> {
+        self.fuzz_targets.as_deref()
+    }
+
+    pub fn repository_redirect(&self) -> Option<&RepositoryRedirect> {
+        self.repository_redirect.as_ref()
+    }
+
+    pub fn repository(&self) -> Option<&str> {
+        self.repository.as_deref()
+    }
+
+    pub fn repository_discovery(&self) -> Option<DiscoveryMethod> {
+        self.repository_discovery
+    }
+
+    pub fn slimming(&self) -> Option<&Slimming> {
+        self.slimming.as_ref()
+    }
+
+    pub fn proc_macro(&self) -> bool {
+        self.proc_macro
+    }
+
+    pub fn build_script(&self) -> bool {
+        self.build_script
+    }
+
+    pub fn build_time(&self) -> bool {
+        self.build_time
+    }
+}
+
+impl AdvisoryRecord {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn patched(&self) -> &[String] {
+        &self.patched
+    }
+
+    pub fn unaffected(&self) -> &[String] {
+        &self.unaffected
+    }
+
+    /// The patched and unaffected version requirements (the affected versions are the others).
+    fn requirements(&self) -> (Vec<VersionReq>, Vec<VersionReq>) {
+        let parse = |reqs: &[String]| {
+            reqs.iter()
+                .filter_map(|req| VersionReq::parse(req).ok())
+                .collect()
+        };
+        (parse(&self.patched), parse(&self.unaffected))
+    }
+
+    /// Does the advisory affect a version?
+    pub fn affects(&self, version: &Version) -> bool {
+        let (patched, unaffected) = self.requirements();
+        cargoaudit::is_affected(&patched, &unaffected, version)
+    }
+
+    /// Returns the first of the (sorted) versions given that is not affected by the advisory
+    /// (e.g. the versions of an [`Update`]), which is the smallest update fixing it.
+    pub fn first_fixed_version<'a>(&self, versions: &'a [Version]) -> Option<&'a Version> {
+        let (patched, unaffected) = self.requirements();
+        cargoaudit::first_fixed_version(&patched, &unaffected, versions)
+    }
+
+    pub fn days_to_fix(&self) -> Option<i64> {
+        self.days_to_fix
+    }
+}
+
+impl Update {
+    /// the versions available, sorted (the last one is the latest)
+    pub fn versions(&self) -> &[Version] {
+        &self.versions
+    }
+
+    /// the changelog and commits between the current and the latest versions
+    pub fn update_metadata(&self) -> &UpdateMetadata {
+        &self.update_metadata
+    }
+
+    pub fn build_rs(&self) -> bool {
+        self.build_rs
+    }
+
+    pub fn build_time_change(&self) -> Option<&BuildTimeChange> {
+        self.build_time_change.as_ref()
+    }
+
+    pub fn toolchain_changes(&self) -> &[String] {
+        &self.toolchain_changes
+    }
+
+    pub fn size_change(&self) -> Option<&SizeChange> {
+        self.size_change.as_ref()
+    }
+
+    pub fn secrets(&self) -> &[SecretFinding] {
+        &self.secrets
+    }
+
+    pub fn risky_files(&self) -> &[RiskyFile] {
+        &self.risky_files
+    }
+
+    pub fn unsafe_changes(&self) -> &[UnsafeChange] {
+        &self.unsafe_changes
+    }
+
+    pub fn unsafe_delta(&self) -> Option<&UnsafeDelta> {
+        self.unsafe_delta.as_ref()
+    }
+
+    pub fn churn(&self) -> Option<&Churn> {
+        self.churn.as_ref()
+    }
+
+    pub fn license_change(&self) -> Option<&LicenseChange> {
+        self.license_change.as_ref()
+    }
+
+    pub fn repository_change(&self) -> Option<&RepositoryChange> {
+        self.repository_change.as_ref()
+    }
+
+    pub fn maintainer_change(&self) -> Option<&MaintainerChange> {
+        self.maintainer_change.as_ref()
+    }
+
+    pub fn advisory_functions(&self) -> &[FunctionMatch] {
+        &self.advisory_functions
+    }
+}
+
+impl Update {
+    /// Records the signals obtained by diffing the current and the new versions
+    /// (the functions affected by advisories are matched by the caller).
+    fn apply_diff(&mut self, crate_diff: diff::CrateDiff) {
+        self.build_rs = crate_diff.build_rs;
+        self.toolchain_changes = crate_diff.toolchain_changes;
+        self.build_time_change = crate_diff.build_time_change;
+        // (the compressed sizes were obtained from crates.io)
+        let size_change = self.size_change.get_or_insert_with(Default::default);
+        let (old_size, new_size) = crate_diff.sizes;
+        size_change.old.uncompressed = old_size.0;
+        size_change.old.files = old_size.1;
+        size_change.new.uncompressed = new_size.0;
+        size_change.new.files = new_size.1;
+        self.secrets = crate_diff.secrets;
+        self.risky_files = crate_diff.risky_files;
+        self.unsafe_changes = crate_diff.unsafe_changes;
+        self.unsafe_delta = crate_diff.unsafe_delta;
+        self.churn = Some(crate_diff.churn);
+        self.repository_change = crate_diff.repository_change;
+    }
+}
+
+impl SizeChange {
+    /// the new package is at least this many times bigger than the old one...
+    const JUMP_RATIO: u64 = 2;
+    /// ...and grew by at least this many bytes
+    const JUMP_BYTES: u64 = 512 * 1024;
+
+    pub fn old_size(&self) -> &PackageSize {
+        &self.old
+    }
+
+    pub fn new_size(&self) -> &PackageSize {
+        &self.new
+    }
+
+    /// Returns true if the package suddenly got much bigger.
+    pub fn is_jump(&self) -> bool {
+        let (old, new) = (self.old.uncompressed, self.new.uncompressed);
+        new >= old * Self::JUMP_RATIO && new - old >= Self::JUMP_BYTES
+    }
+}
+
+impl PackageSize {
+    pub fn compressed(&self) -> Option<u64> {
+        self.compressed
+    }
+
+    pub fn uncompressed(&self) -> u64 {
+        self.uncompressed
+    }
+
+    pub fn files(&self) -> usize {
+        self.files
+    }
 }
 
 //
@@ -98,22 +808,93 @@ impl RustAnalysis {
         repo_dir: &Path,
         previous_analysis: Option<&Self>,
         is_diem: bool,
+        config: &RepoConfig,
+        deadline: &Deadline,
     ) -> Result<Self> {
         // 1. fetch & filter
+        // (this step is mandatory, there is nothing to report without it)
         info!("1. fetching dependencies...");
-        let mut rust_analysis = Self::fetch(repo_dir, is_diem).await?;
+        let (mut rust_analysis, package_graph) =
+            Self::fetch(repo_dir, is_diem, &config.resolve_options).await?;
+        let package_graph = package_graph.as_ref();
+        rust_analysis.disabled_sources = Source::disabled();
+        rust_analysis.offline = crate::sources::offline();
+        if crate::sources::sandbox() {
+            rust_analysis.skipped_for_safety = crate::sources::SANDBOX_SKIPPED
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+        }
+        rust_analysis.mark_forks(&config.forked_crates);
+        rust_analysis.mark_tags(&config.tags);
+        rust_analysis.restrict(package_graph, &config.scope)?;
+        rust_analysis.attribute(package_graph);
+        rust_analysis.licenses(package_graph);
+        rust_analysis.targets(package_graph);
+        rust_analysis.resolve_links(package_graph);
+        rust_analysis.check_default_features(repo_dir, package_graph);
+        rust_analysis.mark_proc_macros(package_graph);
+        rust_analysis.mark_build_time(package_graph);
+        rust_analysis.platforms(repo_dir).await;
+        rust_analysis.blame(repo_dir).await;
+        rust_analysis.ownership(repo_dir);
+        rust_analysis.manifest_lints = manifest_lints::lint(repo_dir);
+        rust_analysis.version_conflicts = conflicts::detect(&rust_analysis.dependencies);
+        rust_analysis
+            .version_conflicts
+            .extend(conflicts::unmatched_requirements(repo_dir));
+
+        // the following steps are stopped if the deadline expires,
+        // in which case the analysis is marked as truncated
 
         // 2. updatable
         info!("3. checking for updates...");
-        rust_analysis.updatable().await?;
+        match deadline.run(rust_analysis.updatable()).await {
+            Some(res) => res?,
+            None => rust_analysis.truncate("updatable"),
+        };
+        match deadline.run(rust_analysis.advisory_history()).await {
+            Some(res) => res?,
+            None => rust_analysis.truncate("advisory_history"),
+        };
+        if deadline
+            .run(rust_analysis.maintainers(previous_analysis))
+            .await
+            .is_none()
+        {
+            rust_analysis.truncate("maintainers");
+        }
+        if deadline
+            .run(rust_analysis.security_policies())
+            .await
+            .is_none()
+        {
+            rust_analysis.truncate("security_policies");
+        }
+        if deadline.run(rust_analysis.redirects()).await.is_none() {
+            rust_analysis.truncate("redirects");
+        }
+        rust_analysis.trust(&config.trusted_crates, &config.trusted_publishers);
 
         // 3. priority
         info!("4. priority engine running...");
-        rust_analysis.priority(repo_dir).await?;
+        match deadline.run(rust_analysis.priority(repo_dir)).await {
+            Some(res) => res?,
+            None => rust_analysis.truncate("priority"),
+        };
+        if config.reachability {
+            rust_analysis.check_reachability(repo_dir);
+        }
 
         // 4. risk
         info!("5. risk engine running...");
-        rust_analysis.risk().await?;
+        let risky_paths = RiskyPaths::new(&config.risky_paths)?;
+        match deadline.run(rust_analysis.risk(&risky_paths)).await {
+            Some(res) => res?,
+            None => rust_analysis.truncate("risk"),
+        };
+        rust_analysis.provenance(&config.trusted_crates, &config.trusted_publishers);
+        rust_analysis.summarize();
 
         // 5. summary of changes since last analysis
         if let Some(old) = previous_analysis {
@@ -125,19 +906,71 @@ impl RustAnalysis {
         Ok(rust_analysis)
     }
 
+    /// Records that a step of the analysis could not complete before the deadline.
+    fn truncate(&mut self, step: &str) {
+        error!("deadline expired during the {} step", step);
+        self.truncated = true;
+        self.skipped_steps.push(step.to_string());
+    }
+
     /// 1. fetch & filter
     /// - filters out internal workspace packages
     /// - might have the same dependency several times but with different version, or as a dev dependency or not (dev), or imported directly or transitively (direct), or with a different repository (repo)
     /// - we filter out duplicates that have the same dependency/version/dev/direct/repo tuple, which happens when the same dependency is imported in different places with different features (in other words, we don't care about features)
-    async fn fetch(repo_dir: &Path, is_diem: bool) -> Result<RustAnalysis> {
+    /// - the graph is resolved according to the [`ResolveOptions`] (target, features) given
+    /// - or, with [`ResolveOptions::lockfile_only`] (or in sandbox mode), read from `Cargo.lock` without invoking cargo
+    ///
+    /// The graph built with guppy is returned too, for the next steps to work on it
+    /// (it is not available in lockfile-only mode, nor for diem).
+    async fn fetch(
+        repo_dir: &Path,
+        is_diem: bool,
+        resolve_options: &ResolveOptions,
+    ) -> Result<(RustAnalysis, Option<PackageGraph>)> {
+        // (reading the manifests, cargo metadata and the resolution are blocking,
+        // they must not hold up the threads of the runtime)
+        let blocking_dir = repo_dir.to_path_buf();
+
+        // 0. without invoking cargo, the dependencies are read from the lockfile
+        // (which is always the case in sandbox mode)
+        let mut resolve_options = resolve_options.clone();
+        resolve_options.lockfile_only |= crate::sources::sandbox();
+        let resolve_options = &resolve_options;
+        if resolve_options.lockfile_only {
+            info!("parsing Cargo.lock...");
+            let locked =
+                tokio::task::spawn_blocking(move || lockfile::get_dependencies(&blocking_dir))
+                    .await??;
+            let dependencies = locked
+                .into_iter()
+                .map(|locked| {
+                    DependencyInfo::new(
+                        locked.name,
+                        locked.version,
+                        locked.source,
+                        locked.dev,
+                        locked.direct,
+                    )
+                })
+                .collect();
+            return Ok((Self::with_dependencies(dependencies, resolve_options), None));
+        }
+
         // 1. this will produce a json file containing no dev dependencies
         // (only transitive dependencies used in release)
         info!("parsing Cargo.toml with guppy...");
-        let manifest_path = repo_dir.join("Cargo.toml");
-        let (no_dev_summary, all_summary) = if is_diem {
-            CargoGuppy::fetch(repo_dir).await?
+        let (package_graph, (no_dev_summary, all_summary)) = if is_diem {
+            (None, CargoGuppy::fetch(repo_dir).await?)
         } else {
-            guppy::get_guppy_summaries(&manifest_path)?
+            let manifest_path = blocking_dir.join("Cargo.toml");
+            let options = resolve_options.clone();
+            let (package_graph, summaries) = tokio::task::spawn_blocking(move || -> Result<_> {
+                let package_graph = guppy::package_graph(&manifest_path)?;
+                let summaries = guppy::summaries(&package_graph, &manifest_path, &options)?;
+                Ok((package_graph, summaries))
+            })
+            .await??;
+            (Some(package_graph), summaries)
         };
 
         info!("filter result...");
@@ -172,34 +1005,338 @@ impl RustAnalysis {
             let direct = matches!(package_info.status, PackageStatus::Direct);
 
             // insert
-            dependencies.push(DependencyInfo {
-                name: summary_id.name.clone(),
-                version: summary_id.version.clone(),
-                repo: summary_id.source.clone(),
-                update: None,
+            let mut dependency = DependencyInfo::new(
+                summary_id.name.clone(),
+                summary_id.version.clone(),
+                summary_id.source.clone(),
                 dev,
                 direct,
-            });
+            );
+            dependency.features = package_info.features.iter().cloned().collect();
+            dependencies.push(dependency);
         }
 
+        Ok((
+            Self::with_dependencies(dependencies, resolve_options),
+            package_graph,
+        ))
+    }
+
+    /// Sorts and deduplicates the fetched dependencies into a new analysis.
+    fn with_dependencies(
+        mut dependencies: Vec<DependencyInfo>,
+        resolve_options: &ResolveOptions,
+    ) -> Self {
         // sort
         info!("sorting dependencies");
         dependencies.sort_by_cached_key(|d| (d.name.clone(), d.version.clone(), d.dev, d.direct));
 
         // remove duplicates of tuples (name, version, repo, dev, direct)
+        // (a package built for both the target and the host can have different features, which we merge)
         info!("removing duplicates");
-        dependencies.dedup();
+        dependencies.dedup_by(|duplicate, dependency| {
+            let same = duplicate.name == dependency.name
+                && duplicate.version == dependency.version
+                && duplicate.repo == dependency.repo
+                && duplicate.dev == dependency.dev
+                && duplicate.direct == dependency.direct;
+            if same {
+                for feature in duplicate.features.drain(..) {
+                    if !dependency.features.contains(&feature) {
+                        dependency.features.push(feature);
+                    }
+                }
+                dependency.features.sort();
+            }
+            same
+        });
+
+        //
+        Self {
+            statistics: Statistics::default(),
+            dependencies,
+            rustsec: RustSec::default(),
+            change_summary: None,
+            resolve_options: resolve_options.clone(),
+            scope: ScopeOptions::default(),
+            tags: TagOptions::default(),
+            truncated: false,
+            skipped_steps: Vec::new(),
+            disabled_sources: Vec::new(),
+            offline: false,
+            license_exposure: Vec::new(),
+            skipped_for_safety: Vec::new(),
+            reachability: Vec::new(),
+            links: Vec::new(),
+            build_time: BuildTimeSurface::default(),
+            namespaces: Vec::new(),
+            manifest_lints: Vec::new(),
+            version_conflicts: Vec::new(),
+        }
+    }
+
+    /// Removes the dependencies that are not in the scope of the analysis.
+    fn restrict(
+        &mut self,
+        package_graph: Option<&PackageGraph>,
+        scope_options: &ScopeOptions,
+    ) -> Result<()> {
+        let scope = Scope::new(scope_options)?;
+        self.scope = scope_options.clone();
+
+        // the depth is only available with guppy
+        let depths = match package_graph {
+            Some(package_graph) if scope.needs_depth() => guppy::depths(package_graph),
+            _ => HashMap::new(),
+        };
+
+        self.dependencies.retain(|dependency| {
+            let depth = depths.get(&dependency.summary_id()).copied();
+            scope.contains(&dependency.name, dependency.direct, depth)
+        });
+
+        Ok(())
+    }
+
+    /// Records which direct dependencies pull each transitive dependency in,
+    /// so that transitive updates can be reviewed together with the direct update causing them.
+    /// (This is only available with guppy.)
+    fn attribute(&mut self, package_graph: Option<&PackageGraph>) {
+        let introducers = match package_graph {
+            Some(package_graph) => guppy::introducers(package_graph),
+            None => return,
+        };
+        for dependency in &mut self.dependencies {
+            if dependency.direct {
+                continue;
+            }
+            let key = dependency.summary_id();
+            if let Some(introduced_by) = introducers.get(&key) {
+                dependency.introduced_by = introduced_by.iter().cloned().collect();
+            }
+        }
+    }
+
+    /// Records the license of every dependency and the workspace members linking against it,
+    /// to report which members are exposed to copyleft code.
+    /// (This is only available with guppy.)
+    fn licenses(&mut self, package_graph: Option<&PackageGraph>) {
+        let package_licenses = match package_graph {
+            Some(package_graph) => guppy::licenses(package_graph),
+            None => return,
+        };
+        for dependency in &mut self.dependencies {
+            let key = dependency.summary_id();
+            if let Some((license, linked_by)) = package_licenses.get(&key) {
+                dependency.license = license.clone();
+                dependency.license_class = licenses::classify(license.as_deref());
+                dependency.linked_by = linked_by.iter().cloned().collect();
+            }
+        }
+        self.license_exposure = licenses::exposure(&self.dependencies);
+    }
+
+    /// Records the workspace targets including every dependency
+    /// (the targets of the members linking against it, see [`Self::licenses`]).
+    /// (This is only available with guppy.)
+    fn targets(&mut self, package_graph: Option<&PackageGraph>) {
+        let targets = match package_graph {
+            Some(package_graph) => guppy::targets(package_graph),
+            None => return,
+        };
+        for dependency in &mut self.dependencies {
+            let dependency_targets: BTreeSet<&String> = dependency
+                .linked_by
+                .iter()
+                .filter_map(|member| targets.get(member))
+                .flatten()
+                .collect();
+            dependency.targets = dependency_targets.into_iter().cloned().collect();
+        }
+    }
+
+    /// Records the links between the packages of the dependency graph (see [`graph`]).
+    /// (This is only available with guppy.)
+    fn resolve_links(&mut self, package_graph: Option<&PackageGraph>) {
+        if let Some(package_graph) = package_graph {
+            self.links = guppy::links(package_graph);
+        }
+    }
+
+    /// Marks the dependencies that are procedural macros (see [`proc_macros`]).
+    /// (This is only available with guppy.)
+    fn mark_proc_macros(&mut self, package_graph: Option<&PackageGraph>) {
+        let proc_macros = match package_graph {
+            Some(package_graph) => guppy::proc_macros(package_graph),
+            None => return,
+        };
+        for dependency in &mut self.dependencies {
+            let key = dependency.summary_id();
+            dependency.proc_macro = proc_macros.contains(&key);
+        }
+    }
+
+    /// Marks the dependencies whose code runs at build time (see [`build_time`]).
+    /// (This is only available with guppy.)
+    fn mark_build_time(&mut self, package_graph: Option<&PackageGraph>) {
+        let build_time = match package_graph {
+            Some(package_graph) => guppy::build_time_packages(package_graph),
+            None => return,
+        };
+        for dependency in &mut self.dependencies {
+            let key = dependency.summary_id();
+            let build_script = build_time.get(&key).copied();
+            dependency.build_script = build_script.unwrap_or(false);
+            dependency.build_time = build_script.is_some() || dependency.proc_macro;
+        }
+    }
+
+    /// Finds the direct dependencies whose default features pull in crates
+    /// that the workspace doesn't request (see [`slimming`]).
+    /// (This is only available with guppy.)
+    fn check_default_features(&mut self, repo_dir: &Path, package_graph: Option<&PackageGraph>) {
+        let package_graph = match package_graph {
+            Some(package_graph) => package_graph,
+            None => return,
+        };
+        let bloat = match guppy::default_features_bloat(
+            package_graph,
+            &repo_dir.join("Cargo.toml"),
+            &self.resolve_options,
+        ) {
+            Ok(bloat) => bloat,
+            Err(e) => {
+                error!("couldn't check the default features of dependencies: {}", e);
+                return;
+            }
+        };
+        for dependency in self
+            .dependencies
+            .iter_mut()
+            .filter(|dependency| dependency.direct && !dependency.dev)
+        {
+            let key = dependency.summary_id();
+            if let Some((default_features, dropped)) = bloat.get(&key) {
+                dependency.slimming = Some(Slimming::new(default_features.clone(), dropped));
+            }
+        }
+    }
+
+    /// Records the platforms every dependency is compiled for.
+    async fn platforms(&mut self, repo_dir: &Path) {
+        // (this requires cargo metadata)
+        if self.resolve_options.lockfile_only {
+            return;
+        }
+        let platforms = match platforms::get_platforms(repo_dir).await {
+            Ok(platforms) => platforms,
+            Err(e) => {
+                error!("couldn't obtain the platforms of dependencies: {}", e);
+                return;
+            }
+        };
+        for dependency in &mut self.dependencies {
+            let key = (dependency.name.clone(), dependency.version.clone());
+            if let Some(platforms) = platforms.get(&key) {
+                dependency.platforms = platforms.clone();
+            }
+        }
+    }
+
+    /// Records the commit that introduced every dependency.
+    async fn blame(&mut self, repo_dir: &Path) {
+        let introductions = match blame::introductions(repo_dir).await {
+            Ok(introductions) => introductions,
+            Err(e) => {
+                info!("couldn't find who introduced the dependencies: {}", e);
+                return;
+            }
+        };
+        for dependency in &mut self.dependencies {
+            dependency.introduced = introductions
+                .get(&CrateName::from(dependency.name.as_str()))
+                .cloned();
+        }
+    }
+
+    /// Records the owners of the workspace members using every dependency (see [`codeowners`]):
+    /// the members linking against it, or without guppy, the members declaring it.
+    /// The owners of a workspace root are added for the dependencies its members inherit,
+    /// since the requirements of those are changed in the manifest of the root.
+    fn ownership(&mut self, repo_dir: &Path) {
+        let codeowners = match CodeOwners::find(repo_dir) {
+            Some(codeowners) => codeowners,
+            None => return,
+        };
+        let packages = lockfile::local_packages(repo_dir);
+        let owners = codeowners.owners_of_packages(repo_dir, &packages);
+        for dependency in &mut self.dependencies {
+            let name = CrateName::from(dependency.name.as_str());
+            let members: Vec<CrateName> = if dependency.linked_by.is_empty() {
+                packages
+                    .iter()
+                    .filter(|package| {
+                        package.dependencies.normal.contains(&name)
+                            || package.dependencies.dev.contains(&name)
+                    })
+                    .map(|package| package.name.clone())
+                    .collect()
+            } else {
+                dependency
+                    .linked_by
+                    .iter()
+                    .map(|member| CrateName::from(member.as_str()))
+                    .collect()
+            };
+            let mut code_owners: BTreeSet<&String> = members
+                .iter()
+                .filter_map(|member| owners.get(member))
+                .flatten()
+                .collect();
+            for package in &packages {
+                let root = match &package.workspace_manifest {
+                    Some(root) if package.dependencies.inherited.contains(&name) => root,
+                    _ => continue,
+                };
+                let path = root
+                    .strip_prefix(repo_dir)
+                    .unwrap_or(root)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                code_owners.extend(codeowners.owners_of(&path));
+            }
+            dependency.code_owners = code_owners.into_iter().cloned().collect();
+        }
+    }
 
-        //
-        Ok(Self {
-            dependencies,
-            rustsec: RustSec::default(),
-            change_summary: None,
-        })
+    /// Marks the dependencies that are internal forks: the configured crates not coming from crates.io
+    /// (a crates.io copy of a forked crate is not the fork).
+    /// Their sources intentionally differ from crates.io,
+    /// so the source checks annotate them instead of alarming (their advisories still apply).
+    fn mark_forks(&mut self, forked_crates: &[String]) {
+        for dependency in &mut self.dependencies {
+            dependency.forked = !matches!(dependency.repo, SummarySource::CratesIo)
+                && forked_crates
+                    .iter()
+                    .any(|forked| crate_name::same_crate(forked, &dependency.name));
+        }
+    }
+
+    /// Tags the dependencies as configured, and keeps the rules of the tags for the reviews.
+    fn mark_tags(&mut self, options: &TagOptions) {
+        for dependency in &mut self.dependencies {
+            dependency.tags = options.tags_of(&dependency.name);
+        }
+        self.tags = options.clone();
     }
 
     /// 3. Checks for updates in a set of crates
     async fn updatable(&mut self) -> Result<()> {
+        if !Source::CratesIo.is_enabled() {
+            info!("skipping update check, crates.io is disabled");
+            return Ok(());
+        }
+
         // filter out non-crates.io dependencies
         let mut dependencies: Vec<String> = self
             .dependencies
@@ -221,12 +1358,41 @@ impl RustAnalysis {
                     cratesio::Crates::get_all_versions(&dependency).await,
                 )
             })
-            .buffer_unordered(10);
+            .buffer_unordered(CONCURRENCY);
 
         // extract the result as a hashmap of name -> semver
         let mut dep_to_versions: HashMap<String, Vec<Version>> = HashMap::new();
+        let mut crate_sizes: HashMap<(String, Version), u64> = HashMap::new();
+        let mut crate_licenses: HashMap<(String, Version), Option<String>> = HashMap::new();
+        let mut crate_publishers: HashMap<(String, Version), String> = HashMap::new();
+        let mut crate_release_dates: HashMap<(String, Version), String> = HashMap::new();
+        let mut dep_to_links: HashMap<String, Links> = HashMap::new();
         while let Some((dependency, crate_)) = iterator.next().await {
             if let Ok(crate_) = crate_ {
+                dep_to_links.insert(
+                    dependency.clone(),
+                    Links::from_crate_info(&crate_.crate_info),
+                );
+                for version in &crate_.versions {
+                    let num = match Version::parse(&version.num) {
+                        Ok(num) => num,
+                        Err(_) => continue,
+                    };
+                    if let Some(crate_size) = version.crate_size {
+                        crate_sizes.insert((dependency.clone(), num.clone()), crate_size);
+                    }
+                    if let Some(published_by) = &version.published_by {
+                        crate_publishers.insert(
+                            (dependency.clone(), num.clone()),
+                            published_by.login.clone(),
+                        );
+                    }
+                    crate_release_dates.insert(
+                        (dependency.clone(), num.clone()),
+                        version.created_at.clone(),
+                    );
+                    crate_licenses.insert((dependency.clone(), num), version.license.clone());
+                }
                 let mut versions: Vec<Version> = crate_
                     .versions
                     .iter()
@@ -242,20 +1408,88 @@ impl RustAnalysis {
 
         // update our list of dependencies with that new information
         for dependency in &mut self.dependencies {
+            // (the README of the package is only read if it is in the local cargo cache)
+            let name = &dependency.name;
+            let version = dependency.version.to_string();
+            let discovered = dep_to_links.get(name.as_str()).and_then(|links| {
+                links.discover(|| {
+                    diff::local_sources(name, &version)
+                        .and_then(|crate_dir| discovery::readme(&crate_dir))
+                })
+            });
+            dependency.repository = discovered.as_ref().map(|(url, _)| url.clone());
+            dependency.repository_discovery = discovered.map(|(_, method)| method);
+
             let versions = dep_to_versions.get(dependency.name.as_str());
             if let Some(versions) = versions {
-                // get GREAT versions
-                // TODO: since the list is sorted, it should be faster to find the matching version and split_at there
-                let greater_versions: Vec<Version> = versions
-                    .iter()
-                    .filter(|&version| version > &dependency.version)
-                    .cloned()
-                    .collect();
+                // get GREAT versions (see [`versions`] for pre-releases and build metadata)
+                let greater_versions =
+                    self::versions::update_versions(&dependency.version, versions);
 
                 // any update available?
                 if !greater_versions.is_empty() {
+                    let compressed_size = |version: &Version| {
+                        crate_sizes
+                            .get(&(dependency.name.clone(), version.clone()))
+                            .copied()
+                    };
+                    let size_change = SizeChange {
+                        old: PackageSize {
+                            compressed: compressed_size(&dependency.version),
+                            ..Default::default()
+                        },
+                        new: PackageSize {
+                            compressed: greater_versions.last().and_then(compressed_size),
+                            ..Default::default()
+                        },
+                    };
+                    // (the licenses are compared as declared on crates.io,
+                    // which is the same as in the manifests)
+                    let license = |version: &Version| {
+                        crate_licenses
+                            .get(&(dependency.name.clone(), version.clone()))
+                            .cloned()
+                    };
+                    let license_change = match (
+                        license(&dependency.version),
+                        greater_versions.last().and_then(license),
+                    ) {
+                        (Some(old), Some(new)) => LicenseChange::new(old, new),
+                        _ => None,
+                    };
+                    let publisher = |version: &Version| {
+                        crate_publishers
+                            .get(&(dependency.name.clone(), version.clone()))
+                            .cloned()
+                    };
+                    let previous_publishers: Vec<String> = versions
+                        .iter()
+                        .filter(|&version| version <= &dependency.version)
+                        .filter_map(publisher)
+                        .collect();
+                    let release_date = |version: &Version| {
+                        crate_release_dates
+                            .get(&(dependency.name.clone(), version.clone()))
+                            .cloned()
+                    };
+                    let mut maintainer_change = MaintainerChange::new(
+                        publisher(&dependency.version),
+                        greater_versions.last().and_then(publisher),
+                        &previous_publishers,
+                    );
+                    let releases: Vec<(String, String)> = versions
+                        .iter()
+                        .filter_map(|version| Some((publisher(version)?, release_date(version)?)))
+                        .collect();
+                    maintainer_change.check_tenure(
+                        &releases,
+                        greater_versions.last().and_then(release_date).as_deref(),
+                    );
                     let update = Update {
                         versions: greater_versions,
+                        size_change: Some(size_change),
+                        license_change,
+                        maintainer_change: Some(maintainer_change),
                         ..Default::default()
                     };
                     dependency.update = Some(update);
@@ -267,19 +1501,425 @@ impl RustAnalysis {
         Ok(())
     }
 
+    /// Records the past RUSTSEC advisories of every crates.io dependency,
+    /// and how long it took to release a fix for them.
+    async fn advisory_history(&mut self) -> Result<()> {
+        if !Source::Advisories.is_enabled() {
+            info!("skipping advisory history, the advisory database is disabled");
+            return Ok(());
+        }
+        let advisory_db = cargoaudit::advisory_db().await?;
+
+        // only the crates with past advisories need their release dates
+        let mut dependencies: Vec<String> = self
+            .dependencies
+            .iter()
+            .filter(|dep| matches!(dep.repo, SummarySource::CratesIo))
+            .filter(|dep| !cargoaudit::advisories_for_crate(&advisory_db, &dep.name).is_empty())
+            .map(|dep| dep.name.clone())
+            .collect();
+        dependencies.dedup();
+
+        let mut iterator = stream::iter(dependencies)
+            .map(|dependency| async move {
+                // (without crates.io, the history is recorded without the time to fix)
+                let crate_ = if Source::CratesIo.is_enabled() {
+                    Some(cratesio::Crates::get_all_versions(&dependency).await)
+                } else {
+                    None
+                };
+                (dependency, crate_)
+            })
+            .buffer_unordered(CONCURRENCY);
+
+        let mut dep_to_history: HashMap<String, Vec<AdvisoryRecord>> = HashMap::new();
+        while let Some((dependency, crate_)) = iterator.next().await {
+            let releases = match crate_ {
+                Some(Ok(crate_)) => cargoaudit::release_dates(&crate_.versions),
+                Some(Err(e)) => {
+                    error!("couldn't get releases of {}: {}", dependency, e);
+                    Vec::new()
+                }
+                None => Vec::new(),
+            };
+            let history = cargoaudit::advisories_for_crate(&advisory_db, &dependency)
+                .into_iter()
+                .map(|advisory| AdvisoryRecord {
+                    id: advisory.metadata.id.to_string(),
+                    date: advisory.metadata.date.as_str().to_string(),
+                    patched: advisory
+                        .versions
+                        .patched
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                    unaffected: advisory
+                        .versions
+                        .unaffected
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                    days_to_fix: cargoaudit::days_to_fix(advisory, &releases),
+                })
+                .collect();
+            dep_to_history.insert(dependency, history);
+        }
+
+        for dependency in &mut self.dependencies {
+            if let Some(history) = dep_to_history.get(&dependency.name) {
+                dependency.advisory_history = history.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the crates.io owners of the dependencies with an update,
+    /// and flags the updates whose maintainers changed (see [`maintainers`]).
+    /// The owners are compared with the ones recorded by the previous analysis.
+    async fn maintainers(&mut self, previous_analysis: Option<&Self>) {
+        if !Source::CratesIo.is_enabled() {
+            info!("skipping maintainer checks, crates.io is disabled");
+            return;
+        }
+
+        // fetch the owners of every dependency with an update, and of the ones running at build time
+        let mut dependencies: Vec<String> = self
+            .dependencies
+            .iter()
+            .filter(|dep| dep.update.is_some() || dep.proc_macro || dep.build_time)
+            .map(|dep| dep.name.clone())
+            .collect();
+        dependencies.sort();
+        dependencies.dedup();
+
+        let mut iterator = stream::iter(dependencies)
+            .map(|dependency| async move {
+                let owners = cratesio::Crates::get_owners(&dependency).await;
+                (dependency, owners)
+            })
+            .buffer_unordered(CONCURRENCY);
+
+        let mut dep_to_owners: HashMap<String, Vec<String>> = HashMap::new();
+        while let Some((dependency, owners)) = iterator.next().await {
+            match owners {
+                Ok(owners) => {
+                    let owners = owners.into_iter().map(|owner| owner.login).collect();
+                    dep_to_owners.insert(dependency, owners);
+                }
+                Err(e) => error!("couldn't get owners of {}: {}", dependency, e),
+            }
+        }
+
+        // the owners seen by the previous analysis
+        let previous_owners: HashMap<&str, &[String]> = previous_analysis
+            .map(|analysis| {
+                analysis
+                    .dependencies
+                    .iter()
+                    .filter(|dep| !dep.owners.is_empty())
+                    .map(|dep| (dep.name.as_str(), dep.owners.as_slice()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for dependency in &mut self.dependencies {
+            if let Some(owners) = dep_to_owners.get(&dependency.name) {
+                dependency.owners = owners.clone();
+            }
+            if let Some(change) = dependency
+                .update
+                .as_mut()
+                .and_then(|update| update.maintainer_change.as_mut())
+            {
+                let previous_owners = previous_owners
+                    .get(dependency.name.as_str())
+                    .copied()
+                    .unwrap_or_default();
+                change.check_owners(&dependency.owners, previous_owners);
+            }
+        }
+    }
+
+    /// Fetches the security policies and the fuzz targets of the GitHub repositories of the dependencies
+    /// (see [`github::get_security_policy`] and [`fuzzing`]).
+    async fn security_policies(&mut self) {
+        if !Source::GitHub.is_enabled() {
+            info!("skipping security policies, github is disabled");
+            return;
+        }
+
+        // (crates of a same workspace share a repository)
+        let repositories: BTreeSet<(String, String)> = self
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.repository.as_deref())
+            .filter_map(github::repository_path)
+            .map(|(owner, name)| (owner.to_string(), name.to_string()))
+            .collect();
+
+        let mut iterator = stream::iter(repositories)
+            .map(|(owner, name)| async move {
+                let (policy, fuzz_manifest) = futures::join!(
+                    github::get_security_policy(&owner, &name),
+                    github::get_file(&owner, &name, fuzzing::FUZZ_MANIFEST)
+                );
+                (owner, name, policy, fuzz_manifest)
+            })
+            .buffer_unordered(CONCURRENCY);
+
+        let mut policies: HashMap<(String, String), SecurityPolicy> = HashMap::new();
+        let mut fuzz_targets: HashMap<(String, String), Vec<String>> = HashMap::new();
+        while let Some((owner, name, policy, fuzz_manifest)) = iterator.next().await {
+            match fuzz_manifest {
+                Ok(manifest) => {
+                    let targets = manifest
+                        .map(|manifest| fuzzing::fuzz_targets(&manifest))
+                        .unwrap_or_default();
+                    fuzz_targets.insert((owner.clone(), name.clone()), targets);
+                }
+                Err(e) => error!("couldn't get the fuzz targets of {}/{}: {}", owner, name, e),
+            }
+            match policy {
+                Ok(policy) => {
+                    policies.insert((owner, name), policy);
+                }
+                Err(e) => error!(
+                    "couldn't get the security policy of {}/{}: {}",
+                    owner, name, e
+                ),
+            }
+        }
+
+        for dependency in &mut self.dependencies {
+            let repository = dependency
+                .repository
+                .as_deref()
+                .and_then(github::repository_path)
+                .map(|(owner, name)| (owner.to_string(), name.to_string()));
+            if let Some(repository) = repository {
+                dependency.security_policy = policies.get(&repository).cloned();
+                dependency.fuzz_targets = fuzz_targets.get(&repository).cloned();
+            }
+        }
+    }
+
+    /// Records the GitHub repositories of the dependencies that redirect to another repository
+    /// (see [`provenance::repository_redirect`]).
+    /// This needs an API call per repository (or a query per batch of repositories with the GraphQL API,
+    /// see [`github_graphql`]), so it is skipped without GitHub credentials.
+    async fn redirects(&mut self) {
+        if !Source::GitHub.is_enabled() {
+            info!("skipping repository redirects, github is disabled");
+            return;
+        }
+        if !github_auth::current().is_configured() {
+            info!("skipping repository redirects due to GitHub credentials not found");
+            return;
+        }
+
+        let repositories: BTreeSet<(String, String, String)> = self
+            .dependencies
+            .iter()
+            .filter_map(|dep| dep.repository.as_deref())
+            .filter_map(|repository| {
+                let (owner, name) = github::repository_path(repository)?;
+                Some((repository.to_string(), owner.to_string(), name.to_string()))
+            })
+            .collect();
+
+        // (with the GraphQL API, the repositories are looked up by batches)
+        let lookups: Vec<(String, Result<github::RepositoryStats>)> = if github_graphql::enabled() {
+            let paths: Vec<(String, String)> = repositories
+                .iter()
+                .map(|(_, owner, name)| (owner.clone(), name.clone()))
+                .collect();
+            let mut stats = match github_graphql::get_repositories_stats(&paths).await {
+                Ok(stats) => stats,
+                Err(e) => {
+                    error!("couldn't look up the repositories: {}", e);
+                    return;
+                }
+            };
+            repositories
+                .into_iter()
+                .map(|(repository, owner, name)| {
+                    let stats = stats
+                        .remove(&(owner, name))
+                        .ok_or_else(|| anyhow!("repository not found"));
+                    (repository, stats)
+                })
+                .collect()
+        } else {
+            stream::iter(repositories)
+                .map(|(repository, owner, name)| async move {
+                    let stats = github::get_repository_stats(&owner, &name).await;
+                    (repository, stats)
+                })
+                .buffer_unordered(CONCURRENCY)
+                .collect()
+                .await
+        };
+
+        // (a failed lookup is not a redirect)
+        let mut redirects: HashMap<String, RepositoryRedirect> = HashMap::new();
+        for (repository, stats) in lookups {
+            match stats {
+                Ok(stats) => {
+                    if let Some(redirect) =
+                        provenance::repository_redirect(&repository, &stats.full_name)
+                    {
+                        redirects.insert(repository, redirect);
+                    }
+                }
+                Err(e) => error!("couldn't look up the repository {}: {}", repository, e),
+            }
+        }
+
+        for dependency in &mut self.dependencies {
+            if let Some(repository) = &dependency.repository {
+                dependency.repository_redirect = redirects.get(repository).cloned();
+            }
+        }
+    }
+
+    /// Marks the dependencies that are published by a trusted publisher.
+    /// A crate is trusted if it is explicitly trusted,
+    /// if its GitHub repository belongs to a trusted organization,
+    /// or if one of its crates.io owners is trusted
+    /// (the owners are only known for the dependencies with an update, see [`Self::maintainers`]).
+    fn trust(&mut self, trusted_crates: &[String], trusted_publishers: &[String]) {
+        for dependency in &mut self.dependencies {
+            let trusted_org = dependency
+                .repository
+                .as_deref()
+                .and_then(github::repository_owner)
+                .map(|org| trusted_publishers.iter().any(|p| p == org))
+                .unwrap_or(false);
+            let trusted_owner = dependency.owners.iter().any(|owner| {
+                trusted_publishers
+                    .iter()
+                    .any(|p| p == cratesio::organization(owner))
+            });
+            let trusted_crate = trusted_crates
+                .iter()
+                .any(|trusted| crate_name::same_crate(trusted, &dependency.name));
+            dependency.trusted = trusted_crate || trusted_org || trusted_owner;
+        }
+    }
+
+    /// Re-checks the provenance of the updates that change the repository of a crate:
+    /// the new repository becomes the one of the dependency (the old one is kept in the update),
+    /// and a crate trusted because of the organization owning the old repository
+    /// loses that trust if the new repository is not owned by a trusted organization.
+    fn provenance(&mut self, trusted_crates: &[String], trusted_publishers: &[String]) {
+        let trusted_org = |repository: Option<&str>| {
+            repository
+                .and_then(github::repository_owner)
+                .map(|org| trusted_publishers.iter().any(|p| p == org))
+                .unwrap_or(false)
+        };
+        for dependency in &mut self.dependencies {
+            let trusted_crate = trusted_crates
+                .iter()
+                .any(|trusted| crate_name::same_crate(trusted, &dependency.name));
+            let change = match dependency
+                .update
+                .as_mut()
+                .and_then(|update| update.repository_change.as_mut())
+            {
+                Some(change) => change,
+                None => continue,
+            };
+            dependency.repository = change.new.clone();
+            if dependency.trusted
+                && !trusted_crate
+                && trusted_org(Some(&change.old))
+                && !trusted_org(change.new.as_deref())
+            {
+                dependency.trusted = false;
+                change.lost_trust = true;
+            }
+        }
+    }
+
+    /// Estimates whether the workspace calls into the functions affected by the RUSTSEC vulnerabilities
+    /// (see [`reachability`]).
+    fn check_reachability(&mut self, repo_dir: &Path) {
+        info!("estimating the reachability of advisories...");
+        let packages = lockfile::local_packages(repo_dir);
+        let sources = reachability::workspace_sources(repo_dir, &packages);
+        self.reachability = reachability::analyze(&self.rustsec.vulnerabilities, &sources);
+    }
+
+    /// Computes the aggregate statistics of the analysis,
+    /// with the sources of the dependencies available locally (nothing is downloaded).
+    fn summarize(&mut self) {
+        // (every crate is scanned once, whatever the number of summaries)
+        let mut sources: HashMap<(String, Version), Option<report::SourceInfo>> = HashMap::new();
+        for dependency in &self.dependencies {
+            let key = (dependency.name.clone(), dependency.version.clone());
+            sources.entry(key).or_insert_with(|| {
+                let crate_dir =
+                    diff::local_sources(&dependency.name, &dependency.version.to_string())?;
+                report::SourceInfo::from_dir(&crate_dir).ok()
+            });
+        }
+        let source_of = |name: &str, version: &Version| {
+            sources
+                .get(&(name.to_string(), version.clone()))
+                .cloned()
+                .flatten()
+        };
+
+        let warnings = self.rustsec.warnings.values().map(Vec::len).sum();
+        self.statistics = Statistics::new(
+            &self.dependencies,
+            self.rustsec.vulnerabilities.len(),
+            warnings,
+            source_of,
+        );
+        self.build_time = BuildTimeSurface::new(&self.dependencies, source_of);
+        self.namespaces = namespaces::group(&self.dependencies, source_of);
+    }
+
     /// 4. priority engine
     async fn priority(&mut self, repo_dir: &Path) -> Result<()> {
         // 1. get cargo-audit results
-        info!("running cargo-audit");
-        let report = cargoaudit::audit(repo_dir).await?;
-        self.rustsec.vulnerabilities = report.vulnerabilities.list;
-        self.rustsec.warnings = report.warnings;
+        if Source::Advisories.is_enabled() {
+            info!("running cargo-audit");
+            let report = cargoaudit::audit(repo_dir, self.resolve_options.lockfile_only).await?;
+            self.rustsec.vulnerabilities = report.vulnerabilities.list;
+            self.rustsec.warnings = report.warnings;
+        } else {
+            info!("skipping cargo-audit, the advisory database is disabled");
+        }
+
+        // only keep the advisories of the dependencies in scope
+        let in_scope: HashSet<CrateName> = self
+            .dependencies
+            .iter()
+            .map(|dependency| CrateName::from(dependency.name.as_str()))
+            .collect();
+        self.rustsec
+            .vulnerabilities
+            .retain(|vuln| in_scope.contains(&CrateName::from(vuln.package.name.as_str())));
+        self.rustsec.warnings = std::mem::take(&mut self.rustsec.warnings)
+            .into_iter()
+            .map(|(kind, mut warnings)| {
+                warnings.retain(|warning| {
+                    in_scope.contains(&CrateName::from(warning.package.name.as_str()))
+                });
+                (kind, warnings)
+            })
+            .filter(|(_, warnings)| !warnings.is_empty())
+            .collect();
 
         // 2. fetch every changelog via dependabot
-        if std::env::var("GITHUB_TOKEN").is_err()
-            || std::env::var("GITHUB_TOKEN") == Ok("".to_string())
-        {
-            info!("skipping dependabot run due to GITHUB_TOKEN env var not found");
+        if !Source::GitHub.is_enabled() {
+            info!("skipping dependabot run, github is disabled");
+        } else if !github_auth::current().is_configured() {
+            info!("skipping dependabot run due to GitHub credentials not found (e.g. a GITHUB_TOKEN env var)");
         } else {
             info!("running dependabot to get changelogs");
             let iterator = stream::iter(&mut self.dependencies)
@@ -312,7 +1952,7 @@ impl RustAnalysis {
                         };
                     }
                 })
-                .buffer_unordered(10);
+                .buffer_unordered(CONCURRENCY);
             iterator.collect::<()>().await;
         }
 
@@ -321,7 +1961,17 @@ impl RustAnalysis {
     }
 
     /// 5. risk engine
-    async fn risk(&mut self) -> Result<()> {
+    async fn risk(&mut self, risky_paths: &RiskyPaths) -> Result<()> {
+        // the crates to diff are downloaded from crates.io
+        if !Source::CratesIo.is_enabled() {
+            info!("skipping crate diffs, crates.io is disabled");
+            return Ok(());
+        }
+
+        // (package, advisory, function) for the advisories listing affected functions
+        let affected = &reachability::affected_functions(&self.rustsec.vulnerabilities);
+        let advisory_reachability = &self.reachability;
+
         // fetch versions for each dependency in that list
         let iterator = stream::iter(&mut self.dependencies)
             .map(|dependency| async move {
@@ -344,21 +1994,54 @@ impl RustAnalysis {
                         format!("{}=={}", original_dep_name, original_dep_version);
                     let cargo_crate_new_version =
                         format!("{}=={}", original_dep_name, latest_version);
+                    let affected: Vec<&(String, String, String)> = affected
+                        .iter()
+                        .filter(|(package, _, _)| {
+                            CrateName::from(package.as_str()) == original_dep_name.as_str()
+                        })
+                        .collect();
+                    let affected_functions: Vec<String> = affected
+                        .iter()
+                        .map(|(_, _, function)| function.clone())
+                        .collect();
 
-                    match diff::is_diff_in_buildrs(
+                    match diff::diff_crate_versions(
                         &cargo_crate_original_version,
                         &cargo_crate_new_version,
+                        risky_paths,
+                        &affected_functions,
                     )
                     .await
                     {
-                        Ok(update_build_rs) => update.build_rs = update_build_rs,
+                        Ok(crate_diff) => {
+                            update.advisory_functions = affected
+                                .iter()
+                                .map(|(package, advisory, function)| FunctionMatch {
+                                    advisory: advisory.clone(),
+                                    function: function.clone(),
+                                    changed: crate_diff.changed_functions.contains(function),
+                                    referenced: advisory_reachability
+                                        .iter()
+                                        .find(|entry| {
+                                            entry.id == *advisory && entry.package == *package
+                                        })
+                                        .map(|entry| {
+                                            entry
+                                                .references
+                                                .iter()
+                                                .any(|reference| reference.function == *function)
+                                        }),
+                                })
+                                .collect();
+                            update.apply_diff(crate_diff);
+                        }
                         Err(e) => {
-                            error!("error checking build.rs diff: {}", e)
+                            error!("error diffing crate versions: {}", e)
                         }
                     };
                 }
             })
-            .buffer_unordered(10);
+            .buffer_unordered(CONCURRENCY);
         iterator.collect::<()>().await;
         Ok(())
     }
@@ -380,6 +2063,74 @@ pub struct ChangeSummary {
     new_updates: Vec<DependencyInfo>,
     /// new RUSTSEC advisories
     new_rustsec: RustSec,
+    /// crates that were not compiled before
+    #[serde(default)]
+    new_crates: Vec<BuildChange>,
+    /// features that were not activated before
+    #[serde(default)]
+    new_features: Vec<BuildChange>,
+    /// platforms that crates are now compiled for, but weren't before
+    #[serde(default)]
+    new_platforms: Vec<PlatformChange>,
+    /// RUSTSEC advisories withdrawn or amended since the last analysis (see [`monitor::revisions`])
+    #[serde(default)]
+    revised_rustsec: Vec<Revision>,
+}
+
+/// A change in what gets compiled, observed since the last analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BuildChange {
+    name: String,
+    version: Version,
+    /// The newly activated features (all the features, for a new crate).
+    features: Vec<String>,
+    /// The dependencies that were updated since the last analysis,
+    /// and that are likely to have caused this change.
+    caused_by: Vec<String>,
+}
+
+/// A platform that dependencies are now compiled for, observed since the last analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlatformChange {
+    /// The platform (a cfg expression or a target triple).
+    platform: String,
+    /// The crates compiled only for platforms including this one.
+    crates: Vec<String>,
+    /// The dependencies that were updated since the last analysis,
+    /// and that are likely to have caused this change.
+    caused_by: Vec<String>,
+}
+
+impl PlatformChange {
+    pub fn platform(&self) -> &str {
+        &self.platform
+    }
+
+    pub fn crates(&self) -> &[String] {
+        &self.crates
+    }
+
+    pub fn caused_by(&self) -> &[String] {
+        &self.caused_by
+    }
+}
+
+impl BuildChange {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    pub fn caused_by(&self) -> &[String] {
+        &self.caused_by
+    }
 }
 
 impl ChangeSummary {
@@ -445,12 +2196,23 @@ impl ChangeSummary {
         //
 
         // new vulns
+        // (an advisory amended since the last analysis is not new, see the revised ones below)
+        let same = |a: &Vulnerability, b: &Vulnerability| {
+            a.advisory.id == b.advisory.id
+                && a.package.name == b.package.name
+                && a.package.version == b.package.version
+        };
         let new_vulnerabilities: Vec<Vulnerability> = new
             .rustsec
             .vulnerabilities
             .iter()
             // remove what is contained in the previous vulns
-            .filter(|v| !old.rustsec.vulnerabilities.contains(&v))
+            .filter(|v| {
+                !old.rustsec
+                    .vulnerabilities
+                    .iter()
+                    .any(|old_v| same(old_v, *v))
+            })
             .cloned()
             .collect();
         rust_changes.new_rustsec.vulnerabilities = new_vulnerabilities;
@@ -480,7 +2242,291 @@ impl ChangeSummary {
         }
         rust_changes.new_rustsec.warnings = new_warnings;
 
+        // revised advisories (withdrawn, or amended) for dependencies that weren't updated
+        // (an advisory missing from the history of the crate was withdrawn, if the history was fetched)
+        let history_fetched = !new
+            .skipped_steps
+            .iter()
+            .any(|step| step == "advisory_history")
+            && !new.disabled_sources.contains(&Source::Advisories);
+        rust_changes.revised_rustsec = monitor::revisions(
+            &monitor::report_findings(&old.rustsec.vulnerabilities, &old.rustsec.warnings),
+            &monitor::report_findings(&new.rustsec.vulnerabilities, &new.rustsec.warnings),
+            |package, version| {
+                new.dependencies
+                    .iter()
+                    .any(|d| d.name == package && d.version.to_string() == version)
+            },
+            |id| {
+                history_fetched
+                    && !new
+                        .dependencies
+                        .iter()
+                        .any(|d| d.advisory_history.iter().any(|record| record.id == id))
+            },
+        );
+
+        //
+        // check for new crates and features being compiled
+        //
+
+        let (new_crates, new_features) = build_changes(old, new);
+        rust_changes.new_crates = new_crates;
+        rust_changes.new_features = new_features;
+        rust_changes.new_platforms = platform_changes(old, new);
+
         //
         Ok(rust_changes)
     }
+
+    pub fn new_updates(&self) -> &[DependencyInfo] {
+        &self.new_updates
+    }
+
+    pub fn new_rustsec(&self) -> &RustSec {
+        &self.new_rustsec
+    }
+
+    pub fn new_crates(&self) -> &[BuildChange] {
+        &self.new_crates
+    }
+
+    pub fn new_features(&self) -> &[BuildChange] {
+        &self.new_features
+    }
+
+    pub fn new_platforms(&self) -> &[PlatformChange] {
+        &self.new_platforms
+    }
+
+    pub fn revised_rustsec(&self) -> &[Revision] {
+        &self.revised_rustsec
+    }
+}
+
+/// Finds the platforms that dependencies are compiled for in the new analysis but not in the old one,
+/// and attributes them to the dependencies updated in between.
+fn platform_changes(old: &RustAnalysis, new: &RustAnalysis) -> Vec<PlatformChange> {
+    // analyses made before platforms were recorded can't be diffed
+    if old.dependencies.iter().all(|d| d.platforms.is_empty()) {
+        return Vec::new();
+    }
+    let old_platforms: HashSet<&str> = old
+        .dependencies
+        .iter()
+        .flat_map(|d| d.platforms.iter().map(String::as_str))
+        .collect();
+    let old_versions: HashSet<(&str, &Version)> = old
+        .dependencies
+        .iter()
+        .map(|d| (d.name.as_str(), &d.version))
+        .collect();
+    let old_names: HashSet<&str> = old.dependencies.iter().map(|d| d.name.as_str()).collect();
+
+    let mut changes: BTreeMap<&str, PlatformChange> = BTreeMap::new();
+    for dependency in &new.dependencies {
+        for platform in &dependency.platforms {
+            if old_platforms.contains(platform.as_str()) {
+                continue;
+            }
+            let change = changes.entry(platform).or_insert_with(|| PlatformChange {
+                platform: platform.clone(),
+                crates: Vec::new(),
+                caused_by: Vec::new(),
+            });
+            if !change.crates.contains(&dependency.name) {
+                change.crates.push(dependency.name.clone());
+            }
+            // the updated crates (already compiled before, with a different version) pulling it in
+            let updated = std::iter::once(&dependency.name)
+                .chain(dependency.introduced_by.iter())
+                .filter(|name| old_names.contains(name.as_str()))
+                .filter(|name| {
+                    new.dependencies.iter().any(|d| {
+                        &d.name == *name && !old_versions.contains(&(d.name.as_str(), &d.version))
+                    })
+                });
+            for name in updated {
+                if !change.caused_by.contains(name) {
+                    change.caused_by.push(name.clone());
+                }
+            }
+        }
+    }
+
+    changes.into_iter().map(|(_, change)| change).collect()
+}
+
+/// Diffs the crates and the features compiled in two analyses,
+/// and attributes the new ones to the dependencies updated in between.
+/// (New cfg expressions and target triples are diffed by [`platform_changes`].)
+fn build_changes(old: &RustAnalysis, new: &RustAnalysis) -> (Vec<BuildChange>, Vec<BuildChange>) {
+    // versions and features previously compiled, by name
+    let mut old_versions: HashMap<&str, HashSet<&Version>> = HashMap::new();
+    let mut old_features: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for dependency in &old.dependencies {
+        old_versions
+            .entry(&dependency.name)
+            .or_default()
+            .insert(&dependency.version);
+        old_features
+            .entry(&dependency.name)
+            .or_default()
+            .extend(dependency.features.iter().map(String::as_str));
+    }
+    // (analyses made before features were recorded can't be diffed)
+    let has_features = old.dependencies.iter().any(|d| !d.features.is_empty());
+
+    // dependencies that were updated in between
+    let mut new_versions: HashMap<&str, HashSet<&Version>> = HashMap::new();
+    for dependency in &new.dependencies {
+        new_versions
+            .entry(&dependency.name)
+            .or_default()
+            .insert(&dependency.version);
+    }
+    let updated: HashSet<&str> = new_versions
+        .iter()
+        .filter(|(name, versions)| matches!(old_versions.get(*name), Some(old) if old != *versions))
+        .map(|(name, _)| *name)
+        .collect();
+    let caused_by = |dependency: &DependencyInfo| -> Vec<String> {
+        std::iter::once(&dependency.name)
+            .chain(dependency.introduced_by.iter())
+            .filter(|name| updated.contains(name.as_str()))
+            .cloned()
+            .collect()
+    };
+
+    let mut new_crates = Vec::new();
+    let mut new_features: Vec<BuildChange> = Vec::new();
+    for dependency in &new.dependencies {
+        match old_features.get(dependency.name.as_str()) {
+            None => {
+                if new_crates.iter().any(|c: &BuildChange| {
+                    c.name == dependency.name && c.version == dependency.version
+                }) {
+                    continue;
+                }
+                new_crates.push(BuildChange {
+                    name: dependency.name.clone(),
+                    version: dependency.version.clone(),
+                    features: dependency.features.clone(),
+                    caused_by: caused_by(dependency),
+                });
+            }
+            Some(features) if has_features => {
+                let activated: Vec<String> = dependency
+                    .features
+                    .iter()
+                    .filter(|feature| !features.contains(feature.as_str()))
+                    .filter(|feature| {
+                        !new_features
+                            .iter()
+                            .any(|c| c.name == dependency.name && c.features.contains(feature))
+                    })
+                    .cloned()
+                    .collect();
+                if !activated.is_empty() {
+                    new_features.push(BuildChange {
+                        name: dependency.name.clone(),
+                        version: dependency.version.clone(),
+                        features: activated,
+                        caused_by: caused_by(dependency),
+                    });
+                }
+            }
+            Some(_) => (),
+        }
+    }
+
+    (new_crates, new_features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_changes() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        let dependency = |name: &str, version: &str, features: &[&str], introduced_by: &[&str]| {
+            let mut dependency = DependencyInfo::new(
+                name.to_string(),
+                v(version),
+                SummarySource::CratesIo,
+                false,
+                introduced_by.is_empty(),
+            );
+            dependency.features = features.iter().map(|f| f.to_string()).collect();
+            dependency.introduced_by = introduced_by.iter().map(|d| d.to_string()).collect();
+            dependency
+        };
+        let old = RustAnalysis {
+            dependencies: vec![
+                dependency("a", "1.0.0", &["std"], &[]),
+                dependency("c", "0.1.0", &["default"], &[]),
+            ],
+            ..Default::default()
+        };
+        let new = RustAnalysis {
+            dependencies: vec![
+                dependency("a", "1.1.0", &["std", "alloc"], &[]),
+                dependency("b", "0.2.0", &["default"], &["a"]),
+                dependency("c", "0.1.0", &["default"], &[]),
+            ],
+            ..Default::default()
+        };
+
+        let (new_crates, new_features) = build_changes(&old, &new);
+        assert_eq!(
+            new_crates,
+            vec![BuildChange {
+                name: "b".to_string(),
+                version: v("0.2.0"),
+                features: vec!["default".to_string()],
+                caused_by: vec!["a".to_string()],
+            }]
+        );
+        assert_eq!(
+            new_features,
+            vec![BuildChange {
+                name: "a".to_string(),
+                version: v("1.1.0"),
+                features: vec!["alloc".to_string()],
+                caused_by: vec!["a".to_string()],
+            }]
+        );
+
+        // nothing changes between identical analyses
+        let (new_crates, new_features) = build_changes(&new, &new);
+        assert!(new_crates.is_empty());
+        assert!(new_features.is_empty());
+    }
+
+    #[test]
+    fn test_mark_forks() {
+        let dependency = |repo: SummarySource| {
+            DependencyInfo::new(
+                "serde".to_string(),
+                Version::parse("1.0.123").unwrap(),
+                repo,
+                false,
+                true,
+            )
+        };
+        let mut analysis = RustAnalysis {
+            dependencies: vec![
+                dependency(SummarySource::CratesIo),
+                dependency(SummarySource::External {
+                    source: "git+https://github.com/acme/serde?rev=1#1".to_string(),
+                }),
+            ],
+            ..Default::default()
+        };
+        analysis.mark_forks(&["serde".to_string()]);
+        // (the crates.io copy of a forked crate is not the fork)
+        let forked: Vec<bool> = analysis.dependencies.iter().map(|d| d.forked).collect();
+        assert_eq!(forked, vec![false, true]);
+    }
 }
@@ -0,0 +1,387 @@
+//! This module monitors a pinned set of dependencies (a `Cargo.lock` snapshot) for new advisories:
+//! each check refreshes the RUSTSEC advisory database, audits the lockfile,
+//! and only reports the advisories that didn't affect it at the previous check.
+//! The state between checks is a small JSON file (see [`MonitorState`]),
+//! so that checks can run from cron (see the `monitor` binary) as well as in a loop.
+//!
+//! Advisories also change after their publication: they get withdrawn, or their affected versions amended.
+//! A finding that goes away while its package version is still in the lockfile wasn't fixed by an update,
+//! so it is reported as revised (see [`Revision`]) for teams to stop chasing it,
+//! as well as the findings whose patched versions changed.
+
+use anyhow::{ensure, Context, Result};
+use chrono::{DateTime, Utc};
+use rustsec::{
+    advisory::Informational, lockfile::Lockfile, report::WarningInfo, Database, Vulnerability,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use super::{cargoaudit, lockfile_format};
+use crate::sources::Source;
+
+/// An advisory affecting a package of the lockfile.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// the RUSTSEC identifier (e.g. `RUSTSEC-2020-0001`)
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub title: String,
+    /// the kind of advisory (`vulnerability`, or an informational kind like `unmaintained`)
+    pub kind: String,
+    /// the versions that are not affected by the advisory
+    pub patched: Vec<String>,
+}
+
+impl Finding {
+    /// identifies the finding across checks
+    pub fn key(&self) -> String {
+        format!("{}:{}@{}", self.id, self.package, self.version)
+    }
+}
+
+/// How an advisory was revised since it was reported.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RevisionKind {
+    /// the advisory is no longer in the database
+    Withdrawn,
+    /// the advisory no longer affects the version (its affected versions were amended)
+    NoLongerAffected,
+    /// the advisory still affects the version, but its patched versions changed
+    PatchedChanged,
+}
+
+/// A finding revised since it was reported, while its package version didn't change.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revision {
+    /// the finding as it was reported
+    pub finding: Finding,
+    pub kind: RevisionKind,
+    /// the versions now patched (for [`RevisionKind::PatchedChanged`])
+    pub patched: Vec<String>,
+}
+
+impl Revision {
+    /// Describes the revision (e.g. `RUSTSEC-2021-0003 (smallvec 1.6.0) was withdrawn`).
+    pub fn describe(&self) -> String {
+        let revision = match self.kind {
+            RevisionKind::Withdrawn => "was withdrawn".to_string(),
+            RevisionKind::NoLongerAffected => "no longer affects this version".to_string(),
+            RevisionKind::PatchedChanged => format!(
+                "now patched in {} (was {})",
+                list_or_none(&self.patched),
+                list_or_none(&self.finding.patched)
+            ),
+        };
+        format!(
+            "{} ({} {}) {}",
+            self.finding.id, self.finding.package, self.finding.version, revision
+        )
+    }
+}
+
+fn list_or_none(versions: &[String]) -> String {
+    if versions.is_empty() {
+        "none".to_string()
+    } else {
+        versions.join(", ")
+    }
+}
+
+/// Compares findings with the previous ones, and returns the revised ones:
+/// a finding gone while `locked` (by package name and version) tells its package version is still used
+/// was withdrawn (`withdrawn` telling if an advisory is no longer in the database), or no longer affects it.
+pub fn revisions(
+    previous: &[Finding],
+    findings: &[Finding],
+    locked: impl Fn(&str, &str) -> bool,
+    withdrawn: impl Fn(&str) -> bool,
+) -> Vec<Revision> {
+    let mut revisions = Vec::new();
+    for finding in previous {
+        let kind = match findings.iter().find(|f| f.key() == finding.key()) {
+            Some(current) if current.patched != finding.patched => {
+                revisions.push(Revision {
+                    finding: finding.clone(),
+                    kind: RevisionKind::PatchedChanged,
+                    patched: current.patched.clone(),
+                });
+                continue;
+            }
+            Some(_) => continue,
+            // (the package was updated or removed: fixed, not revised)
+            None if !locked(&finding.package, &finding.version) => continue,
+            None if withdrawn(&finding.id) => RevisionKind::Withdrawn,
+            None => RevisionKind::NoLongerAffected,
+        };
+        revisions.push(Revision {
+            finding: finding.clone(),
+            kind,
+            patched: Vec::new(),
+        });
+    }
+    revisions
+}
+
+/// The result of a check.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CheckReport {
+    /// the findings that didn't affect the lockfile at the previous check
+    pub new: Vec<Finding>,
+    /// the findings of the previous check that were revised
+    pub revised: Vec<Revision>,
+}
+
+impl CheckReport {
+    pub fn is_empty(&self) -> bool {
+        self.new.is_empty() && self.revised.is_empty()
+    }
+}
+
+/// What was seen by the previous checks.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct MonitorState {
+    /// the findings already reported (see [`Finding::key`])
+    pub known: BTreeSet<String>,
+    pub last_check: Option<DateTime<Utc>>,
+    /// the findings of the previous check (to notice the revised advisories)
+    pub findings: Vec<Finding>,
+}
+
+impl MonitorState {
+    /// Loads the state of the previous checks (an empty state if there was none).
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let state = fs::read_to_string(path)?;
+        serde_json::from_str(&state).with_context(|| format!("invalid monitor state {:?}", path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Records the findings of a check and returns the new ones.
+    /// (Findings that stopped affecting the lockfile are forgotten, so they are reported again if they come back.)
+    pub fn update(&mut self, findings: Vec<Finding>) -> Vec<Finding> {
+        let new = findings
+            .iter()
+            .filter(|finding| !self.known.contains(&finding.key()))
+            .cloned()
+            .collect();
+        self.known = findings.iter().map(Finding::key).collect();
+        self.last_check = Some(Utc::now());
+        self.findings = findings;
+        new
+    }
+}
+
+/// Lists the advisories affecting the packages of a lockfile.
+pub fn findings(advisory_db: &Database, lockfile: &Lockfile) -> Vec<Finding> {
+    let mut settings = rustsec::report::Settings::default();
+    settings.informational_warnings = vec![
+        Informational::Unmaintained,
+        Informational::Notice,
+        Informational::Unsound,
+    ];
+    let report = rustsec::Report::generate(advisory_db, lockfile, &settings);
+    report_findings(&report.vulnerabilities.list, &report.warnings)
+}
+
+/// Lists the findings of an audit (see [`super::RustSec`]).
+pub fn report_findings(vulnerabilities: &[Vulnerability], warnings: &WarningInfo) -> Vec<Finding> {
+    let patched = |versions: Option<&rustsec::advisory::Versions>| -> Vec<String> {
+        versions
+            .map(|versions| versions.patched.iter().map(ToString::to_string).collect())
+            .unwrap_or_default()
+    };
+    let mut findings: Vec<Finding> = vulnerabilities
+        .iter()
+        .map(|vuln| Finding {
+            id: vuln.advisory.id.to_string(),
+            package: vuln.package.name.as_str().to_string(),
+            version: vuln.package.version.to_string(),
+            title: vuln.advisory.title.clone(),
+            kind: "vulnerability".to_string(),
+            patched: patched(Some(&vuln.versions)),
+        })
+        .collect();
+    for (kind, warnings) in warnings {
+        for warning in warnings {
+            if let Some(advisory) = &warning.advisory {
+                findings.push(Finding {
+                    id: advisory.id.to_string(),
+                    package: warning.package.name.as_str().to_string(),
+                    version: warning.package.version.to_string(),
+                    title: advisory.title.clone(),
+                    kind: kind.to_string(),
+                    patched: patched(warning.versions.as_ref()),
+                });
+            }
+        }
+    }
+    findings.sort_by_key(Finding::key);
+    findings.dedup_by_key(|finding| finding.key());
+    findings
+}
+
+/// Checks a lockfile against the latest advisory database,
+/// and returns the findings that are new or revised since the previous check (recorded in the state).
+pub async fn check(lockfile_path: &Path, state: &mut MonitorState) -> Result<CheckReport> {
+    ensure!(
+        Source::Advisories.is_enabled(),
+        "the advisories source is disabled"
+    );
+    let lockfile = lockfile_format::load(lockfile_path)
+        .with_context(|| format!("couldn't parse {:?}", lockfile_path))?;
+    // (each check refreshes the shared database, whatever its age)
+    let advisory_db = cargoaudit::advisory_db_with_max_age(Duration::from_secs(0)).await?;
+    let findings = findings(&advisory_db, &lockfile);
+    let revised = revisions(
+        &state.findings,
+        &findings,
+        |package, version| {
+            lockfile.packages.iter().any(|locked| {
+                locked.name.as_str() == package && locked.version.to_string() == version
+            })
+        },
+        |id| {
+            !advisory_db
+                .iter()
+                .any(|advisory| advisory.metadata.id.to_string() == id)
+        },
+    );
+    Ok(CheckReport {
+        new: state.update(findings),
+        revised,
+    })
+}
+
+/// Renders the new and revised findings of a check as a markdown notification.
+pub fn to_markdown(lockfile_path: &Path, report: &CheckReport) -> String {
+    let mut markdown = String::new();
+    if !report.new.is_empty() || report.revised.is_empty() {
+        let _ = writeln!(
+            markdown,
+            "## {} new advisories affect {}\n",
+            report.new.len(),
+            lockfile_path.display()
+        );
+    }
+    for finding in &report.new {
+        let _ = writeln!(
+            markdown,
+            "- [{id}](https://rustsec.org/advisories/{id}.html) ({}) {} {}: {} (patched: {})",
+            finding.kind,
+            finding.package,
+            finding.version,
+            finding.title,
+            list_or_none(&finding.patched),
+            id = finding.id,
+        );
+    }
+    if !report.revised.is_empty() {
+        let _ = writeln!(
+            markdown,
+            "\n## {} advisories affecting {} were revised\n",
+            report.revised.len(),
+            lockfile_path.display()
+        );
+        for revision in &report.revised {
+            let _ = writeln!(
+                markdown,
+                "- [{}](https://rustsec.org/advisories/{}.html) {}",
+                revision.finding.id,
+                revision.finding.id,
+                revision.describe()
+            );
+        }
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monitor_state() {
+        let finding = |id: &str, version: &str| Finding {
+            id: id.to_string(),
+            package: "smallvec".to_string(),
+            version: version.to_string(),
+            title: "Buffer overflow".to_string(),
+            kind: "vulnerability".to_string(),
+            patched: vec![">= 1.6.1".to_string()],
+        };
+        let mut state = MonitorState::default();
+
+        // the first check reports everything
+        let new = state.update(vec![finding("RUSTSEC-2021-0003", "1.6.0")]);
+        assert_eq!(new.len(), 1);
+
+        // nothing new
+        let new = state.update(vec![finding("RUSTSEC-2021-0003", "1.6.0")]);
+        assert!(new.is_empty());
+
+        // a new advisory is published
+        let new = state.update(vec![
+            finding("RUSTSEC-2021-0003", "1.6.0"),
+            finding("RUSTSEC-2021-0099", "1.6.0"),
+        ]);
+        assert_eq!(new, vec![finding("RUSTSEC-2021-0099", "1.6.0")]);
+        let report = CheckReport {
+            new,
+            revised: Vec::new(),
+        };
+        assert!(to_markdown(Path::new("Cargo.lock"), &report).contains("RUSTSEC-2021-0099"));
+        assert!(state.last_check.is_some());
+        assert_eq!(state.findings.len(), 2);
+    }
+
+    #[test]
+    fn test_revisions() {
+        let finding = |id: &str, version: &str, patched: &str| Finding {
+            id: id.to_string(),
+            package: "smallvec".to_string(),
+            version: version.to_string(),
+            title: "Buffer overflow".to_string(),
+            kind: "vulnerability".to_string(),
+            patched: vec![patched.to_string()],
+        };
+        let previous = vec![
+            finding("RUSTSEC-2021-0001", "1.6.0", ">= 1.6.1"),
+            finding("RUSTSEC-2021-0002", "1.6.0", ">= 1.6.1"),
+            finding("RUSTSEC-2021-0003", "1.6.0", ">= 1.6.1"),
+            finding("RUSTSEC-2021-0004", "0.6.0", ">= 1.6.1"),
+        ];
+        let findings = vec![finding("RUSTSEC-2021-0003", "1.6.0", ">= 1.6.2")];
+        let locked = |_: &str, version: &str| version == "1.6.0";
+        let withdrawn = |id: &str| id == "RUSTSEC-2021-0001";
+
+        // (smallvec 0.6.0 was updated, its advisory is fixed rather than revised)
+        let revisions = revisions(&previous, &findings, locked, withdrawn);
+        let kinds: Vec<RevisionKind> = revisions.iter().map(|revision| revision.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RevisionKind::Withdrawn,
+                RevisionKind::NoLongerAffected,
+                RevisionKind::PatchedChanged
+            ]
+        );
+        assert_eq!(
+            revisions[2].describe(),
+            "RUSTSEC-2021-0003 (smallvec 1.6.0) now patched in >= 1.6.2 (was >= 1.6.1)"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! This module groups the dependencies by the upstream namespace they come from:
+//! the GitHub organization (or user) hosting their repository, or their crates.io owners otherwise.
+//! Trusting a crate mostly means trusting the people who can push to it, and a handful of organizations
+//! (e.g. `tokio-rs`, `rust-lang`) often publish a large share of the code a project depends on:
+//! a concentration of trust that the per-crate metrics don't show.
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{graph, report::SourceInfo, DependencyInfo};
+use crate::common::github;
+
+/// What a namespace is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamespaceKind {
+    /// the owner of the GitHub repository of the crates
+    Organization,
+    /// a crates.io owner of the crates (their repository is not on GitHub)
+    Publisher,
+    /// the crates whose repository and owners are unknown
+    Unknown,
+}
+
+impl NamespaceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Organization => "organization",
+            Self::Publisher => "publisher",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// The dependencies coming from one upstream namespace.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Namespace {
+    /// the GitHub owner or crates.io owner (lowercase), `unknown` for the unknown namespace
+    pub name: String,
+    pub kind: NamespaceKind,
+    /// the crates of the namespace (e.g. `tokio 1.2.0`)
+    pub crates: Vec<String>,
+    /// the crates whose sources were available locally (the counts below only cover them)
+    pub measured: usize,
+    /// the lines of rust code of the crates
+    pub rust_lines: usize,
+    /// the `unsafe` keywords in the code of the crates (see [`super::unsafety`])
+    pub unsafe_keywords: usize,
+}
+
+impl Namespace {
+    /// Describes the namespace (e.g. `tokio-rs: 5 crates, 40000 lines of rust, 120 unsafe`).
+    pub fn describe(&self) -> String {
+        format!(
+            "{}: {} crates, {} lines of rust, {} unsafe",
+            self.name,
+            self.crates.len(),
+            self.rust_lines,
+            self.unsafe_keywords
+        )
+    }
+}
+
+/// Returns the namespace of a dependency.
+fn namespace_of(dependency: &DependencyInfo) -> (NamespaceKind, String) {
+    if let Some(owner) = dependency.repository().and_then(github::repository_owner) {
+        return (NamespaceKind::Organization, owner.to_lowercase());
+    }
+    // (the first owner is the one who created the crate on crates.io)
+    match dependency.owners().first() {
+        Some(owner) => (NamespaceKind::Publisher, owner.to_lowercase()),
+        None => (
+            NamespaceKind::Unknown,
+            NamespaceKind::Unknown.as_str().to_string(),
+        ),
+    }
+}
+
+/// Groups dependencies by namespace, the namespaces with the most code first,
+/// `source_of` returning the source information of a dependency if available locally.
+pub fn group(
+    dependencies: &[DependencyInfo],
+    source_of: impl Fn(&str, &Version) -> Option<SourceInfo>,
+) -> Vec<Namespace> {
+    // (a dependency used in several ways is counted once)
+    let crates: BTreeMap<(&str, &Version), &DependencyInfo> = dependencies
+        .iter()
+        .map(|dependency| ((dependency.name(), dependency.version()), dependency))
+        .collect();
+
+    let mut namespaces: BTreeMap<(NamespaceKind, String), Namespace> = BTreeMap::new();
+    for ((name, version), dependency) in crates {
+        let (kind, namespace) = namespace_of(dependency);
+        let namespace = namespaces
+            .entry((kind, namespace.clone()))
+            .or_insert_with(|| Namespace {
+                name: namespace,
+                kind,
+                crates: Vec::new(),
+                measured: 0,
+                rust_lines: 0,
+                unsafe_keywords: 0,
+            });
+        namespace.crates.push(graph::node_id(name, version));
+        if let Some(source) = source_of(name, version) {
+            namespace.measured += 1;
+            namespace.rust_lines += source.rust_lines;
+            namespace.unsafe_keywords += source.unsafe_keywords;
+        }
+    }
+
+    let mut namespaces: Vec<Namespace> = namespaces.into_iter().map(|(_, ns)| ns).collect();
+    namespaces.sort_by(|a, b| {
+        b.rust_lines
+            .cmp(&a.rust_lines)
+            .then_with(|| b.crates.len().cmp(&a.crates.len()))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    namespaces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guppy_summaries::SummarySource;
+
+    #[test]
+    fn test_group() {
+        let dependency = |name: &str, repository: Option<&str>, owner: Option<&str>| {
+            let mut dependency = DependencyInfo::new(
+                name.to_string(),
+                Version::parse("1.0.0").unwrap(),
+                SummarySource::CratesIo,
+                false,
+                false,
+            );
+            dependency.repository = repository.map(ToString::to_string);
+            dependency.owners = owner.into_iter().map(ToString::to_string).collect();
+            dependency
+        };
+        let dependencies = vec![
+            dependency("tokio", Some("https://github.com/tokio-rs/tokio"), None),
+            dependency("tokio", Some("https://github.com/tokio-rs/tokio"), None),
+            dependency("mio", Some("https://github.com/Tokio-rs/mio"), None),
+            dependency("libc", Some("https://github.com/rust-lang/libc"), None),
+            dependency("foo", Some("https://gitlab.com/alice/foo"), Some("alice")),
+            dependency("bar", None, None),
+        ];
+        let namespaces = group(&dependencies, |name, _| {
+            if name == "bar" {
+                return None;
+            }
+            Some(SourceInfo {
+                rust_lines: if name == "libc" { 5000 } else { 1000 },
+                unsafe_keywords: 10,
+                ..Default::default()
+            })
+        });
+
+        let summary: Vec<(&str, NamespaceKind, usize, usize)> = namespaces
+            .iter()
+            .map(|ns| (ns.name.as_str(), ns.kind, ns.crates.len(), ns.rust_lines))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("rust-lang", NamespaceKind::Organization, 1, 5000),
+                ("tokio-rs", NamespaceKind::Organization, 2, 2000),
+                ("alice", NamespaceKind::Publisher, 1, 1000),
+                ("unknown", NamespaceKind::Unknown, 1, 0),
+            ]
+        );
+        assert_eq!(
+            namespaces[1].crates,
+            vec!["mio 1.0.0".to_string(), "tokio 1.0.0".to_string()]
+        );
+        assert_eq!(
+            namespaces[1].describe(),
+            "tokio-rs: 2 crates, 2000 lines of rust, 20 unsafe"
+        );
+        assert_eq!(namespaces[3].measured, 0);
+    }
+}
@@ -0,0 +1,225 @@
+//! This module finds out on which platforms every dependency gets compiled,
+//! by following the platform-specific dependencies (`[target.'cfg(windows)'.dependencies]`)
+//! declared along the dependency graph.
+//!
+//! A dependency reachable without crossing any platform-specific edge is compiled everywhere.
+//! Otherwise, it is attributed the first platform gates (cfg expressions or target triples) met on the way,
+//! which is enough to tell that e.g. a whole `windows-sys` subtree is only compiled on windows.
+
+use anyhow::{ensure, Result};
+use semver::Version;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::audit;
+
+//
+// Output of cargo metadata
+//
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+    resolve: Option<Resolve>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    version: Version,
+}
+
+#[derive(Deserialize)]
+struct Resolve {
+    nodes: Vec<Node>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    id: String,
+    #[serde(default)]
+    deps: Vec<NodeDep>,
+}
+
+#[derive(Deserialize)]
+struct NodeDep {
+    pkg: String,
+    #[serde(default)]
+    dep_kinds: Vec<DepKind>,
+}
+
+#[derive(Deserialize)]
+struct DepKind {
+    target: Option<String>,
+}
+
+/// How a package is reached from the workspace.
+#[derive(Clone, PartialEq)]
+enum Reach {
+    /// on every platform
+    Always,
+    /// only on the given platforms
+    Gated(BTreeSet<String>),
+}
+
+impl Reach {
+    /// Merges another way of reaching a package, returns true if it changed anything.
+    fn merge(&mut self, other: &Reach) -> bool {
+        match (&mut *self, other) {
+            (Reach::Always, _) => false,
+            (_, Reach::Always) => {
+                *self = Reach::Always;
+                true
+            }
+            (Reach::Gated(gates), Reach::Gated(other_gates)) => {
+                let len = gates.len();
+                gates.extend(other_gates.iter().cloned());
+                gates.len() != len
+            }
+        }
+    }
+}
+
+/// Returns the platform gates of every (non-workspace) package of a workspace.
+/// An empty list means that the package is compiled on every platform.
+pub async fn get_platforms(repo_dir: &Path) -> Result<HashMap<(String, Version), Vec<String>>> {
+    audit::command(
+        None,
+        "cargo",
+        &[
+            "metadata",
+            "--format-version",
+            "1",
+            "--manifest-path",
+            &repo_dir.join("Cargo.toml").to_string_lossy(),
+        ],
+    );
+    let output = Command::new("cargo")
+        .args(&["metadata", "--format-version", "1", "--manifest-path"])
+        .arg(repo_dir.join("Cargo.toml"))
+        .output()
+        .await?;
+    ensure!(
+        output.status.success(),
+        "couldn't run cargo metadata: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let metadata: Metadata = serde_json::from_slice(&output.stdout)?;
+    Ok(platforms(&metadata))
+}
+
+fn platforms(metadata: &Metadata) -> HashMap<(String, Version), Vec<String>> {
+    let nodes: HashMap<&str, &Node> = match &metadata.resolve {
+        Some(resolve) => resolve
+            .nodes
+            .iter()
+            .map(|node| (node.id.as_str(), node))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    // propagate from the workspace members until nothing changes
+    let mut reaches: HashMap<&str, Reach> = HashMap::new();
+    let mut worklist: Vec<&str> = Vec::new();
+    for member in &metadata.workspace_members {
+        reaches.insert(member, Reach::Always);
+        worklist.push(member);
+    }
+    while let Some(id) = worklist.pop() {
+        let node = match nodes.get(id) {
+            Some(node) => node,
+            None => continue,
+        };
+        let reach = reaches[id].clone();
+        for dep in &node.deps {
+            // an edge is platform-specific if every kind of dependency is
+            let gates: Option<BTreeSet<String>> = dep
+                .dep_kinds
+                .iter()
+                .map(|kind| kind.target.clone())
+                .collect();
+            let contribution = match (&reach, gates) {
+                (Reach::Always, None) => Reach::Always,
+                (Reach::Always, Some(gates)) if gates.is_empty() => Reach::Always,
+                (Reach::Always, Some(gates)) => Reach::Gated(gates),
+                (Reach::Gated(gates), _) => Reach::Gated(gates.clone()),
+            };
+            let changed = match reaches.get_mut(dep.pkg.as_str()) {
+                Some(existing) => existing.merge(&contribution),
+                None => {
+                    reaches.insert(&dep.pkg, contribution);
+                    true
+                }
+            };
+            if changed {
+                worklist.push(&dep.pkg);
+            }
+        }
+    }
+
+    metadata
+        .packages
+        .iter()
+        .filter(|package| !metadata.workspace_members.contains(&package.id))
+        .filter_map(|package| {
+            let gates = match reaches.get(package.id.as_str())? {
+                Reach::Always => Vec::new(),
+                Reach::Gated(gates) => gates.iter().cloned().collect(),
+            };
+            Some(((package.name.clone(), package.version.clone()), gates))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platforms() {
+        let metadata = r#"{
+            "packages": [
+                {"id": "ws", "name": "ws", "version": "0.1.0"},
+                {"id": "a", "name": "a", "version": "1.0.0"},
+                {"id": "winapi", "name": "winapi", "version": "0.3.9"},
+                {"id": "winapi-x86", "name": "winapi-x86", "version": "0.4.0"},
+                {"id": "libc", "name": "libc", "version": "0.2.0"}
+            ],
+            "workspace_members": ["ws"],
+            "resolve": {"nodes": [
+                {"id": "ws", "deps": [
+                    {"pkg": "a", "dep_kinds": [{"kind": null, "target": null}]},
+                    {"pkg": "winapi", "dep_kinds": [{"kind": null, "target": "cfg(windows)"}]}
+                ]},
+                {"id": "a", "deps": [
+                    {"pkg": "libc", "dep_kinds": [{"kind": null, "target": "cfg(unix)"}]}
+                ]},
+                {"id": "winapi", "deps": [
+                    {"pkg": "winapi-x86", "dep_kinds": [{"kind": null, "target": null}]},
+                    {"pkg": "libc", "dep_kinds": [{"kind": null, "target": null}]}
+                ]},
+                {"id": "winapi-x86", "deps": []},
+                {"id": "libc", "deps": []}
+            ]}
+        }"#;
+        let metadata: Metadata = serde_json::from_str(metadata).unwrap();
+        let platforms = platforms(&metadata);
+
+        let get = |name: &str| {
+            platforms
+                .iter()
+                .find(|((n, _), _)| n == name)
+                .map(|(_, gates)| gates.clone())
+                .unwrap()
+        };
+        assert!(get("a").is_empty());
+        assert_eq!(get("winapi"), vec!["cfg(windows)"]);
+        assert_eq!(get("winapi-x86"), vec!["cfg(windows)"]);
+        assert_eq!(get("libc"), vec!["cfg(unix)", "cfg(windows)"]);
+        assert!(!platforms.keys().any(|(name, _)| name == "ws"));
+    }
+}
@@ -0,0 +1,125 @@
+//! This module implements the first phase of a two-phase analysis:
+//! everything an analysis needs from the network (the RUSTSEC advisory database,
+//! the crates.io responses, the sources of the updated crates) is downloaded into the cache,
+//! so that [`RustAnalysis::get_dependencies`] can then run offline (see [`crate::sources::offline`]).
+//! This makes the duration of the analysis itself predictable,
+//! and lets CI pipelines run the network-heavy part separately.
+//!
+//! Note that GitHub (used for changelogs) is not prefetched.
+
+use anyhow::Result;
+use futures::{stream, StreamExt};
+use guppy_summaries::SummarySource;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+use super::{cargoaudit, diff, RustAnalysis, CONCURRENCY};
+use crate::cache;
+use crate::model::RepoConfig;
+use crate::sources::Source;
+
+/// What was fetched by [`RustAnalysis::prefetch`].
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PrefetchSummary {
+    /// was the RUSTSEC advisory database fetched?
+    pub advisory_db: bool,
+    /// number of crates.io dependencies whose metadata was fetched
+    pub cratesio_dependencies: usize,
+    /// the crate versions whose sources were downloaded (`<name>==<version>`)
+    pub crates: Vec<String>,
+    /// what could not be fetched
+    pub errors: Vec<String>,
+}
+
+impl RustAnalysis {
+    /// Downloads everything [`RustAnalysis::get_dependencies`] needs into the cache.
+    pub async fn prefetch(
+        repo_dir: &Path,
+        is_diem: bool,
+        config: &RepoConfig,
+    ) -> Result<PrefetchSummary> {
+        let mut summary = PrefetchSummary::default();
+
+        // the dependency graph (cargo downloads the dependencies it doesn't have yet)
+        let (mut rust_analysis, package_graph) =
+            Self::fetch(repo_dir, is_diem, &config.resolve_options).await?;
+        rust_analysis.mark_forks(&config.forked_crates);
+        rust_analysis.restrict(package_graph.as_ref(), &config.scope)?;
+        if !rust_analysis.resolve_options.lockfile_only {
+            if let Err(e) = cargoaudit::generate_lockfile(repo_dir).await {
+                summary.errors.push(e.to_string());
+            }
+        }
+
+        // RUSTSEC advisory database
+        if Source::Advisories.is_enabled() {
+            info!("prefetching the RUSTSEC advisory database");
+            match cargoaudit::advisory_db().await {
+                Ok(_) => summary.advisory_db = true,
+                Err(e) => summary.errors.push(e.to_string()),
+            }
+        }
+
+        if !Source::CratesIo.is_enabled() {
+            return Ok(summary);
+        }
+
+        // crates.io responses (cached as they are fetched)
+        info!("prefetching crates.io metadata");
+        let mut cratesio_dependencies: Vec<&str> = rust_analysis
+            .dependencies
+            .iter()
+            .filter(|dep| matches!(dep.repo, SummarySource::CratesIo))
+            .map(|dep| dep.name.as_str())
+            .collect();
+        cratesio_dependencies.dedup();
+        summary.cratesio_dependencies = cratesio_dependencies.len();
+        rust_analysis.updatable().await?;
+        rust_analysis.maintainers(None).await;
+
+        // sources of both versions of every update (they are diffed by the risk engine)
+        info!("prefetching the sources of the updated crates");
+        let crates: BTreeSet<String> = rust_analysis
+            .dependencies
+            .iter()
+            .filter_map(|dep| {
+                let update = dep.update.as_ref()?;
+                let latest = update.versions.last()?;
+                Some(vec![
+                    format!("{}=={}", dep.name, dep.version),
+                    format!("{}=={}", dep.name, latest),
+                ])
+            })
+            .flatten()
+            .filter(|crate_with_version| !cache::crates_dir().join(crate_with_version).exists())
+            .collect();
+        let crates_dir = cache::crates_dir();
+        let mut downloads = stream::iter(crates)
+            .map(|crate_with_version| {
+                let crates_dir = &crates_dir;
+                async move {
+                    let res = diff::download_cargo_crate(&crate_with_version, crates_dir).await;
+                    (crate_with_version, res)
+                }
+            })
+            .buffer_unordered(CONCURRENCY);
+        while let Some((crate_with_version, res)) = downloads.next().await {
+            match res {
+                Ok(()) => summary.crates.push(crate_with_version),
+                Err(e) => {
+                    // (a partial download must not be used by the analysis)
+                    let _ = fs::remove_dir_all(crates_dir.join(&crate_with_version));
+                    summary
+                        .errors
+                        .push(format!("couldn't download {}: {}", crate_with_version, e));
+                }
+            }
+        }
+        summary.crates.sort();
+
+        Ok(summary)
+    }
+}
@@ -0,0 +1,112 @@
+//! This module lists the procedural macros of the dependency graph.
+//! Proc macros are compiled for the host and executed by the compiler while building the workspace,
+//! with the same access to the machine as the build itself (files, network, credentials),
+//! so they deserve more scrutiny than the dependencies that only end up in the artifacts:
+//! who pulls them in, and who can publish them.
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    graph::{self, Link},
+    DependencyInfo,
+};
+
+/// A procedural macro of the dependency graph.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProcMacro {
+    pub name: String,
+    pub version: Version,
+    /// is it a direct dependency of the workspace?
+    pub direct: bool,
+    /// is it only used by dev-dependencies?
+    pub dev: bool,
+    /// the packages depending on it (e.g. `serde 1.0.123`), workspace members included
+    pub dependents: Vec<String>,
+    /// the workspace members it is compiled for (see [`DependencyInfo::linked_by`])
+    pub linked_by: Vec<String>,
+    /// the crates.io owners of the crate, who can publish new versions
+    pub owners: Vec<String>,
+}
+
+/// Lists the procedural macros among dependencies, with their dependents in the graph given by `links`.
+pub fn census(dependencies: &[DependencyInfo], links: &[Link]) -> Vec<ProcMacro> {
+    let mut proc_macros: BTreeMap<(&str, &Version), ProcMacro> = BTreeMap::new();
+    for dependency in dependencies.iter().filter(|dep| dep.proc_macro()) {
+        let proc_macro = proc_macros
+            .entry((dependency.name(), dependency.version()))
+            .or_insert_with(|| ProcMacro {
+                name: dependency.name().to_string(),
+                version: dependency.version().clone(),
+                direct: false,
+                dev: true,
+                dependents: Vec::new(),
+                linked_by: dependency.linked_by().to_vec(),
+                owners: dependency.owners().to_vec(),
+            });
+        proc_macro.direct |= dependency.direct();
+        proc_macro.dev &= dependency.dev();
+    }
+
+    proc_macros
+        .into_iter()
+        .map(|(_, mut proc_macro)| {
+            let id = graph::node_id(&proc_macro.name, &proc_macro.version);
+            let dependents: BTreeSet<&String> = links
+                .iter()
+                .filter(|link| link.to == id)
+                .map(|link| &link.from)
+                .collect();
+            proc_macro.dependents = dependents.into_iter().cloned().collect();
+            proc_macro
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guppy_summaries::SummarySource;
+
+    #[test]
+    fn test_census() {
+        let dependency = |name: &str, direct: bool, proc_macro: bool| {
+            let mut dependency = DependencyInfo::new(
+                name.to_string(),
+                Version::parse("1.0.0").unwrap(),
+                SummarySource::CratesIo,
+                false,
+                direct,
+            );
+            dependency.proc_macro = proc_macro;
+            dependency
+        };
+        let dependencies = vec![
+            dependency("serde", true, false),
+            dependency("serde_derive", false, true),
+            dependency("serde_derive", true, true),
+        ];
+        let link = |from: &str, to: &str| Link {
+            from: from.to_string(),
+            to: to.to_string(),
+            dev_only: false,
+            from_workspace: from == "service 0.1.0",
+        };
+        let links = vec![
+            link("service 0.1.0", "serde 1.0.0"),
+            link("service 0.1.0", "serde_derive 1.0.0"),
+            link("serde 1.0.0", "serde_derive 1.0.0"),
+        ];
+
+        let proc_macros = census(&dependencies, &links);
+        assert_eq!(proc_macros.len(), 1);
+        assert_eq!(proc_macros[0].name, "serde_derive");
+        assert!(proc_macros[0].direct);
+        assert!(!proc_macros[0].dev);
+        assert_eq!(
+            proc_macros[0].dependents,
+            vec!["serde 1.0.0".to_string(), "service 0.1.0".to_string()]
+        );
+    }
+}
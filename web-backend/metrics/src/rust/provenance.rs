@@ -0,0 +1,166 @@
+//! This module detects updates that change the repository declared by a crate.
+//! A project moving to another repository is usually benign (a transfer to a new organization),
+//! but it is also what a hijacked crate looks like,
+//! and it invalidates the provenance checks made against the old repository
+//! (see [`crate::model::RepoConfig::trusted_publishers`]).
+//!
+//! It also records the GitHub repositories that redirect to another one (renamed or transferred):
+//! a repository transferred to another owner is reported as a provenance note.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::common::github;
+
+/// A change of the `repository` field of the manifest between two versions of a crate.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RepositoryChange {
+    /// the repository declared by the current version
+    pub old: String,
+    /// the repository declared by the new version (none if it was removed)
+    pub new: Option<String>,
+    /// set if the crate was trusted because of the old repository,
+    /// and is not anymore because of the new one
+    #[serde(default)]
+    pub lost_trust: bool,
+}
+
+/// A declared GitHub repository that redirects to another one.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RepositoryRedirect {
+    /// the repository declared by the crate (`owner/name`)
+    pub declared: String,
+    /// the repository it redirects to (`owner/name`)
+    pub canonical: String,
+    /// the repository belongs to another owner (not merely renamed)
+    pub owner_changed: bool,
+}
+
+impl RepositoryRedirect {
+    /// Describes the redirect (e.g. `a/b redirects to c/b (transferred to another owner)`).
+    pub fn describe(&self) -> String {
+        format!(
+            "{} redirects to {}{}",
+            self.declared,
+            self.canonical,
+            if self.owner_changed {
+                " (transferred to another owner)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// Compares a declared repository URL with the full name (`owner/name`) of the GitHub repository
+/// the API returned for it, after following redirects.
+pub fn repository_redirect(declared_url: &str, canonical: &str) -> Option<RepositoryRedirect> {
+    let (owner, name) = github::repository_path(declared_url)?;
+    let declared = format!("{}/{}", owner, name);
+    if declared.to_lowercase() == canonical.to_lowercase() {
+        return None;
+    }
+    let canonical_owner = canonical.split('/').next().unwrap_or_default();
+    Some(RepositoryRedirect {
+        declared,
+        canonical: canonical.to_string(),
+        owner_changed: !owner.eq_ignore_ascii_case(canonical_owner),
+    })
+}
+
+/// Compares the repositories declared by two extracted crates.
+/// A repository added by the new version is not reported.
+pub fn repository_change(original_crate: &Path, new_crate: &Path) -> Option<RepositoryChange> {
+    let old = repository(original_crate)?;
+    let new = repository(new_crate);
+    let moved = match &new {
+        Some(new) => normalize(&old) != normalize(new),
+        None => true,
+    };
+    if moved {
+        Some(RepositoryChange {
+            old,
+            new,
+            lost_trust: false,
+        })
+    } else {
+        None
+    }
+}
+
+/// Reads the repository declared in the manifest of an extracted crate.
+fn repository(crate_dir: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(crate_dir.join("Cargo.toml")).ok()?;
+    let manifest: toml::Value = toml::from_str(&manifest).ok()?;
+    manifest
+        .get("package")?
+        .get("repository")?
+        .as_str()
+        .map(ToString::to_string)
+}
+
+/// Normalizes a repository URL, so that cosmetic changes (scheme, case, `.git` suffix, ...)
+/// are not reported.
+pub(super) fn normalize(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .trim_end_matches('/');
+    url.trim_end_matches(".git").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_repository_change() {
+        let write_manifest = |repository: Option<&str>| {
+            let dir = tempdir().unwrap();
+            let mut manifest = "[package]\nname = \"a\"\nversion = \"0.1.0\"\n".to_string();
+            if let Some(repository) = repository {
+                manifest.push_str(&format!("repository = \"{}\"\n", repository));
+            }
+            fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+            dir
+        };
+        let original = write_manifest(Some("https://github.com/a/a"));
+        let cosmetic = write_manifest(Some("https://www.github.com/A/a.git/"));
+        let moved = write_manifest(Some("https://github.com/b/a"));
+        let removed = write_manifest(None);
+
+        assert!(repository_change(original.path(), cosmetic.path()).is_none());
+        assert!(repository_change(removed.path(), original.path()).is_none());
+        assert_eq!(
+            repository_change(original.path(), moved.path()),
+            Some(RepositoryChange {
+                old: "https://github.com/a/a".to_string(),
+                new: Some("https://github.com/b/a".to_string()),
+                lost_trust: false,
+            })
+        );
+        assert_eq!(
+            repository_change(original.path(), removed.path()).map(|change| change.new),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn test_repository_redirect() {
+        assert!(repository_redirect(
+            "https://github.com/Servo/rust-smallvec.git",
+            "servo/rust-smallvec"
+        )
+        .is_none());
+        let renamed = repository_redirect("https://github.com/a/old", "a/new").unwrap();
+        assert!(!renamed.owner_changed);
+        assert_eq!(renamed.describe(), "a/old redirects to a/new");
+        let transferred = repository_redirect("https://github.com/a/lib", "b/lib").unwrap();
+        assert!(transferred.owner_changed);
+        assert!(repository_redirect("https://gitlab.com/a/lib", "b/lib").is_none());
+    }
+}
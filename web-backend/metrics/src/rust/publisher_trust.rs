@@ -0,0 +1,192 @@
+//! This module looks at the account security of the owners of a crate, as far as it can be seen from outside.
+//! crates.io doesn't expose whether its users enabled two-factor authentication (on GitHub, which it delegates
+//! logins to), so the following signals stand in for it:
+//!
+//! - the team owners (e.g. `github:rust-lang:libs`) are managed by a GitHub organization,
+//!   which can require two-factor authentication from its members,
+//! - a crate owned by a single user can be taken over with a single account,
+//! - the organization hosting the repository can be verified (it proved it owns its domains),
+//! - the publisher of a version should be a (public) member of the organization hosting the repository.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::cratesio;
+use crate::common::github;
+use crate::http::{self, Transport};
+
+/// An owner of a crate on crates.io.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OwnerTrust {
+    /// the login of a user (e.g. `dtolnay`), or of a team (e.g. `github:rust-lang:libs`)
+    pub login: String,
+    /// the owner is a team of a GitHub organization
+    pub team: bool,
+    /// the user is a public member of the organization hosting the repository
+    /// (teams belong to their organization)
+    pub organization_member: bool,
+}
+
+/// What can be said about the account security of the owners of a crate.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PublisherTrust {
+    pub owners: Vec<OwnerTrust>,
+    /// the GitHub organization hosting the repository (none if the repository belongs to a user)
+    pub organization: Option<String>,
+    /// the organization proved that it owns its domains
+    pub verified_organization: bool,
+    /// the crates.io user who published the version (not recorded for old versions)
+    pub publisher: Option<String>,
+}
+
+impl PublisherTrust {
+    /// Returns the concerns about the owners, from the most to the least serious.
+    pub fn concerns(&self) -> Vec<String> {
+        let mut concerns = Vec::new();
+        let users: Vec<&OwnerTrust> = self.owners.iter().filter(|owner| !owner.team).collect();
+        let has_team = users.len() < self.owners.len();
+        if !has_team && users.len() == 1 {
+            concerns.push(format!(
+                "a single user ({}) owns the crate, a single compromised account can publish it",
+                users[0].login
+            ));
+        } else if !has_team && !users.is_empty() {
+            concerns.push(
+                "no team owns the crate, the security of the owners isn't enforced by an organization"
+                    .to_string(),
+            );
+        }
+        if let (Some(organization), Some(publisher)) = (&self.organization, &self.publisher) {
+            let member = self
+                .owners
+                .iter()
+                .any(|owner| &owner.login == publisher && owner.organization_member);
+            if !member {
+                concerns.push(format!(
+                    "the publisher ({}) isn't a public member of the organization hosting the repository ({})",
+                    publisher, organization
+                ));
+            }
+        }
+        if let Some(organization) = &self.organization {
+            if !self.verified_organization {
+                concerns.push(format!(
+                    "the organization hosting the repository ({}) isn't verified",
+                    organization
+                ));
+            }
+        }
+        concerns
+    }
+}
+
+/// Looks at the owners of a crate (their crates.io logins) and the publisher of a version,
+/// given the GitHub owner of the repository if any (see [`github::repository_owner`]).
+pub async fn publisher_trust(
+    owners: &[String],
+    publisher: Option<String>,
+    repository_owner: Option<&str>,
+) -> Result<PublisherTrust> {
+    let transport = http::default_transport("whackadep")?;
+    publisher_trust_with(&*transport, owners, publisher, repository_owner).await
+}
+
+/// Same as [`publisher_trust`], with the given transport (e.g. a mock, see [`crate::http`]).
+pub async fn publisher_trust_with(
+    transport: &dyn Transport,
+    owners: &[String],
+    publisher: Option<String>,
+    repository_owner: Option<&str>,
+) -> Result<PublisherTrust> {
+    // (the repository can belong to a user, which isn't an organization)
+    let organization = match repository_owner {
+        Some(owner) => github::get_organization_with(transport, owner).await?,
+        None => None,
+    };
+    let mut trust = PublisherTrust {
+        organization: organization.as_ref().map(|org| org.login.clone()),
+        verified_organization: organization.map(|org| org.is_verified).unwrap_or(false),
+        publisher,
+        ..Default::default()
+    };
+    for login in owners {
+        let team = login.starts_with("github:");
+        let organization_member = match &trust.organization {
+            Some(organization) if team => {
+                cratesio::organization(login).eq_ignore_ascii_case(organization)
+            }
+            // (crates.io users log in with GitHub, so they have the same login)
+            Some(organization) => {
+                github::is_public_member_with(transport, organization, login).await?
+            }
+            None => false,
+        };
+        trust.owners.push(OwnerTrust {
+            login: login.clone(),
+            team,
+            organization_member,
+        });
+    }
+    Ok(trust)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{MockTransport, Response};
+
+    #[tokio::test]
+    async fn test_publisher_trust() {
+        let transport = MockTransport::new()
+            .with_response(
+                "https://api.github.com/orgs/servo",
+                Response::ok(r#"{ "login": "servo", "is_verified": false }"#),
+            )
+            .with_response(
+                "https://api.github.com/orgs/servo/public_members/mbrubeck",
+                Response::empty(204),
+            )
+            .with_response(
+                "https://api.github.com/orgs/servo/public_members/newcomer",
+                Response::empty(404),
+            );
+        let owners = vec![
+            "mbrubeck".to_string(),
+            "newcomer".to_string(),
+            "github:servo:cargo-publish".to_string(),
+        ];
+        let trust = publisher_trust_with(
+            &transport,
+            &owners,
+            Some("newcomer".to_string()),
+            Some("servo"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(trust.organization.as_deref(), Some("servo"));
+        assert!(trust.owners[0].organization_member);
+        assert!(!trust.owners[1].organization_member);
+        assert!(trust.owners[2].team && trust.owners[2].organization_member);
+        let concerns = trust.concerns();
+        assert_eq!(concerns.len(), 2);
+        assert!(concerns[0].starts_with("the publisher (newcomer) isn't a public member"));
+        assert!(concerns[1].ends_with("(servo) isn't verified"));
+
+        // a crate of a single user, hosted by the user
+        let transport = MockTransport::new()
+            .with_response("https://api.github.com/orgs/bob", Response::empty(404));
+        let trust = publisher_trust_with(
+            &transport,
+            &["bob".to_string()],
+            Some("bob".to_string()),
+            Some("bob"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(trust.organization, None);
+        assert_eq!(
+            trust.concerns(),
+            vec!["a single user (bob) owns the crate, a single compromised account can publish it"]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! This module estimates whether the code of the workspace actually calls into the functions
+//! mentioned by the RUSTSEC advisories affecting its dependencies (their `affected.functions`),
+//! to help prioritizing advisories.
+//!
+//! The call graph is coarse and lexical: the sources of the workspace members are scanned
+//! (without comments and literals, see [`super::unsafety`]) for calls to, or paths to,
+//! the affected functions, in files that also mention their crate or their type.
+//! Calls going through other dependencies are not followed, so an advisory without such a reference
+//! is only "not obviously" reachable.
+//! Nothing is built or executed. This is opt-in, see [`crate::model::RepoConfig::reachability`].
+//!
+//! The affected functions are also matched against the updates of the vulnerable dependencies
+//! (see [`changed_functions`]), to tell whether an update touches them.
+
+use rustsec::Vulnerability;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{lockfile::LocalPackage, unsafety};
+
+/// The label of an advisory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reachability {
+    /// the workspace references an affected function
+    LikelyReachable,
+    /// the workspace doesn't reference the affected functions directly
+    NotObviouslyReachable,
+    /// the advisory doesn't list the affected functions
+    Unknown,
+}
+
+impl Reachability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Reachability::LikelyReachable => "likely reachable",
+            Reachability::NotObviouslyReachable => "not obviously reachable",
+            Reachability::Unknown => "unknown",
+        }
+    }
+}
+
+/// A reference to an affected function in the workspace.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// the workspace member
+    pub member: String,
+    /// the file, relative to the directory of the member
+    pub file: String,
+    /// the affected function (as listed by the advisory)
+    pub function: String,
+}
+
+/// The reachability of an advisory affecting a dependency.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AdvisoryReachability {
+    pub id: String,
+    pub package: String,
+    /// the affected functions listed by the advisory (e.g. `smallvec::SmallVec::insert_many`)
+    pub functions: Vec<String>,
+    pub reachability: Reachability,
+    pub references: Vec<Reference>,
+}
+
+/// An affected function of an advisory, matched against the update of the vulnerable dependency
+/// and against the workspace.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FunctionMatch {
+    pub advisory: String,
+    /// the path of the function (e.g. `smallvec::SmallVec::insert_many`)
+    pub function: String,
+    /// the definition of the function changed between the current and the new version
+    pub changed: bool,
+    /// the workspace references the function (none if the reachability was not estimated)
+    pub referenced: Option<bool>,
+}
+
+impl FunctionMatch {
+    /// Describes the match (e.g. `smallvec::SmallVec::insert_many (changed by the update, referenced by the workspace)`).
+    pub fn describe(&self) -> String {
+        let mut matches = Vec::new();
+        matches.push(if self.changed {
+            "changed by the update"
+        } else {
+            "not changed by the update"
+        });
+        match self.referenced {
+            Some(true) => matches.push("referenced by the workspace"),
+            Some(false) => matches.push("not referenced by the workspace"),
+            None => (),
+        }
+        format!("{} ({})", self.function, matches.join(", "))
+    }
+}
+
+/// Returns the affected functions of the vulnerabilities: (package, advisory, function path).
+pub fn affected_functions(vulnerabilities: &[Vulnerability]) -> Vec<(String, String, String)> {
+    vulnerabilities
+        .iter()
+        .flat_map(|vuln| {
+            let functions: Vec<String> = vuln
+                .affected
+                .as_ref()
+                .map(|affected| affected.functions.keys().map(ToString::to_string).collect())
+                .unwrap_or_default();
+            functions.into_iter().map(move |function| {
+                (
+                    vuln.package.name.to_string(),
+                    vuln.advisory.id.to_string(),
+                    function,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the functions (given by their paths) whose definition changed
+/// in the rust files changed between two extracted crates.
+pub fn changed_functions(
+    original_crate: &Path,
+    new_crate: &Path,
+    changed_files: &[String],
+    functions: &[String],
+) -> Vec<String> {
+    let files: Vec<(Vec<char>, Vec<char>)> = changed_files
+        .iter()
+        .filter(|path| path.ends_with(".rs"))
+        .map(|path| {
+            let before = fs::read_to_string(original_crate.join(path)).unwrap_or_default();
+            let after = fs::read_to_string(new_crate.join(path)).unwrap_or_default();
+            (
+                unsafety::strip(&before).chars().collect(),
+                unsafety::strip(&after).chars().collect(),
+            )
+        })
+        .collect();
+    functions
+        .iter()
+        .filter(|function| {
+            let name = function.rsplit("::").next().unwrap_or_default().trim();
+            files
+                .iter()
+                .any(|(before, after)| definitions(before, name) != definitions(after, name))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the definitions of the functions with a given name in stripped code
+/// (from `fn` to the end of their body, whitespace normalized), sorted.
+fn definitions(code: &[char], name: &str) -> Vec<String> {
+    let mut definitions: Vec<String> = identifiers(code, name)
+        .into_iter()
+        .filter_map(|(start, end)| {
+            // (the `fn` keyword before the name)
+            let mut keyword_end = start;
+            while keyword_end > 0 && code[keyword_end - 1].is_whitespace() {
+                keyword_end -= 1;
+            }
+            let keyword_start = keyword_end.checked_sub(2)?;
+            if code[keyword_start..keyword_end] != ['f', 'n']
+                || (keyword_start > 0 && is_ident(code[keyword_start - 1]))
+            {
+                return None;
+            }
+            let mut depth = 0;
+            let mut body_end = code.len();
+            for (i, c) in code.iter().enumerate().skip(end) {
+                match c {
+                    ';' if depth == 0 => {
+                        body_end = i + 1;
+                        break;
+                    }
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth <= 0 {
+                            body_end = i + 1;
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            let definition: String = code[keyword_start..body_end].iter().collect();
+            Some(definition.split_whitespace().collect::<Vec<_>>().join(" "))
+        })
+        .collect();
+    definitions.sort();
+    definitions
+}
+
+/// A rust file of the workspace, with comments and literals removed.
+pub struct SourceFile {
+    member: String,
+    file: String,
+    code: String,
+}
+
+/// Reads the rust files of the workspace members
+/// (each file belongs to the member with the closest manifest).
+pub(crate) fn workspace_sources(repo_dir: &Path, packages: &[LocalPackage]) -> Vec<SourceFile> {
+    let members: Vec<(PathBuf, String)> = packages
+        .iter()
+        .filter_map(|package| {
+            let dir = package.manifest_path.parent()?;
+            Some((dir.to_path_buf(), package.name.to_string()))
+        })
+        .collect();
+
+    let mut sources = Vec::new();
+    let mut dirs = vec![repo_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() {
+                if file_name != "target" && !file_name.starts_with('.') {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            if !file_name.ends_with(".rs") {
+                continue;
+            }
+            let member = members
+                .iter()
+                .filter(|(dir, _)| path.starts_with(dir))
+                .max_by_key(|(dir, _)| dir.components().count());
+            let (member_dir, member) = match member {
+                Some(member) => member,
+                None => continue,
+            };
+            if let Ok(content) = fs::read_to_string(&path) {
+                sources.push(SourceFile {
+                    member: member.clone(),
+                    file: path
+                        .strip_prefix(member_dir)
+                        .unwrap_or(&path)
+                        .to_string_lossy()
+                        .replace('\\', "/"),
+                    code: unsafety::strip(&content),
+                });
+            }
+        }
+    }
+    sources.sort_by(|a, b| (&a.member, &a.file).cmp(&(&b.member, &b.file)));
+    sources
+}
+
+/// Labels the vulnerabilities according to the references of the workspace to their affected functions.
+pub fn analyze(
+    vulnerabilities: &[Vulnerability],
+    sources: &[SourceFile],
+) -> Vec<AdvisoryReachability> {
+    vulnerabilities
+        .iter()
+        .map(|vuln| {
+            let functions: Vec<String> = vuln
+                .affected
+                .as_ref()
+                .map(|affected| affected.functions.keys().map(ToString::to_string).collect())
+                .unwrap_or_default();
+            let references: Vec<Reference> = functions
+                .iter()
+                .flat_map(|function| references(sources, function))
+                .collect();
+            let reachability = if functions.is_empty() {
+                Reachability::Unknown
+            } else if references.is_empty() {
+                Reachability::NotObviouslyReachable
+            } else {
+                Reachability::LikelyReachable
+            };
+            AdvisoryReachability {
+                id: vuln.advisory.id.to_string(),
+                package: vuln.package.name.to_string(),
+                functions,
+                reachability,
+                references,
+            }
+        })
+        .collect()
+}
+
+/// Finds the files referencing a function given by its path (e.g. `smallvec::SmallVec::insert_many`):
+/// the files calling it (or naming it in a path) that also mention its crate or its type.
+fn references(sources: &[SourceFile], function: &str) -> Vec<Reference> {
+    let segments: Vec<String> = function
+        .split("::")
+        .map(|segment| segment.trim().replace('-', "_"))
+        .collect();
+    let (name, krate) = match (segments.last(), segments.first()) {
+        (Some(name), Some(krate)) if segments.len() > 1 => (name, krate),
+        _ => return Vec::new(),
+    };
+    let parent = &segments[segments.len() - 2];
+    sources
+        .iter()
+        .filter(|source| {
+            let code: Vec<char> = source.code.chars().collect();
+            (!identifiers(&code, krate).is_empty() || !identifiers(&code, parent).is_empty())
+                && identifiers(&code, name)
+                    .into_iter()
+                    .any(|(start, end)| is_reference(&code, start, end))
+        })
+        .map(|source| Reference {
+            member: source.member.clone(),
+            file: source.file.clone(),
+            function: function.to_string(),
+        })
+        .collect()
+}
+
+/// Returns the (start, end) positions of an identifier in stripped code.
+fn identifiers(code: &[char], ident: &str) -> Vec<(usize, usize)> {
+    let ident: Vec<char> = ident.chars().collect();
+    if ident.is_empty() {
+        return Vec::new();
+    }
+    (0..code.len())
+        .filter(|&i| code[i..].starts_with(&ident))
+        .filter(|&i| i == 0 || !is_ident(code[i - 1]))
+        .filter(|&i| code.get(i + ident.len()).map_or(true, |c| !is_ident(*c)))
+        .map(|i| (i, i + ident.len()))
+        .collect()
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Is an identifier called (`f(`, `.f(`, `f::<`) or named in a path (`Type::f`, `use krate::f`)?
+fn is_reference(code: &[char], start: usize, end: usize) -> bool {
+    let next: String = code[end..]
+        .iter()
+        .filter(|c| !c.is_whitespace())
+        .take(3)
+        .collect();
+    let previous: String = code[..start]
+        .iter()
+        .rev()
+        .filter(|c| !c.is_whitespace())
+        .take(2)
+        .collect();
+    next.starts_with('(') || next.starts_with("::<") || previous == "::"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definitions() {
+        let code = |code: &str| -> Vec<char> { unsafety::strip(code).chars().collect() };
+        let before = code("impl A {\n    pub fn insert_many(&mut self) {\n        self.grow();\n    }\n}\nfn grow() {}");
+        let reformatted =
+            code("impl A {\n  pub fn insert_many(&mut self) { self.grow(); }\n}\nfn grow() {}");
+        let fixed = code("impl A {\n    pub fn insert_many(&mut self) {\n        self.reserve();\n    }\n}\nfn grow() {}");
+
+        assert_eq!(
+            definitions(&before, "insert_many"),
+            vec!["fn insert_many(&mut self) { self.grow(); }".to_string()]
+        );
+        // calls are not definitions
+        assert_eq!(
+            definitions(&before, "grow"),
+            vec!["fn grow() {}".to_string()]
+        );
+        assert_eq!(
+            definitions(&before, "insert_many"),
+            definitions(&reformatted, "insert_many")
+        );
+        assert_ne!(
+            definitions(&before, "insert_many"),
+            definitions(&fixed, "insert_many")
+        );
+    }
+
+    #[test]
+    fn test_references() {
+        let source = |file: &str, code: &str| SourceFile {
+            member: "service".to_string(),
+            file: file.to_string(),
+            code: unsafety::strip(code),
+        };
+        let sources = vec![
+            source(
+                "src/calls.rs",
+                "use smallvec::SmallVec;\nfn f(v: &mut SmallVec<[u8; 4]>) { v.insert_many(0, vec![1]); }",
+            ),
+            // only mentioned in a comment
+            source(
+                "src/comment.rs",
+                "use smallvec::SmallVec;\n// v.insert_many(0, x)\nfn g() {}",
+            ),
+            // another insert_many, in a file that doesn't use smallvec
+            source("src/other.rs", "fn h(v: &mut Other) { v.insert_many(0, 1); }"),
+            source("src/path.rs", "use smallvec::SmallVec;\nlet f = SmallVec::insert_many;"),
+        ];
+        let found = references(&sources, "smallvec::SmallVec::insert_many");
+        let files: Vec<&str> = found.iter().map(|r| r.file.as_str()).collect();
+        assert_eq!(files, vec!["src/calls.rs", "src/path.rs"]);
+        assert_eq!(found[0].member, "service");
+
+        assert!(references(&sources, "smallvec::SmallVec::grow").is_empty());
+        assert!(references(&sources, "insert_many").is_empty());
+    }
+}
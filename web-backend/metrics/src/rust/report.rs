@@ -0,0 +1,637 @@
+//! This module produces a standalone report about a single crate version,
+//! to help evaluating whether to adopt it as a new dependency.
+
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+use super::{
+    cargoaudit,
+    compare::{self, CrateComparison},
+    crate_name,
+    cratesio::Crates,
+    diff, fuzzing, geiger,
+    publisher_trust::{self, PublisherTrust},
+    unsafety,
+};
+use crate::common::github;
+use crate::dashboard::escape;
+use crate::locale::Locale;
+use crate::sources::Source;
+use crate::verbosity::Verbosity;
+
+/// number of releases listed in a report
+const RECENT_RELEASES: usize = 10;
+/// the name of the unsafe scanner of [`unsafety`]
+const INTERNAL_SCANNER: &str = "internal";
+/// number of files listed as unsafe hotspots
+const UNSAFE_HOTSPOTS: usize = 5;
+/// The environment variable letting crate reports count unsafe code with cargo-geiger (`1` or `true`).
+/// Reports are produced on demand for any crate, and cargo-geiger builds it (running its build script
+/// and proc macros), so only the internal scanner is used by default.
+pub const REPORT_GEIGER_VAR: &str = "REPORT_GEIGER";
+
+/// Do crate reports use cargo-geiger? (see [`REPORT_GEIGER_VAR`])
+fn report_geiger() -> bool {
+    std::env::var(REPORT_GEIGER_VAR)
+        .map(|geiger| geiger == "1" || geiger.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// An advisory published for the crate (past or present).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdvisorySummary {
+    pub id: String,
+    pub title: String,
+    pub date: String,
+    /// versions that are not affected by the advisory
+    pub patched: Vec<String>,
+    /// versions that were never affected by the advisory
+    #[serde(default)]
+    pub unaffected: Vec<String>,
+    /// does the advisory affect the version being reported on?
+    pub affects_version: bool,
+}
+
+/// A release published on crates.io.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Release {
+    pub version: String,
+    pub created_at: String,
+}
+
+/// Information about the source code published on crates.io.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SourceInfo {
+    /// the git commit the crate was published from (from `.cargo_vcs_info.json`)
+    pub vcs_commit: Option<String>,
+    /// does the crate have a build script?
+    pub build_rs: bool,
+    /// number of rust files
+    pub rust_files: usize,
+    /// number of lines of rust code (including comments)
+    pub rust_lines: usize,
+    /// number of unsafe usages found in the rust code (`unsafe` keywords for the internal scanner,
+    /// unsafe functions, expressions, impls, traits and methods for cargo-geiger)
+    pub unsafe_keywords: usize,
+    /// the scanner that produced the unsafe numbers (e.g. `cargo-geiger 0.11.0`, or `internal`)
+    #[serde(default = "internal_scanner")]
+    pub unsafe_scanner: String,
+    /// the cargo-fuzz targets included in the package (see [`fuzzing`])
+    #[serde(default)]
+    pub fuzz_targets: Vec<String>,
+    /// the path of the crate in its git repository (from `.cargo_vcs_info.json`, empty at the root)
+    #[serde(default)]
+    pub vcs_path: Option<String>,
+    /// the files with the most `unsafe` keywords (found by the internal scanner), where to start auditing
+    #[serde(default)]
+    pub unsafe_hotspots: Vec<UnsafeHotspot>,
+}
+
+/// A file of a crate containing unsafe code.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnsafeHotspot {
+    /// the path of the file, relative to the crate root
+    pub path: String,
+    pub unsafe_keywords: usize,
+    /// the lines with `unsafe` keywords
+    pub lines: Vec<usize>,
+}
+
+impl UnsafeHotspot {
+    /// Links to a line of the file in the repository the crate was published from (on GitHub),
+    /// or to the file on docs.rs.
+    pub fn url(
+        &self,
+        name: &str,
+        version: &str,
+        repository: Option<&str>,
+        source: &SourceInfo,
+        line: usize,
+    ) -> String {
+        let repository = repository
+            .map(|repository| repository.trim_end_matches('/').trim_end_matches(".git"))
+            .filter(|repository| repository.starts_with("https://github.com/"));
+        match (repository, &source.vcs_commit) {
+            (Some(repository), Some(commit)) => {
+                let prefix = match source.vcs_path.as_deref() {
+                    Some(path) if !path.is_empty() => format!("{}/", path.trim_end_matches('/')),
+                    _ => String::new(),
+                };
+                format!(
+                    "{}/blob/{}/{}{}#L{}",
+                    repository, commit, prefix, self.path, line
+                )
+            }
+            _ => format!(
+                "https://docs.rs/crate/{}/{}/source/{}",
+                name, version, self.path
+            ),
+        }
+    }
+}
+
+fn internal_scanner() -> String {
+    INTERNAL_SCANNER.to_string()
+}
+
+/// A complete report about a single crate version.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrateReport {
+    pub version: String,
+    pub metrics: CrateComparison,
+    pub advisories: Vec<AdvisorySummary>,
+    pub recent_releases: Vec<Release>,
+    pub source: Option<SourceInfo>,
+    /// the data sources that were disabled, the corresponding sections are missing
+    #[serde(default)]
+    pub disabled_sources: Vec<Source>,
+    /// the account security of the owners (see [`publisher_trust`])
+    #[serde(default)]
+    pub publisher_trust: Option<PublisherTrust>,
+}
+
+/// Checks a crate name and version received from a user,
+/// as they are used to download and extract the crate.
+pub fn validate(name: &str, version: Option<&str>) -> Result<()> {
+    ensure!(crate_name::is_valid(name), "invalid crate name: {}", name);
+    if let Some(version) = version {
+        semver::Version::parse(version)
+            .with_context(|| format!("invalid version of {}: {}", name, version))?;
+    }
+    Ok(())
+}
+
+impl CrateReport {
+    /// Produces a report for a crate version (the latest version if none is given).
+    pub async fn new(name: &str, version: Option<&str>) -> Result<Self> {
+        validate(name, version)?;
+
+        // general metrics
+        let mut metrics = compare::compare_crates(&[name.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("couldn't obtain metrics for {}", name))?;
+        let version = match version {
+            Some(version) => version.to_string(),
+            None => metrics
+                .latest_version
+                .clone()
+                .ok_or_else(|| anyhow!("couldn't find the latest version of {}", name))?,
+        };
+
+        // releases
+        let releases = if Source::CratesIo.is_enabled() {
+            Crates::get_all_versions(name).await?.versions
+        } else {
+            Vec::new()
+        };
+        let recent_releases = releases
+            .iter()
+            .take(RECENT_RELEASES)
+            .map(|release| Release {
+                version: release.num.clone(),
+                created_at: release.created_at.clone(),
+            })
+            .collect();
+
+        // owners
+        let publisher = releases
+            .iter()
+            .find(|release| release.num == version)
+            .and_then(|release| release.published_by.as_ref())
+            .map(|publisher| publisher.login.clone());
+        // (the repository may have moved since it was declared, GitHub returns where it is now)
+        let repository_owner = match &metrics.github {
+            Some(stats) => stats.full_name.split('/').next(),
+            None => metrics
+                .repository
+                .as_deref()
+                .and_then(github::repository_owner),
+        };
+        let publisher_trust = if Source::CratesIo.is_enabled() && Source::GitHub.is_enabled() {
+            match publisher_trust::publisher_trust(&metrics.owners, publisher, repository_owner)
+                .await
+            {
+                Ok(trust) => Some(trust),
+                Err(e) => {
+                    tracing::error!("couldn't look at the owners of {}: {}", name, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // advisories
+        let advisory_db = if Source::Advisories.is_enabled() {
+            Some(cargoaudit::advisory_db().await?)
+        } else {
+            None
+        };
+        let semver_version = rustsec::Version::parse(&version).ok();
+        let advisories = advisory_db
+            .iter()
+            .flat_map(|advisory_db| cargoaudit::advisories_for_crate(advisory_db, name))
+            .map(|advisory| AdvisorySummary {
+                id: advisory.metadata.id.to_string(),
+                title: advisory.metadata.title.clone(),
+                date: advisory.metadata.date.as_str().to_string(),
+                patched: advisory
+                    .versions
+                    .patched
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                unaffected: advisory
+                    .versions
+                    .unaffected
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                affects_version: semver_version
+                    .as_ref()
+                    .map(|version| advisory.versions.is_vulnerable(version))
+                    .unwrap_or(false),
+            })
+            .collect();
+
+        // source code (already analyzed with the metrics if the version is the latest one)
+        let latest_source = metrics
+            .code
+            .take()
+            .filter(|_| metrics.latest_version.as_deref() == Some(version.as_str()));
+        let source = match latest_source {
+            Some(source) => Some(source),
+            None => match SourceInfo::new(name, &version).await {
+                Ok(source) => Some(source),
+                Err(e) => {
+                    tracing::error!("couldn't analyze the source of {}: {}", name, e);
+                    None
+                }
+            },
+        };
+
+        Ok(Self {
+            version,
+            metrics,
+            advisories,
+            recent_releases,
+            source,
+            disabled_sources: Source::disabled(),
+            publisher_trust,
+        })
+    }
+
+    /// Renders the report as markdown
+    /// (only the metrics with [`Verbosity::Minimal`], and the raw metrics as well with [`Verbosity::Full`]).
+    pub fn to_markdown(&self, locale: &Locale, verbosity: Verbosity) -> String {
+        let mut markdown = String::new();
+        let _ = writeln!(markdown, "# {} {}\n", self.metrics.name, self.version);
+        if let Some(description) = &self.metrics.description {
+            let _ = writeln!(markdown, "> {}\n", description.trim());
+        }
+
+        if !self.disabled_sources.is_empty() {
+            let disabled: Vec<&str> = self.disabled_sources.iter().map(Source::as_str).collect();
+            let _ = writeln!(
+                markdown,
+                "> **Reduced report**: produced without the following data sources: {}.\n",
+                disabled.join(", ")
+            );
+        }
+
+        markdown.push_str("## Metrics\n\n");
+        markdown.push_str(&compare::render_markdown(&[self.metrics.clone()], locale));
+        if let Some(redirect) = &self.metrics.repository_redirect {
+            let _ = writeln!(
+                markdown,
+                "\n> **Provenance**: the declared repository {}.",
+                redirect.describe()
+            );
+        }
+        if verbosity == Verbosity::Minimal {
+            return markdown;
+        }
+
+        markdown.push_str("\n## Advisories\n\n");
+        if self.advisories.is_empty() {
+            markdown.push_str("No RUSTSEC advisory was ever published for this crate.\n");
+        }
+        for advisory in &self.advisories {
+            let _ = writeln!(
+                markdown,
+                "- [{id}](https://rustsec.org/advisories/{id}.html) ({}): {}{} (patched: {}{})",
+                locale.date_str(&advisory.date),
+                advisory.title,
+                if advisory.affects_version {
+                    " **affects this version**"
+                } else {
+                    ""
+                },
+                advisory.patched.join(", "),
+                if advisory.unaffected.is_empty() {
+                    String::new()
+                } else {
+                    format!("; unaffected: {}", advisory.unaffected.join(", "))
+                },
+                id = advisory.id,
+            );
+        }
+
+        if let Some(trust) = &self.publisher_trust {
+            markdown.push_str("\n## Publisher trust\n\n");
+            for owner in &trust.owners {
+                let _ = writeln!(
+                    markdown,
+                    "- {} ({}{})",
+                    owner.login,
+                    if owner.team { "team" } else { "user" },
+                    match &trust.organization {
+                        Some(organization) if owner.organization_member => {
+                            format!(", member of {}", organization)
+                        }
+                        _ => String::new(),
+                    }
+                );
+            }
+            if let Some(publisher) = &trust.publisher {
+                let _ = writeln!(markdown, "- version published by: {}", publisher);
+            }
+            if let Some(organization) = &trust.organization {
+                let _ = writeln!(
+                    markdown,
+                    "- repository hosted by the {}organization {}",
+                    if trust.verified_organization {
+                        "verified "
+                    } else {
+                        ""
+                    },
+                    organization
+                );
+            }
+            for concern in trust.concerns() {
+                let _ = writeln!(markdown, "- **concern**: {}", concern);
+            }
+            markdown.push_str(
+                "\n> crates.io doesn't tell whether the owners enabled two-factor authentication.\n",
+            );
+        }
+
+        markdown.push_str("\n## Source\n\n");
+        match &self.source {
+            Some(source) => {
+                let _ = writeln!(
+                    markdown,
+                    "- published from commit: {}",
+                    source
+                        .vcs_commit
+                        .as_deref()
+                        .unwrap_or("unknown (no `.cargo_vcs_info.json`)")
+                );
+                let _ = writeln!(markdown, "- build script: {}", source.build_rs);
+                let _ = writeln!(
+                    markdown,
+                    "- rust code: {} lines in {} files",
+                    locale.number(source.rust_lines as u64),
+                    locale.number(source.rust_files as u64)
+                );
+                let _ = writeln!(
+                    markdown,
+                    "- `unsafe` usages: {} (counted by {})",
+                    locale.number(source.unsafe_keywords as u64),
+                    source.unsafe_scanner
+                );
+                if !source.fuzz_targets.is_empty() {
+                    let _ = writeln!(
+                        markdown,
+                        "- fuzz targets: {}",
+                        source.fuzz_targets.join(", ")
+                    );
+                }
+                if !source.unsafe_hotspots.is_empty() {
+                    markdown.push_str("- `unsafe` hotspots (where to start auditing):\n");
+                }
+                for hotspot in &source.unsafe_hotspots {
+                    let url = |line| {
+                        hotspot.url(
+                            &self.metrics.name,
+                            &self.version,
+                            self.metrics.repository.as_deref(),
+                            source,
+                            line,
+                        )
+                    };
+                    let lines: Vec<String> = hotspot
+                        .lines
+                        .iter()
+                        .map(|&line| format!("[{}]({})", line, url(line)))
+                        .collect();
+                    let _ = writeln!(
+                        markdown,
+                        "  - [{}]({}): {} `unsafe` on lines {}",
+                        hotspot.path,
+                        url(hotspot.lines.first().copied().unwrap_or(1)),
+                        hotspot.unsafe_keywords,
+                        lines.join(", ")
+                    );
+                }
+            }
+            None => markdown.push_str("The source code could not be analyzed.\n"),
+        }
+
+        markdown.push_str("\n## Recent releases\n\n");
+        for release in &self.recent_releases {
+            let _ = writeln!(
+                markdown,
+                "- {} ({})",
+                release.version,
+                locale.date_str(&release.created_at)
+            );
+        }
+
+        if verbosity == Verbosity::Full {
+            markdown.push_str("\n## Raw metrics\n\n");
+            let raw = serde_json::json!({
+                "metrics": self.metrics,
+                "source": self.source,
+                "disabled_sources": self.disabled_sources,
+            });
+            let _ = writeln!(
+                markdown,
+                "```json\n{}\n```",
+                serde_json::to_string_pretty(&raw).unwrap_or_default()
+            );
+        }
+
+        markdown
+    }
+
+    /// Renders the report as a standalone HTML page.
+    pub fn to_html(&self, locale: &Locale, verbosity: Verbosity) -> String {
+        let mut html = String::new();
+        let _ = writeln!(
+            html,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{} {}</title></head><body>",
+            escape(&self.metrics.name),
+            escape(&self.version)
+        );
+        // the markdown is simple enough to be displayed as preformatted text
+        let _ = writeln!(
+            html,
+            "<pre>{}</pre>",
+            escape(&self.to_markdown(locale, verbosity))
+        );
+        html.push_str("</body></html>\n");
+        html
+    }
+}
+
+impl SourceInfo {
+    /// Downloads a crate version from crates.io and analyzes its source
+    /// (with cargo-geiger only if [`REPORT_GEIGER_VAR`] is set).
+    pub async fn new(name: &str, version: &str) -> Result<Self> {
+        validate(name, Some(version))?;
+        let out_dir = tempdir()?;
+        let crate_with_version = format!("{}=={}", name, version);
+        diff::download_cargo_crate(&crate_with_version, out_dir.path()).await?;
+        let crate_dir = out_dir.path().join(&crate_with_version);
+        let mut source = Self::from_dir(&crate_dir)?;
+
+        // use cargo-geiger if it's allowed and available, or keep the numbers of the internal scanner
+        let geiger_version = if report_geiger() && Source::Geiger.is_enabled() {
+            geiger::version().await
+        } else {
+            None
+        };
+        if let Some(geiger_version) = geiger_version {
+            match geiger::count_unsafe(&crate_dir, name).await {
+                Ok(count) => {
+                    source.unsafe_keywords = count as usize;
+                    source.unsafe_scanner = geiger_version;
+                }
+                Err(e) => tracing::warn!(
+                    "cargo geiger failed on {}, falling back to the internal scanner: {}",
+                    crate_with_version,
+                    e
+                ),
+            }
+        }
+        Ok(source)
+    }
+
+    /// Analyzes the source of an extracted crate.
+    pub fn from_dir(crate_dir: &Path) -> Result<Self> {
+        let mut source = SourceInfo {
+            build_rs: crate_dir.join("build.rs").exists(),
+            unsafe_scanner: internal_scanner(),
+            ..Default::default()
+        };
+
+        // .cargo_vcs_info.json is added by cargo publish when the crate is in a git repository
+        if let Ok(vcs_info) = fs::read_to_string(crate_dir.join(".cargo_vcs_info.json")) {
+            let vcs_info: serde_json::Value = serde_json::from_str(&vcs_info)?;
+            source.vcs_commit = vcs_info["git"]["sha1"].as_str().map(ToString::to_string);
+            source.vcs_path = vcs_info["path_in_vcs"].as_str().map(ToString::to_string);
+        }
+
+        source.fuzz_targets = fuzzing::fuzz_targets_in_dir(crate_dir);
+        source.scan(crate_dir, crate_dir)?;
+        source.unsafe_hotspots.sort_by(|a, b| {
+            b.unsafe_keywords
+                .cmp(&a.unsafe_keywords)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        source.unsafe_hotspots.truncate(UNSAFE_HOTSPOTS);
+        Ok(source)
+    }
+
+    /// Counts rust files, lines and `unsafe` keywords recursively,
+    /// and records the files containing unsafe code.
+    fn scan(&mut self, crate_dir: &Path, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.scan(crate_dir, &path)?;
+            } else if path.extension().map(|ext| ext == "rs").unwrap_or(false) {
+                let content = fs::read_to_string(&path)?;
+                self.rust_files += 1;
+                self.rust_lines += content.lines().count();
+                let unsafe_lines = unsafety::unsafe_lines(&content);
+                let unsafe_keywords = unsafe_lines.iter().map(|(_, count)| count).sum();
+                self.unsafe_keywords += unsafe_keywords;
+                if unsafe_keywords > 0 {
+                    self.unsafe_hotspots.push(UnsafeHotspot {
+                        path: path
+                            .strip_prefix(crate_dir)
+                            .unwrap_or(&path)
+                            .to_string_lossy()
+                            .replace('\\', "/"),
+                        unsafe_keywords,
+                        lines: unsafe_lines.into_iter().map(|(line, _)| line).collect(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("serde", None).is_ok());
+        assert!(validate("serde_json", Some("1.0.64")).is_ok());
+        assert!(validate("../../tmp/x", None).is_err());
+        assert!(validate("serde", Some("1.0.0/../../x")).is_err());
+        assert!(validate("serde", Some("latest")).is_err());
+    }
+
+    #[test]
+    fn test_source_info() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("src/lib.rs"),
+            "pub fn f() {\n    unsafe { g() }\n}\nunsafe fn g() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(".cargo_vcs_info.json"),
+            r#"{"git": {"sha1": "abcdef"}}"#,
+        )
+        .unwrap();
+
+        let source = SourceInfo::from_dir(dir.path()).unwrap();
+        assert_eq!(source.vcs_commit.as_deref(), Some("abcdef"));
+        assert!(!source.build_rs);
+        assert_eq!(source.rust_files, 1);
+        assert_eq!(source.rust_lines, 4);
+        assert_eq!(source.unsafe_keywords, 2);
+        assert_eq!(source.unsafe_scanner, INTERNAL_SCANNER);
+        assert_eq!(
+            source.unsafe_hotspots,
+            vec![UnsafeHotspot {
+                path: "src/lib.rs".to_string(),
+                unsafe_keywords: 2,
+                lines: vec![2, 4],
+            }]
+        );
+        assert_eq!(
+            source.unsafe_hotspots[0].url(
+                "foo",
+                "1.0.0",
+                Some("https://github.com/acme/foo.git"),
+                &source,
+                2
+            ),
+            "https://github.com/acme/foo/blob/abcdef/src/lib.rs#L2"
+        );
+    }
+}
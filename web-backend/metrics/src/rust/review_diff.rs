@@ -0,0 +1,263 @@
+//! This module compares a review of a group of updates with the one posted before
+//! (e.g. when a dependabot PR is force-pushed or rebased),
+//! so that the updated PR comment leads with what changed (new findings, resolved findings)
+//! instead of making reviewers read an identical wall of text again.
+//! The reviews posted on a PR are recorded as [`PostedReview`]s (see [`crate::model::Reviews`]).
+
+use chrono::{DateTime, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::{
+    theme::MarkdownTheme,
+    verdict::{GroupVerdict, Verdict},
+};
+
+/// The last review posted on a pull request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostedReview {
+    pub repository: String,
+    /// the pull request (or any key identifying the successive reviews of a group of updates)
+    pub pull_request: String,
+    pub review: GroupVerdict,
+    pub posted: Option<DateTime<Utc>>,
+}
+
+impl PostedReview {
+    pub fn new(repository: &str, pull_request: &str, review: &GroupVerdict) -> Self {
+        Self {
+            repository: repository.to_string(),
+            pull_request: pull_request.to_string(),
+            review: review.clone(),
+            posted: Some(Utc::now()),
+        }
+    }
+}
+
+/// A finding of the review of an update (one of its reasons).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub name: String,
+    /// the version the update goes to
+    pub to: Version,
+    /// the verdict of the update
+    pub verdict: Verdict,
+    pub reason: String,
+}
+
+/// An update whose new version changed since the previous review (e.g. dependabot bumped it again).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bump {
+    pub name: String,
+    pub previous: Version,
+    pub current: Version,
+}
+
+/// What changed between two reviews of the same group of updates.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ReviewDiff {
+    /// the overall verdicts (previous, current), if the verdict changed
+    pub verdict_change: Option<(Verdict, Verdict)>,
+    /// the updates that are new to the group (name and version)
+    pub added_updates: Vec<(String, Version)>,
+    /// the updates that left the group
+    pub removed_updates: Vec<String>,
+    pub bumps: Vec<Bump>,
+    /// the findings that weren't in the previous review
+    pub new_findings: Vec<Finding>,
+    /// the findings of the previous review that are gone
+    pub resolved_findings: Vec<Finding>,
+}
+
+/// The findings of a review, by (crate, reason).
+fn findings(review: &GroupVerdict) -> BTreeMap<(&str, &str), Finding> {
+    review
+        .updates
+        .iter()
+        .flat_map(|update| {
+            update.reasons.iter().map(move |reason| {
+                (
+                    (update.name.as_str(), reason.as_str()),
+                    Finding {
+                        name: update.name.clone(),
+                        to: update.to.clone(),
+                        verdict: update.verdict,
+                        reason: reason.clone(),
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+impl ReviewDiff {
+    /// Compares a review with the previous one.
+    /// A finding is identified by its crate and its reason, regardless of the version the update goes to.
+    pub fn new(previous: &GroupVerdict, current: &GroupVerdict) -> Self {
+        let previous_versions: BTreeMap<&str, &Version> = previous
+            .updates
+            .iter()
+            .map(|update| (update.name.as_str(), &update.to))
+            .collect();
+        let current_names: BTreeSet<&str> = current
+            .updates
+            .iter()
+            .map(|update| update.name.as_str())
+            .collect();
+
+        let mut diff = Self::default();
+        if previous.verdict != current.verdict {
+            diff.verdict_change = Some((previous.verdict, current.verdict));
+        }
+        for update in &current.updates {
+            match previous_versions.get(update.name.as_str()) {
+                None => diff
+                    .added_updates
+                    .push((update.name.clone(), update.to.clone())),
+                Some(&version) if *version != update.to => diff.bumps.push(Bump {
+                    name: update.name.clone(),
+                    previous: version.clone(),
+                    current: update.to.clone(),
+                }),
+                Some(_) => (),
+            }
+        }
+        diff.removed_updates = previous_versions
+            .keys()
+            .filter(|name| !current_names.contains(*name))
+            .map(ToString::to_string)
+            .collect();
+
+        let previous_findings = findings(previous);
+        let mut current_findings = findings(current);
+        for (key, finding) in previous_findings {
+            // (the findings of a removed update go with it)
+            if current_findings.remove(&key).is_none() && current_names.contains(key.0) {
+                diff.resolved_findings.push(finding);
+            }
+        }
+        diff.new_findings = current_findings
+            .into_iter()
+            .map(|(_, finding)| finding)
+            .collect();
+        diff
+    }
+
+    /// Returns true if the reviews are identical (as far as the comment goes).
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Renders the changes as a markdown section, to lead the updated review.
+    pub fn to_markdown(&self, theme: &MarkdownTheme) -> String {
+        let mut markdown = String::from("### Since the last review\n\n");
+        if self.is_empty() {
+            markdown.push_str("Nothing changed.\n\n");
+            return markdown;
+        }
+        if let Some((previous, current)) = self.verdict_change {
+            let _ = writeln!(
+                markdown,
+                "- verdict: {} → {}",
+                theme.icon(previous),
+                theme.icon(current)
+            );
+        }
+        for (name, version) in &self.added_updates {
+            let _ = writeln!(markdown, "- new update: **{}** {}", name, version);
+        }
+        for name in &self.removed_updates {
+            let _ = writeln!(markdown, "- update no longer in the group: **{}**", name);
+        }
+        for bump in &self.bumps {
+            let _ = writeln!(
+                markdown,
+                "- **{}** now goes to {} (was {})",
+                bump.name, bump.current, bump.previous
+            );
+        }
+        for finding in &self.new_findings {
+            let _ = writeln!(
+                markdown,
+                "- {} new: **{}** {}",
+                theme.icon(finding.verdict),
+                finding.name,
+                finding.reason
+            );
+        }
+        for finding in &self.resolved_findings {
+            let _ = writeln!(
+                markdown,
+                "- {} resolved: **{}** ~~{}~~",
+                theme.icon(Verdict::Pass),
+                finding.name,
+                finding.reason
+            );
+        }
+        markdown.push('\n');
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rust::fixtures;
+
+    #[test]
+    fn test_review_diff() {
+        let previous = fixtures::group_verdict();
+        let diff = ReviewDiff::new(&previous, &previous);
+        assert!(diff.is_empty());
+        assert_eq!(
+            diff.to_markdown(&MarkdownTheme::default()),
+            "### Since the last review\n\nNothing changed.\n\n"
+        );
+
+        // the failing update is bumped to a version without its finding, another update joins the group
+        let mut updates = previous.updates.clone();
+        let failing = updates
+            .iter_mut()
+            .find(|update| update.verdict == Verdict::Fail)
+            .unwrap();
+        let name = failing.name.clone();
+        let resolved = failing.reasons.remove(0);
+        failing.to = Version::parse("9.9.9").unwrap();
+        failing.verdict = Verdict::Warn;
+        let mut added = failing.clone();
+        added.name = "tokio".to_string();
+        added.reasons = vec!["build.rs changed".to_string()];
+        updates.push(added);
+        let current = GroupVerdict::from_updates(updates);
+
+        let diff = ReviewDiff::new(&previous, &current);
+        assert_eq!(diff.verdict_change, Some((Verdict::Fail, Verdict::Warn)));
+        assert_eq!(
+            diff.added_updates,
+            vec![("tokio".to_string(), Version::parse("9.9.9").unwrap())]
+        );
+        assert!(diff.removed_updates.is_empty());
+        assert_eq!(diff.bumps.len(), 1);
+        assert_eq!(diff.bumps[0].name, name);
+        assert_eq!(diff.resolved_findings.len(), 1);
+        assert_eq!(diff.resolved_findings[0].reason, resolved);
+        let new: Vec<&str> = diff
+            .new_findings
+            .iter()
+            .map(|finding| finding.name.as_str())
+            .collect();
+        assert_eq!(new, vec!["tokio"]);
+        let markdown = diff.to_markdown(&MarkdownTheme::default());
+        assert!(markdown.contains(&format!("~~{}~~", resolved)));
+        assert!(markdown.contains("new: **tokio** build.rs changed"));
+
+        // removing an update doesn't resolve its findings
+        let current = GroupVerdict::from_updates(previous.updates[..1].to_vec());
+        let diff = ReviewDiff::new(&previous, &current);
+        assert_eq!(diff.removed_updates.len(), 2);
+        assert!(diff.resolved_findings.is_empty());
+    }
+}
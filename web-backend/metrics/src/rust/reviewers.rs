@@ -0,0 +1,145 @@
+//! This module suggests who should review a group of updates, according to what their reviews found:
+//! a repository maps the categories of findings to reviewer groups (e.g. unsafe changes to a memory-safety team,
+//! build script changes to a build team, see [`crate::model::RepoConfig::reviewers`]),
+//! and the review of a group of updates lists the groups to assign (see [`super::verdict::GroupVerdict::assign`]),
+//! so that CI automation can request the right reviewers on the PR.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::verdict::UpdateVerdict;
+
+/// The kind of a finding of a review (see [`super::verdict::UpdateVerdict::categories`]).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum FindingCategory {
+    /// RUSTSEC advisories affecting the new version, or not fixed in the lockfile
+    Advisory,
+    /// changes to unsafe code
+    Unsafe,
+    /// build scripts, toolchain requirements and code running at build time
+    Build,
+    /// secrets added to the package
+    Secrets,
+    /// changes to the paths configured as risky (see [`super::risky_paths`])
+    RiskyFiles,
+    /// license changes
+    License,
+    /// where the crate comes from: repository, maintainers, forks
+    Provenance,
+    /// breaking version changes
+    Breaking,
+    /// jumps of the size of the package
+    Size,
+}
+
+impl FindingCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Advisory => "advisory",
+            Self::Unsafe => "unsafe",
+            Self::Build => "build",
+            Self::Secrets => "secrets",
+            Self::RiskyFiles => "risky-files",
+            Self::License => "license",
+            Self::Provenance => "provenance",
+            Self::Breaking => "breaking",
+            Self::Size => "size",
+        }
+    }
+}
+
+/// A reviewer group to assign, and why.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Assignment {
+    /// the reviewer (e.g. `@acme/memory-safety`)
+    pub reviewer: String,
+    /// the categories of findings that the reviewer is assigned for
+    pub categories: Vec<FindingCategory>,
+    /// the updates with these findings (e.g. `libc 0.2.86 → 0.2.87`)
+    pub updates: Vec<String>,
+}
+
+/// Lists the reviewers to assign to updates, `groups` mapping categories of findings to reviewers.
+pub fn assign(
+    updates: &[UpdateVerdict],
+    groups: &BTreeMap<FindingCategory, Vec<String>>,
+) -> Vec<Assignment> {
+    let mut assignments: BTreeMap<&str, (BTreeSet<FindingCategory>, Vec<String>)> = BTreeMap::new();
+    for update in updates {
+        let name = format!("{} {} → {}", update.name, update.from, update.to);
+        for category in &update.categories {
+            for reviewer in groups.get(category).into_iter().flatten() {
+                let (categories, updates) = assignments.entry(reviewer.as_str()).or_default();
+                categories.insert(*category);
+                if !updates.contains(&name) {
+                    updates.push(name.clone());
+                }
+            }
+        }
+    }
+    assignments
+        .into_iter()
+        .map(|(reviewer, (categories, updates))| Assignment {
+            reviewer: reviewer.to_string(),
+            categories: categories.into_iter().collect(),
+            updates,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rust::verdict::{UpdateDetails, Verdict};
+    use semver::Version;
+
+    #[test]
+    fn test_assign() {
+        let update = |name: &str, categories: Vec<FindingCategory>| UpdateVerdict {
+            name: name.to_string(),
+            from: Version::parse("1.0.0").unwrap(),
+            to: Version::parse("1.0.1").unwrap(),
+            verdict: Verdict::Warn,
+            reasons: Vec::new(),
+            owners: Vec::new(),
+            affects: Vec::new(),
+            details: UpdateDetails::default(),
+            build_time_change: None,
+            tags: Vec::new(),
+            categories,
+            violations: Vec::new(),
+        };
+        let updates = vec![
+            update(
+                "libc",
+                vec![FindingCategory::Build, FindingCategory::Unsafe],
+            ),
+            update("smallvec", vec![FindingCategory::Unsafe]),
+            update("itoa", vec![FindingCategory::Breaking]),
+        ];
+        let groups: BTreeMap<FindingCategory, Vec<String>> = serde_json::from_str(
+            r#"{"unsafe": ["@acme/memory-safety"], "build": ["@acme/build", "@acme/memory-safety"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            assign(&updates, &groups),
+            vec![
+                Assignment {
+                    reviewer: "@acme/build".to_string(),
+                    categories: vec![FindingCategory::Build],
+                    updates: vec!["libc 1.0.0 → 1.0.1".to_string()],
+                },
+                Assignment {
+                    reviewer: "@acme/memory-safety".to_string(),
+                    categories: vec![FindingCategory::Unsafe, FindingCategory::Build],
+                    updates: vec![
+                        "libc 1.0.0 → 1.0.1".to_string(),
+                        "smallvec 1.0.0 → 1.0.1".to_string()
+                    ],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! This module allows configuring which files of a dependency are considered risky when they change,
+//! so that the files changed by an update are triaged according to the threat model of a project
+//! (e.g. changes to `src/crypto/**` are escalated, changes to `**/fuzz/**` are ignored).
+
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Path patterns (globs, relative to the root of a crate) used to triage the files changed by an update.
+/// If a path matches several patterns, `exclude` wins over `escalate`, which wins over `flag`.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RiskyPathOptions {
+    /// changes to these paths are considered high risk (e.g. `src/crypto/**`)
+    pub escalate: Vec<String>,
+    /// changes to these paths are worth a look (e.g. `*.sh`)
+    pub flag: Vec<String>,
+    /// changes to these paths are ignored (e.g. `**/fuzz/**`)
+    pub exclude: Vec<String>,
+}
+
+/// How risky the change of a file is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Flagged,
+    Escalated,
+}
+
+/// A file changed by an update, that matches a risky path pattern.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RiskyFile {
+    pub path: String,
+    pub level: RiskLevel,
+}
+
+/// A compiled version of [`RiskyPathOptions`].
+pub struct RiskyPaths {
+    escalate: GlobSet,
+    flag: GlobSet,
+    exclude: GlobSet,
+}
+
+impl RiskyPaths {
+    pub fn new(options: &RiskyPathOptions) -> Result<Self> {
+        Ok(Self {
+            escalate: build_globset(&options.escalate)?,
+            flag: build_globset(&options.flag)?,
+            exclude: build_globset(&options.exclude)?,
+        })
+    }
+
+    /// Returns the risk level of a changed file, if it matches a risky path pattern.
+    pub fn level(&self, path: &str) -> Option<RiskLevel> {
+        if self.exclude.is_match(path) {
+            None
+        } else if self.escalate.is_match(path) {
+            Some(RiskLevel::Escalated)
+        } else if self.flag.is_match(path) {
+            Some(RiskLevel::Flagged)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the changed files that match a risky path pattern.
+    pub fn triage(&self, changed_files: &[String]) -> Vec<RiskyFile> {
+        changed_files
+            .iter()
+            .filter_map(|path| {
+                self.level(path).map(|level| RiskyFile {
+                    path: path.clone(),
+                    level,
+                })
+            })
+            .collect()
+    }
+}
+
+fn build_globset(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    builder.build().map_err(anyhow::Error::msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_risky_paths() {
+        let risky_paths = RiskyPaths::new(&RiskyPathOptions {
+            escalate: vec!["src/crypto/**".to_string()],
+            flag: vec!["*.sh".to_string(), "src/**".to_string()],
+            exclude: vec!["**/fuzz/**".to_string()],
+        })
+        .unwrap();
+        assert_eq!(
+            risky_paths.level("src/crypto/aes.rs"),
+            Some(RiskLevel::Escalated)
+        );
+        assert_eq!(risky_paths.level("src/lib.rs"), Some(RiskLevel::Flagged));
+        assert_eq!(
+            risky_paths.level("scripts/install.sh"),
+            Some(RiskLevel::Flagged)
+        );
+        assert_eq!(risky_paths.level("src/crypto/fuzz/target.rs"), None);
+        assert_eq!(risky_paths.level("README.md"), None);
+
+        let changed_files = vec!["README.md".to_string(), "src/crypto/aes.rs".to_string()];
+        assert_eq!(
+            risky_paths.triage(&changed_files),
+            vec![RiskyFile {
+                path: "src/crypto/aes.rs".to_string(),
+                level: RiskLevel::Escalated
+            }]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! This module allows restricting an analysis to a subset of the dependencies,
+//! so that large workspaces can be analyzed faster (or more precisely).
+//! The scope is applied right after the dependency graph is obtained,
+//! so that every following step (updates, advisories, build.rs changes) honors it.
+
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Restricts the dependencies being analyzed.
+/// By default, every dependency is analyzed.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScopeOptions {
+    /// only analyze the direct dependencies of the workspace
+    pub only_direct: bool,
+    /// only analyze the crates whose name matches one of these globs (e.g. `tokio*`)
+    /// (every crate is included if empty)
+    pub include: Vec<String>,
+    /// do not analyze the crates whose name matches one of these globs
+    pub exclude: Vec<String>,
+    /// only analyze the dependencies that are at most this far from the workspace
+    /// (direct dependencies are at depth 1)
+    pub max_depth: Option<usize>,
+}
+
+/// A compiled version of [`ScopeOptions`].
+pub struct Scope {
+    only_direct: bool,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    max_depth: Option<usize>,
+}
+
+impl Scope {
+    pub fn new(options: &ScopeOptions) -> Result<Self> {
+        let include = if options.include.is_empty() {
+            None
+        } else {
+            Some(build_globset(&options.include)?)
+        };
+        Ok(Self {
+            only_direct: options.only_direct,
+            include,
+            exclude: build_globset(&options.exclude)?,
+            max_depth: options.max_depth,
+        })
+    }
+
+    /// Returns true if a dependency is in scope.
+    /// The depth of the dependency is not always known (in which case `max_depth` is not enforced).
+    pub fn contains(&self, name: &str, direct: bool, depth: Option<usize>) -> bool {
+        if self.only_direct && !direct {
+            return false;
+        }
+        if let Some(include) = &self.include {
+            if !include.is_match(name) {
+                return false;
+            }
+        }
+        if self.exclude.is_match(name) {
+            return false;
+        }
+        match (self.max_depth, depth) {
+            (Some(max_depth), Some(depth)) => depth <= max_depth,
+            _ => true,
+        }
+    }
+
+    /// Returns true if the depth of the dependencies is needed to apply the scope.
+    pub fn needs_depth(&self) -> bool {
+        self.max_depth.is_some()
+    }
+}
+
+fn build_globset(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    builder.build().map_err(anyhow::Error::msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope() {
+        let scope = Scope::new(&ScopeOptions::default()).unwrap();
+        assert!(scope.contains("serde", false, Some(3)));
+
+        let scope = Scope::new(&ScopeOptions {
+            only_direct: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(scope.contains("serde", true, Some(1)));
+        assert!(!scope.contains("serde", false, Some(2)));
+
+        let scope = Scope::new(&ScopeOptions {
+            include: vec!["tokio*".to_string()],
+            exclude: vec!["tokio-macros".to_string()],
+            max_depth: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(scope.contains("tokio", true, Some(1)));
+        assert!(scope.contains("tokio-util", false, None));
+        assert!(!scope.contains("tokio-macros", false, Some(2)));
+        assert!(!scope.contains("tokio-stream", false, Some(3)));
+        assert!(!scope.contains("serde", true, Some(1)));
+    }
+}
@@ -0,0 +1,511 @@
+//! This module reviews the crates of a workspace before they are published (a "self review"),
+//! for projects whose crates are consumed as dependencies elsewhere:
+//! the update their users will get is reviewed the way this service reviews updates of dependencies,
+//! catching packaging mistakes before the release rather than in the PRs of the users.
+//!
+//! For each publishable member, the package built by `cargo package` (from the working tree) is diffed
+//! against the latest version published on crates.io (build scripts, unsafe code, secrets, risky files,
+//! size, repository, see [`UpdateVerdict`]), and checked against the git repository it is published from:
+//! a packaged file that is not committed (generated, forgotten, or modified locally)
+//! can't be reviewed from the repository the crate claims to come from.
+//! A version that is already published, but whose contents changed, needs a bump.
+//!
+//! Members with `publish = false`, never published, or unchanged since their version was published, are skipped.
+
+use anyhow::{ensure, Context, Result};
+use guppy_summaries::SummarySource;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use crate::audit;
+use crate::sources::Source;
+use crate::verbosity::Verbosity;
+
+use super::{
+    cratesio, diff,
+    reviewers::FindingCategory,
+    risky_paths::RiskyPaths,
+    theme::MarkdownTheme,
+    verdict::{GroupVerdict, UpdateVerdict, Verdict, Violation},
+    DependencyInfo, RustAnalysis, Update,
+};
+
+/// The files cargo generates in a package (the original manifest is kept as `Cargo.toml.orig`).
+pub(super) const GENERATED_FILES: [&str; 3] = ["Cargo.toml", "Cargo.lock", ".cargo_vcs_info.json"];
+
+/// The review of a workspace member about to be published.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemberReview {
+    /// the review of the update from the latest published version to the local one
+    pub verdict: UpdateVerdict,
+    /// the packaged files that are not committed (untracked, or changed since the last commit)
+    pub uncommitted_files: Vec<String>,
+}
+
+/// The self review of a workspace.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct SelfReview {
+    pub members: Vec<MemberReview>,
+    /// the members not reviewed, and why (e.g. `publish = false`)
+    pub skipped: Vec<(String, String)>,
+    /// the members that couldn't be reviewed, e.g. because they can't be packaged
+    pub errors: Vec<(String, String)>,
+}
+
+/// A workspace member, as listed by `cargo metadata`.
+#[derive(Deserialize, Debug)]
+pub(super) struct Member {
+    pub(super) name: String,
+    pub(super) version: Version,
+    pub(super) manifest_path: PathBuf,
+    /// the registries the member can be published to (none means any, empty means `publish = false`)
+    pub(super) publish: Option<Vec<String>>,
+    pub(super) description: Option<String>,
+    pub(super) license: Option<String>,
+    pub(super) license_file: Option<String>,
+    pub(super) repository: Option<String>,
+}
+
+impl Member {
+    /// Is the member published? (`publish = false` is listed as no registry)
+    pub(super) fn is_published(&self) -> bool {
+        self.publish
+            .as_ref()
+            .map_or(true, |registries| !registries.is_empty())
+    }
+
+    pub(super) fn dir(&self) -> Result<&Path> {
+        self.manifest_path.parent().context("invalid manifest path")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Metadata {
+    packages: Vec<Member>,
+}
+
+impl SelfReview {
+    /// The worst verdict of the members (failing if some couldn't be reviewed).
+    pub fn verdict(&self) -> Verdict {
+        if !self.errors.is_empty() {
+            return Verdict::Fail;
+        }
+        self.members
+            .iter()
+            .map(|member| member.verdict.verdict)
+            .max()
+            .unwrap_or(Verdict::Pass)
+    }
+
+    /// Renders the review as markdown, like the review of dependency updates (see [`GroupVerdict::to_markdown`]).
+    pub fn to_markdown(&self, theme: &MarkdownTheme, verbosity: Verbosity) -> String {
+        let updates = self
+            .members
+            .iter()
+            .map(|member| member.verdict.clone())
+            .collect();
+        let mut markdown = GroupVerdict::from_updates(updates).to_markdown(theme, verbosity);
+        if !self.errors.is_empty() {
+            let _ = writeln!(markdown, "\n**couldn't be reviewed**:\n");
+            for (name, error) in &self.errors {
+                let _ = writeln!(markdown, "- `{}`: {}", name, error);
+            }
+        }
+        if !self.skipped.is_empty() && verbosity != Verbosity::Minimal {
+            let _ = writeln!(markdown, "\n**skipped**:\n");
+            for (name, reason) in &self.skipped {
+                let _ = writeln!(markdown, "- `{}`: {}", name, reason);
+            }
+        }
+        markdown
+    }
+}
+
+/// Reviews the publishable members of the workspace of a repository against their published versions.
+pub async fn review(repo_dir: &Path, risky_paths: &RiskyPaths) -> Result<SelfReview> {
+    ensure!(
+        Source::CratesIo.is_enabled(),
+        "a self review compares the members with their versions published on crates.io, which can't be used"
+    );
+    let client = cratesio::Client::new()?;
+    let mut review = SelfReview::default();
+    for member in workspace_members(repo_dir).await? {
+        if !member.is_published() {
+            review
+                .skipped
+                .push((member.name, "publish = false".to_string()));
+            continue;
+        }
+        let published: Vec<Version> = match client.get_crate(&member.name).await {
+            Ok(crates) => crates
+                .versions
+                .iter()
+                .filter_map(|version| Version::parse(&version.num).ok())
+                .collect(),
+            Err(e) => {
+                debug!("couldn't get the versions of {}: {}", member.name, e);
+                review
+                    .skipped
+                    .push((member.name, "never published on crates.io".to_string()));
+                continue;
+            }
+        };
+        // (the baseline of a backport is the version it is backported to)
+        let baseline = match latest_before(&published, &member.version) {
+            Some(baseline) => baseline,
+            None => {
+                review.skipped.push((
+                    member.name.clone(),
+                    format!("no version up to {} published on crates.io", member.version),
+                ));
+                continue;
+            }
+        };
+
+        info!(
+            "reviewing {} {} against {}",
+            member.name, member.version, baseline
+        );
+        match review_member(&member, &baseline, risky_paths).await {
+            Ok(Some(member_review)) => review.members.push(member_review),
+            Ok(None) => review.skipped.push((
+                member.name,
+                format!("unchanged since {} was published", baseline),
+            )),
+            Err(e) => review.errors.push((member.name, format!("{:#}", e))),
+        }
+    }
+    Ok(review)
+}
+
+/// Returns the latest of the published versions up to a version.
+fn latest_before(published: &[Version], version: &Version) -> Option<Version> {
+    published
+        .iter()
+        .filter(|published| *published <= version)
+        .max()
+        .cloned()
+}
+
+/// Lists the members of the workspace of a repository.
+pub(super) async fn workspace_members(repo_dir: &Path) -> Result<Vec<Member>> {
+    let args = ["metadata", "--no-deps", "--format-version", "1"];
+    audit::command(Some(repo_dir), "cargo", &args);
+    let output = Command::new("cargo")
+        .current_dir(repo_dir)
+        .args(&args)
+        .output()
+        .await?;
+    ensure!(
+        output.status.success(),
+        "couldn't list the members of the workspace: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let metadata: Metadata = serde_json::from_slice(&output.stdout)
+        .context("couldn't parse the output of cargo metadata")?;
+    Ok(metadata.packages)
+}
+
+/// Reviews a member against a published version, returning none if its package didn't change.
+async fn review_member(
+    member: &Member,
+    baseline: &Version,
+    risky_paths: &RiskyPaths,
+) -> Result<Option<MemberReview>> {
+    let member_dir = member.dir()?;
+    let work_dir = tempdir()?;
+    let local_crate = work_dir.path().join("local");
+    let package = package(member, &work_dir.path().join("target")).await?;
+    diff::extract_package(&package, &local_crate).await?;
+    let published_crate = format!("{}=={}", member.name, baseline);
+    diff::download_cargo_crate(&published_crate, work_dir.path()).await?;
+    let published_crate = work_dir.path().join(published_crate);
+
+    let local_files = read_files(&local_crate)?;
+    if same_contents(&read_files(&published_crate)?, &local_files) {
+        return Ok(None);
+    }
+
+    // the update from the published version to the local one, as the users of the crate will get it
+    let crate_diff =
+        diff::diff_crate_dirs(&published_crate, &local_crate, None, risky_paths, &[]).await?;
+    let mut update = Update {
+        versions: vec![member.version.clone()],
+        ..Default::default()
+    };
+    update.apply_diff(crate_diff);
+    let mut dependency = DependencyInfo::new(
+        member.name.clone(),
+        baseline.clone(),
+        SummarySource::CratesIo,
+        false,
+        false,
+    );
+    dependency.update = Some(update);
+    let mut verdict = UpdateVerdict::new(&RustAnalysis::default(), &dependency)
+        .context("the update couldn't be reviewed")?;
+
+    if *baseline == member.version {
+        flag(
+            &mut verdict,
+            Verdict::Fail,
+            "version-already-published",
+            format!(
+                "{} is already published with different contents: the version needs a bump",
+                baseline
+            ),
+        );
+    }
+    let uncommitted_files = match uncommitted_files(member_dir).await {
+        Ok(uncommitted) => local_files
+            .keys()
+            .map(|path| source_path(path))
+            .filter(|path| uncommitted.contains(*path))
+            .map(ToString::to_string)
+            .collect(),
+        Err(e) => {
+            debug!(
+                "couldn't check the files of {} against git: {}",
+                member.name, e
+            );
+            Vec::new()
+        }
+    };
+    if !uncommitted_files.is_empty() {
+        flag(
+            &mut verdict,
+            Verdict::Warn,
+            "uncommitted-files-packaged",
+            format!(
+                "packages files that are not committed: {}",
+                uncommitted_files.join(", ")
+            ),
+        );
+    }
+
+    Ok(Some(MemberReview {
+        verdict,
+        uncommitted_files,
+    }))
+}
+
+/// Adds a provenance finding to a review.
+fn flag(verdict: &mut UpdateVerdict, level: Verdict, rule: &str, reason: String) {
+    verdict.verdict = verdict.verdict.max(level);
+    verdict.violations.push(Violation {
+        rule: rule.to_string(),
+        verdict: level,
+        category: Some(FindingCategory::Provenance),
+        message: reason.clone(),
+        evidence: None,
+    });
+    verdict.reasons.push(reason);
+    if !verdict.categories.contains(&FindingCategory::Provenance) {
+        verdict.categories.push(FindingCategory::Provenance);
+        verdict.categories.sort();
+    }
+}
+
+/// Builds the package of a member from the working tree (as `cargo publish` would, without uploading it).
+pub(super) async fn package(member: &Member, target_dir: &Path) -> Result<PathBuf> {
+    let manifest_path = member.manifest_path.to_string_lossy();
+    let target = target_dir.to_string_lossy();
+    // (uncommitted files are packaged, and reported)
+    let args: [&str; 7] = [
+        "package",
+        "--no-verify",
+        "--allow-dirty",
+        "--manifest-path",
+        &manifest_path,
+        "--target-dir",
+        &target,
+    ];
+    audit::command(None, "cargo", &args);
+    let output = Command::new("cargo").args(&args).output().await?;
+    ensure!(
+        output.status.success(),
+        "couldn't be packaged: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(target_dir
+        .join("package")
+        .join(format!("{}-{}.crate", member.name, member.version)))
+}
+
+/// Reads the files of an extracted crate, by path relative to the crate root.
+pub(super) fn read_files(crate_dir: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut files = BTreeMap::new();
+    let mut dirs = vec![crate_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let relative = path
+                .strip_prefix(crate_dir)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            files.insert(relative, fs::read(&path)?);
+        }
+    }
+    Ok(files)
+}
+
+/// Do two packages have the same contents? (the files generated by cargo depend on its version)
+fn same_contents(published: &BTreeMap<String, Vec<u8>>, local: &BTreeMap<String, Vec<u8>>) -> bool {
+    sources(published).eq(sources(local))
+}
+
+fn sources(files: &BTreeMap<String, Vec<u8>>) -> impl Iterator<Item = (&String, &Vec<u8>)> {
+    files
+        .iter()
+        .filter(|(path, _)| !GENERATED_FILES.contains(&path.as_str()))
+}
+
+/// Returns the path in the member directory of a packaged file.
+pub(super) fn source_path(path: &str) -> &str {
+    match path {
+        "Cargo.toml.orig" => "Cargo.toml",
+        path => path,
+    }
+}
+
+/// Lists the files of a directory (relative to it) that are untracked, or changed since the last commit.
+async fn uncommitted_files(dir: &Path) -> Result<BTreeSet<String>> {
+    let mut files = untracked_files(dir).await?;
+    files.extend(modified_files(dir).await?);
+    Ok(files)
+}
+
+/// Lists the files of a directory (relative to it) that are not tracked by git, nor ignored.
+pub(super) async fn untracked_files(dir: &Path) -> Result<BTreeSet<String>> {
+    git_files(dir, &["ls-files", "--others", "--exclude-standard"]).await
+}
+
+/// Lists the tracked files of a directory (relative to it) changed since the last commit, staged or not.
+pub(super) async fn modified_files(dir: &Path) -> Result<BTreeSet<String>> {
+    git_files(dir, &["diff", "--name-only", "--relative", "HEAD"]).await
+}
+
+/// Lists the files of a directory (relative to it) tracked by git.
+pub(super) async fn tracked_files(dir: &Path) -> Result<BTreeSet<String>> {
+    git_files(dir, &["ls-files"]).await
+}
+
+async fn git_files(dir: &Path, args: &[&str]) -> Result<BTreeSet<String>> {
+    audit::command(Some(dir), "git", args);
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .await?;
+    ensure!(
+        output.status.success(),
+        "couldn't run git {}: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(ToString::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn test_workspace_members() {
+        let dir = tempdir().unwrap();
+        testing::write_crate(
+            dir.path(),
+            &[
+                ("Cargo.toml", "[workspace]\nmembers = [\"a\", \"b\"]\n"),
+                (
+                    "a/Cargo.toml",
+                    "[package]\nname = \"a\"\nversion = \"0.2.0\"\n",
+                ),
+                ("a/src/lib.rs", ""),
+                (
+                    "b/Cargo.toml",
+                    "[package]\nname = \"b\"\nversion = \"0.1.0\"\npublish = false\n",
+                ),
+                ("b/src/lib.rs", ""),
+            ],
+        )
+        .unwrap();
+        let members = workspace_members(dir.path()).await.unwrap();
+        let members: Vec<(&str, String, Option<usize>)> = members
+            .iter()
+            .map(|member| {
+                (
+                    member.name.as_str(),
+                    member.version.to_string(),
+                    member.publish.as_ref().map(Vec::len),
+                )
+            })
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                ("a", "0.2.0".to_string(), None),
+                ("b", "0.1.0".to_string(), Some(0))
+            ]
+        );
+
+        let published: Vec<Version> = ["0.1.0", "0.2.0", "0.3.0"]
+            .iter()
+            .map(|version| Version::parse(version).unwrap())
+            .collect();
+        assert_eq!(
+            latest_before(&published, &Version::parse("0.2.1").unwrap()),
+            Some(Version::parse("0.2.0").unwrap())
+        );
+        assert_eq!(
+            latest_before(&published, &Version::parse("0.0.1").unwrap()),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_uncommitted_files() {
+        let dir = tempdir().unwrap();
+        testing::git_repo(
+            dir.path(),
+            &[&[
+                ("a/Cargo.toml", "[package]\nname = \"a\"\n"),
+                ("a/src/lib.rs", "pub fn f() {}\n"),
+                ("b/src/lib.rs", ""),
+            ]],
+        )
+        .await
+        .unwrap();
+        testing::write_crate(
+            dir.path(),
+            &[
+                ("a/src/lib.rs", "pub fn f() { g() }\n"),
+                ("a/src/generated.rs", "fn g() {}\n"),
+                ("b/src/lib.rs", "// changed\n"),
+            ],
+        )
+        .unwrap();
+
+        let files = uncommitted_files(&dir.path().join("a")).await.unwrap();
+        assert_eq!(
+            files.into_iter().collect::<Vec<_>>(),
+            vec!["src/generated.rs", "src/lib.rs"]
+        );
+        assert_eq!(source_path("Cargo.toml.orig"), "Cargo.toml");
+    }
+}
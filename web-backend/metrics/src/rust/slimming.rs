@@ -0,0 +1,83 @@
+//! This module reports the direct dependencies whose default features pull in crates
+//! that the workspace doesn't ask for: declaring them with `default-features = false`
+//! (and the features actually requested by the workspace) would compile less code.
+//! The dropped crates are found by resolving the graph again without the default features
+//! (see [`super::guppy::default_features_bloat`]), and their lines of code are counted
+//! when their sources are available locally (nothing is downloaded).
+//!
+//! Note that the code of the workspace might still rely on a default feature
+//! (e.g. `std`), which only a build can tell.
+
+use guppy_summaries::SummaryId;
+use serde::{Deserialize, Serialize};
+
+use super::{diff, report::SourceInfo};
+
+/// What disabling the default features of a direct dependency would save.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Slimming {
+    /// the default features of the dependency that the workspace doesn't request explicitly
+    pub default_features: Vec<String>,
+    /// the packages that would not be compiled anymore (e.g. `libc 0.2.86`)
+    pub dropped: Vec<String>,
+    /// the lines of rust code of the dropped packages
+    pub dropped_lines: usize,
+    /// the number of dropped packages whose sources were not available locally
+    /// (their lines are not counted)
+    pub uncounted: usize,
+}
+
+impl Slimming {
+    pub fn new(default_features: Vec<String>, dropped: &[SummaryId]) -> Self {
+        let mut slimming = Self {
+            default_features,
+            dropped: dropped
+                .iter()
+                .map(|id| format!("{} {}", id.name, id.version))
+                .collect(),
+            ..Default::default()
+        };
+        for id in dropped {
+            match diff::local_sources(&id.name, &id.version.to_string())
+                .and_then(|crate_dir| SourceInfo::from_dir(&crate_dir).ok())
+            {
+                Some(source) => slimming.dropped_lines += source.rust_lines,
+                None => slimming.uncounted += 1,
+            }
+        }
+        slimming
+    }
+
+    /// Describes the savings (e.g. `drops 2 crates (1200 lines of rust): a 0.1.0, b 0.2.0`).
+    pub fn describe(&self) -> String {
+        let mut lines = format!("{} lines of rust", self.dropped_lines);
+        if self.uncounted > 0 {
+            lines.push_str(&format!(", {} crates not counted", self.uncounted));
+        }
+        format!(
+            "drops {} crates ({}): {}",
+            self.dropped.len(),
+            lines,
+            self.dropped.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let slimming = Slimming {
+            default_features: vec!["std".to_string()],
+            dropped: vec!["a 0.1.0".to_string(), "b 0.2.0".to_string()],
+            dropped_lines: 1200,
+            uncounted: 1,
+        };
+        assert_eq!(
+            slimming.describe(),
+            "drops 2 crates (1200 lines of rust, 1 crates not counted): a 0.1.0, b 0.2.0"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! This module summarizes an analysis in a few numbers (see [`Statistics`]),
+//! so that dashboards don't have to recompute them from the dependencies.
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{report::SourceInfo, DependencyInfo};
+
+/// Aggregate statistics of an analysis.
+/// A dependency used in several ways (e.g. directly and transitively) is counted once.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Statistics {
+    /// the number of dependencies (distinct crate versions)
+    pub dependencies: usize,
+    /// the dependencies imported directly by the workspace
+    pub direct: usize,
+    /// the dependencies only imported transitively
+    pub transitive: usize,
+    /// the dependencies only used as dev-dependencies
+    pub dev: usize,
+    /// the dependencies with an update
+    pub updatable: usize,
+    /// the dependencies whose sources were available locally
+    /// (the source statistics below only cover them)
+    pub measured: usize,
+    /// the lines of rust code of the dependencies
+    pub rust_lines: usize,
+    /// the `unsafe` keywords in the code of the dependencies (see [`super::unsafety`])
+    pub unsafe_keywords: usize,
+    /// the dependencies with a build script
+    pub build_scripts: usize,
+    /// the RUSTSEC vulnerabilities affecting the dependencies
+    pub vulnerabilities: usize,
+    /// the RUSTSEC warnings (unmaintained, yanked, etc.) about the dependencies
+    pub warnings: usize,
+    /// the dependencies that are procedural macros (see [`super::proc_macros`])
+    #[serde(default)]
+    pub proc_macros: usize,
+}
+
+impl Statistics {
+    /// Computes the statistics of dependencies,
+    /// `source_of` returning the source information of a dependency if available locally.
+    pub fn new(
+        dependencies: &[DependencyInfo],
+        vulnerabilities: usize,
+        warnings: usize,
+        source_of: impl Fn(&str, &Version) -> Option<SourceInfo>,
+    ) -> Self {
+        // (direct, dev only, updatable, proc macro) per crate version
+        let mut crates: BTreeMap<(&str, &Version), (bool, bool, bool, bool)> = BTreeMap::new();
+        for dependency in dependencies {
+            let entry = crates
+                .entry((dependency.name(), dependency.version()))
+                .or_insert((false, true, false, false));
+            entry.0 |= dependency.direct();
+            entry.1 &= dependency.dev();
+            entry.2 |= dependency.update().is_some();
+            entry.3 |= dependency.proc_macro();
+        }
+
+        let mut statistics = Self {
+            dependencies: crates.len(),
+            vulnerabilities,
+            warnings,
+            ..Default::default()
+        };
+        for ((name, version), (direct, dev, updatable, proc_macro)) in crates {
+            if direct {
+                statistics.direct += 1;
+            } else {
+                statistics.transitive += 1;
+            }
+            if dev {
+                statistics.dev += 1;
+            }
+            if updatable {
+                statistics.updatable += 1;
+            }
+            if proc_macro {
+                statistics.proc_macros += 1;
+            }
+            if let Some(source) = source_of(name, version) {
+                statistics.measured += 1;
+                statistics.rust_lines += source.rust_lines;
+                statistics.unsafe_keywords += source.unsafe_keywords;
+                if source.build_rs {
+                    statistics.build_scripts += 1;
+                }
+            }
+        }
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guppy_summaries::SummarySource;
+
+    #[test]
+    fn test_statistics() {
+        let dependency = |name: &str, dev: bool, direct: bool| {
+            DependencyInfo::new(
+                name.to_string(),
+                Version::parse("1.0.0").unwrap(),
+                SummarySource::CratesIo,
+                dev,
+                direct,
+            )
+        };
+        let dependencies = vec![
+            dependency("a", false, true),
+            dependency("a", false, false),
+            dependency("b", true, true),
+            dependency("c", false, false),
+        ];
+        let statistics = Statistics::new(&dependencies, 1, 2, |name, _| {
+            if name == "c" {
+                return None;
+            }
+            Some(SourceInfo {
+                rust_lines: 100,
+                unsafe_keywords: 2,
+                build_rs: name == "a",
+                ..Default::default()
+            })
+        });
+        assert_eq!(
+            statistics,
+            Statistics {
+                dependencies: 3,
+                direct: 2,
+                transitive: 1,
+                dev: 1,
+                updatable: 0,
+                measured: 2,
+                rust_lines: 200,
+                unsafe_keywords: 4,
+                build_scripts: 1,
+                vulnerabilities: 1,
+                warnings: 2,
+                proc_macros: 0,
+            }
+        );
+    }
+}
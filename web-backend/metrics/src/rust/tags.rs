@@ -0,0 +1,124 @@
+//! This module lets a project tag its dependencies according to the trust boundary they sit on
+//! (e.g. `crypto`, `network`, `serialization`, `internal`), and apply stricter review rules to the sensitive tags:
+//! a one-line patch of a TLS implementation deserves more attention than a major bump of a CLI parser.
+//! Tags are carried by the dependencies (see [`super::DependencyInfo::tags`]) and shown in the reviews,
+//! and their policies are applied by the reviews (see [`super::verdict`]).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{crate_name, verdict::Verdict};
+
+/// The tags of the dependencies of a repository, and the rules applied to each tag.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct TagOptions {
+    /// the tags of crates (e.g. `ring: [crypto]`), crate names are matched like cargo does (`-` and `_` are the same)
+    pub crates: BTreeMap<String, Vec<String>>,
+    /// the rules applied to the updates of the crates with a tag (e.g. `crypto: { min_verdict: warn }`)
+    pub policies: BTreeMap<String, TagPolicy>,
+}
+
+/// The rules applied to the updates of the crates with a tag.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct TagPolicy {
+    /// the least verdict of any update, whatever its size (`warn` requires a manual review)
+    pub min_verdict: Option<Verdict>,
+    /// the updates that would be a warning fail instead
+    pub escalate_warnings: bool,
+}
+
+impl TagOptions {
+    /// Returns the tags of a crate (sorted, without duplicates).
+    pub fn tags_of(&self, name: &str) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .crates
+            .iter()
+            .filter(|(tagged, _)| crate_name::same_crate(tagged, name))
+            .flat_map(|(_, tags)| tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Applies the policies of tags to the verdict of an update, returning the new verdict
+    /// with the reasons of the changes (e.g. `crypto dependency: manual review required`).
+    pub fn apply(&self, tags: &[String], verdict: Verdict) -> (Verdict, Vec<String>) {
+        let rules = self.rules(tags, verdict);
+        let new_verdict = rules
+            .iter()
+            .map(|(_, level, _)| *level)
+            .fold(verdict, Verdict::max);
+        (
+            new_verdict,
+            rules.into_iter().map(|(_, _, reason)| reason).collect(),
+        )
+    }
+
+    /// Lists the rules of the policies of the tags that raise the verdict of an update,
+    /// as (rule identifier, verdict, reason) (see [`super::verdict::Violation`]).
+    pub fn rules(&self, tags: &[String], verdict: Verdict) -> Vec<(&'static str, Verdict, String)> {
+        let mut rules = Vec::new();
+        for tag in tags {
+            let policy = match self.policies.get(tag) {
+                Some(policy) => policy,
+                None => continue,
+            };
+            if policy.escalate_warnings && verdict == Verdict::Warn {
+                rules.push((
+                    "tag-escalate-warnings",
+                    Verdict::Fail,
+                    format!("{} dependency: warnings are failures", tag),
+                ));
+            }
+            if let Some(min_verdict) = policy.min_verdict.filter(|min| *min > verdict) {
+                rules.push((
+                    "tag-min-verdict",
+                    min_verdict,
+                    match min_verdict {
+                        Verdict::Warn => format!("{} dependency: manual review required", tag),
+                        _ => format!("{} dependency: at least {}", tag, min_verdict.as_str()),
+                    },
+                ));
+            }
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let options: TagOptions = serde_json::from_str(
+            r#"{
+                "crates": {"ring": ["crypto"], "rustls": ["crypto", "network"], "tokio": ["network"]},
+                "policies": {
+                    "crypto": {"min_verdict": "warn", "escalate_warnings": true},
+                    "network": {"min_verdict": "warn"}
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(options.tags_of("rustls"), vec!["crypto", "network"]);
+        assert!(options.tags_of("serde").is_empty());
+
+        // a small update of a crypto crate still requires a review
+        let (verdict, reasons) = options.apply(&options.tags_of("ring"), Verdict::Pass);
+        assert_eq!(verdict, Verdict::Warn);
+        assert_eq!(reasons, vec!["crypto dependency: manual review required"]);
+
+        // and its warnings are failures
+        let (verdict, reasons) = options.apply(&options.tags_of("rustls"), Verdict::Warn);
+        assert_eq!(verdict, Verdict::Fail);
+        assert_eq!(reasons, vec!["crypto dependency: warnings are failures"]);
+
+        let (verdict, reasons) = options.apply(&options.tags_of("serde"), Verdict::Pass);
+        assert_eq!(verdict, Verdict::Pass);
+        assert!(reasons.is_empty());
+    }
+}
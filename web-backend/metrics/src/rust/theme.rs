@@ -0,0 +1,96 @@
+//! This module lets organizations adapt the markdown produced by the reviews
+//! (see [`super::verdict::GroupVerdict::to_markdown`]) to the conventions of their review tooling:
+//! how verdicts are pictured (badges, emoji, or plain text) and the header of a review.
+
+use serde::{Deserialize, Serialize};
+
+use super::verdict::Verdict;
+
+/// How verdicts are pictured.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IconStyle {
+    /// shields.io badges (e.g. ![warn](https://img.shields.io/badge/verdict-warn-yellow))
+    Badge,
+    /// GitHub emoji (e.g. :warning:)
+    Emoji,
+    /// plain text, without images or emoji (e.g. `[WARN]`)
+    Text,
+}
+
+impl Default for IconStyle {
+    fn default() -> Self {
+        IconStyle::Badge
+    }
+}
+
+/// The markdown theme of a repository (see [`crate::model::RepoConfig::markdown_theme`]).
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MarkdownTheme {
+    /// how verdicts are pictured
+    pub style: IconStyle,
+    /// custom icons (any markdown) replacing the ones of the style, per verdict
+    pub pass_icon: Option<String>,
+    pub warn_icon: Option<String>,
+    pub fail_icon: Option<String>,
+    /// custom header text of a review, in which `{count}` is replaced by the number of updates
+    /// (by default `{count} updates reviewed`)
+    pub header: Option<String>,
+}
+
+impl MarkdownTheme {
+    const DEFAULT_HEADER: &'static str = "{count} updates reviewed";
+
+    /// Returns the icon of a verdict.
+    pub fn icon(&self, verdict: Verdict) -> String {
+        let custom = match verdict {
+            Verdict::Pass => &self.pass_icon,
+            Verdict::Warn => &self.warn_icon,
+            Verdict::Fail => &self.fail_icon,
+        };
+        if let Some(custom) = custom {
+            return custom.clone();
+        }
+        match self.style {
+            IconStyle::Badge => verdict.badge(),
+            IconStyle::Emoji => match verdict {
+                Verdict::Pass => ":white_check_mark:",
+                Verdict::Warn => ":warning:",
+                Verdict::Fail => ":x:",
+            }
+            .to_string(),
+            IconStyle::Text => format!("[{}]", verdict.as_str().to_uppercase()),
+        }
+    }
+
+    /// Returns the header of a review of `count` updates.
+    pub fn header(&self, count: usize) -> String {
+        self.header
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_HEADER)
+            .replace("{count}", &count.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme() {
+        let theme = MarkdownTheme::default();
+        assert!(theme.icon(Verdict::Warn).starts_with("![warn]"));
+        assert_eq!(theme.header(3), "3 updates reviewed");
+
+        let theme = MarkdownTheme {
+            style: IconStyle::Text,
+            fail_icon: Some("**BLOCKED**".to_string()),
+            header: Some("Dependency review ({count} crates)".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(theme.icon(Verdict::Pass), "[PASS]");
+        assert_eq!(theme.icon(Verdict::Fail), "**BLOCKED**");
+        assert_eq!(theme.header(2), "Dependency review (2 crates)");
+    }
+}
@@ -0,0 +1,223 @@
+//! This module detects changes in the toolchain required to build a crate between two of its versions:
+//! a new or higher `rust-version`, new build dependencies, or new nightly features
+//! (cargo features, `#![feature]` attributes, `-Z` flags passed by a build script).
+
+use semver::Version;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// The toolchain requirements of a crate, as found in its (crates.io) sources.
+#[derive(Default, Debug)]
+struct Requirements {
+    rust_version: Option<String>,
+    build_dependencies: BTreeSet<String>,
+    cargo_features: BTreeSet<String>,
+    nightly_features: BTreeSet<String>,
+    unstable_flags: bool,
+}
+
+impl Requirements {
+    fn new(crate_dir: &Path) -> Self {
+        let mut requirements = Self::default();
+
+        // manifest
+        let manifest = fs::read_to_string(crate_dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|manifest| toml::from_str::<toml::Value>(&manifest).ok());
+        if let Some(manifest) = manifest {
+            requirements.rust_version = manifest
+                .get("package")
+                .and_then(|package| package.get("rust-version"))
+                .and_then(toml::Value::as_str)
+                .map(ToString::to_string);
+            requirements.cargo_features = string_array(manifest.get("cargo-features"));
+            requirements.build_dependencies = table_keys(manifest.get("build-dependencies"));
+            if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+                for target in targets.values() {
+                    requirements
+                        .build_dependencies
+                        .extend(table_keys(target.get("build-dependencies")));
+                }
+            }
+        }
+
+        // sources
+        requirements.scan(crate_dir);
+        requirements
+    }
+
+    /// Looks for `#![feature(...)]` attributes and `-Z` flags in build scripts.
+    fn scan(&mut self, dir: &Path) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.is_dir() {
+                self.scan(&path);
+                continue;
+            }
+            if path.extension().map(|ext| ext != "rs").unwrap_or(true) {
+                continue;
+            }
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+            self.nightly_features.extend(feature_attributes(&content));
+            if path
+                .file_name()
+                .map(|name| name == "build.rs")
+                .unwrap_or(false)
+                && content.contains("\"-Z")
+            {
+                self.unstable_flags = true;
+            }
+        }
+    }
+}
+
+/// Lists the toolchain requirements introduced by a new version of a crate,
+/// given the extracted sources of both versions.
+pub fn toolchain_changes(original_crate: &Path, new_crate: &Path) -> Vec<String> {
+    let old = Requirements::new(original_crate);
+    let new = Requirements::new(new_crate);
+    let mut changes = Vec::new();
+
+    // rust-version
+    match (&old.rust_version, &new.rust_version) {
+        (None, Some(new_version)) => changes.push(format!("rust-version {} added", new_version)),
+        (Some(old_version), Some(new_version)) => {
+            if let (Some(old), Some(new)) = (
+                parse_rust_version(old_version),
+                parse_rust_version(new_version),
+            ) {
+                if new > old {
+                    changes.push(format!(
+                        "rust-version raised from {} to {}",
+                        old_version, new_version
+                    ));
+                }
+            }
+        }
+        _ => (),
+    }
+
+    // build dependencies
+    for dependency in new.build_dependencies.difference(&old.build_dependencies) {
+        changes.push(format!("new build-dependency {}", dependency));
+    }
+
+    // nightly
+    for feature in new.cargo_features.difference(&old.cargo_features) {
+        changes.push(format!("requires the nightly cargo feature {}", feature));
+    }
+    for feature in new.nightly_features.difference(&old.nightly_features) {
+        changes.push(format!("uses the nightly feature {}", feature));
+    }
+    if new.unstable_flags && !old.unstable_flags {
+        changes.push("build.rs passes -Z flags".to_string());
+    }
+
+    changes
+}
+
+/// Parses a `rust-version` (which can omit the minor and patch numbers).
+fn parse_rust_version(rust_version: &str) -> Option<Version> {
+    let mut parts = rust_version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map(str::parse).unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().map(str::parse).unwrap_or(Ok(0)).ok()?;
+    Some(Version::new(major, minor, patch))
+}
+
+/// Extracts the features enabled via `#![feature(...)]` attributes.
+fn feature_attributes(content: &str) -> Vec<String> {
+    let mut features = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("#![feature(") {
+        rest = &rest[start + "#![feature(".len()..];
+        let end = match rest.find(')') {
+            Some(end) => end,
+            None => break,
+        };
+        features.extend(
+            rest[..end]
+                .split(',')
+                .map(str::trim)
+                .filter(|feature| !feature.is_empty())
+                .map(ToString::to_string),
+        );
+        rest = &rest[end..];
+    }
+    features
+}
+
+fn string_array(value: Option<&toml::Value>) -> BTreeSet<String> {
+    value
+        .and_then(toml::Value::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(toml::Value::as_str)
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn table_keys(value: Option<&toml::Value>) -> BTreeSet<String> {
+    value
+        .and_then(toml::Value::as_table)
+        .map(|table| table.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_toolchain_changes() {
+        let old = tempdir().unwrap();
+        fs::write(
+            old.path().join("Cargo.toml"),
+            "[package]\nname = \"a\"\nrust-version = \"1.51\"\n",
+        )
+        .unwrap();
+        fs::write(old.path().join("lib.rs"), "pub fn f() {}\n").unwrap();
+
+        let new = tempdir().unwrap();
+        fs::write(
+            new.path().join("Cargo.toml"),
+            "cargo-features = [\"edition2024\"]\n[package]\nname = \"a\"\nrust-version = \"1.56.1\"\n[build-dependencies]\ncc = \"1\"\n",
+        )
+        .unwrap();
+        fs::write(
+            new.path().join("lib.rs"),
+            "#![feature(never_type, specialization)]\npub fn f() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            new.path().join("build.rs"),
+            "fn main() { std::process::Command::new(\"rustc\").arg(\"-Zunstable-options\"); }\n",
+        )
+        .unwrap();
+
+        assert!(toolchain_changes(old.path(), old.path()).is_empty());
+        assert_eq!(
+            toolchain_changes(old.path(), new.path()),
+            vec![
+                "rust-version raised from 1.51 to 1.56.1",
+                "new build-dependency cc",
+                "requires the nightly cargo feature edition2024",
+                "uses the nightly feature never_type",
+                "uses the nightly feature specialization",
+                "build.rs passes -Z flags",
+            ]
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! This module finds `unsafe` code in rust sources.
+//! A small lexer strips comments and literals first, so that an `unsafe` mentioned in a comment
+//! or in a string doesn't count, and the unsafe regions (blocks, functions, impls) are extracted
+//! so that a change to the unsafe code itself can be told apart from unrelated edits
+//! in a file that happens to contain unsafe code.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A rust file changed by an update, that contains unsafe code (before or after the update).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnsafeChange {
+    /// the file, relative to the crate root
+    pub path: String,
+    /// number of `unsafe` keywords before the update
+    pub unsafe_before: usize,
+    /// number of `unsafe` keywords after the update
+    pub unsafe_after: usize,
+    /// did the unsafe code itself change? (as opposed to other code in the file)
+    pub unsafe_code_changed: bool,
+}
+
+/// Compares the unsafe code of the rust files changed between two extracted crates.
+pub fn unsafe_changes(
+    original_crate: &Path,
+    new_crate: &Path,
+    changed_files: &[String],
+) -> Vec<UnsafeChange> {
+    changed_files
+        .iter()
+        .filter(|path| path.ends_with(".rs"))
+        .filter_map(|path| {
+            let before = fs::read_to_string(original_crate.join(path)).unwrap_or_default();
+            let after = fs::read_to_string(new_crate.join(path)).unwrap_or_default();
+            let (regions_before, regions_after) = (unsafe_regions(&before), unsafe_regions(&after));
+            if regions_before.is_empty() && regions_after.is_empty() {
+                return None;
+            }
+            Some(UnsafeChange {
+                path: path.clone(),
+                unsafe_before: regions_before.len(),
+                unsafe_after: regions_after.len(),
+                unsafe_code_changed: regions_before != regions_after,
+            })
+        })
+        .collect()
+}
+
+/// Counts the `unsafe` keywords of some rust code (outside of comments and literals).
+pub fn count_unsafe(source: &str) -> usize {
+    let code: Vec<char> = strip(source).chars().collect();
+    unsafe_keywords(&code).len()
+}
+
+/// Returns the unsafe regions of some rust code, starting at an `unsafe` keyword
+/// and ending at the end of the following block (or at the end of the statement),
+/// with comments and literals removed and whitespace normalized. The regions are sorted.
+pub fn unsafe_regions(source: &str) -> Vec<String> {
+    let code: Vec<char> = strip(source).chars().collect();
+    let mut regions: Vec<String> = unsafe_keywords(&code)
+        .into_iter()
+        .map(|start| {
+            let mut depth = 0;
+            let mut end = code.len();
+            for (i, c) in code.iter().enumerate().skip(start) {
+                match c {
+                    ';' if depth == 0 => {
+                        end = i + 1;
+                        break;
+                    }
+                    '{' => depth += 1,
+                    // (end of an enclosing block, e.g. after an `unsafe fn()` type)
+                    '}' if depth == 0 => {
+                        end = i;
+                        break;
+                    }
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = i + 1;
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            let region: String = code[start..end].iter().collect();
+            region.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+        .collect();
+    regions.sort();
+    regions
+}
+
+/// Returns the positions of the `unsafe` keywords in stripped code.
+fn unsafe_keywords(code: &[char]) -> Vec<usize> {
+    let keyword: Vec<char> = "unsafe".chars().collect();
+    (0..code.len())
+        .filter(|&i| code[i..].starts_with(&keyword))
+        .filter(|&i| i == 0 || !is_ident(code[i - 1]))
+        .filter(|&i| code.get(i + keyword.len()).map_or(true, |c| !is_ident(*c)))
+        .collect()
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Removes the comments and the content of the string and char literals of some rust code.
+pub(crate) fn strip(source: &str) -> String {
+    strip_with_offsets(source).0
+}
+
+/// Same as [`strip`], also returning the position in `source` (in chars)
+/// that every char of the stripped code comes from.
+fn strip_with_offsets(source: &str) -> (String, Vec<usize>) {
+    let chars: Vec<char> = source.chars().collect();
+    let len = chars.len();
+    let mut code = String::with_capacity(source.len());
+    let mut offsets = Vec::with_capacity(len);
+    let mut emit = |code: &mut String, text: &str, at: usize| {
+        code.push_str(text);
+        offsets.extend(text.chars().map(|_| at));
+    };
+    let mut i = 0;
+    while i < len {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let after_ident = i > 0 && is_ident(chars[i - 1]);
+
+        // line comment
+        if c == '/' && next == Some('/') {
+            while i < len && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        // block comment (which can be nested)
+        if c == '/' && next == Some('*') {
+            let start = i;
+            let mut depth = 0;
+            while i < len {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            emit(&mut code, " ", start);
+            continue;
+        }
+
+        // raw string (r"...", r#"..."#, br"...")
+        if !after_ident && (c == 'r' || (c == 'b' && next == Some('r'))) {
+            let mut j = if c == 'r' { i + 1 } else { i + 2 };
+            let mut hashes = 0;
+            while chars.get(j) == Some(&'#') {
+                hashes += 1;
+                j += 1;
+            }
+            if chars.get(j) == Some(&'"') {
+                j += 1;
+                while j < len {
+                    if chars[j] == '"' && (1..=hashes).all(|h| chars.get(j + h) == Some(&'#')) {
+                        j += 1 + hashes;
+                        break;
+                    }
+                    j += 1;
+                }
+                emit(&mut code, "\"\"", i);
+                i = j;
+                continue;
+            }
+        }
+
+        // string
+        if c == '"' {
+            let start = i;
+            i += 1;
+            while i < len {
+                match chars[i] {
+                    '\\' => i += 2,
+                    '"' => {
+                        i += 1;
+                        break;
+                    }
+                    _ => i += 1,
+                }
+            }
+            emit(&mut code, "\"\"", start);
+            continue;
+        }
+
+        // char literal (as opposed to a lifetime)
+        if c == '\'' {
+            let start = i;
+            if next == Some('\\') {
+                // skip the escaped character, then look for the closing quote
+                i += 3;
+                while i < len && chars[i] != '\'' {
+                    i += 1;
+                }
+                i += 1;
+                emit(&mut code, "' '", start);
+                continue;
+            }
+            if chars.get(i + 2) == Some(&'\'') {
+                i += 3;
+                emit(&mut code, "' '", start);
+                continue;
+            }
+        }
+
+        emit(&mut code, c.encode_utf8(&mut [0; 4]), i);
+        i += 1;
+    }
+    (code, offsets)
+}
+
+/// Returns the lines (starting at 1) of the `unsafe` keywords of some rust code
+/// (outside of comments and literals), with the number of keywords on each line.
+pub fn unsafe_lines(source: &str) -> Vec<(usize, usize)> {
+    let (code, offsets) = strip_with_offsets(source);
+    let code: Vec<char> = code.chars().collect();
+    // (the line of every char of the source)
+    let mut line = 1;
+    let lines: Vec<usize> = source
+        .chars()
+        .map(|c| {
+            let current = line;
+            if c == '\n' {
+                line += 1;
+            }
+            current
+        })
+        .collect();
+    let mut unsafe_lines: Vec<(usize, usize)> = Vec::new();
+    for keyword in unsafe_keywords(&code) {
+        let line = lines[offsets[keyword]];
+        match unsafe_lines.last_mut() {
+            Some((last, count)) if *last == line => *count += 1,
+            _ => unsafe_lines.push((line, 1)),
+        }
+    }
+    unsafe_lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_count_unsafe() {
+        let source = r##"
+            // unsafe in a comment
+            /* unsafe in a /* nested */ block comment */
+            const S: &str = "unsafe in a string \" unsafe";
+            const R: &str = r#"unsafe in a "raw" string"#;
+            const C: char = '"';
+            fn f<'a>(x: &'a u8) -> u8 {
+                let not_unsafe_ident = 1;
+                unsafe { *(x as *const u8) }
+            }
+            unsafe impl Send for X {}
+        "##;
+        assert_eq!(count_unsafe(source), 2);
+    }
+
+    #[test]
+    fn test_unsafe_lines() {
+        let source = "/* unsafe\n */\nconst S: &str = \"\n\";\nfn f() { unsafe { g() } }\nunsafe fn g() { unsafe {} }\n";
+        assert_eq!(unsafe_lines(source), vec![(5, 1), (6, 2)]);
+    }
+
+    #[test]
+    fn test_unsafe_regions() {
+        let source = "fn f() {\n    unsafe {\n        g(); // comment\n    }\n}\nunsafe extern \"C\" fn h();\n";
+        assert_eq!(
+            unsafe_regions(source),
+            vec!["unsafe extern \"\" fn h();", "unsafe { g(); }"]
+        );
+    }
+
+    #[test]
+    fn test_unsafe_changes() {
+        let old = tempdir().unwrap();
+        let new = tempdir().unwrap();
+        let write = |dir: &Path, path: &str, content: &str| {
+            fs::write(dir.join(path), content).unwrap();
+        };
+        // unrelated edit in a file containing unsafe code
+        write(old.path(), "a.rs", "fn f() { unsafe { g() } }\nfn h() {}\n");
+        write(
+            new.path(),
+            "a.rs",
+            "fn f() { unsafe { g() } }\nfn h() { i() }\n",
+        );
+        // unsafe code changed
+        write(old.path(), "b.rs", "fn f() { unsafe { g() } }\n");
+        write(new.path(), "b.rs", "fn f() { unsafe { g(); k() } }\n");
+        // no unsafe code
+        write(old.path(), "c.rs", "fn f() {}\n");
+        write(new.path(), "c.rs", "fn f() { g() }\n");
+
+        let changed_files = vec!["a.rs".to_string(), "b.rs".to_string(), "c.rs".to_string()];
+        let changes = unsafe_changes(old.path(), new.path(), &changed_files);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, "a.rs");
+        assert!(!changes[0].unsafe_code_changed);
+        assert_eq!(changes[1].path, "b.rs");
+        assert!(changes[1].unsafe_code_changed);
+    }
+}
@@ -0,0 +1,1210 @@
+//! This module reviews a group of updates (like the ones dependabot bundles in a single PR),
+//! giving a verdict for each update as well as an overall verdict for the group.
+//! The verdicts are derived from the signals collected by an analysis.
+//! The findings that need a look are also listed as violations of rules with stable identifiers,
+//! pointing to their evidence in the analysis (see [`Violation`]), for CI systems and bots to branch on.
+
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::{
+    backfill,
+    build_time::BuildTimeChange,
+    cargoaudit, crate_name,
+    discovery::DiscoveryMethod,
+    fix_check::FixCheck,
+    lockfile_format,
+    review_diff::ReviewDiff,
+    reviewers::{self, Assignment, FindingCategory},
+    risky_paths::RiskLevel,
+    theme::MarkdownTheme,
+    unsafety::UnsafeChange,
+    versions, DependencyInfo, RustAnalysis,
+};
+use crate::dashboard::table::{Align, Cell, Table};
+use crate::verbosity::Verbosity;
+
+/// The outcome of the review of an update (sorted from best to worst).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// nothing to report
+    Pass,
+    /// the update needs a closer look
+    Warn,
+    /// the update should not be merged as is
+    Fail,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Pass => "pass",
+            Verdict::Warn => "warn",
+            Verdict::Fail => "fail",
+        }
+    }
+
+    /// a markdown badge for the verdict
+    pub fn badge(&self) -> String {
+        let color = match self {
+            Verdict::Pass => "success",
+            Verdict::Warn => "yellow",
+            Verdict::Fail => "critical",
+        };
+        format!(
+            "![{verdict}](https://img.shields.io/badge/verdict-{verdict}-{})",
+            color,
+            verdict = self.as_str()
+        )
+    }
+}
+
+/// The review of a single update.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpdateVerdict {
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+    pub verdict: Verdict,
+    pub reasons: Vec<String>,
+    /// the owners of the workspace members using the dependency (see [`super::codeowners`])
+    #[serde(default)]
+    pub owners: Vec<String>,
+    /// the workspace targets (libraries and binaries) that include the dependency,
+    /// which need to be rebuilt and released with the update
+    #[serde(default)]
+    pub affects: Vec<String>,
+    /// the signals behind the verdict, rendered with [`Verbosity::Full`]
+    #[serde(default)]
+    pub details: UpdateDetails,
+    /// how the update grows the code running at build time (see [`super::build_time`])
+    #[serde(default)]
+    pub build_time_change: Option<BuildTimeChange>,
+    /// the tags of the dependency (see [`super::tags`])
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// the categories of the findings that need a look (see [`super::reviewers`])
+    #[serde(default)]
+    pub categories: Vec<FindingCategory>,
+    /// the rules broken by the update (the findings that need a look), for CI systems and bots
+    #[serde(default)]
+    pub violations: Vec<Violation>,
+}
+
+/// A finding that needs a look, identified by the rule it breaks (e.g. `build-script-changed`),
+/// so that CI systems and bots can branch on rules instead of parsing the reasons.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// the identifier of the rule, stable across releases (kebab-case)
+    pub rule: String,
+    pub verdict: Verdict,
+    /// none for the rules of tag policies (see [`super::tags`])
+    pub category: Option<FindingCategory>,
+    /// the reason, as rendered in the review
+    pub message: String,
+    /// a JSON pointer to the evidence in the analysis (e.g. `/rust_dependencies/dependencies/3/update/build_rs`),
+    /// none if the update isn't part of an analysis
+    pub evidence: Option<String>,
+}
+
+/// The raw signals collected about an update.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct UpdateDetails {
+    /// every version between the current one and the new one (the hops of the update)
+    pub hops: Vec<Version>,
+    /// the direct dependencies pulling the dependency in (if it is a transitive dependency)
+    pub introduced_by: Vec<String>,
+    pub unsafe_changes: Vec<UnsafeChange>,
+    /// the changelog between the two versions
+    pub changelog: Option<String>,
+    /// (metric, value)
+    pub metrics: Vec<(String, String)>,
+}
+
+/// The review of a group of updates.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupVerdict {
+    /// the worst verdict of the group
+    pub verdict: Verdict,
+    pub updates: Vec<UpdateVerdict>,
+    /// the advisories the updates claim to fix, checked against the lockfile resolved with the updates
+    /// (see [`Self::check_lockfile`])
+    #[serde(default)]
+    pub fix_checks: Vec<FixCheck>,
+    /// the reviewers to assign according to the findings (see [`Self::assign`])
+    #[serde(default)]
+    pub assignments: Vec<Assignment>,
+    /// the rules broken by the updates (sorted, see [`Violation`])
+    #[serde(default)]
+    pub violated_rules: Vec<String>,
+    /// what changed since the previous review of the same updates, if any (see [`Self::compare`])
+    #[serde(default)]
+    pub since_last_review: Option<ReviewDiff>,
+}
+
+impl UpdateVerdict {
+    /// Reviews the update of a dependency (if it has one).
+    pub fn new(analysis: &RustAnalysis, dependency: &DependencyInfo) -> Option<Self> {
+        let update = dependency.update.as_ref()?;
+        let to = update.versions.last()?.clone();
+        // (the evidence of a finding is a JSON pointer into the analysis, if the dependency is part of it)
+        let dependency_pointer = analysis
+            .dependencies
+            .iter()
+            .position(|dep| std::ptr::eq(dep, dependency) || dep == dependency)
+            .map(|idx| format!("/rust_dependencies/dependencies/{}", idx));
+        let evidence = |path: String| {
+            dependency_pointer
+                .as_ref()
+                .map(|pointer| format!("{}{}", pointer, path))
+        };
+        let mut verdict = Verdict::Pass;
+        let mut reasons = Vec::new();
+        let mut categories = BTreeSet::new();
+        let mut violations = Vec::new();
+        let mut flag = |level: Verdict,
+                        category: FindingCategory,
+                        rule: &'static str,
+                        evidence: Option<String>,
+                        reason: String| {
+            verdict = verdict.max(level);
+            // (only the findings that need a look are worth assigning reviewers)
+            if level > Verdict::Pass {
+                categories.insert(category);
+                violations.push(Violation {
+                    rule: rule.to_string(),
+                    verdict: level,
+                    category: Some(category),
+                    message: reason.clone(),
+                    evidence,
+                });
+            }
+            reasons.push(reason);
+        };
+
+        // RUSTSEC
+        for (idx, vuln) in analysis.rustsec.vulnerabilities.iter().enumerate() {
+            // (other versions of the crate may be locked too)
+            if vuln.package.name.as_str() != dependency.name
+                || vuln.package.version != dependency.version
+            {
+                continue;
+            }
+            let advisory = Some(format!(
+                "/rust_dependencies/rustsec/vulnerabilities/{}",
+                idx
+            ));
+            let first_fixed = cargoaudit::first_fixed_version(
+                &vuln.versions.patched,
+                &vuln.versions.unaffected,
+                &update.versions,
+            );
+            if vuln.versions.is_vulnerable(&to) {
+                let reason = format!("{} still affects the new version", vuln.advisory.id);
+                let patched: Vec<String> = vuln
+                    .versions
+                    .patched
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                flag(
+                    Verdict::Fail,
+                    FindingCategory::Advisory,
+                    "advisory-not-fixed",
+                    advisory,
+                    match first_fixed {
+                        // (a fix that regressed since)
+                        Some(fixed) => format!("{} (updating to {} would fix it)", reason, fixed),
+                        None if patched.is_empty() => format!("{} (no patched version)", reason),
+                        None => format!("{} (patched: {})", reason, patched.join(", ")),
+                    },
+                );
+            } else {
+                flag(
+                    Verdict::Pass,
+                    FindingCategory::Advisory,
+                    "advisory-fixed",
+                    advisory,
+                    match first_fixed {
+                        Some(fixed) if fixed != &to => {
+                            format!("fixes {} (fixed since {})", vuln.advisory.id, fixed)
+                        }
+                        _ => format!("fixes {}", vuln.advisory.id),
+                    },
+                );
+            }
+            // (informative: whether the update touches the affected functions, and the workspace uses them)
+            for (function_idx, function) in update.advisory_functions.iter().enumerate() {
+                if function.advisory == vuln.advisory.id.as_str() {
+                    flag(
+                        Verdict::Pass,
+                        FindingCategory::Advisory,
+                        "advisory-function",
+                        evidence(format!("/update/advisory_functions/{}", function_idx)),
+                        format!("{}: {}", function.advisory, function.describe()),
+                    );
+                }
+            }
+        }
+
+        // risk signals
+        if update.build_rs {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Build,
+                "build-script-changed",
+                evidence("/update/build_rs".to_string()),
+                "build.rs changed".to_string(),
+            );
+        }
+        for (idx, change) in update.toolchain_changes.iter().enumerate() {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Build,
+                "toolchain-changed",
+                evidence(format!("/update/toolchain_changes/{}", idx)),
+                change.clone(),
+            );
+        }
+        if let Some(change) = &update.build_time_change {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Build,
+                "build-time-code-grows",
+                evidence("/update/build_time_change".to_string()),
+                format!(
+                    "grows the code running at build time: {}",
+                    change.describe()
+                ),
+            );
+        }
+        if let Some(size_change) = update.size_change.as_ref().filter(|s| s.is_jump()) {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Size,
+                "package-size-jump",
+                evidence("/update/size_change".to_string()),
+                format!(
+                    "package size jumped from {} to {} bytes ({} to {} files)",
+                    size_change.old.uncompressed,
+                    size_change.new.uncompressed,
+                    size_change.old.files,
+                    size_change.new.files
+                ),
+            );
+        }
+        if !compatible(&dependency.version, &to) {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Breaking,
+                "breaking-version-change",
+                evidence("/update/versions".to_string()),
+                "breaking version change".to_string(),
+            );
+        }
+        for (idx, finding) in update.secrets.iter().enumerate() {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Secrets,
+                "secret-added",
+                evidence(format!("/update/secrets/{}", idx)),
+                match finding.line {
+                    Some(line) => format!("{} in {}:{}", finding.kind, finding.file, line),
+                    None => format!("{} {}", finding.kind, finding.file),
+                },
+            );
+        }
+        for (idx, risky_file) in update.risky_files.iter().enumerate() {
+            let evidence = evidence(format!("/update/risky_files/{}", idx));
+            match risky_file.level {
+                RiskLevel::Escalated => flag(
+                    Verdict::Fail,
+                    FindingCategory::RiskyFiles,
+                    "high-risk-file-changed",
+                    evidence,
+                    format!("high-risk file {} changed", risky_file.path),
+                ),
+                RiskLevel::Flagged => flag(
+                    Verdict::Warn,
+                    FindingCategory::RiskyFiles,
+                    "risky-file-changed",
+                    evidence,
+                    format!("risky file {} changed", risky_file.path),
+                ),
+            }
+        }
+        for (idx, change) in update
+            .unsafe_changes
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.unsafe_code_changed)
+        {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Unsafe,
+                "unsafe-code-changed",
+                evidence(format!("/update/unsafe_changes/{}", idx)),
+                format!("unsafe code changed in {}", change.path),
+            );
+        }
+        if let Some(delta) = update.unsafe_delta.as_ref().filter(|d| d.after > d.before) {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Unsafe,
+                "unsafe-usages-grow",
+                evidence("/update/unsafe_delta".to_string()),
+                format!(
+                    "unsafe usages grow from {} to {} ({})",
+                    delta.before, delta.after, delta.scanner
+                ),
+            );
+        }
+        if let Some(change) = &update.license_change {
+            let level = if change.is_more_restrictive() {
+                Verdict::Warn
+            } else {
+                Verdict::Pass
+            };
+            flag(
+                level,
+                FindingCategory::License,
+                "license-changed",
+                evidence("/update/license_change".to_string()),
+                format!(
+                    "license changed from {} to {} ({})",
+                    change.old.as_deref().unwrap_or("none"),
+                    change.new.as_deref().unwrap_or("none"),
+                    change.new_class().as_str()
+                ),
+            );
+        }
+        if let Some(change) = &update.repository_change {
+            flag(
+                Verdict::Warn,
+                FindingCategory::Provenance,
+                "repository-changed",
+                evidence("/update/repository_change".to_string()),
+                format!(
+                    "repository changed from {} to {}{}",
+                    change.old,
+                    change.new.as_deref().unwrap_or("none"),
+                    if change.lost_trust {
+                        " (no longer owned by a trusted publisher)"
+                    } else {
+                        ""
+                    }
+                ),
+            );
+        }
+        if let Some(redirect) = &dependency.repository_redirect {
+            flag(
+                if redirect.owner_changed {
+                    Verdict::Warn
+                } else {
+                    Verdict::Pass
+                },
+                FindingCategory::Provenance,
+                "repository-redirected",
+                evidence("/repository_redirect".to_string()),
+                format!("repository {}", redirect.describe()),
+            );
+        }
+        if let Some(change) = &update.maintainer_change {
+            for reason in change.reasons() {
+                flag(
+                    Verdict::Warn,
+                    FindingCategory::Provenance,
+                    "maintainers-changed",
+                    evidence("/update/maintainer_change".to_string()),
+                    reason,
+                );
+            }
+        }
+        if dependency.trusted {
+            flag(
+                Verdict::Pass,
+                FindingCategory::Provenance,
+                "trusted-publisher",
+                evidence("/trusted".to_string()),
+                "trusted publisher".to_string(),
+            );
+        }
+
+        // stricter rules for the sensitive tags
+        for (rule, level, reason) in analysis.tags.rules(&dependency.tags, verdict) {
+            verdict = verdict.max(level);
+            violations.push(Violation {
+                rule: rule.to_string(),
+                verdict: level,
+                category: None,
+                message: reason.clone(),
+                evidence: evidence("/tags".to_string()),
+            });
+            reasons.push(reason);
+        }
+
+        Some(Self {
+            name: dependency.name.clone(),
+            from: dependency.version.clone(),
+            to,
+            verdict,
+            reasons,
+            owners: dependency.code_owners.clone(),
+            affects: dependency.targets.clone(),
+            details: UpdateDetails::new(dependency),
+            build_time_change: update.build_time_change.clone(),
+            tags: dependency.tags.clone(),
+            categories: categories.into_iter().collect(),
+            violations,
+        })
+    }
+
+    /// Reviews the downgrade of a dependency to a lower version (e.g. a rollback resolved with the updates),
+    /// checking whether it brings back the advisories of the crate that its current version fixed.
+    pub fn downgrade(analysis: &RustAnalysis, dependency: &DependencyInfo, to: &Version) -> Self {
+        let dependency_pointer = analysis
+            .dependencies
+            .iter()
+            .position(|dep| std::ptr::eq(dep, dependency) || dep == dependency)
+            .map(|idx| format!("/rust_dependencies/dependencies/{}", idx));
+        let evidence = |path: &str| {
+            dependency_pointer
+                .as_ref()
+                .map(|pointer| format!("{}{}", pointer, path))
+        };
+        let mut violations = vec![Violation {
+            rule: "version-downgraded".to_string(),
+            verdict: Verdict::Warn,
+            category: Some(FindingCategory::Breaking),
+            message: format!("downgraded from {} to {}", dependency.version, to),
+            evidence: evidence("/version"),
+        }];
+        for (idx, advisory) in dependency.advisory_history.iter().enumerate() {
+            if advisory.affects(to) && !advisory.affects(&dependency.version) {
+                violations.push(Violation {
+                    rule: "advisory-reintroduced".to_string(),
+                    verdict: Verdict::Fail,
+                    category: Some(FindingCategory::Advisory),
+                    message: format!(
+                        "reintroduces {} (fixed in {})",
+                        advisory.id(),
+                        dependency.version
+                    ),
+                    evidence: evidence(&format!("/advisory_history/{}", idx)),
+                });
+            }
+        }
+        let categories: BTreeSet<FindingCategory> = violations
+            .iter()
+            .filter_map(|violation| violation.category)
+            .collect();
+
+        Self {
+            name: dependency.name.clone(),
+            from: dependency.version.clone(),
+            to: to.clone(),
+            verdict: violations
+                .iter()
+                .map(|violation| violation.verdict)
+                .max()
+                .unwrap_or(Verdict::Pass),
+            reasons: violations
+                .iter()
+                .map(|violation| violation.message.clone())
+                .collect(),
+            owners: dependency.code_owners.clone(),
+            affects: dependency.targets.clone(),
+            details: UpdateDetails {
+                introduced_by: dependency.introduced_by.clone(),
+                ..Default::default()
+            },
+            build_time_change: None,
+            tags: dependency.tags.clone(),
+            categories: categories.into_iter().collect(),
+            violations,
+        }
+    }
+
+    /// Does the update go to a lower version (see [`Self::downgrade`])?
+    pub fn is_downgrade(&self) -> bool {
+        self.to < self.from
+    }
+
+    /// Renders the version the update goes to, labeled if it is a pre-release or a downgrade.
+    fn target(&self) -> String {
+        if self.is_downgrade() {
+            format!("{} (downgrade)", versions::label(&self.to))
+        } else {
+            versions::label(&self.to)
+        }
+    }
+
+    /// Renders the review as a collapsible markdown section.
+    pub fn to_markdown(&self, theme: &MarkdownTheme, verbosity: Verbosity) -> String {
+        let mut markdown = String::new();
+        let _ = writeln!(
+            markdown,
+            "<details{}>\n<summary>{} <strong>{}</strong> {} → {}</summary>\n",
+            // failing updates are expanded by default
+            if self.verdict == Verdict::Fail {
+                " open"
+            } else {
+                ""
+            },
+            theme.icon(self.verdict),
+            self.name,
+            self.from,
+            self.target()
+        );
+        if self.reasons.is_empty() {
+            markdown.push_str("- nothing to report\n");
+        }
+        for reason in &self.reasons {
+            let _ = writeln!(markdown, "- {}", reason);
+        }
+        if !self.affects.is_empty() {
+            let _ = writeln!(markdown, "\naffects: {}", self.affects.join(", "));
+        }
+        if !self.tags.is_empty() {
+            let _ = writeln!(markdown, "\ntags: {}", self.tags.join(", "));
+        }
+        if verbosity == Verbosity::Full {
+            markdown.push_str(&self.details.to_markdown());
+        }
+        markdown.push_str("\n</details>\n");
+        markdown
+    }
+
+    /// Summarizes the review as a row of a table (see [`GroupVerdict::to_markdown`]).
+    fn summary_row(&self, theme: &MarkdownTheme) -> Vec<Cell> {
+        vec![
+            Cell::text(theme.icon(self.verdict)),
+            Cell::strong(&self.name),
+            Cell::text(&self.from),
+            Cell::text(self.target()),
+            Cell::text(if self.reasons.is_empty() {
+                "nothing to report".to_string()
+            } else {
+                self.reasons.join("; ")
+            }),
+            Cell::text(self.affects.join(", ")),
+        ]
+    }
+}
+
+impl UpdateDetails {
+    fn new(dependency: &DependencyInfo) -> Self {
+        let update = match &dependency.update {
+            Some(update) => update,
+            None => return Self::default(),
+        };
+        let mut metrics = Vec::new();
+        let mut metric = |name: &str, value: String| metrics.push((name.to_string(), value));
+        metric(
+            "license",
+            dependency.license.as_deref().unwrap_or("none").to_string(),
+        );
+        metric("direct", dependency.direct.to_string());
+        metric("dev", dependency.dev.to_string());
+        metric("features", dependency.features.join(", "));
+        metric(
+            "platforms",
+            if dependency.platforms.is_empty() {
+                "all".to_string()
+            } else {
+                dependency.platforms.join(", ")
+            },
+        );
+        metric("linked by", dependency.linked_by.join(", "));
+        // (only the repositories that were not declared are worth a mention)
+        if let (Some(repository), Some(method)) =
+            (&dependency.repository, dependency.repository_discovery)
+        {
+            if method != DiscoveryMethod::Repository {
+                metric(
+                    "repository",
+                    format!("{} (found in the {})", repository, method.as_str()),
+                );
+            }
+        }
+        if let Some(policy) = &dependency.security_policy {
+            metric("security policy", policy.describe());
+        }
+        if let Some(targets) = &dependency.fuzz_targets {
+            metric("fuzz targets", targets.join(", "));
+        }
+        metric("crates.io owners", dependency.owners.join(", "));
+        if let Some(churn) = &update.churn {
+            metric(
+                "churn (hand-written)",
+                format!(
+                    "+{} -{}",
+                    churn.hand_written.added, churn.hand_written.removed
+                ),
+            );
+            metric(
+                "churn (generated)",
+                format!("+{} -{}", churn.generated.added, churn.generated.removed),
+            );
+        }
+        if let Some(delta) = &update.unsafe_delta {
+            metric(
+                "unsafe usages",
+                format!("{} → {} ({})", delta.before, delta.after, delta.scanner),
+            );
+        }
+        if let Some(size_change) = &update.size_change {
+            metric(
+                "package size (bytes)",
+                format!(
+                    "{} → {}",
+                    size_change.old.uncompressed, size_change.new.uncompressed
+                ),
+            );
+            metric(
+                "package files",
+                format!("{} → {}", size_change.old.files, size_change.new.files),
+            );
+        }
+
+        Self {
+            hops: update.versions.clone(),
+            introduced_by: dependency.introduced_by.clone(),
+            unsafe_changes: update.unsafe_changes.clone(),
+            changelog: update
+                .update_metadata
+                .changelog_text()
+                .map(ToString::to_string),
+            metrics,
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        if !self.hops.is_empty() {
+            let hops: Vec<String> = self.hops.iter().map(ToString::to_string).collect();
+            let _ = writeln!(markdown, "\n**versions**: {}", hops.join(" → "));
+        }
+        if !self.introduced_by.is_empty() {
+            let _ = writeln!(
+                markdown,
+                "\n**pulled in by**: {}",
+                self.introduced_by.join(", ")
+            );
+        }
+        if !self.unsafe_changes.is_empty() {
+            markdown.push_str("\n**`unsafe` changes**:\n\n");
+            for change in &self.unsafe_changes {
+                let _ = writeln!(
+                    markdown,
+                    "- {}: {} → {} `unsafe`{}",
+                    change.path,
+                    change.unsafe_before,
+                    change.unsafe_after,
+                    if change.unsafe_code_changed {
+                        " (unsafe code changed)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        if !self.metrics.is_empty() {
+            let mut table = Table::new()
+                .column("metric", Align::Left)
+                .column("value", Align::Left);
+            for (name, value) in &self.metrics {
+                table.row(vec![Cell::text(name), Cell::text(value)]);
+            }
+            let _ = write!(markdown, "\n{}", table.to_markdown());
+        }
+        if let Some(changelog) = &self.changelog {
+            let _ = writeln!(
+                markdown,
+                "\n**changelog**:\n\n```\n{}\n```",
+                changelog.trim()
+            );
+        }
+        markdown
+    }
+}
+
+impl GroupVerdict {
+    /// Reviews the updates of the given crates (every update if no crate is given).
+    pub fn new(analysis: &RustAnalysis, crates: &[String]) -> Self {
+        let updates: Vec<UpdateVerdict> = analysis
+            .dependencies
+            .iter()
+            .filter(|dependency| {
+                crates.is_empty()
+                    || crates
+                        .iter()
+                        .any(|name| crate_name::same_crate(name, &dependency.name))
+            })
+            .filter_map(|dependency| UpdateVerdict::new(analysis, dependency))
+            .collect();
+        Self::from_updates(updates)
+    }
+
+    /// Groups reviews of updates (e.g. to test a renderer, see [`super::fixtures`]),
+    /// the verdict of the group being the worst verdict of the updates.
+    pub fn from_updates(updates: Vec<UpdateVerdict>) -> Self {
+        let mut group = Self {
+            verdict: Verdict::Pass,
+            updates,
+            fix_checks: Vec::new(),
+            assignments: Vec::new(),
+            violated_rules: Vec::new(),
+            since_last_review: None,
+        };
+        group.summarize();
+        group
+    }
+
+    /// Updates the verdict and the rules broken by the group from its updates.
+    fn summarize(&mut self) {
+        self.verdict = self
+            .updates
+            .iter()
+            .map(|update| update.verdict)
+            .max()
+            .unwrap_or(Verdict::Pass);
+        let rules: BTreeSet<&str> = self
+            .updates
+            .iter()
+            .flat_map(|update| &update.violations)
+            .map(|violation| violation.rule.as_str())
+            .collect();
+        self.violated_rules = rules.into_iter().map(ToString::to_string).collect();
+    }
+
+    /// Checks the advisories that the updates claim to fix (the vulnerabilities of the analysis
+    /// that don't affect their new versions) against the lockfile resolved with the updates,
+    /// e.g. the `Cargo.lock` of the dependabot PR (see [`super::fix_check`]).
+    /// The updates whose fixes didn't make it to the lockfile fail,
+    /// and the dependencies the lockfile moves to a lower version are reviewed as downgrades.
+    pub fn check_lockfile(&mut self, analysis: &RustAnalysis, lockfile: &str) -> Result<()> {
+        let lockfile = lockfile_format::parse(lockfile)
+            .context("couldn't parse the Cargo.lock of the updates")?;
+        for update in &mut self.updates {
+            let claimed: Vec<_> = analysis
+                .rustsec
+                .vulnerabilities
+                .iter()
+                .enumerate()
+                .filter(|(_, vuln)| {
+                    vuln.package.name.as_str() == update.name
+                        && vuln.package.version == update.from
+                        && !vuln.versions.is_vulnerable(&update.to)
+                })
+                .collect();
+            for (idx, vuln) in claimed {
+                let check = FixCheck::new(
+                    vuln.advisory.id.as_str(),
+                    &update.name,
+                    |version| vuln.versions.is_vulnerable(version),
+                    &lockfile,
+                );
+                if !check.fixed() {
+                    update.verdict = Verdict::Fail;
+                    update.violations.push(Violation {
+                        rule: "advisory-fix-not-locked".to_string(),
+                        verdict: Verdict::Fail,
+                        category: Some(FindingCategory::Advisory),
+                        message: check.describe(),
+                        evidence: Some(format!(
+                            "/rust_dependencies/rustsec/vulnerabilities/{}",
+                            idx
+                        )),
+                    });
+                    update.reasons.push(check.describe());
+                    if !update.categories.contains(&FindingCategory::Advisory) {
+                        update.categories.insert(0, FindingCategory::Advisory);
+                    }
+                }
+                self.fix_checks.push(check);
+            }
+        }
+
+        // downgrades (only of the dependencies locked in a single version, see [`backfill::diff_packages`])
+        let mut current: BTreeMap<String, BTreeSet<Version>> = BTreeMap::new();
+        for dependency in &analysis.dependencies {
+            current
+                .entry(dependency.name.clone())
+                .or_default()
+                .insert(dependency.version.clone());
+        }
+        let (changes, _, _) = backfill::diff_packages(&current, &backfill::packages(&lockfile));
+        for change in changes.iter().filter(|change| change.downgrade) {
+            let dependency = analysis
+                .dependencies
+                .iter()
+                .find(|dependency| dependency.name == change.name);
+            if let Some(dependency) = dependency {
+                self.updates.retain(|update| update.name != change.name);
+                self.updates
+                    .push(UpdateVerdict::downgrade(analysis, dependency, &change.to));
+            }
+        }
+        self.summarize();
+        Ok(())
+    }
+
+    /// Lists the reviewers to assign to the updates, `groups` mapping categories of findings
+    /// to reviewer groups (see [`crate::model::RepoConfig::reviewers`]).
+    /// To call once the reviews are complete (e.g. after [`Self::check_lockfile`]).
+    pub fn assign(&mut self, groups: &BTreeMap<FindingCategory, Vec<String>>) {
+        self.assignments = reviewers::assign(&self.updates, groups);
+    }
+
+    /// Compares the review with the previous one posted on the same PR (e.g. before a force-push),
+    /// the markdown then leads with the changes.
+    pub fn compare(&mut self, previous: &GroupVerdict) {
+        self.since_last_review = Some(ReviewDiff::new(previous, self));
+    }
+
+    /// Renders the review as markdown (e.g. to be posted as a PR comment),
+    /// with one collapsible section per update
+    /// (or a single table with [`Verbosity::Minimal`]).
+    pub fn to_markdown(&self, theme: &MarkdownTheme, verbosity: Verbosity) -> String {
+        let mut markdown = String::new();
+        let _ = writeln!(
+            markdown,
+            "## {} {}\n",
+            theme.icon(self.verdict),
+            theme.header(self.updates.len())
+        );
+        if let Some(diff) = &self.since_last_review {
+            markdown.push_str(&diff.to_markdown(theme));
+        }
+        if verbosity == Verbosity::Minimal {
+            let mut table = Table::new()
+                .column("", Align::Center)
+                .column("crate", Align::Left)
+                .column("from", Align::Left)
+                .column("to", Align::Left)
+                .column("reasons", Align::Left)
+                .column("affects", Align::Left);
+            for update in &self.updates {
+                table.row(update.summary_row(theme));
+            }
+            markdown.push_str(&table.to_markdown());
+            markdown.push('\n');
+        } else {
+            for update in &self.updates {
+                markdown.push_str(&update.to_markdown(theme, verbosity));
+                markdown.push('\n');
+            }
+        }
+        if !self.fix_checks.is_empty() {
+            markdown.push_str("### Advisory fixes in Cargo.lock\n\n");
+            for check in &self.fix_checks {
+                let _ = writeln!(
+                    markdown,
+                    "- {} {}",
+                    theme.icon(if check.fixed() {
+                        Verdict::Pass
+                    } else {
+                        Verdict::Fail
+                    }),
+                    check.describe()
+                );
+            }
+            markdown.push('\n');
+        }
+        let build_time: Vec<&UpdateVerdict> = self
+            .updates
+            .iter()
+            .filter(|update| update.build_time_change.is_some())
+            .collect();
+        if !build_time.is_empty() {
+            markdown.push_str("### Build-time surface\n\n");
+            markdown.push_str("These updates grow the code running on the build machines:\n\n");
+            for update in build_time {
+                if let Some(change) = &update.build_time_change {
+                    let _ = writeln!(
+                        markdown,
+                        "- **{}** {} → {}: {}",
+                        update.name,
+                        update.from,
+                        update.target(),
+                        change.describe()
+                    );
+                }
+            }
+            markdown.push('\n');
+        }
+        if !self.assignments.is_empty() {
+            markdown.push_str("### Assign to\n\n");
+            for assignment in &self.assignments {
+                let categories: Vec<&str> = assignment
+                    .categories
+                    .iter()
+                    .map(FindingCategory::as_str)
+                    .collect();
+                let _ = writeln!(
+                    markdown,
+                    "- {} ({}): {}",
+                    assignment.reviewer,
+                    categories.join(", "),
+                    assignment.updates.join(", ")
+                );
+            }
+            markdown.push('\n');
+        }
+        let owners: BTreeSet<&str> = self
+            .updates
+            .iter()
+            .flat_map(|update| update.owners.iter().map(String::as_str))
+            .filter(|owner| owner.starts_with('@'))
+            .collect();
+        if !owners.is_empty() {
+            let owners: Vec<&str> = owners.into_iter().collect();
+            let _ = writeln!(markdown, "cc {}", owners.join(" "));
+        }
+        markdown
+    }
+}
+
+/// Checks if a version change is compatible according to cargo's caret requirements
+/// (which only match the pre-releases of the release required).
+fn compatible(from: &Version, to: &Version) -> bool {
+    if to.is_prerelease() && (from.major, from.minor, from.patch) != (to.major, to.minor, to.patch)
+    {
+        return false;
+    }
+    match (from.major, from.minor) {
+        (0, 0) => to.major == 0 && to.minor == 0 && to.patch == from.patch,
+        (0, minor) => to.major == 0 && to.minor == minor,
+        (major, _) => to.major == major,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rust::{theme::IconStyle, AdvisoryRecord, RustSec, Update};
+    use guppy_summaries::SummarySource;
+
+    #[test]
+    fn test_compatible() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        assert!(compatible(&v("1.2.3"), &v("1.4.0")));
+        assert!(!compatible(&v("1.2.3"), &v("2.0.0")));
+        assert!(compatible(&v("0.3.1"), &v("0.3.9")));
+        assert!(!compatible(&v("0.3.1"), &v("0.4.0")));
+        assert!(!compatible(&v("0.0.1"), &v("0.0.2")));
+        assert!(compatible(&v("2.0.0-beta.3"), &v("2.0.0-rc.1")));
+        assert!(compatible(&v("2.0.0-rc.1"), &v("2.1.0")));
+        assert!(!compatible(&v("1.2.3"), &v("1.3.0-alpha")));
+        assert!(compatible(&v("1.2.3+build.1"), &v("1.2.4+build.2")));
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut dependency = DependencyInfo::new(
+            "libc".to_string(),
+            Version::parse("0.2.86").unwrap(),
+            SummarySource::CratesIo,
+            false,
+            true,
+        );
+        dependency.update = Some(Update {
+            versions: vec![Version::parse("0.2.87").unwrap()],
+            build_rs: true,
+            ..Default::default()
+        });
+        let analysis = RustAnalysis {
+            dependencies: vec![dependency],
+            ..Default::default()
+        };
+
+        let group = GroupVerdict::new(&analysis, &[]);
+        assert_eq!(group.violated_rules, vec!["build-script-changed"]);
+        let violation = &group.updates[0].violations[0];
+        assert_eq!(violation.verdict, Verdict::Warn);
+        assert_eq!(violation.category, Some(FindingCategory::Build));
+        assert_eq!(violation.message, "build.rs changed");
+
+        // the evidence points into the analysis (the `rust_dependencies` of an analysis)
+        let evidence = violation.evidence.as_deref().unwrap();
+        let analysis = serde_json::to_value(&analysis).unwrap();
+        assert_eq!(
+            analysis.pointer(evidence.trim_start_matches("/rust_dependencies")),
+            Some(&serde_json::Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let mut dependency = DependencyInfo::new(
+            "smallvec".to_string(),
+            Version::parse("1.6.1").unwrap(),
+            SummarySource::CratesIo,
+            false,
+            true,
+        );
+        dependency.advisory_history = vec![AdvisoryRecord {
+            id: "RUSTSEC-2021-0003".to_string(),
+            date: "2021-01-08".to_string(),
+            patched: vec![">= 1.6.1".to_string()],
+            unaffected: vec!["< 1.3.0".to_string()],
+            days_to_fix: Some(1),
+        }];
+        let analysis = RustAnalysis {
+            dependencies: vec![dependency],
+            ..Default::default()
+        };
+
+        let mut group = GroupVerdict::new(&analysis, &[]);
+        assert!(group.updates.is_empty());
+        group
+            .check_lockfile(
+                &analysis,
+                "[[package]]\nname = \"smallvec\"\nversion = \"1.6.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+            )
+            .unwrap();
+        assert_eq!(group.verdict, Verdict::Fail);
+        assert_eq!(
+            group.violated_rules,
+            vec!["advisory-reintroduced", "version-downgraded"]
+        );
+        let downgrade = &group.updates[0];
+        assert!(downgrade.is_downgrade());
+        assert_eq!(downgrade.reasons[0], "downgraded from 1.6.1 to 1.6.0");
+        assert!(downgrade
+            .to_markdown(&MarkdownTheme::default(), Verbosity::Standard)
+            .contains("1.6.1 → 1.6.0 (downgrade)"));
+    }
+
+    #[test]
+    fn test_advisory_of_other_version() {
+        // smallvec 0.6.9 is locked too, and is the one affected by the advisory
+        let advisory: rustsec::Advisory = "```toml
+[advisory]
+id = \"RUSTSEC-2019-0009\"
+package = \"smallvec\"
+date = \"2019-06-06\"
+
+[versions]
+patched = [\">= 0.6.10\"]
+unaffected = [\"< 0.6.5\"]
+```
+
+# Double-free and use-after-free in SmallVec::grow()
+"
+        .parse()
+        .unwrap();
+        let package = rustsec::package::Package {
+            name: "smallvec".parse().unwrap(),
+            version: Version::parse("0.6.9").unwrap(),
+            source: None,
+            checksum: None,
+            dependencies: Vec::new(),
+            replace: None,
+        };
+        let mut dependency = DependencyInfo::new(
+            "smallvec".to_string(),
+            Version::parse("1.6.0").unwrap(),
+            SummarySource::CratesIo,
+            false,
+            true,
+        );
+        dependency.update = Some(Update {
+            versions: vec![Version::parse("1.6.1").unwrap()],
+            ..Default::default()
+        });
+        let analysis = RustAnalysis {
+            dependencies: vec![dependency],
+            rustsec: RustSec {
+                vulnerabilities: vec![rustsec::Vulnerability::new(&advisory, &package)],
+                warnings: Default::default(),
+            },
+            ..Default::default()
+        };
+
+        // the update of smallvec 1.6.0 doesn't claim to fix it
+        let mut group = GroupVerdict::new(&analysis, &[]);
+        assert_eq!(group.verdict, Verdict::Pass);
+        assert!(group.updates[0].reasons.is_empty());
+        group
+            .check_lockfile(
+                &analysis,
+                "[[package]]\nname = \"smallvec\"\nversion = \"0.6.9\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n[[package]]\nname = \"smallvec\"\nversion = \"1.6.1\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+            )
+            .unwrap();
+        assert!(group.fix_checks.is_empty());
+        assert_eq!(group.verdict, Verdict::Pass);
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let update = UpdateVerdict {
+            name: "serde".to_string(),
+            from: Version::parse("1.0.0").unwrap(),
+            to: Version::parse("1.0.1").unwrap(),
+            verdict: Verdict::Warn,
+            reasons: vec!["build.rs changed".to_string()],
+            owners: vec!["@acme/core".to_string()],
+            affects: vec!["service-a (bin)".to_string(), "cli-b (bin)".to_string()],
+            details: UpdateDetails {
+                hops: vec![Version::parse("1.0.1").unwrap()],
+                changelog: Some("- fix a bug".to_string()),
+                ..Default::default()
+            },
+            build_time_change: None,
+            tags: Vec::new(),
+            categories: vec![FindingCategory::Build],
+            violations: Vec::new(),
+        };
+        let mut group = GroupVerdict {
+            verdict: Verdict::Warn,
+            updates: vec![update],
+            fix_checks: Vec::new(),
+            assignments: Vec::new(),
+            violated_rules: Vec::new(),
+            since_last_review: None,
+        };
+        let markdown = group.to_markdown(&MarkdownTheme::default(), Verbosity::Standard);
+        assert!(markdown.starts_with("## ![warn]"));
+        assert!(markdown.contains("<details>\n<summary>"));
+        assert!(markdown.contains("<strong>serde</strong> 1.0.0 → 1.0.1"));
+        assert!(markdown.contains("- build.rs changed"));
+        assert!(markdown.contains("\naffects: service-a (bin), cli-b (bin)\n"));
+        assert!(markdown.ends_with("cc @acme/core\n"));
+        assert!(!markdown.contains("fix a bug"));
+
+        // a single table
+        let markdown = group.to_markdown(&MarkdownTheme::default(), Verbosity::Minimal);
+        assert!(!markdown.contains("<details>"));
+        assert!(markdown.contains(
+            "| **serde** | 1.0.0 | 1.0.1 | build.rs changed | service-a (bin), cli-b (bin) |"
+        ));
+
+        // everything
+        let markdown = group.to_markdown(&MarkdownTheme::default(), Verbosity::Full);
+        assert!(markdown.contains("**versions**: 1.0.1"));
+        assert!(markdown.contains("```\n- fix a bug\n```"));
+
+        // no-emoji mode
+        let theme = MarkdownTheme {
+            style: IconStyle::Text,
+            ..Default::default()
+        };
+        let markdown = group.to_markdown(&theme, Verbosity::Standard);
+        assert!(markdown.starts_with("## [WARN] 1 updates reviewed"));
+        assert!(!markdown.contains("!["));
+
+        // reviewers assigned by finding category
+        let mut groups = BTreeMap::new();
+        groups.insert(FindingCategory::Build, vec!["@acme/build".to_string()]);
+        group.assign(&groups);
+        let markdown = group.to_markdown(&MarkdownTheme::default(), Verbosity::Standard);
+        assert!(markdown.contains("### Assign to\n\n- @acme/build (build): serde 1.0.0 → 1.0.1\n"));
+    }
+}
@@ -0,0 +1,88 @@
+//! This module decides which published versions a dependency can be updated to,
+//! following the rules of cargo for the versions that aren't plain releases:
+//!
+//! - a pre-release (e.g. `2.0.0-rc.1`) is only proposed to a dependency locked at a pre-release
+//!   of the same release (e.g. `2.0.0-beta.3`), as cargo doesn't select pre-releases otherwise,
+//! - pre-releases are ordered by their identifiers (numeric ones numerically: `rc.2` < `rc.10`),
+//!   and come before their release,
+//! - build metadata (e.g. `0.10.2+wasi-snapshot-preview1`) is ignored when comparing versions,
+//!   so versions only differing by it are the same version.
+
+use semver::Version;
+
+/// Is a version a candidate to update a dependency locked at `current`?
+pub fn is_candidate(current: &Version, version: &Version) -> bool {
+    version > current
+        && (!version.is_prerelease() || (current.is_prerelease() && same_release(current, version)))
+}
+
+/// Are two versions (pre-releases of) the same release?
+fn same_release(a: &Version, b: &Version) -> bool {
+    (a.major, a.minor, a.patch) == (b.major, b.minor, b.patch)
+}
+
+/// Returns the versions a dependency locked at `current` can be updated to, sorted
+/// (the last one is the target of the update, the others are the hops of the update).
+pub fn update_versions(current: &Version, published: &[Version]) -> Vec<Version> {
+    let mut versions: Vec<Version> = published
+        .iter()
+        .filter(|version| is_candidate(current, version))
+        .cloned()
+        .collect();
+    versions.sort();
+    // (keeps one of the versions that only differ by their build metadata)
+    versions.dedup();
+    versions
+}
+
+/// Renders a version, labeled if it is a pre-release (e.g. `2.0.0-rc.1 (pre-release)`).
+pub fn label(version: &Version) -> String {
+    if version.is_prerelease() {
+        format!("{} (pre-release)", version)
+    } else {
+        version.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_versions() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        let published: Vec<Version> = [
+            "1.0.0",
+            "1.1.0",
+            "2.0.0-rc.10",
+            "2.0.0-rc.2",
+            "1.2.0+build.5",
+            "1.2.0+build.4",
+            "2.0.0-alpha",
+        ]
+        .iter()
+        .map(|version| v(version))
+        .collect();
+
+        // a release is only updated to releases
+        let versions = update_versions(&v("1.0.0"), &published);
+        let versions: Vec<String> = versions.iter().map(ToString::to_string).collect();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0], "1.1.0");
+        assert!(versions[1].starts_with("1.2.0+build."));
+
+        // a pre-release goes through the pre-releases of its release, in order
+        assert_eq!(
+            update_versions(&v("2.0.0-alpha"), &published),
+            vec![v("2.0.0-rc.2"), v("2.0.0-rc.10")]
+        );
+        assert!(!is_candidate(&v("1.1.0-beta"), &v("2.0.0-rc.2")));
+        assert!(is_candidate(&v("1.1.0-beta"), &v("1.2.0")));
+
+        // build metadata doesn't make a version newer
+        assert!(!is_candidate(&v("1.2.0+build.4"), &v("1.2.0+build.5")));
+
+        assert_eq!(label(&v("2.0.0-rc.2")), "2.0.0-rc.2 (pre-release)");
+        assert_eq!(label(&v("1.2.0+build.5")), "1.2.0+build.5");
+    }
+}
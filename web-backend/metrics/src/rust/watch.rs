@@ -0,0 +1,273 @@
+//! This module watches the direct dependencies of a repository for new releases,
+//! for teams that update manually on a schedule rather than via dependabot:
+//! each run reports the direct dependencies that published new versions since the previous run
+//! (with a snippet of their changelog and their advisory status),
+//! and records the latest versions seen in a [`WatchState`] (see [`crate::model::Watches`]).
+
+use chrono::{DateTime, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::{DependencyInfo, RustAnalysis};
+use crate::dashboard::table::{Align, Cell, Table};
+use crate::verbosity::Verbosity;
+
+/// The number of lines of the changelog included in a report.
+const CHANGELOG_SNIPPET_LINES: usize = 15;
+
+/// What the previous runs of the watcher have seen.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct WatchState {
+    pub repository: String,
+    /// the latest version seen for each direct dependency
+    #[serde(default)]
+    pub latest_seen: BTreeMap<String, Version>,
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+impl WatchState {
+    /// The state after a run on an analysis.
+    pub fn new(repository: &str, analysis: &RustAnalysis) -> Self {
+        let latest_seen = analysis
+            .dependencies()
+            .iter()
+            .filter(|dependency| dependency.direct())
+            .map(|dependency| {
+                let latest = dependency
+                    .update()
+                    .and_then(|update| update.versions().last())
+                    .unwrap_or_else(|| dependency.version());
+                (dependency.name().to_string(), latest.clone())
+            })
+            .collect();
+        Self {
+            repository: repository.to_string(),
+            latest_seen,
+            last_run: Some(Utc::now()),
+        }
+    }
+}
+
+/// A direct dependency that published new versions since the previous run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewRelease {
+    pub name: String,
+    /// the version used by the repository
+    pub current: Version,
+    pub latest: Version,
+    /// the versions published since the previous run
+    /// (every version newer than the current one on the first run)
+    pub new_versions: Vec<Version>,
+    pub changelog_url: Option<String>,
+    /// the first lines of the changelog between the current and the latest versions
+    pub changelog: Option<String>,
+    /// the RUSTSEC advisories affecting the current version
+    pub advisories: Vec<String>,
+    /// the ones of them that still affect the latest version
+    pub unfixed_advisories: Vec<String>,
+}
+
+impl NewRelease {
+    /// Returns the new releases of a direct dependency, if it published any after the version last seen.
+    pub fn new(dependency: &DependencyInfo, last_seen: Option<&Version>) -> Option<Self> {
+        if !dependency.direct() {
+            return None;
+        }
+        let update = dependency.update()?;
+        let latest = update.versions().last()?;
+        let new_versions: Vec<Version> = update
+            .versions()
+            .iter()
+            .filter(|version| last_seen.map_or(true, |seen| *version > seen))
+            .cloned()
+            .collect();
+        if new_versions.is_empty() {
+            return None;
+        }
+
+        let changelog = update.update_metadata().changelog_text().map(|text| {
+            text.lines()
+                .take(CHANGELOG_SNIPPET_LINES)
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        let affecting: Vec<_> = dependency
+            .advisory_history()
+            .iter()
+            .filter(|advisory| advisory.affects(dependency.version()))
+            .collect();
+        Some(Self {
+            name: dependency.name().to_string(),
+            current: dependency.version().clone(),
+            latest: latest.clone(),
+            new_versions,
+            changelog_url: update
+                .update_metadata()
+                .changelog_url()
+                .map(ToString::to_string),
+            changelog,
+            advisories: affecting
+                .iter()
+                .map(|advisory| advisory.id().to_string())
+                .collect(),
+            unfixed_advisories: affecting
+                .iter()
+                .filter(|advisory| advisory.affects(latest))
+                .map(|advisory| advisory.id().to_string())
+                .collect(),
+        })
+    }
+}
+
+/// The new releases of the direct dependencies of a repository since the previous run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatchReport {
+    pub repository: String,
+    /// the date of the previous run (none on the first run)
+    pub since: Option<DateTime<Utc>>,
+    pub releases: Vec<NewRelease>,
+}
+
+impl WatchReport {
+    pub fn new(repository: &str, analysis: &RustAnalysis, previous: Option<&WatchState>) -> Self {
+        let mut releases: Vec<NewRelease> = analysis
+            .dependencies()
+            .iter()
+            .filter_map(|dependency| {
+                let last_seen = previous.and_then(|state| state.latest_seen.get(dependency.name()));
+                NewRelease::new(dependency, last_seen)
+            })
+            .collect();
+        releases.sort_by(|a, b| a.name.cmp(&b.name));
+        releases.dedup_by(|a, b| a.name == b.name);
+        Self {
+            repository: repository.to_string(),
+            since: previous.and_then(|state| state.last_run),
+            releases,
+        }
+    }
+
+    /// Renders the report as markdown
+    /// (a single table with [`Verbosity::Minimal`], [`Verbosity::Full`] has nothing more to show).
+    pub fn to_markdown(&self, verbosity: Verbosity) -> String {
+        let mut markdown = String::new();
+        let since = match self.since {
+            Some(since) => format!("since {}", since.format("%Y-%m-%d")),
+            None => "(first run)".to_string(),
+        };
+        let _ = writeln!(
+            markdown,
+            "## New releases of the direct dependencies of {} {}\n",
+            self.repository, since
+        );
+        if self.releases.is_empty() {
+            markdown.push_str("No new release.\n");
+            return markdown;
+        }
+
+        if verbosity == Verbosity::Minimal {
+            let mut table = Table::new()
+                .column("crate", Align::Left)
+                .column("current", Align::Left)
+                .column("latest", Align::Left)
+                .column("new versions", Align::Right)
+                .column("advisories", Align::Left);
+            for release in &self.releases {
+                table.row(vec![
+                    Cell::strong(&release.name),
+                    Cell::text(&release.current),
+                    Cell::text(&release.latest),
+                    Cell::text(release.new_versions.len()),
+                    Cell::text(release.advisories.join(", ")),
+                ]);
+            }
+            markdown.push_str(&table.to_markdown());
+            return markdown;
+        }
+
+        for release in &self.releases {
+            let new_versions: Vec<String> = release
+                .new_versions
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            let _ = writeln!(
+                markdown,
+                "### {} {} → {}\n\nnew versions: {}\n",
+                release.name,
+                release.current,
+                release.latest,
+                new_versions.join(", ")
+            );
+            if !release.advisories.is_empty() {
+                let _ = writeln!(
+                    markdown,
+                    "advisories affecting {}: {} (still affecting {}: {})\n",
+                    release.current,
+                    release.advisories.join(", "),
+                    release.latest,
+                    if release.unfixed_advisories.is_empty() {
+                        "none".to_string()
+                    } else {
+                        release.unfixed_advisories.join(", ")
+                    }
+                );
+            }
+            if let Some(changelog) = &release.changelog {
+                let _ = writeln!(markdown, "```\n{}\n```\n", changelog);
+            }
+            if let Some(url) = &release.changelog_url {
+                let _ = writeln!(markdown, "[changelog]({})\n", url);
+            }
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rust::{AdvisoryRecord, Update};
+    use guppy_summaries::SummarySource;
+
+    #[test]
+    fn test_new_releases() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        let mut dependency = DependencyInfo::new(
+            "smallvec".to_string(),
+            v("1.6.0"),
+            SummarySource::CratesIo,
+            false,
+            true,
+        );
+        dependency.update = Some(Update {
+            versions: vec![v("1.6.1"), v("1.7.0")],
+            ..Default::default()
+        });
+        dependency.advisory_history = vec![AdvisoryRecord {
+            id: "RUSTSEC-2021-0003".to_string(),
+            date: "2021-01-08".to_string(),
+            patched: vec![">= 1.6.1".to_string()],
+            unaffected: Vec::new(),
+            days_to_fix: Some(0),
+        }];
+
+        // first run: everything newer than the current version
+        let release = NewRelease::new(&dependency, None).unwrap();
+        assert_eq!(release.latest, v("1.7.0"));
+        assert_eq!(release.new_versions, vec![v("1.6.1"), v("1.7.0")]);
+        assert_eq!(release.advisories, vec!["RUSTSEC-2021-0003".to_string()]);
+        assert!(release.unfixed_advisories.is_empty());
+
+        // only what was published since
+        let release = NewRelease::new(&dependency, Some(&v("1.6.1"))).unwrap();
+        assert_eq!(release.new_versions, vec![v("1.7.0")]);
+        assert!(NewRelease::new(&dependency, Some(&v("1.7.0"))).is_none());
+
+        // transitive dependencies are not watched
+        dependency.direct = false;
+        assert!(NewRelease::new(&dependency, None).is_none());
+    }
+}
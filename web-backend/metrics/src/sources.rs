@@ -0,0 +1,165 @@
+//! This module lists the external data sources used by an analysis,
+//! each of which can be disabled at compile time (via cargo features)
+//! or at runtime (via the `DISABLED_SOURCES` environment variable, e.g. `DISABLED_SOURCES=github,geiger`).
+//! An analysis still computes everything that doesn't depend on a disabled source.
+//!
+//! In offline mode (`OFFLINE=1`), the sources requiring network access are disabled,
+//! unless their data was prefetched (see [`crate::cache`]),
+//! and the advisory database is read from disk (see [`crate::rust::cargoaudit::advisory_db_path`]).
+//!
+//! In sandbox mode (`SANDBOX=1`), meant for untrusted repositories, nothing runs code of the analyzed
+//! repository or of its dependencies (build scripts, proc macros): the dependency graph is read from `Cargo.lock`
+//! (see [`crate::rust::lockfile`]), unsafe code is only counted by the internal scanner,
+//! and crates are only diffed from their packages. The analyses skipped are listed in [`SANDBOX_SKIPPED`].
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// The environment variable listing the sources disabled at runtime (comma-separated).
+pub const DISABLED_SOURCES_VAR: &str = "DISABLED_SOURCES";
+/// The environment variable enabling the offline mode (`1` or `true`).
+pub const OFFLINE_VAR: &str = "OFFLINE";
+/// The environment variable enabling the sandbox mode (`1` or `true`).
+pub const SANDBOX_VAR: &str = "SANDBOX";
+
+/// The analyses skipped in sandbox mode, because they would execute code of the analyzed repository
+/// or of its dependencies.
+pub const SANDBOX_SKIPPED: &[&str] = &[
+    "cargo metadata (the dependency graph is read from Cargo.lock: no depth, introducers, licenses or platforms)",
+    "cargo generate-lockfile (the repository must commit its Cargo.lock)",
+    "cargo geiger (unsafe code is counted by the internal scanner)",
+    "crate sources extracted by cargo (crates are diffed from their packages only)",
+];
+
+/// Is the offline mode enabled? In that case only local data is used.
+pub fn offline() -> bool {
+    std::env::var(OFFLINE_VAR)
+        .map(|offline| offline == "1" || offline.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Is the sandbox mode enabled? In that case no code of the analyzed repository or of its dependencies is run.
+pub fn sandbox() -> bool {
+    std::env::var(SANDBOX_VAR)
+        .map(|sandbox| sandbox == "1" || sandbox.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// An external data source.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// the GitHub API (changelogs, repository metrics)
+    GitHub,
+    /// the crates.io API and crate downloads (updates, owners, diffs)
+    CratesIo,
+    /// the cargo-geiger binary (unsafe code)
+    Geiger,
+    /// the RUSTSEC advisory database (cargo-audit, advisory history)
+    Advisories,
+}
+
+impl Source {
+    pub const ALL: [Source; 4] = [
+        Source::GitHub,
+        Source::CratesIo,
+        Source::Geiger,
+        Source::Advisories,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::GitHub => "github",
+            Source::CratesIo => "cratesio",
+            Source::Geiger => "geiger",
+            Source::Advisories => "advisories",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|source| source.as_str() == name.trim().to_lowercase())
+    }
+
+    /// Was the source compiled in? (see the cargo features of this crate)
+    fn compiled(&self) -> bool {
+        match self {
+            Source::GitHub => cfg!(feature = "github"),
+            Source::CratesIo => cfg!(feature = "cratesio"),
+            Source::Geiger => cfg!(feature = "geiger"),
+            Source::Advisories => cfg!(feature = "advisories"),
+        }
+    }
+
+    /// Can the source be used offline?
+    fn available_offline(&self) -> bool {
+        match self {
+            Source::GitHub => false,
+            // (if the crates.io responses were prefetched)
+            Source::CratesIo => crate::cache::cache_dir().join("responses").exists(),
+            // (cargo-geiger runs with the dependencies found in the cargo cache)
+            Source::Geiger => true,
+            Source::Advisories => crate::rust::cargoaudit::advisory_db_path().exists(),
+        }
+    }
+
+    /// Does the source run code of the analyzed crates?
+    /// (cargo-geiger builds them, which runs their build scripts and proc macros)
+    fn executes_code(&self) -> bool {
+        matches!(self, Source::Geiger)
+    }
+
+    /// Can the source be used?
+    pub fn is_enabled(&self) -> bool {
+        self.compiled()
+            && !runtime_disabled().contains(self)
+            && (!offline() || self.available_offline())
+            && !(sandbox() && self.executes_code())
+    }
+
+    /// Returns the sources that can't be used.
+    pub fn disabled() -> Vec<Source> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(|source| !source.is_enabled())
+            .collect()
+    }
+}
+
+/// Parses the sources disabled at runtime.
+fn runtime_disabled() -> Vec<Source> {
+    std::env::var(DISABLED_SOURCES_VAR)
+        .map(|sources| parse_sources(&sources))
+        .unwrap_or_default()
+}
+
+fn parse_sources(sources: &str) -> Vec<Source> {
+    sources
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .filter_map(|name| {
+            let source = Source::from_name(name);
+            if source.is_none() {
+                warn!("unknown source in {}: {}", DISABLED_SOURCES_VAR, name);
+            }
+            source
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sources() {
+        assert_eq!(
+            parse_sources("github, GEIGER,unknown,"),
+            vec![Source::GitHub, Source::Geiger]
+        );
+        assert!(parse_sources("").is_empty());
+    }
+}
@@ -0,0 +1,287 @@
+//! Utilities to test the analyzers deterministically and without network access,
+//! for the tests of this crate and for its users:
+//!
+//! - cassettes: HTTP interactions recorded in a JSON file (see [`Cassette`] and [`RecordingTransport`]),
+//!   played back by a [`MockTransport`],
+//! - crates: extracted crate directories and canned `.crate` packages built from a list of files
+//!   (see [`write_crate`] and [`package`]),
+//! - repositories: small git repositories built from a list of commits (see [`git_repo`]),
+//!   and the vendored sample workspace of `resources/test` (see [`sample_repo`]).
+//!
+//! The fixtures vendored by this crate are in `resources/test`.
+
+use anyhow::{ensure, Context, Result};
+use futures::future::{BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+use tokio::process::Command;
+
+use crate::http::{MockTransport, Response, Transport};
+
+/// The directory of the fixtures vendored by this crate.
+pub fn resources_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("resources")
+        .join("test")
+}
+
+/// A small workspace with a dependency (see `resources/test/sample_repo`).
+pub fn sample_repo() -> PathBuf {
+    resources_dir().join("sample_repo")
+}
+
+/// A cassette vendored by this crate (see `resources/test/cassettes`).
+pub fn cassette(name: &str) -> Result<Cassette> {
+    Cassette::load(
+        &resources_dir()
+            .join("cassettes")
+            .join(format!("{}.json", name)),
+    )
+}
+
+/// A recorded request and its response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Interaction {
+    pub url: String,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// the body of the response (bodies that aren't UTF-8 are not recorded, see [`package`] for packages)
+    pub body: String,
+}
+
+/// Recorded HTTP interactions.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: &Path) -> Result<Self> {
+        let cassette = fs::read_to_string(path)
+            .with_context(|| format!("couldn't read the cassette {}", path.display()))?;
+        serde_json::from_str(&cassette)
+            .with_context(|| format!("invalid cassette {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns a transport playing back the interactions.
+    pub fn transport(&self) -> MockTransport {
+        self.interactions
+            .iter()
+            .fold(MockTransport::new(), |transport, interaction| {
+                transport.with_response(
+                    &interaction.url,
+                    Response {
+                        status: interaction.status,
+                        headers: interaction.headers.clone(),
+                        body: interaction.body.clone().into_bytes(),
+                    },
+                )
+            })
+    }
+}
+
+/// Records the interactions of another transport (e.g. the network) in a cassette.
+/// Requests are sent unconditionally (without the ETag of a cached response),
+/// so that the cassette has every body.
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn Transport>) -> Self {
+        Self {
+            inner,
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// The interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette
+            .lock()
+            .map(|cassette| cassette.clone())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, url: &str, response: &Response) {
+        let body = match String::from_utf8(response.body.clone()) {
+            Ok(body) => body,
+            Err(_) => return,
+        };
+        if let Ok(mut cassette) = self.cassette.lock() {
+            cassette.interactions.push(Interaction {
+                url: url.to_string(),
+                status: response.status,
+                headers: response.headers.clone(),
+                body,
+            });
+        }
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+    ) -> BoxFuture<'a, Result<Response>> {
+        async move {
+            let headers: Vec<(&str, String)> = headers
+                .iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("if-none-match"))
+                .cloned()
+                .collect();
+            let response = self.inner.get(url, &headers).await?;
+            self.record(url, &response);
+            Ok(response)
+        }
+        .boxed()
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<Response>> {
+        async move {
+            let response = self.inner.post(url, headers, body).await?;
+            self.record(url, &response);
+            Ok(response)
+        }
+        .boxed()
+    }
+
+    fn is_network(&self) -> bool {
+        self.inner.is_network()
+    }
+}
+
+/// Writes files (paths relative to `dir`, e.g. `src/lib.rs`) in a directory, e.g. an extracted crate.
+pub fn write_crate(dir: &Path, files: &[(&str, &str)]) -> Result<()> {
+    for (path, content) in files {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Builds the package (`.crate` file) of a crate version containing the given files,
+/// as crates.io serves it (a gzipped tarball of a `<name>-<version>` directory).
+pub async fn package(name: &str, version: &str, files: &[(&str, &str)]) -> Result<Vec<u8>> {
+    let dir = tempdir()?;
+    let crate_dir = format!("{}-{}", name, version);
+    write_crate(&dir.path().join(&crate_dir), files)?;
+    let output = Command::new("tar")
+        .current_dir(dir.path())
+        .args(&["-czf", "package.crate", &crate_dir])
+        .output()
+        .await?;
+    ensure!(
+        output.status.success(),
+        "couldn't build the package: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(fs::read(dir.path().join("package.crate"))?)
+}
+
+/// Creates a git repository in `dir` with a commit per list of files (written on top of the previous commit).
+pub async fn git_repo(dir: &Path, commits: &[&[(&str, &str)]]) -> Result<()> {
+    git(dir, &["init", "--quiet"]).await?;
+    for (i, files) in commits.iter().enumerate() {
+        write_crate(dir, files)?;
+        git(dir, &["add", "--all"]).await?;
+        git(
+            dir,
+            &[
+                "-c",
+                "user.name=whackadep",
+                "-c",
+                "user.email=whackadep@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                &format!("commit {}", i + 1),
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .await?;
+    ensure!(
+        output.status.success(),
+        "couldn't run git {}: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_recording() {
+        let transport = MockTransport::new().with_response(
+            "https://crates.io/api/v1/crates/a",
+            Response::ok("{}").with_header("ETag", "\"1\""),
+        );
+        let recorder = RecordingTransport::new(Arc::new(transport));
+        recorder
+            .get("https://crates.io/api/v1/crates/a", &[])
+            .await
+            .unwrap();
+        assert!(recorder
+            .get("https://crates.io/api/v1/crates/b", &[])
+            .await
+            .is_err());
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        recorder.cassette().save(&path).unwrap();
+        let cassette = Cassette::load(&path).unwrap();
+        assert_eq!(cassette.interactions.len(), 1);
+
+        let response = cassette
+            .transport()
+            .get("https://crates.io/api/v1/crates/a", &[])
+            .await
+            .unwrap();
+        assert_eq!(response.header("etag"), Some("\"1\""));
+        assert_eq!(response.text().unwrap(), "{}");
+    }
+
+    #[tokio::test]
+    async fn test_git_repo() {
+        let dir = tempdir().unwrap();
+        git_repo(
+            dir.path(),
+            &[&[("a.txt", "1")], &[("a.txt", "2"), ("b/c.txt", "3")]],
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "2");
+        assert!(dir.path().join(".git").exists());
+    }
+}
@@ -0,0 +1,299 @@
+//! This module measures the time spent in the slow parts of a run (requests to GitHub and crates.io,
+//! cargo-geiger, git clones and fetches, diffs of crate versions),
+//! so that users can see what to cache (see [`crate::rust::RustAnalysis::prefetch`])
+//! or disable (see [`crate::sources`]) to speed up their CI.
+//!
+//! The times are summed over the calls of each section: as calls run concurrently,
+//! the sum for a section can exceed the duration of the run.
+//! They are accumulated for the whole process (the analyses of the service run one after the other),
+//! from the last [`reset`].
+//! Setting the `TIMINGS` environment variable (`1` or `true`) adds a [`TimingReport`] to each analysis
+//! and logs it in human-readable form.
+
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::dashboard::table::{Align, Cell, Table};
+use crate::http::{Response, Transport};
+
+/// The environment variable enabling the timing reports (`1` or `true`).
+pub const TIMINGS_VAR: &str = "TIMINGS";
+
+/// Are the timing reports enabled?
+pub fn enabled() -> bool {
+    std::env::var(TIMINGS_VAR)
+        .map(|timings| timings == "1" || timings.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// A section of a run.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Section {
+    /// requests to the GitHub API (and github.com)
+    GitHub,
+    /// requests to the crates.io API and crate downloads
+    CratesIo,
+    /// cargo-geiger runs
+    Geiger,
+    /// clones and fetches of git repositories (the analyzed one, the advisory database, the crates.io index)
+    GitClone,
+    /// diffs of crate versions (changed files, secrets, churn, etc. but not cargo-geiger)
+    Diff,
+}
+
+impl Section {
+    pub const ALL: [Section; 5] = [
+        Section::GitHub,
+        Section::CratesIo,
+        Section::Geiger,
+        Section::GitClone,
+        Section::Diff,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Section::GitHub => "github",
+            Section::CratesIo => "crates.io",
+            Section::Geiger => "geiger",
+            Section::GitClone => "git clones",
+            Section::Diff => "diffs",
+        }
+    }
+
+    /// What to do to spend less time in the section.
+    fn advice(&self) -> &'static str {
+        match self {
+            Section::GitHub => "prefetch, or DISABLED_SOURCES=github",
+            Section::CratesIo => "prefetch, or DISABLED_SOURCES=cratesio",
+            Section::Geiger => "keep the geiger cache, or DISABLED_SOURCES=geiger",
+            Section::GitClone => "keep the clones between runs",
+            Section::Diff => "prefetch the crates, or restrict the scope",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// The nanoseconds spent in each section, and the number of calls.
+static NANOS: [AtomicU64; 5] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+static CALLS: [AtomicU64; 5] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// Adds the duration of a call to a section.
+pub fn record(section: Section, duration: Duration) {
+    NANOS[section.index()].fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    CALLS[section.index()].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Forgets the times measured so far (e.g. at the start of an analysis).
+pub fn reset() {
+    for section in &Section::ALL {
+        NANOS[section.index()].store(0, Ordering::Relaxed);
+        CALLS[section.index()].store(0, Ordering::Relaxed);
+    }
+}
+
+/// Runs a future, adding the time it takes to a section.
+pub async fn time<F: Future>(section: Section, future: F) -> F::Output {
+    let start = Instant::now();
+    let output = future.await;
+    record(section, start.elapsed());
+    output
+}
+
+/// Runs a blocking function, adding the time it takes to a section.
+pub fn time_blocking<T>(section: Section, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let output = f();
+    record(section, start.elapsed());
+    output
+}
+
+/// The time spent in a section.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SectionTiming {
+    pub section: Section,
+    pub calls: u64,
+    /// the sum of the durations of the calls
+    pub seconds: f64,
+}
+
+/// The time spent in each section of a run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TimingReport {
+    /// the duration of the run
+    pub total_seconds: f64,
+    /// the sections with calls, the slowest first
+    pub sections: Vec<SectionTiming>,
+}
+
+impl TimingReport {
+    /// The times measured since the last [`reset`], for a run of the given duration.
+    pub fn new(total: Duration) -> Self {
+        let mut sections: Vec<SectionTiming> = Section::ALL
+            .iter()
+            .map(|section| SectionTiming {
+                section: *section,
+                calls: CALLS[section.index()].load(Ordering::Relaxed),
+                seconds: Duration::from_nanos(NANOS[section.index()].load(Ordering::Relaxed))
+                    .as_secs_f64(),
+            })
+            .filter(|timing| timing.calls > 0)
+            .collect();
+        sections.sort_by(|a, b| {
+            b.seconds
+                .partial_cmp(&a.seconds)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Self {
+            total_seconds: total.as_secs_f64(),
+            sections,
+        }
+    }
+
+    /// Renders the report as a (markdown) table, e.g. for the logs of a CI job.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "time spent (run: {:.1}s):\n", self.total_seconds);
+        let mut table = Table::new()
+            .column("section", Align::Left)
+            .column("calls", Align::Right)
+            .column("time", Align::Right)
+            .column("of the run", Align::Right)
+            .column("to speed it up", Align::Left);
+        for timing in &self.sections {
+            let share = if self.total_seconds > 0.0 {
+                format!("{:.0}%", 100.0 * timing.seconds / self.total_seconds)
+            } else {
+                "-".to_string()
+            };
+            table.row(vec![
+                Cell::text(timing.section.as_str()),
+                Cell::text(timing.calls),
+                Cell::text(format!("{:.1}s", timing.seconds)),
+                Cell::text(share),
+                Cell::text(timing.section.advice()),
+            ]);
+        }
+        text.push_str(&table.to_markdown());
+        text
+    }
+}
+
+/// Times the requests sent by another transport, by host (see [`crate::http::default_transport`]).
+pub struct TimedTransport {
+    inner: Arc<dyn Transport>,
+}
+
+impl TimedTransport {
+    pub fn new(inner: Arc<dyn Transport>) -> Self {
+        Self { inner }
+    }
+}
+
+/// The section of a request, if it goes to a timed host.
+fn request_section(url: &str) -> Option<Section> {
+    let host = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()?;
+    if host == "github.com" || host.ends_with(".github.com") {
+        Some(Section::GitHub)
+    } else if host == "crates.io" || host.ends_with(".crates.io") {
+        Some(Section::CratesIo)
+    } else {
+        None
+    }
+}
+
+impl Transport for TimedTransport {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+    ) -> BoxFuture<'a, Result<Response>> {
+        match request_section(url) {
+            Some(section) => time(section, self.inner.get(url, headers)).boxed(),
+            None => self.inner.get(url, headers),
+        }
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<Response>> {
+        match request_section(url) {
+            Some(section) => time(section, self.inner.post(url, headers, body)).boxed(),
+            None => self.inner.post(url, headers, body),
+        }
+    }
+
+    fn is_network(&self) -> bool {
+        self.inner.is_network()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        assert_eq!(
+            request_section("https://api.github.com/repos/a/b"),
+            Some(Section::GitHub)
+        );
+        assert_eq!(
+            request_section("https://static.crates.io/crates/a/a-1.0.0.crate"),
+            Some(Section::CratesIo)
+        );
+        assert_eq!(request_section("https://docs.rs/crate/a"), None);
+
+        // (other tests record times concurrently, so only lower bounds are checked)
+        let slept = time_blocking(Section::Geiger, || {
+            std::thread::sleep(Duration::from_millis(20));
+            1
+        });
+        assert_eq!(slept, 1);
+        record(Section::Diff, Duration::from_secs(2));
+        let report = TimingReport::new(Duration::from_secs(4));
+        let diff = report
+            .sections
+            .iter()
+            .find(|timing| timing.section == Section::Diff)
+            .unwrap();
+        assert!(diff.calls >= 1 && diff.seconds >= 2.0);
+        let geiger = report
+            .sections
+            .iter()
+            .find(|timing| timing.section == Section::Geiger)
+            .unwrap();
+        assert!(geiger.seconds >= 0.02);
+        let text = report.to_text();
+        assert!(text.starts_with("time spent (run: 4.0s):"));
+        assert!(text.contains("DISABLED_SOURCES=geiger"));
+    }
+}
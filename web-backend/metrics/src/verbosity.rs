@@ -0,0 +1,65 @@
+//! This module defines how much the renderers (reviews, reports, dashboards, digests) say.
+//! Every renderer takes a [`Verbosity`]:
+//!
+//! - `minimal` summarizes each update (or crate) in a single table row,
+//! - `standard` (the default) is the usual output,
+//! - `full` adds everything the analysis knows: inline changes (changelogs, `unsafe` changes per file),
+//!   the versions hopped over by updates, and the raw metrics.
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// How much a renderer says (see the module documentation), sorted from the least to the most.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    Minimal,
+    Standard,
+    Full,
+}
+
+impl Default for Verbosity {
+    fn default() -> Self {
+        Verbosity::Standard
+    }
+}
+
+impl Verbosity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verbosity::Minimal => "minimal",
+            Verbosity::Standard => "standard",
+            Verbosity::Full => "full",
+        }
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = Error;
+
+    fn from_str(verbosity: &str) -> Result<Self> {
+        match verbosity {
+            "minimal" => Ok(Verbosity::Minimal),
+            "standard" => Ok(Verbosity::Standard),
+            "full" => Ok(Verbosity::Full),
+            _ => bail!(
+                "unknown verbosity {} (expected minimal, standard or full)",
+                verbosity
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity() {
+        assert_eq!("full".parse::<Verbosity>().unwrap(), Verbosity::Full);
+        assert!("loud".parse::<Verbosity>().is_err());
+        assert!(Verbosity::Minimal < Verbosity::default());
+        assert_eq!(Verbosity::Minimal.as_str(), "minimal");
+    }
+}
@@ -4,10 +4,28 @@
 extern crate rocket;
 
 use metrics::{
-    model::{Config, Db, Dependencies},
+    analysis::MetricsApp,
+    dashboard,
+    locale::Locale,
+    model::{Config, Db, Dependencies, History, RepoConfig, Reviews, Watches},
+    redact,
+    rust::{
+        batch::BatchReport,
+        codeowners, compare,
+        graph::DependencyGraph,
+        report::{self, CrateReport},
+        review_diff::PostedReview,
+        verdict::GroupVerdict,
+        watch::{WatchReport, WatchState},
+    },
+    verbosity::Verbosity,
     MetricsRequest,
 };
-use rocket::State;
+use rocket::{
+    http::ContentType,
+    response::content::{Custom, Html},
+    State,
+};
 use rocket_contrib::json::Json;
 use serde::Deserialize;
 use std::sync::mpsc::{sync_channel, SyncSender};
@@ -25,9 +43,28 @@ fn index() -> &'static str {
     // TODO: print other routes?
     "/\n
     /refresh?repo=<REPO>\n
+    /backfill?repo=<REPO>\n
+    /batch?repos=<REPO1,REPO2,...>\n
+    /batch_report?repos=<REPO1,REPO2,...>&format=<json|markdown>\n
+    /history?repo=<REPO>\n
+    /estimate?repo=<REPO>\n
+    /prefetch?repo=<REPO>\n
     /dependencies?repo=<REPO>\n
+    /dashboard?repo=<REPO>&locale=<LOCALE>&verbosity=<minimal|standard|full>\n
+    /badge?repo=<REPO>\n
+    /compare?crates=<CRATE1,CRATE2,...>&format=<json|markdown>&locale=<LOCALE>\n
+    /crate_report?name=<CRATE>&version=<VERSION>&format=<json|markdown>&locale=<LOCALE>&verbosity=<minimal|standard|full>\n
+    /crate_report_html?name=<CRATE>&version=<VERSION>&locale=<LOCALE>&verbosity=<minimal|standard|full>\n
+    /verdict?repo=<REPO>&crates=<CRATE1,CRATE2,...>&format=<json|markdown>&verbosity=<minimal|standard|full>&pr=<PR>\n
+    /verdict_lockfile?repo=<REPO>&crates=<CRATE1,CRATE2,...>&format=<json|markdown>&verbosity=<minimal|standard|full>&pr=<PR>\n
+    /watch?repo=<REPO>&format=<json|markdown>&verbosity=<minimal|standard|full>\n
+    /teams?repo=<REPO>\n
+    /manifest_lints?repo=<REPO>&format=<json|patch>\n
+    /graph?repo=<REPO>&format=<json|cypher|graphml>\n
     /repos\n
-    /add_repo"
+    /add_repo\n
+    /repo_config?repo=<REPO>\n
+    /update_repo_config"
 }
 
 #[get("/refresh?<repo>")]
@@ -57,9 +94,162 @@ async fn refresh(state: State<App, '_>, repo: String) -> &'static str {
     "ok"
 }
 
-#[get("/dependencies?<repo>")]
+#[get("/backfill?<repo>")]
+/// starts reviewing the dependency updates found in the git history of the repo given
+/// (if the metrics service is not busy), see /history for the result
+async fn backfill(state: State<App, '_>, repo: String) -> &'static str {
+    // check if we have the repo in our config
+    let config = Config::new(state.db.clone());
+    match config.repo_exists(&repo).await {
+        Ok(true) => (),
+        Ok(false) => return "add the repository first",
+        Err(e) => {
+            error!("{}", e);
+            return "error, check the logs";
+        }
+    };
+
+    // try to request metrics service
+    let sender = state.metrics_requester.lock().unwrap();
+    if sender
+        .try_send(MetricsRequest::Backfill { repo_url: repo })
+        .is_err()
+    {
+        return "metrics service is busy";
+    }
+    //
+    "ok"
+}
+
+#[get("/batch?<repos>")]
+/// starts analyzing several repositories one after the other (if the metrics service is not busy),
+/// see /batch_report for the consolidated result
+async fn batch(state: State<App, '_>, repos: String) -> String {
+    let repo_urls = split_list(&repos);
+    if repo_urls.is_empty() {
+        return "error, no repositories to analyze".to_string();
+    }
+
+    // check if we have the repos in our config
+    let config = Config::new(state.db.clone());
+    for repo in &repo_urls {
+        match config.repo_exists(repo).await {
+            Ok(true) => (),
+            Ok(false) => return format!("add the repository {} first", repo),
+            Err(e) => {
+                error!("{}", e);
+                return "error, check the logs".to_string();
+            }
+        };
+    }
+
+    // try to request metrics service
+    let sender = state.metrics_requester.lock().unwrap();
+    if sender
+        .try_send(MetricsRequest::Batch { repo_urls })
+        .is_err()
+    {
+        return "metrics service is busy".to_string();
+    }
+    //
+    "ok".to_string()
+}
+
+#[get("/batch_report?<repos>&<format>")]
+/// consolidates the latest analyses of several repositories,
+/// ranking the riskiest dependencies across them
+async fn batch_report(state: State<App, '_>, repos: String, format: Option<String>) -> String {
+    let dependencies = Dependencies::new(state.db.clone());
+    let mut analyses = Vec::new();
+    for repo in split_list(&repos) {
+        match dependencies.get_last_analysis(&repo).await {
+            Ok(analysis) => analyses.push((repo, analysis)),
+            Err(e) => {
+                error!("couldn't get dependencies of {}: {}", repo, e);
+                return "an error happened while retrieving dependencies".to_string();
+            }
+        }
+    }
+
+    let report = BatchReport::new(&analyses);
+    if format.as_deref() == Some("markdown") {
+        return report.to_markdown();
+    }
+    match serde_json::to_string(&report) {
+        Ok(report) => report,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/history?<repo>")]
+/// obtains the dependency updates found in the git history of a repository (see /backfill)
+async fn history(state: State<App, '_>, repo: String) -> String {
+    let history = History::new(state.db.clone());
+    match history.get_backfill(&repo).await {
+        Ok(Some(backfill)) => match serde_json::to_string(&backfill) {
+            Ok(backfill) => backfill,
+            Err(e) => format!("error: {}", e),
+        },
+        Ok(None) => "no backfill found, run /backfill first".to_string(),
+        Err(e) => {
+            error!("couldn't get the history of {}: {}", repo, e);
+            "an error happened while retrieving the history".to_string()
+        }
+    }
+}
+
+#[get("/estimate?<repo>")]
+/// estimates the cost of an analysis for the repo given, without running it
+async fn estimate(state: State<App, '_>, repo: String) -> String {
+    // check if we have the repo in our config
+    let config = Config::new(state.db.clone());
+    match config.repo_exists(&repo).await {
+        Ok(true) => (),
+        Ok(false) => return "add the repository first".to_string(),
+        Err(e) => {
+            error!("{}", e);
+            return "error, check the logs".to_string();
+        }
+    };
+
+    let metrics = MetricsApp::with_db(state.db.clone());
+    match metrics.estimate(&repo, &metrics::repos_dir()).await {
+        Ok(estimate) => match serde_json::to_string(&estimate) {
+            Ok(estimate) => estimate,
+            Err(e) => format!("error: {}", e),
+        },
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/prefetch?<repo>")]
+/// downloads everything an analysis of the repo given needs, so that it can then run offline
+async fn prefetch(state: State<App, '_>, repo: String) -> String {
+    // check if we have the repo in our config
+    let config = Config::new(state.db.clone());
+    match config.repo_exists(&repo).await {
+        Ok(true) => (),
+        Ok(false) => return "add the repository first".to_string(),
+        Err(e) => {
+            error!("{}", e);
+            return "error, check the logs".to_string();
+        }
+    };
+
+    let metrics = MetricsApp::with_db(state.db.clone());
+    match metrics.prefetch(&repo, &metrics::repos_dir()).await {
+        Ok(summary) => match serde_json::to_string(&summary) {
+            Ok(summary) => summary,
+            Err(e) => format!("error: {}", e),
+        },
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/dependencies?<repo>&<redact>")]
 /// obtains latest analysis result for a repository
-async fn dependencies(state: State<App, '_>, repo: String) -> String {
+/// (with `redact`, without internal names, paths and URLs, so that it can be shared)
+async fn dependencies(state: State<App, '_>, repo: String, redact: Option<bool>) -> String {
     // check if we have the repo in our config
     let config = Config::new(state.db.clone());
     match config.repo_exists(&repo).await {
@@ -73,7 +263,11 @@ async fn dependencies(state: State<App, '_>, repo: String) -> String {
 
     // read from db
     let dependencies = Dependencies::new(state.db.clone());
-    match dependencies.get_last_analysis(&repo).await {
+    let analysis = match dependencies.get_last_analysis(&repo).await {
+        Ok(Some(analysis)) if redact.unwrap_or(false) => redact::redact(&analysis).map(Some),
+        res => res,
+    };
+    match analysis {
         Ok(Some(analysis)) => match serde_json::to_string(&analysis) {
             Ok(dependencies) => return dependencies,
             Err(e) => {
@@ -91,6 +285,446 @@ async fn dependencies(state: State<App, '_>, repo: String) -> String {
     "an error happened while retrieving dependencies".to_string()
 }
 
+#[get("/dashboard?<repo>&<redact>&<locale>&<verbosity>")]
+/// renders a static HTML dashboard out of the last analyses of a repository
+/// (with `redact`, without internal names, paths and URLs, so that it can be shared).
+/// Dates and numbers are formatted according to `locale`, and the dashboard says as much as `verbosity`,
+/// both defaulting to the configuration of the repository.
+async fn dashboard(
+    state: State<App, '_>,
+    repo: String,
+    redact: Option<bool>,
+    locale: Option<String>,
+    verbosity: Option<String>,
+) -> Html<String> {
+    let dependencies = Dependencies::new(state.db.clone());
+    let analyses = match dependencies.get_analyses(&repo, 30).await {
+        Ok(analyses) if redact.unwrap_or(false) => analyses.iter().map(redact::redact).collect(),
+        res => res,
+    };
+    let analyses = match analyses {
+        Ok(analyses) => analyses,
+        Err(e) => {
+            error!("couldn't get analyses: {}", e);
+            return Html("an error happened while retrieving analyses".to_string());
+        }
+    };
+    let repo_config = match Config::new(state.db.clone()).get_repo(&repo).await {
+        Ok(repo_config) => repo_config.unwrap_or_default(),
+        Err(e) => {
+            error!("couldn't get the configuration of {}: {}", repo, e);
+            RepoConfig::default()
+        }
+    };
+    let locale = match locale {
+        Some(locale) => Locale::new(&locale),
+        None => repo_config.locale,
+    };
+    let verbosity = match parse_verbosity(verbosity) {
+        Ok(verbosity) => verbosity.unwrap_or(repo_config.verbosity),
+        Err(e) => return Html(format!("error: {}", e)),
+    };
+    match dashboard::render(&analyses, &locale, verbosity) {
+        Ok(html) => Html(html),
+        Err(e) => Html(format!("error: {}", e)),
+    }
+}
+
+#[get("/badge?<repo>")]
+/// renders an SVG badge summarizing the health of the dependencies of the last analysis
+/// (e.g. `deps | 3 advisories / 212 crates`), to embed in a README
+async fn badge(state: State<App, '_>, repo: String) -> Custom<String> {
+    let dependencies = Dependencies::new(state.db.clone());
+    let badge = match dependencies.get_last_analysis(&repo).await {
+        Ok(Some(analysis)) => dashboard::badge::health(&analysis),
+        Ok(None) => dashboard::badge::unknown(),
+        Err(e) => {
+            error!("couldn't get dependencies: {}", e);
+            dashboard::badge::unknown()
+        }
+    };
+    Custom(ContentType::SVG, badge)
+}
+
+#[get("/compare?<crates>&<format>&<locale>")]
+/// compares candidate crates (that are not dependencies yet) side by side
+async fn compare(crates: String, format: Option<String>, locale: Option<String>) -> String {
+    let names: Vec<String> = crates
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(ToString::to_string)
+        .collect();
+    if names.is_empty() {
+        return "error, no crates to compare".to_string();
+    }
+    // (the latest version of each crate is downloaded)
+    if let Err(e) = names
+        .iter()
+        .try_for_each(|name| report::validate(name, None))
+    {
+        return format!("error: {}", e);
+    }
+
+    let comparisons = match compare::compare_crates(&names).await {
+        Ok(comparisons) => comparisons,
+        Err(e) => return format!("error: {}", e),
+    };
+    if format.as_deref() == Some("markdown") {
+        let locale = locale.as_deref().map(Locale::new).unwrap_or_default();
+        return compare::render_markdown(&comparisons, &locale);
+    }
+    match serde_json::to_string(&comparisons) {
+        Ok(comparisons) => comparisons,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/verdict?<repo>&<crates>&<format>&<verbosity>&<pr>")]
+/// reviews a group of updates (e.g. a grouped dependabot PR) based on the latest analysis,
+/// giving a verdict per update and an overall verdict
+/// (with `pr`, the changes since the previous review of the same PR are listed first,
+/// without recording this review, see the POST route)
+async fn verdict(
+    state: State<App, '_>,
+    repo: String,
+    crates: Option<String>,
+    format: Option<String>,
+    verbosity: Option<String>,
+    pr: Option<String>,
+) -> String {
+    review(state, repo, crates, format, verbosity, pr, None, false).await
+}
+
+#[post("/verdict?<repo>&<crates>&<format>&<verbosity>&<pr>")]
+/// same as GET /verdict, also recording the review of `pr` for the next one to be compared with
+async fn record_verdict(
+    state: State<App, '_>,
+    repo: String,
+    crates: Option<String>,
+    format: Option<String>,
+    verbosity: Option<String>,
+    pr: Option<String>,
+) -> String {
+    review(state, repo, crates, format, verbosity, pr, None, true).await
+}
+
+#[post(
+    "/verdict_lockfile?<repo>&<crates>&<format>&<verbosity>&<pr>",
+    data = "<lockfile>"
+)]
+/// same as /verdict, also checking that the advisories fixed by the updates are fixed
+/// in the `Cargo.lock` resolved with the updates (e.g. the one of the dependabot PR), sent as the body
+async fn verdict_lockfile(
+    state: State<App, '_>,
+    repo: String,
+    crates: Option<String>,
+    format: Option<String>,
+    verbosity: Option<String>,
+    pr: Option<String>,
+    lockfile: String,
+) -> String {
+    review(
+        state,
+        repo,
+        crates,
+        format,
+        verbosity,
+        pr,
+        Some(lockfile),
+        true,
+    )
+    .await
+}
+
+/// reviews a group of updates (see /verdict), checking the lockfile resolved with the updates if given,
+/// and comparing the review with the previous one of the pull request if given
+/// (recording it in place of the previous one if `record` is set)
+#[allow(clippy::too_many_arguments)]
+async fn review(
+    state: State<App, '_>,
+    repo: String,
+    crates: Option<String>,
+    format: Option<String>,
+    verbosity: Option<String>,
+    pr: Option<String>,
+    lockfile: Option<String>,
+    record: bool,
+) -> String {
+    let verbosity = match parse_verbosity(verbosity) {
+        Ok(verbosity) => verbosity,
+        Err(e) => return format!("error: {}", e),
+    };
+    let crates: Vec<String> = crates
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(ToString::to_string)
+        .collect();
+
+    let dependencies = Dependencies::new(state.db.clone());
+    let analysis = match dependencies.get_last_analysis(&repo).await {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => return "no dependency analysis found".to_string(),
+        Err(e) => {
+            error!("couldn't get dependencies: {}", e);
+            return "an error happened while retrieving dependencies".to_string();
+        }
+    };
+
+    let config = Config::new(state.db.clone());
+    let repo_config = match config.get_repo(&repo).await {
+        Ok(repo_config) => repo_config.unwrap_or_default(),
+        Err(e) => {
+            error!("couldn't get the configuration of {}: {}", repo, e);
+            RepoConfig::default()
+        }
+    };
+
+    let mut verdict = GroupVerdict::new(analysis.rust_dependencies(), &crates);
+    if let Some(lockfile) = lockfile {
+        if let Err(e) = verdict.check_lockfile(analysis.rust_dependencies(), &lockfile) {
+            return format!("error: {}", e);
+        }
+    }
+    verdict.assign(&repo_config.reviewers);
+    if let Some(pr) = pr {
+        let reviews = Reviews::new(state.db.clone());
+        match reviews.get_review(&repo, &pr).await {
+            Ok(Some(previous)) => verdict.compare(&previous.review),
+            Ok(None) => (),
+            Err(e) => error!("couldn't get the previous review of {}#{}: {}", repo, pr, e),
+        }
+        if record {
+            if let Err(e) = reviews
+                .save_review(&PostedReview::new(&repo, &pr, &verdict))
+                .await
+            {
+                error!("couldn't save the review of {}#{}: {}", repo, pr, e);
+            }
+        }
+    }
+    if format.as_deref() == Some("markdown") {
+        return verdict.to_markdown(
+            &repo_config.markdown_theme,
+            verbosity.unwrap_or(repo_config.verbosity),
+        );
+    }
+    match serde_json::to_string(&verdict) {
+        Ok(verdict) => verdict,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/watch?<repo>&<format>&<verbosity>")]
+/// reports the direct dependencies that published new versions since the last recorded watch
+/// (based on the latest analysis), for repositories that are updated manually on a schedule
+/// (without recording this one, see the POST route)
+async fn watch(
+    state: State<App, '_>,
+    repo: String,
+    format: Option<String>,
+    verbosity: Option<String>,
+) -> String {
+    watch_report(state, repo, format, verbosity, false).await
+}
+
+#[post("/watch?<repo>&<format>&<verbosity>")]
+/// same as GET /watch, also recording the versions reported, for the next watch to start from
+async fn record_watch(
+    state: State<App, '_>,
+    repo: String,
+    format: Option<String>,
+    verbosity: Option<String>,
+) -> String {
+    watch_report(state, repo, format, verbosity, true).await
+}
+
+/// reports the new versions since the last recorded watch (see /watch),
+/// recording the current state in its place if `record` is set
+async fn watch_report(
+    state: State<App, '_>,
+    repo: String,
+    format: Option<String>,
+    verbosity: Option<String>,
+    record: bool,
+) -> String {
+    let verbosity = match parse_verbosity(verbosity) {
+        Ok(verbosity) => verbosity.unwrap_or_default(),
+        Err(e) => return format!("error: {}", e),
+    };
+    let dependencies = Dependencies::new(state.db.clone());
+    let analysis = match dependencies.get_last_analysis(&repo).await {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => return "no dependency analysis found".to_string(),
+        Err(e) => {
+            error!("couldn't get dependencies: {}", e);
+            return "an error happened while retrieving dependencies".to_string();
+        }
+    };
+
+    let watches = Watches::new(state.db.clone());
+    let previous = match watches.get_state(&repo).await {
+        Ok(previous) => previous,
+        Err(e) => {
+            error!("couldn't get the watch state of {}: {}", repo, e);
+            return "an error happened while retrieving the previous run".to_string();
+        }
+    };
+    let report = WatchReport::new(&repo, analysis.rust_dependencies(), previous.as_ref());
+    if record {
+        let state = WatchState::new(&repo, analysis.rust_dependencies());
+        if let Err(e) = watches.save_state(&state).await {
+            error!("couldn't save the watch state of {}: {}", repo, e);
+        }
+    }
+
+    if format.as_deref() == Some("markdown") {
+        return report.to_markdown(verbosity);
+    }
+    match serde_json::to_string(&report) {
+        Ok(report) => report,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/teams?<repo>")]
+/// reports the health of the dependencies used by each team (as declared in CODEOWNERS),
+/// based on the latest analysis
+async fn teams(state: State<App, '_>, repo: String) -> String {
+    let dependencies = Dependencies::new(state.db.clone());
+    let analysis = match dependencies.get_last_analysis(&repo).await {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => return "no dependency analysis found".to_string(),
+        Err(e) => {
+            error!("couldn't get dependencies: {}", e);
+            return "an error happened while retrieving dependencies".to_string();
+        }
+    };
+
+    let reports = codeowners::team_reports(analysis.rust_dependencies());
+    match serde_json::to_string(&reports) {
+        Ok(reports) => reports,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/manifest_lints?<repo>&<format>")]
+/// lists the dependency declarations of the manifests to fix, based on the latest analysis,
+/// as JSON or as a patch applying the suggested fixes (with `git apply`)
+async fn manifest_lints(state: State<App, '_>, repo: String, format: Option<String>) -> String {
+    let dependencies = Dependencies::new(state.db.clone());
+    let analysis = match dependencies.get_last_analysis(&repo).await {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => return "no dependency analysis found".to_string(),
+        Err(e) => {
+            error!("couldn't get dependencies: {}", e);
+            return "an error happened while retrieving dependencies".to_string();
+        }
+    };
+
+    let lints = analysis.rust_dependencies().manifest_lints();
+    if format.as_deref() == Some("patch") {
+        return lints
+            .iter()
+            .filter_map(|lint| lint.patch.as_deref())
+            .collect();
+    }
+    match serde_json::to_string(lints) {
+        Ok(lints) => lints,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/graph?<repo>&<redact>&<format>")]
+/// exports the dependency graph of the latest analysis, annotated with its results,
+/// as JSON, Cypher statements (for graph databases) or GraphML
+/// (with `redact`, without internal names, paths and URLs, so that it can be shared)
+async fn graph(
+    state: State<App, '_>,
+    repo: String,
+    redact: Option<bool>,
+    format: Option<String>,
+) -> String {
+    let dependencies = Dependencies::new(state.db.clone());
+    let analysis = match dependencies.get_last_analysis(&repo).await {
+        Ok(Some(analysis)) if redact.unwrap_or(false) => redact::redact(&analysis).map(Some),
+        res => res,
+    };
+    let analysis = match analysis {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => return "no dependency analysis found".to_string(),
+        Err(e) => {
+            error!("couldn't get dependencies: {}", e);
+            return "an error happened while retrieving dependencies".to_string();
+        }
+    };
+
+    let graph = DependencyGraph::new(analysis.rust_dependencies());
+    match format.as_deref() {
+        Some("cypher") => return graph.to_cypher(analysis.repository()),
+        Some("graphml") => return graph.to_graphml(),
+        _ => (),
+    }
+    match serde_json::to_string(&graph) {
+        Ok(graph) => graph,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/crate_report?<name>&<version>&<format>&<locale>&<verbosity>")]
+/// produces a report about a single crate version (the latest one if none is given)
+async fn crate_report(
+    name: String,
+    version: Option<String>,
+    format: Option<String>,
+    locale: Option<String>,
+    verbosity: Option<String>,
+) -> String {
+    let verbosity = match parse_verbosity(verbosity) {
+        Ok(verbosity) => verbosity.unwrap_or_default(),
+        Err(e) => return format!("error: {}", e),
+    };
+    if let Err(e) = report::validate(&name, version.as_deref()) {
+        return format!("error: {}", e);
+    }
+    let report = match CrateReport::new(&name, version.as_deref()).await {
+        Ok(report) => report,
+        Err(e) => return format!("error: {}", e),
+    };
+    if format.as_deref() == Some("markdown") {
+        let locale = locale.as_deref().map(Locale::new).unwrap_or_default();
+        return report.to_markdown(&locale, verbosity);
+    }
+    match serde_json::to_string(&report) {
+        Ok(report) => report,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[get("/crate_report_html?<name>&<version>&<locale>&<verbosity>")]
+/// same as /crate_report, but renders the report as a standalone HTML page
+async fn crate_report_html(
+    name: String,
+    version: Option<String>,
+    locale: Option<String>,
+    verbosity: Option<String>,
+) -> Html<String> {
+    let locale = locale.as_deref().map(Locale::new).unwrap_or_default();
+    let verbosity = match parse_verbosity(verbosity) {
+        Ok(verbosity) => verbosity.unwrap_or_default(),
+        Err(e) => return Html(format!("error: {}", dashboard::escape(&e.to_string()))),
+    };
+    if let Err(e) = report::validate(&name, version.as_deref()) {
+        return Html(format!("error: {}", dashboard::escape(&e.to_string())));
+    }
+    match CrateReport::new(&name, version.as_deref()).await {
+        Ok(report) => Html(report.to_html(&locale, verbosity)),
+        Err(e) => Html(format!("error: {}", dashboard::escape(&e.to_string()))),
+    }
+}
+
 #[get("/repos")]
 /// obtains latest analysis result for a repository
 async fn repos(state: State<App, '_>) -> String {
@@ -129,6 +763,38 @@ async fn add_repo(state: State<App, '_>, repo_form: Json<RepoForm>) -> String {
     }
 }
 
+#[get("/repo_config?<repo>")]
+/// obtains the configuration of a repository
+async fn repo_config(state: State<App, '_>, repo: String) -> String {
+    let config = Config::new(state.db.clone());
+    let repo_config = match config.get_repo(&repo).await {
+        Ok(Some(repo_config)) => repo_config,
+        Ok(None) => return "add the repository first".to_string(),
+        Err(e) => return format!("error: {}", e),
+    };
+    match serde_json::to_string(&repo_config) {
+        Err(e) => format!("error: {}", e),
+        Ok(repo_config) => repo_config,
+    }
+}
+
+#[post("/update_repo_config", format = "json", data = "<repo_config>")]
+/// replaces the configuration of a repository
+async fn update_repo_config(state: State<App, '_>, repo_config: Json<RepoConfig>) -> String {
+    let config = Config::new(state.db.clone());
+    match config.repo_exists(&repo_config.repo).await {
+        Ok(true) => (),
+        Ok(false) => return "add the repository first".to_string(),
+        Err(e) => return format!("error: {}", e),
+    };
+
+    info!("updating configuration of repository: {}", repo_config.repo);
+    match config.update_repo(&repo_config).await {
+        Ok(()) => "ok".to_string(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
 // TODO: complete this function
 fn valid_repo_url(repo: &str) -> bool {
     if repo.is_empty() {
@@ -137,6 +803,20 @@ fn valid_repo_url(repo: &str) -> bool {
     true
 }
 
+/// parses the `verbosity` parameter of a route (minimal, standard or full)
+fn parse_verbosity(verbosity: Option<String>) -> anyhow::Result<Option<Verbosity>> {
+    verbosity.as_deref().map(str::parse).transpose()
+}
+
+/// parses a comma-separated list parameter of a route (e.g. `repos`)
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
 //
 // App
 //
@@ -170,7 +850,35 @@ async fn rocket() -> rocket::Rocket {
 
     // start server
     info!("starting rocket server");
-    rocket::ignite()
-        .manage(state)
-        .mount("/", routes![index, refresh, dependencies, repos, add_repo])
+    rocket::ignite().manage(state).mount(
+        "/",
+        routes![
+            index,
+            refresh,
+            backfill,
+            batch,
+            batch_report,
+            history,
+            estimate,
+            prefetch,
+            dependencies,
+            dashboard,
+            badge,
+            compare,
+            crate_report,
+            crate_report_html,
+            verdict,
+            record_verdict,
+            verdict_lockfile,
+            watch,
+            record_watch,
+            teams,
+            manifest_lints,
+            graph,
+            repos,
+            add_repo,
+            repo_config,
+            update_repo_config
+        ],
+    )
 }
@@ -17,15 +17,29 @@ export function calculate_priority_score(dep) {
     priority_reasons.push("PATCH version change");
   }
 
+  // trusted publishers
+  if (dep.trusted) {
+    priority_reasons.push("trusted publisher");
+  }
+
   // RUSTSEC
+  // (internal forks usually inherit the bugs of the crate they fork)
   if (dep.vulnerabilities) {
     priority_score += 30;
-    priority_reasons.push("RUSTSEC vulnerability associated");
+    priority_reasons.push(
+      dep.forked
+        ? "RUSTSEC vulnerability associated (internal fork)"
+        : "RUSTSEC vulnerability associated"
+    );
   }
 
   if (dep.warnings) {
     priority_score += 20;
-    priority_reasons.push("RUSTSEC warning associated");
+    priority_reasons.push(
+      dep.forked
+        ? "RUSTSEC warning associated (internal fork)"
+        : "RUSTSEC warning associated"
+    );
   }
 
   //
@@ -7,5 +7,98 @@ export function calculate_risk_score(dep) {
     risk_reasons.push("<code>build.rs</code> file Changed");
   }
 
+  // new toolchain requirements (rust-version, build dependencies, nightly features)
+  if (dep.update.toolchain_changes) {
+    dep.update.toolchain_changes.forEach((change) => {
+      risk_score += 5;
+      risk_reasons.push(change);
+    });
+  }
+
+  // sudden jump in package size (e.g. a bundled binary or a vendored C library)
+  // (same thresholds as the backend: twice as big, and at least 512KB bigger)
+  let size_change = dep.update.size_change;
+  if (size_change) {
+    let old_size = size_change.old.uncompressed;
+    let new_size = size_change.new.uncompressed;
+    if (new_size >= 2 * old_size && new_size - old_size >= 512 * 1024) {
+      risk_score += 10;
+      risk_reasons.push(
+        `package size jumped from ${old_size} to ${new_size} bytes (${size_change.old.files} to ${size_change.new.files} files)`
+      );
+    }
+  }
+
+  // files changed that match the risky path patterns of the repository
+  if (dep.update.risky_files) {
+    dep.update.risky_files.forEach((file) => {
+      if (file.level == "escalated") {
+        risk_score += 20;
+        risk_reasons.push(`high-risk file ${file.path} changed`);
+      } else {
+        risk_score += 5;
+        risk_reasons.push(`risky file ${file.path} changed`);
+      }
+    });
+  }
+
+  // unsafe code changed (as opposed to unrelated edits in files containing unsafe code)
+  if (dep.update.unsafe_changes) {
+    dep.update.unsafe_changes
+      .filter((change) => change.unsafe_code_changed)
+      .forEach((change) => {
+        risk_score += 5;
+        risk_reasons.push(`unsafe code changed in ${change.path}`);
+      });
+  }
+
+  // repository changed (the project moved, or was hijacked)
+  let repository_change = dep.update.repository_change;
+  if (repository_change) {
+    risk_score += 20;
+    risk_reasons.push(
+      `repository changed from ${repository_change.old} to ${repository_change.new || "none"}`
+    );
+  }
+
+  // maintainers changed (precursors of a hostile takeover)
+  let maintainer_change = dep.update.maintainer_change;
+  if (maintainer_change) {
+    let publisher = maintainer_change.new_publisher;
+    if (maintainer_change.first_time_publisher) {
+      risk_score += 15;
+      risk_reasons.push(
+        `published by ${publisher}, who never published this crate before`
+      );
+    }
+    if (maintainer_change.publisher_not_owner) {
+      risk_score += 15;
+      risk_reasons.push(
+        `published by ${publisher}, who is not an owner of the crate`
+      );
+    }
+    if (maintainer_change.new_owners.length > 0) {
+      risk_score += 10;
+      risk_reasons.push(`new owners: ${maintainer_change.new_owners.join(", ")}`);
+    }
+  }
+
+  // license changed (the classes are compared by the backend verdict, any change is worth a look)
+  let license_change = dep.update.license_change;
+  if (license_change) {
+    risk_score += 10;
+    risk_reasons.push(
+      `license changed from ${license_change.old || "none"} to ${license_change.new || "none"}`
+    );
+  }
+
+  // possible secrets and blobs added
+  if (dep.update.secrets && dep.update.secrets.length > 0) {
+    risk_score += 10;
+    risk_reasons.push(
+      `${dep.update.secrets.length} possible secrets or blobs added`
+    );
+  }
+
   return { risk_score, risk_reasons };
 }
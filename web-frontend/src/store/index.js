@@ -26,6 +26,13 @@ const getDefaultState = () => {
     dependencies: [],
     dependency_map: {},
     rustsec: [],
+    truncated: false,
+    skipped_steps: [],
+    disabled_sources: [],
+    offline: false,
+    license_exposure: [],
+    skipped_for_safety: [],
+    statistics: {},
   }
 }
 
@@ -39,8 +46,13 @@ export default new Vuex.Store({
   state,
   getters: {
     // dependencies that have an update available
+    // (transitive updates grouped under a direct update are listed with it)
     updatable_dependencies: state => {
-      return state.dependencies.filter((dependency) => dependency.update != null);
+      return state.dependencies.filter(
+        (dependency) =>
+          dependency.update != null &&
+          !(dependency.grouped_under && dependency.grouped_under.length > 0)
+      );
     },
     // dependencies that have a RUSTSEC advisory but can't be updated
     rustsec_no_updates: state => {
@@ -101,6 +113,16 @@ export default new Vuex.Store({
       state.change_summary = analysis.rust_dependencies.change_summary || {};
       let dependencies = analysis.rust_dependencies.dependencies;
       state.rustsec = analysis.rust_dependencies.rustsec;
+      state.truncated = analysis.rust_dependencies.truncated || false;
+      state.skipped_steps = analysis.rust_dependencies.skipped_steps || [];
+      state.disabled_sources =
+        analysis.rust_dependencies.disabled_sources || [];
+      state.offline = analysis.rust_dependencies.offline || false;
+      state.license_exposure =
+        analysis.rust_dependencies.license_exposure || [];
+      state.skipped_for_safety =
+        analysis.rust_dependencies.skipped_for_safety || [];
+      state.statistics = analysis.rust_dependencies.statistics || {};
 
       // transform
       transform_analysis(dependencies, state.rustsec);
@@ -108,9 +108,56 @@ export function transform_analysis(dependencies, rustsec) {
       );
       dependency.risk_score = risk_score;
       dependency.risk_reasons = risk_reasons;
+      dependency.rolled_up_risk_score = risk_score;
+      dependency.rolled_up_risk_reasons = risk_reasons.slice();
+      dependency.transitive_updates = [];
     }
 
     // end of adding new fields to all dependencies
   });
+
+  group_transitive_updates(dependencies);
+}
+
+// A direct dependency update often bumps its own dependencies as well (in the lockfile),
+// so transitive updates are grouped under the direct updates that pull them in,
+// and their risk signals are rolled up to the direct update.
+function group_transitive_updates(dependencies) {
+  // direct dependencies with an update, by name
+  let direct_updates = {};
+  dependencies.forEach((dependency) => {
+    if (dependency.direct && dependency.update != null) {
+      direct_updates[dependency.name] = direct_updates[dependency.name] || [];
+      direct_updates[dependency.name].push(dependency);
+    }
+  });
+
+  dependencies.forEach((dependency) => {
+    if (dependency.direct || dependency.update == null) {
+      return;
+    }
+    let introducers = (dependency.introduced_by || []).flatMap(
+      (name) => direct_updates[name] || []
+    );
+    dependency.grouped_under = introducers.map((introducer) => introducer.name);
+
+    introducers.forEach((introducer) => {
+      introducer.transitive_updates.push(dependency);
+
+      // risk
+      introducer.rolled_up_risk_score += dependency.risk_score;
+      dependency.risk_reasons.forEach((reason) => {
+        introducer.rolled_up_risk_reasons.push(`${dependency.name}: ${reason}`);
+      });
+
+      // a transitive update can make the direct update more urgent
+      if (dependency.priority_score > introducer.priority_score) {
+        introducer.priority_score = dependency.priority_score;
+        introducer.priority_reasons.push(
+          `transitive update of ${dependency.name} (${dependency.priority_reasons.join(", ")})`
+        );
+      }
+    });
+  });
 }
 